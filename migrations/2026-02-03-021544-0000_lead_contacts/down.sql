@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE leads
+DROP COLUMN contact_id;
+
+DROP TABLE contacts;
@@ -0,0 +1,87 @@
+-- Your SQL goes here
+CREATE TABLE contacts (
+    id SERIAL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES agents (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    name VARCHAR(255) NOT NULL,
+    phone VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    merged_into_id INTEGER REFERENCES contacts (id) ON DELETE SET NULL
+);
+
+SELECT
+    diesel_manage_updated_at ('contacts');
+
+ALTER TABLE leads
+ADD COLUMN contact_id INTEGER REFERENCES contacts (id) ON DELETE SET NULL;
+
+-- Normalise existing phone numbers to E.164 (Indonesian 08xx / 62xx / 8xx forms)
+UPDATE leads
+SET
+    phone = CASE
+        WHEN digits LIKE '+%' THEN digits
+        WHEN digits LIKE '00%' THEN '+' || SUBSTRING(digits FROM 3)
+        WHEN digits LIKE '62%' THEN '+' || digits
+        WHEN digits LIKE '0%' THEN '+62' || SUBSTRING(digits FROM 2)
+        WHEN digits LIKE '8%' THEN '+62' || digits
+        ELSE digits
+    END
+FROM (
+        SELECT id AS lead_id, REGEXP_REPLACE(phone, '[^0-9+]', '', 'g') AS digits
+        FROM leads
+    ) AS normalised
+WHERE
+    leads.id = normalised.lead_id;
+
+UPDATE leads SET phone = '+62' || SUBSTRING(phone FROM 5) WHERE phone LIKE '+620%';
+
+-- Group existing leads into one contact per agent and phone number
+INSERT INTO
+    contacts (user_id, created_at, name, phone, email)
+SELECT DISTINCT
+    ON (user_id, phone) user_id, created_at, name, phone, LOWER(email)
+FROM leads
+ORDER BY user_id, phone, created_at;
+
+UPDATE leads
+SET
+    contact_id = contacts.id
+FROM contacts
+WHERE
+    contacts.user_id = leads.user_id
+    AND contacts.phone = leads.phone;
+
+-- Leads that shared an email under different phone numbers are one contact
+UPDATE contacts
+SET
+    merged_into_id = first.id
+FROM (
+        SELECT DISTINCT
+            ON (user_id, email) id, user_id, email
+        FROM contacts
+        WHERE
+            email IS NOT NULL
+        ORDER BY user_id, email, created_at, id
+    ) AS first
+WHERE
+    contacts.user_id = first.user_id
+    AND contacts.email = first.email
+    AND contacts.id <> first.id;
+
+UPDATE leads
+SET
+    contact_id = contacts.merged_into_id
+FROM contacts
+WHERE
+    contacts.id = leads.contact_id
+    AND contacts.merged_into_id IS NOT NULL;
+
+-- A contact is unique per agent by phone number and by email, merged contacts aside
+CREATE UNIQUE INDEX contacts_user_id_phone_key ON contacts (user_id, phone)
+WHERE
+    merged_into_id IS NULL;
+
+CREATE UNIQUE INDEX contacts_user_id_email_key ON contacts (user_id, email)
+WHERE
+    merged_into_id IS NULL;
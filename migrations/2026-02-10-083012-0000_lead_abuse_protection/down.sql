@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX leads_phone_created_at_idx;
+
+DROP TABLE lead_rejections;
+
+DROP TYPE lead_rejection_reason;
+
+DROP TABLE blocked_contacts;
@@ -0,0 +1,40 @@
+-- Your SQL goes here
+CREATE TABLE blocked_contacts (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    phone VARCHAR(255) UNIQUE,
+    email VARCHAR(255) UNIQUE,
+    reason VARCHAR,
+    created_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    CHECK (
+        phone IS NOT NULL
+        OR email IS NOT NULL
+    )
+);
+
+SELECT
+    diesel_manage_updated_at ('blocked_contacts');
+
+CREATE TYPE lead_rejection_reason AS ENUM (
+    'honeypot',
+    'ip_rate_limited',
+    'phone_rate_limited',
+    'captcha_failed',
+    'blocklisted'
+);
+
+CREATE TABLE lead_rejections (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    reason lead_rejection_reason NOT NULL,
+    ip_address VARCHAR(255) NOT NULL,
+    property_id INTEGER REFERENCES properties (id) ON DELETE SET NULL,
+    name VARCHAR(255) NOT NULL,
+    phone VARCHAR(255) NOT NULL,
+    email VARCHAR(255)
+);
+
+CREATE INDEX lead_rejections_created_at_idx ON lead_rejections (created_at);
+
+CREATE INDEX leads_phone_created_at_idx ON leads (phone, created_at);
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE lead_assignments;
+
+DROP TYPE lead_assignment_reason;
+
+DROP TABLE lead_routing_rules;
+
+DELETE FROM leads WHERE property_id IS NULL;
+
+ALTER TABLE leads
+DROP CONSTRAINT leads_property_id_fkey;
+
+ALTER TABLE leads
+ADD CONSTRAINT leads_property_id_fkey FOREIGN KEY (property_id) REFERENCES properties (id) ON DELETE CASCADE;
+
+ALTER TABLE leads
+ALTER COLUMN property_id
+SET NOT NULL;
+
+ALTER TABLE agents
+DROP COLUMN is_active;
@@ -0,0 +1,41 @@
+-- Your SQL goes here
+ALTER TABLE agents
+ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Leads outlive the listing they came from
+ALTER TABLE leads
+ALTER COLUMN property_id
+DROP NOT NULL;
+
+ALTER TABLE leads
+DROP CONSTRAINT leads_property_id_fkey;
+
+ALTER TABLE leads
+ADD CONSTRAINT leads_property_id_fkey FOREIGN KEY (property_id) REFERENCES properties (id) ON DELETE SET NULL;
+
+CREATE TABLE lead_routing_rules (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    regency VARCHAR(255) NOT NULL,
+    user_id uuid NOT NULL REFERENCES agents (id) ON DELETE CASCADE,
+    last_assigned_at TIMESTAMP,
+    UNIQUE (regency, user_id)
+);
+
+SELECT
+    diesel_manage_updated_at ('lead_routing_rules');
+
+CREATE TYPE lead_assignment_reason AS ENUM ('manual', 'routing', 'agent_removed');
+
+CREATE TABLE lead_assignments (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    lead_id INTEGER NOT NULL REFERENCES leads (id) ON DELETE CASCADE,
+    from_user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    to_user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    assigned_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    reason lead_assignment_reason NOT NULL
+);
+
+CREATE INDEX lead_assignments_lead_id_idx ON lead_assignments (lead_id);
@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE agent_transfers;
+
+-- enum values can't be dropped, so the type is rebuilt without it
+DELETE FROM lead_assignments
+WHERE
+    reason = 'agent_transfer';
+
+ALTER TYPE lead_assignment_reason
+RENAME TO lead_assignment_reason_old;
+
+CREATE TYPE lead_assignment_reason AS ENUM ('manual', 'routing', 'agent_removed');
+
+ALTER TABLE lead_assignments
+ALTER COLUMN reason TYPE lead_assignment_reason USING reason::TEXT::lead_assignment_reason;
+
+DROP TYPE lead_assignment_reason_old;
+
+ALTER TABLE agents
+DROP COLUMN deactivated_at;
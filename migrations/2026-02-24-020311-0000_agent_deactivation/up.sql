@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE agents
+ADD COLUMN deactivated_at TIMESTAMP;
+
+ALTER TYPE lead_assignment_reason ADD VALUE 'agent_transfer';
+
+CREATE TABLE agent_transfers (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    from_user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    to_user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    transferred_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    property_count INTEGER NOT NULL,
+    lead_count INTEGER NOT NULL
+);
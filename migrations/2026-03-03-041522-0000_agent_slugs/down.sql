@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE agent_slug_redirects;
+
+ALTER TABLE agents
+DROP COLUMN slug;
@@ -0,0 +1,63 @@
+-- Your SQL goes here
+ALTER TABLE agents
+ADD COLUMN slug VARCHAR(255);
+
+-- Existing agents keep the url they already had, namesakes get a numbered suffix
+WITH
+    base_slugs AS (
+        SELECT
+            id,
+            created_at,
+            COALESCE(
+                NULLIF(
+                    TRIM(
+                        BOTH '-'
+                        FROM
+                            REGEXP_REPLACE(LOWER(fullname), '[^a-z0-9]+', '-', 'g')
+                    ),
+                    ''
+                ),
+                'agent'
+            ) AS base_slug
+        FROM
+            agents
+    ),
+    numbered_slugs AS (
+        SELECT
+            id,
+            base_slug,
+            ROW_NUMBER() OVER (
+                PARTITION BY
+                    base_slug
+                ORDER BY
+                    created_at,
+                    id
+            ) AS position
+        FROM
+            base_slugs
+    )
+UPDATE agents
+SET
+    slug = CASE
+        WHEN numbered_slugs.position = 1 THEN numbered_slugs.base_slug
+        ELSE numbered_slugs.base_slug || '-' || numbered_slugs.position
+    END
+FROM
+    numbered_slugs
+WHERE
+    agents.id = numbered_slugs.id;
+
+ALTER TABLE agents
+ALTER COLUMN slug
+SET NOT NULL;
+
+ALTER TABLE agents
+ADD CONSTRAINT agents_slug_key UNIQUE (slug);
+
+-- Slugs an agent had before being renamed, so old urls keep working
+CREATE TABLE agent_slug_redirects (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    slug VARCHAR(255) NOT NULL UNIQUE,
+    agent_id uuid NOT NULL REFERENCES agents (id) ON DELETE CASCADE
+);
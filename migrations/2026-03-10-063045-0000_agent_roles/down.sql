@@ -0,0 +1,24 @@
+-- This file should undo anything in `up.sql`
+-- enum values can't be dropped, so the type is rebuilt and the new roles fall back to agent
+ALTER TABLE agents
+ALTER COLUMN role
+DROP DEFAULT;
+
+ALTER TYPE agent_role
+RENAME TO agent_role_old;
+
+CREATE TYPE agent_role AS ENUM ('admin', 'agent');
+
+ALTER TABLE agents
+ALTER COLUMN role TYPE agent_role USING (
+    CASE role::TEXT
+        WHEN 'admin' THEN 'admin'
+        ELSE 'agent'
+    END
+)::agent_role;
+
+ALTER TABLE agents
+ALTER COLUMN role
+SET DEFAULT 'agent';
+
+DROP TYPE agent_role_old;
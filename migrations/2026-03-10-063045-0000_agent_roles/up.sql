@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TYPE agent_role ADD VALUE 'branch_manager';
+
+ALTER TYPE agent_role ADD VALUE 'marketing';
+
+ALTER TYPE agent_role ADD VALUE 'finance';
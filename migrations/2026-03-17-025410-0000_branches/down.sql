@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE agents
+DROP COLUMN branch_id;
+
+DROP TABLE branches;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE branches (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    name VARCHAR(255) NOT NULL UNIQUE,
+    address VARCHAR,
+    phone_number VARCHAR(255)
+);
+
+SELECT
+    diesel_manage_updated_at ('branches');
+
+ALTER TABLE agents
+ADD COLUMN branch_id INTEGER REFERENCES branches (id) ON DELETE SET NULL;
+
+CREATE INDEX agents_branch_id_idx ON agents (branch_id);
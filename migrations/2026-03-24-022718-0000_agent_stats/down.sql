@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP MATERIALIZED VIEW agent_daily_stats;
+
+ALTER TABLE leads
+DROP COLUMN responded_at;
+
+DROP TRIGGER set_sold_at ON properties;
+
+DROP FUNCTION properties_set_sold_at ();
+
+ALTER TABLE properties
+DROP COLUMN sold_at;
@@ -0,0 +1,114 @@
+-- Your SQL goes here
+ALTER TABLE properties
+ADD COLUMN sold_at TIMESTAMP;
+
+UPDATE properties
+SET
+    sold_at = updated_at
+WHERE
+    sold_status = 'sold';
+
+CREATE OR REPLACE FUNCTION properties_set_sold_at () RETURNS trigger AS $$
+BEGIN
+    IF NEW.sold_status = 'sold' AND (TG_OP = 'INSERT' OR OLD.sold_status IS DISTINCT FROM 'sold') THEN
+        NEW.sold_at := NOW();
+    ELSIF NEW.sold_status <> 'sold' THEN
+        NEW.sold_at := NULL;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_sold_at BEFORE INSERT
+OR
+UPDATE ON properties FOR EACH ROW
+EXECUTE PROCEDURE properties_set_sold_at ();
+
+ALTER TABLE leads
+ADD COLUMN responded_at TIMESTAMP;
+
+-- One row per agent per day, refreshed periodically by the api
+CREATE MATERIALIZED VIEW agent_daily_stats AS
+SELECT
+    user_id,
+    day,
+    SUM(new_listings)::INTEGER AS new_listings,
+    SUM(leads_received)::INTEGER AS leads_received,
+    SUM(responded_leads)::INTEGER AS responded_leads,
+    SUM(response_seconds)::BIGINT AS response_seconds,
+    SUM(sold_count)::INTEGER AS sold_count,
+    SUM(sold_value)::BIGINT AS sold_value,
+    SUM(rented_count)::INTEGER AS rented_count,
+    SUM(rented_value)::BIGINT AS rented_value
+FROM
+    (
+        SELECT
+            user_id,
+            created_at::DATE AS day,
+            1 AS new_listings,
+            0 AS leads_received,
+            0 AS responded_leads,
+            0 AS response_seconds,
+            0 AS sold_count,
+            0 AS sold_value,
+            0 AS rented_count,
+            0 AS rented_value
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+        UNION ALL
+        SELECT
+            user_id,
+            sold_at::DATE,
+            0,
+            0,
+            0,
+            0,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE 1
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE price
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 1
+                ELSE 0
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN price
+                ELSE 0
+            END
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+            AND sold_at IS NOT NULL
+        UNION ALL
+        SELECT
+            user_id,
+            created_at::DATE,
+            0,
+            1,
+            CASE
+                WHEN responded_at IS NULL THEN 0
+                ELSE 1
+            END,
+            COALESCE(EXTRACT(EPOCH FROM responded_at - created_at)::BIGINT, 0),
+            0,
+            0,
+            0,
+            0
+        FROM
+            leads
+        WHERE
+            is_deleted = FALSE
+    ) AS events
+GROUP BY
+    user_id,
+    day;
+
+-- required to refresh concurrently
+CREATE UNIQUE INDEX agent_daily_stats_user_id_day_idx ON agent_daily_stats (user_id, day);
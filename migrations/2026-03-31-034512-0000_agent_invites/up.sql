@@ -0,0 +1,21 @@
+-- Your SQL goes here
+CREATE TABLE agent_invites (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    email VARCHAR(255) NOT NULL,
+    role agent_role NOT NULL DEFAULT 'agent',
+    branch_id INTEGER REFERENCES branches (id) ON DELETE SET NULL,
+    -- sha256 of the token, the token itself is only shown once
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    invited_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    expires_at TIMESTAMP NOT NULL,
+    accepted_at TIMESTAMP,
+    agent_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    revoked_at TIMESTAMP
+);
+
+SELECT
+    diesel_manage_updated_at ('agent_invites');
+
+CREATE INDEX agent_invites_email_idx ON agent_invites (email);
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE uploads (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    original_filename VARCHAR(255),
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    -- [{ key, path, width, height, format }]
+    variants JSONB NOT NULL DEFAULT '[]'
+);
+
+SELECT
+    diesel_manage_updated_at ('uploads');
+
+CREATE INDEX uploads_created_at_idx ON uploads (created_at);
@@ -0,0 +1,14 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE watermark_jobs;
+
+DROP TYPE job_status;
+
+DROP TABLE watermark_settings;
+
+DROP TYPE watermark_position;
+
+DROP INDEX uploads_variants_idx;
+
+ALTER TABLE uploads
+DROP COLUMN original_key,
+DROP COLUMN is_watermarked;
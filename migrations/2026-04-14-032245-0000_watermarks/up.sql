@@ -0,0 +1,63 @@
+-- Your SQL goes here
+ALTER TABLE uploads
+ADD COLUMN original_key VARCHAR,
+ADD COLUMN is_watermarked BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- listing photos are checked against the sizes of watermarked uploads
+CREATE INDEX uploads_variants_idx ON uploads USING GIN (variants jsonb_path_ops);
+
+CREATE TYPE watermark_position AS ENUM (
+    'top_left',
+    'top_right',
+    'bottom_left',
+    'bottom_right',
+    'center'
+);
+
+-- Single row, edited by admins
+CREATE TABLE watermark_settings (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    logo_upload_id INTEGER REFERENCES uploads (id) ON DELETE SET NULL,
+    position watermark_position NOT NULL DEFAULT 'bottom_right',
+    opacity REAL NOT NULL DEFAULT 0.5 CHECK (
+        opacity >= 0
+        AND opacity <= 1
+    ),
+    -- logo width relative to the image width
+    scale REAL NOT NULL DEFAULT 0.2 CHECK (
+        scale > 0
+        AND scale <= 1
+    )
+);
+
+SELECT
+    diesel_manage_updated_at ('watermark_settings');
+
+INSERT INTO
+    watermark_settings DEFAULT
+VALUES;
+
+CREATE TYPE job_status AS ENUM ('running', 'done', 'failed');
+
+CREATE TABLE watermark_jobs (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    started_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    status job_status NOT NULL DEFAULT 'running',
+    total INTEGER NOT NULL DEFAULT 0,
+    processed INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    finished_at TIMESTAMP
+);
+
+SELECT
+    diesel_manage_updated_at ('watermark_jobs');
+
+-- one re-render at a time
+CREATE UNIQUE INDEX watermark_jobs_running_idx ON watermark_jobs (status)
+WHERE
+    status = 'running';
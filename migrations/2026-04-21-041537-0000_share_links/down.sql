@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE leads
+DROP COLUMN share_link_id;
+
+DROP TABLE share_links;
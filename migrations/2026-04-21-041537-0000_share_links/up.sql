@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE share_links (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    code VARCHAR(16) NOT NULL UNIQUE,
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    user_id uuid REFERENCES agents (id) ON DELETE SET NULL,
+    channel sold_channel NOT NULL,
+    campaign VARCHAR(255),
+    click_count INTEGER NOT NULL DEFAULT 0,
+    last_clicked_at TIMESTAMP
+);
+
+SELECT
+    diesel_manage_updated_at ('share_links');
+
+CREATE INDEX share_links_property_id_idx ON share_links (property_id);
+
+ALTER TABLE leads
+ADD COLUMN share_link_id INTEGER REFERENCES share_links (id) ON DELETE SET NULL;
+
+CREATE INDEX leads_share_link_id_idx ON leads (share_link_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE property_event_daily;
+
+DROP TABLE property_events;
+
+DROP TYPE property_event_type;
@@ -0,0 +1,35 @@
+-- Your SQL goes here
+CREATE TYPE property_event_type AS ENUM (
+    'view',
+    'gallery_open',
+    'phone_reveal',
+    'whatsapp_click',
+    'share'
+);
+
+-- Append-only, one row per visitor, listing, event type and day
+CREATE TABLE property_events (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    event_type property_event_type NOT NULL,
+    -- sha256 of the visitor id sent by the site, or of the ip and user agent
+    visitor_hash VARCHAR(64) NOT NULL,
+    -- sha256 of the ip, a single ip only counts as a few visitors a day
+    ip_hash VARCHAR(64) NOT NULL,
+    day DATE NOT NULL DEFAULT CURRENT_DATE
+);
+
+CREATE UNIQUE INDEX property_events_visitor_day_idx ON property_events (property_id, event_type, visitor_hash, day);
+
+CREATE INDEX property_events_ip_day_idx ON property_events (property_id, event_type, ip_hash, day);
+
+CREATE TABLE property_event_daily (
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    event_type property_event_type NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (property_id, day, event_type)
+);
+
+CREATE INDEX property_event_daily_event_type_idx ON property_event_daily (event_type, property_id);
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE bank_products (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    bank_id INTEGER NOT NULL REFERENCES banks (id) ON DELETE CASCADE,
+    name VARCHAR NOT NULL,
+    -- annual rates in percent, the fixed rate applies for the first fixed_period_months
+    fixed_rate NUMERIC(6, 3) NOT NULL CHECK (fixed_rate >= 0),
+    fixed_period_months INTEGER NOT NULL DEFAULT 0 CHECK (fixed_period_months >= 0),
+    floating_rate NUMERIC(6, 3) NOT NULL CHECK (floating_rate >= 0),
+    max_tenor_years INTEGER NOT NULL CHECK (max_tenor_years > 0),
+    min_down_payment_percent NUMERIC(5, 2) NOT NULL DEFAULT 0 CHECK (
+        min_down_payment_percent >= 0
+        AND min_down_payment_percent < 100
+    ),
+    -- percent of the loan
+    provision_fee_percent NUMERIC(5, 2) NOT NULL DEFAULT 0 CHECK (provision_fee_percent >= 0),
+    admin_fee BIGINT NOT NULL DEFAULT 0 CHECK (admin_fee >= 0),
+    appraisal_fee BIGINT NOT NULL DEFAULT 0 CHECK (appraisal_fee >= 0)
+);
+
+SELECT
+    diesel_manage_updated_at ('bank_products');
+
+CREATE INDEX bank_products_bank_id_idx ON bank_products (bank_id);
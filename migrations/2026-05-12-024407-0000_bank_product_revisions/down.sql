@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE bank_product_revisions;
+
+ALTER TABLE bank_products
+DROP CONSTRAINT bank_products_validity_check,
+DROP COLUMN revision,
+DROP COLUMN is_active,
+DROP COLUMN is_deleted,
+DROP COLUMN valid_from,
+DROP COLUMN valid_until;
@@ -0,0 +1,68 @@
+-- Your SQL goes here
+ALTER TABLE bank_products
+ADD COLUMN revision INTEGER NOT NULL DEFAULT 1,
+ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE,
+ADD COLUMN is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN valid_from DATE,
+ADD COLUMN valid_until DATE,
+ADD CONSTRAINT bank_products_validity_check CHECK (valid_from <= valid_until);
+
+-- Every version of a product, written on each change so past simulations can be reproduced
+CREATE TABLE bank_product_revisions (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    product_id INTEGER NOT NULL REFERENCES bank_products (id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    changed_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    name VARCHAR NOT NULL,
+    fixed_rate NUMERIC(6, 3) NOT NULL,
+    fixed_period_months INTEGER NOT NULL,
+    floating_rate NUMERIC(6, 3) NOT NULL,
+    max_tenor_years INTEGER NOT NULL,
+    min_down_payment_percent NUMERIC(5, 2) NOT NULL,
+    provision_fee_percent NUMERIC(5, 2) NOT NULL,
+    admin_fee BIGINT NOT NULL,
+    appraisal_fee BIGINT NOT NULL,
+    is_active BOOLEAN NOT NULL,
+    is_deleted BOOLEAN NOT NULL,
+    valid_from DATE,
+    valid_until DATE,
+    UNIQUE (product_id, revision)
+);
+
+INSERT INTO
+    bank_product_revisions (
+        product_id,
+        revision,
+        name,
+        fixed_rate,
+        fixed_period_months,
+        floating_rate,
+        max_tenor_years,
+        min_down_payment_percent,
+        provision_fee_percent,
+        admin_fee,
+        appraisal_fee,
+        is_active,
+        is_deleted,
+        valid_from,
+        valid_until
+    )
+SELECT
+    id,
+    revision,
+    name,
+    fixed_rate,
+    fixed_period_months,
+    floating_rate,
+    max_tenor_years,
+    min_down_payment_percent,
+    provision_fee_percent,
+    admin_fee,
+    appraisal_fee,
+    is_active,
+    is_deleted,
+    valid_from,
+    valid_until
+FROM
+    bank_products;
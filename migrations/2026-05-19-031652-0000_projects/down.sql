@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE properties
+DROP COLUMN project_id;
+
+DROP TABLE projects;
@@ -0,0 +1,28 @@
+-- Your SQL goes here
+CREATE TABLE projects (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    developer_id INTEGER NOT NULL REFERENCES developers (id) ON DELETE CASCADE,
+    slug VARCHAR NOT NULL UNIQUE,
+    name VARCHAR NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    province VARCHAR NOT NULL,
+    regency VARCHAR NOT NULL,
+    street VARCHAR NOT NULL,
+    gmap_iframe TEXT,
+    brochure_path VARCHAR,
+    completion_date DATE,
+    facilities JSONB NOT NULL DEFAULT '[]',
+    unit_types JSONB NOT NULL DEFAULT '[]'
+);
+
+SELECT
+    diesel_manage_updated_at ('projects');
+
+CREATE INDEX projects_developer_id_idx ON projects (developer_id);
+
+ALTER TABLE properties
+ADD COLUMN project_id INTEGER REFERENCES projects (id) ON DELETE SET NULL;
+
+CREATE INDEX properties_project_id_idx ON properties (project_id);
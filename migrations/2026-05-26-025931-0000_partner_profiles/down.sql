@@ -0,0 +1,15 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE developers
+DROP COLUMN description,
+DROP COLUMN website,
+DROP COLUMN contact_email,
+DROP COLUMN contact_phone,
+DROP COLUMN slug;
+
+ALTER TABLE banks
+DROP COLUMN description,
+DROP COLUMN website,
+DROP COLUMN contact_email,
+DROP COLUMN contact_phone,
+DROP COLUMN slug;
@@ -0,0 +1,97 @@
+-- Your SQL goes here
+
+ALTER TABLE developers
+ADD COLUMN description TEXT NOT NULL DEFAULT '',
+ADD COLUMN website VARCHAR,
+ADD COLUMN contact_email VARCHAR,
+ADD COLUMN contact_phone VARCHAR,
+ADD COLUMN slug VARCHAR UNIQUE;
+
+-- slugs from the names, with the id appended where two names give the same slug
+UPDATE developers
+SET
+    slug = named.slug
+FROM
+    (
+        SELECT
+            id,
+            CASE
+                WHEN COUNT(*) OVER (
+                    PARTITION BY
+                        base
+                ) > 1 THEN base || '-' || id
+                ELSE base
+            END AS slug
+        FROM
+            (
+                SELECT
+                    id,
+                    COALESCE(
+                        NULLIF(
+                            TRIM(
+                                BOTH '-'
+                                FROM
+                                    REGEXP_REPLACE(LOWER(name), '[^a-z0-9]+', '-', 'g')
+                            ),
+                            ''
+                        ),
+                        'developer'
+                    ) AS base
+                FROM
+                    developers
+            ) bases
+    ) named
+WHERE
+    developers.id = named.id;
+
+ALTER TABLE developers
+ALTER COLUMN slug
+SET NOT NULL;
+
+ALTER TABLE banks
+ADD COLUMN description TEXT NOT NULL DEFAULT '',
+ADD COLUMN website VARCHAR,
+ADD COLUMN contact_email VARCHAR,
+ADD COLUMN contact_phone VARCHAR,
+ADD COLUMN slug VARCHAR UNIQUE;
+
+-- slugs from the names, with the id appended where two names give the same slug
+UPDATE banks
+SET
+    slug = named.slug
+FROM
+    (
+        SELECT
+            id,
+            CASE
+                WHEN COUNT(*) OVER (
+                    PARTITION BY
+                        base
+                ) > 1 THEN base || '-' || id
+                ELSE base
+            END AS slug
+        FROM
+            (
+                SELECT
+                    id,
+                    COALESCE(
+                        NULLIF(
+                            TRIM(
+                                BOTH '-'
+                                FROM
+                                    REGEXP_REPLACE(LOWER(name), '[^a-z0-9]+', '-', 'g')
+                            ),
+                            ''
+                        ),
+                        'bank'
+                    ) AS base
+                FROM
+                    banks
+            ) bases
+    ) named
+WHERE
+    banks.id = named.id;
+
+ALTER TABLE banks
+ALTER COLUMN slug
+SET NOT NULL;
@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE banks
+DROP COLUMN archived_at;
+
+ALTER TABLE developers
+DROP COLUMN archived_at;
+
+ALTER TABLE projects
+DROP CONSTRAINT projects_developer_id_fkey,
+ADD CONSTRAINT projects_developer_id_fkey FOREIGN KEY (developer_id) REFERENCES developers (id) ON DELETE CASCADE;
+
+ALTER TABLE bank_products
+DROP CONSTRAINT bank_products_bank_id_fkey,
+ADD CONSTRAINT bank_products_bank_id_fkey FOREIGN KEY (bank_id) REFERENCES banks (id) ON DELETE CASCADE;
+
+ALTER TABLE properties
+DROP CONSTRAINT properties_developer_id_fkey,
+ADD CONSTRAINT properties_developer_id_fkey FOREIGN KEY (developer_id) REFERENCES developers (id) ON UPDATE CASCADE ON DELETE CASCADE,
+DROP CONSTRAINT properties_bank_id_fkey,
+ADD CONSTRAINT properties_bank_id_fkey FOREIGN KEY (bank_id) REFERENCES banks (id) ON UPDATE CASCADE ON DELETE CASCADE;
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+-- Deleting a developer or bank must not take its listings along
+ALTER TABLE properties
+DROP CONSTRAINT properties_developer_id_fkey,
+ADD CONSTRAINT properties_developer_id_fkey FOREIGN KEY (developer_id) REFERENCES developers (id) ON UPDATE CASCADE ON DELETE RESTRICT,
+DROP CONSTRAINT properties_bank_id_fkey,
+ADD CONSTRAINT properties_bank_id_fkey FOREIGN KEY (bank_id) REFERENCES banks (id) ON UPDATE CASCADE ON DELETE RESTRICT;
+
+-- Products are kept with their revisions for past simulations, a bank that has any is archived
+ALTER TABLE bank_products
+DROP CONSTRAINT bank_products_bank_id_fkey,
+ADD CONSTRAINT bank_products_bank_id_fkey FOREIGN KEY (bank_id) REFERENCES banks (id) ON DELETE RESTRICT;
+
+ALTER TABLE projects
+DROP CONSTRAINT projects_developer_id_fkey,
+ADD CONSTRAINT projects_developer_id_fkey FOREIGN KEY (developer_id) REFERENCES developers (id) ON DELETE RESTRICT;
+
+-- Archived ones are hidden from public lists
+ALTER TABLE developers
+ADD COLUMN archived_at TIMESTAMP;
+
+ALTER TABLE banks
+ADD COLUMN archived_at TIMESTAMP;
@@ -0,0 +1,28 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER refresh_price_idr ON exchange_rates;
+
+DROP FUNCTION exchange_rates_refresh_price_idr;
+
+DROP TRIGGER set_price_idr ON properties;
+
+DROP FUNCTION properties_set_price_idr;
+
+ALTER TABLE properties
+DROP COLUMN price_idr;
+
+DROP TABLE exchange_rates;
+
+-- Enum values can't be dropped, fails while listings still use them
+ALTER TYPE currency_unit
+RENAME TO currency_unit_old;
+
+CREATE TYPE currency_unit AS ENUM ('IDR', 'USD');
+
+ALTER TABLE properties
+ALTER COLUMN currency
+DROP DEFAULT,
+ALTER COLUMN currency TYPE currency_unit USING currency::TEXT::currency_unit,
+ALTER COLUMN currency
+SET DEFAULT 'IDR';
+
+DROP TYPE currency_unit_old;
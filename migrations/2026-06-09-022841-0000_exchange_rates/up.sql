@@ -0,0 +1,70 @@
+-- Your SQL goes here
+ALTER TYPE currency_unit
+ADD VALUE 'SGD';
+
+ALTER TYPE currency_unit
+ADD VALUE 'AUD';
+
+ALTER TYPE currency_unit
+ADD VALUE 'EUR';
+
+-- Rupiah for one unit of each currency, kept by admins or the import job
+CREATE TABLE exchange_rates (
+    currency currency_unit PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    idr_rate NUMERIC(18, 6) NOT NULL CHECK (idr_rate > 0),
+    source VARCHAR(255) NOT NULL DEFAULT 'manual',
+    updated_by uuid REFERENCES agents (id) ON DELETE SET NULL
+);
+
+SELECT
+    diesel_manage_updated_at ('exchange_rates');
+
+INSERT INTO
+    exchange_rates (currency, idr_rate, source)
+VALUES
+    ('IDR', 1, 'fixed');
+
+-- The price in rupiah for sorting and filtering, NULL while the currency has no rate
+ALTER TABLE properties
+ADD COLUMN price_idr BIGINT;
+
+UPDATE properties
+SET
+    price_idr = price
+WHERE
+    currency = 'IDR';
+
+CREATE INDEX properties_price_idr_idx ON properties (price_idr);
+
+CREATE OR REPLACE FUNCTION properties_set_price_idr () RETURNS trigger AS $$
+BEGIN
+    NEW.price_idr := ROUND(NEW.price * (SELECT idr_rate FROM exchange_rates WHERE currency = NEW.currency));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_price_idr BEFORE INSERT
+OR
+UPDATE OF price,
+currency ON properties FOR EACH ROW
+EXECUTE PROCEDURE properties_set_price_idr ();
+
+CREATE OR REPLACE FUNCTION exchange_rates_refresh_price_idr () RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE properties SET price_idr = NULL WHERE currency = OLD.currency;
+        RETURN OLD;
+    END IF;
+    UPDATE properties SET price_idr = ROUND(price * NEW.idr_rate) WHERE currency = NEW.currency;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER refresh_price_idr
+AFTER INSERT
+OR
+UPDATE OF idr_rate
+OR DELETE ON exchange_rates FOR EACH ROW
+EXECUTE PROCEDURE exchange_rates_refresh_price_idr ();
@@ -0,0 +1,136 @@
+-- This file should undo anything in `up.sql`
+DROP MATERIALIZED VIEW agent_daily_stats;
+
+CREATE OR REPLACE FUNCTION exchange_rates_refresh_price_idr () RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE properties SET price_idr = NULL WHERE currency = OLD.currency;
+        RETURN OLD;
+    END IF;
+    UPDATE properties SET price_idr = ROUND(price * NEW.idr_rate) WHERE currency = NEW.currency;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER set_normalised_prices ON properties;
+
+DROP FUNCTION properties_set_normalised_prices;
+
+CREATE OR REPLACE FUNCTION properties_set_price_idr () RETURNS trigger AS $$
+BEGIN
+    NEW.price_idr := ROUND(NEW.price * (SELECT idr_rate FROM exchange_rates WHERE currency = NEW.currency));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_price_idr BEFORE INSERT
+OR
+UPDATE OF price,
+currency ON properties FOR EACH ROW
+EXECUTE PROCEDURE properties_set_price_idr ();
+
+ALTER TABLE properties
+DROP COLUMN rent_price,
+DROP COLUMN min_rent_duration,
+DROP COLUMN monthly_rent_idr,
+DROP COLUMN min_rent_days;
+
+-- Enum values can't be dropped, fails while listings still use them
+ALTER TYPE rent_time_unit
+RENAME TO rent_time_unit_old;
+
+CREATE TYPE rent_time_unit AS ENUM ('monthly', 'yearly');
+
+ALTER TABLE properties
+ALTER COLUMN rent_time
+DROP DEFAULT,
+ALTER COLUMN rent_time TYPE rent_time_unit USING rent_time::TEXT::rent_time_unit,
+ALTER COLUMN rent_time
+SET DEFAULT 'monthly';
+
+DROP TYPE rent_time_unit_old;
+
+CREATE MATERIALIZED VIEW agent_daily_stats AS
+SELECT
+    user_id,
+    day,
+    SUM(new_listings)::INTEGER AS new_listings,
+    SUM(leads_received)::INTEGER AS leads_received,
+    SUM(responded_leads)::INTEGER AS responded_leads,
+    SUM(response_seconds)::BIGINT AS response_seconds,
+    SUM(sold_count)::INTEGER AS sold_count,
+    SUM(sold_value)::BIGINT AS sold_value,
+    SUM(rented_count)::INTEGER AS rented_count,
+    SUM(rented_value)::BIGINT AS rented_value
+FROM
+    (
+        SELECT
+            user_id,
+            created_at::DATE AS day,
+            1 AS new_listings,
+            0 AS leads_received,
+            0 AS responded_leads,
+            0 AS response_seconds,
+            0 AS sold_count,
+            0 AS sold_value,
+            0 AS rented_count,
+            0 AS rented_value
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+        UNION ALL
+        SELECT
+            user_id,
+            sold_at::DATE,
+            0,
+            0,
+            0,
+            0,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE 1
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE price
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 1
+                ELSE 0
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN price
+                ELSE 0
+            END
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+            AND sold_at IS NOT NULL
+        UNION ALL
+        SELECT
+            user_id,
+            created_at::DATE,
+            0,
+            1,
+            CASE
+                WHEN responded_at IS NULL THEN 0
+                ELSE 1
+            END,
+            COALESCE(EXTRACT(EPOCH FROM responded_at - created_at)::BIGINT, 0),
+            0,
+            0,
+            0,
+            0
+        FROM
+            leads
+        WHERE
+            is_deleted = FALSE
+    ) AS events
+GROUP BY
+    user_id,
+    day;
+
+-- required to refresh concurrently
+CREATE UNIQUE INDEX agent_daily_stats_user_id_day_idx ON agent_daily_stats (user_id, day);
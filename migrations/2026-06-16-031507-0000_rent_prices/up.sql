@@ -0,0 +1,169 @@
+-- Your SQL goes here
+ALTER TYPE rent_time_unit
+ADD VALUE 'daily' BEFORE 'monthly';
+
+ALTER TYPE rent_time_unit
+ADD VALUE 'weekly' BEFORE 'monthly';
+
+ALTER TYPE rent_time_unit
+ADD VALUE 'quarterly' BEFORE 'yearly';
+
+-- rent_price is the rent of a listing for sale or rent, price is then the sale price.
+-- min_rent_duration counts rent_time periods.
+ALTER TABLE properties
+ADD COLUMN rent_price BIGINT CHECK (rent_price >= 0),
+ADD COLUMN min_rent_duration INTEGER CHECK (min_rent_duration > 0),
+ADD COLUMN monthly_rent_idr BIGINT,
+ADD COLUMN min_rent_days INTEGER;
+
+CREATE INDEX properties_monthly_rent_idr_idx ON properties (monthly_rent_idr);
+
+DROP TRIGGER set_price_idr ON properties;
+
+DROP FUNCTION properties_set_price_idr;
+
+-- The new rent_time values can't be used before this migration is committed,
+-- so they are compared as text
+CREATE OR REPLACE FUNCTION properties_set_normalised_prices () RETURNS trigger AS $$
+DECLARE
+    rate NUMERIC := (SELECT idr_rate FROM exchange_rates WHERE currency = NEW.currency);
+    rent BIGINT := CASE NEW.purchase_status
+        WHEN 'for_rent' THEN NEW.price
+        WHEN 'for_sale_or_rent' THEN COALESCE(NEW.rent_price, NEW.price)
+    END;
+    rent_time TEXT := COALESCE(NEW.rent_time::TEXT, 'monthly');
+BEGIN
+    NEW.price_idr := ROUND(NEW.price * rate);
+    NEW.monthly_rent_idr := ROUND(rent * rate * CASE rent_time
+        WHEN 'daily' THEN 365 / 12.0
+        WHEN 'weekly' THEN 52 / 12.0
+        WHEN 'monthly' THEN 1
+        WHEN 'quarterly' THEN 1 / 3.0
+        WHEN 'yearly' THEN 1 / 12.0
+    END);
+    NEW.min_rent_days := CASE WHEN rent IS NOT NULL THEN NEW.min_rent_duration * CASE rent_time
+        WHEN 'daily' THEN 1
+        WHEN 'weekly' THEN 7
+        WHEN 'monthly' THEN 30
+        WHEN 'quarterly' THEN 91
+        WHEN 'yearly' THEN 365
+    END END;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER set_normalised_prices BEFORE INSERT
+OR
+UPDATE OF price,
+currency,
+purchase_status,
+rent_price,
+rent_time,
+min_rent_duration ON properties FOR EACH ROW
+EXECUTE PROCEDURE properties_set_normalised_prices ();
+
+-- Touching currency recomputes the listing through set_normalised_prices
+CREATE OR REPLACE FUNCTION exchange_rates_refresh_price_idr () RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE properties SET currency = currency WHERE currency = OLD.currency;
+        RETURN OLD;
+    END IF;
+    UPDATE properties SET currency = currency WHERE currency = NEW.currency;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+UPDATE properties
+SET
+    currency = currency
+WHERE
+    purchase_status <> 'for_sale';
+
+-- Sales and rents are summed in rupiah, rents as their monthly amount
+DROP MATERIALIZED VIEW agent_daily_stats;
+
+CREATE MATERIALIZED VIEW agent_daily_stats AS
+SELECT
+    user_id,
+    day,
+    SUM(new_listings)::INTEGER AS new_listings,
+    SUM(leads_received)::INTEGER AS leads_received,
+    SUM(responded_leads)::INTEGER AS responded_leads,
+    SUM(response_seconds)::BIGINT AS response_seconds,
+    SUM(sold_count)::INTEGER AS sold_count,
+    SUM(sold_value)::BIGINT AS sold_value,
+    SUM(rented_count)::INTEGER AS rented_count,
+    SUM(rented_value)::BIGINT AS rented_value
+FROM
+    (
+        SELECT
+            user_id,
+            created_at::DATE AS day,
+            1 AS new_listings,
+            0 AS leads_received,
+            0 AS responded_leads,
+            0 AS response_seconds,
+            0 AS sold_count,
+            0 AS sold_value,
+            0 AS rented_count,
+            0 AS rented_value
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+        UNION ALL
+        SELECT
+            user_id,
+            sold_at::DATE,
+            0,
+            0,
+            0,
+            0,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE 1
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 0
+                ELSE COALESCE(price_idr, 0)
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN 1
+                ELSE 0
+            END,
+            CASE
+                WHEN purchase_status = 'for_rent' THEN COALESCE(monthly_rent_idr, 0)
+                ELSE 0
+            END
+        FROM
+            properties
+        WHERE
+            is_deleted = FALSE
+            AND sold_at IS NOT NULL
+        UNION ALL
+        SELECT
+            user_id,
+            created_at::DATE,
+            0,
+            1,
+            CASE
+                WHEN responded_at IS NULL THEN 0
+                ELSE 1
+            END,
+            COALESCE(EXTRACT(EPOCH FROM responded_at - created_at)::BIGINT, 0),
+            0,
+            0,
+            0,
+            0
+        FROM
+            leads
+        WHERE
+            is_deleted = FALSE
+    ) AS events
+GROUP BY
+    user_id,
+    day;
+
+-- required to refresh concurrently
+CREATE UNIQUE INDEX agent_daily_stats_user_id_day_idx ON agent_daily_stats (user_id, day);
@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE leads
+DROP COLUMN check_in,
+DROP COLUMN check_out;
+
+DROP TABLE property_seasonal_prices;
+
+DROP TABLE property_availability;
+
+DROP TABLE property_calendar_feeds;
+
+DROP TYPE availability_kind;
@@ -0,0 +1,63 @@
+-- Your SQL goes here
+CREATE TYPE availability_kind AS ENUM ('blocked', 'booked');
+
+-- External calendars (Airbnb, Booking.com, ...) synced into the availability of a listing
+CREATE TABLE property_calendar_feeds (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    url TEXT NOT NULL,
+    last_synced_at TIMESTAMP,
+    last_error TEXT
+);
+
+SELECT
+    diesel_manage_updated_at ('property_calendar_feeds');
+
+-- Days a listing can't be rented, ends_on is the check-out day and isn't included
+CREATE TABLE property_availability (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    kind availability_kind NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    note TEXT,
+    lead_id INTEGER REFERENCES leads (id) ON DELETE SET NULL,
+    -- set on ranges imported from a feed, replaced on every sync
+    feed_id INTEGER REFERENCES property_calendar_feeds (id) ON DELETE CASCADE,
+    external_uid VARCHAR(255),
+    CHECK (starts_on < ends_on)
+);
+
+SELECT
+    diesel_manage_updated_at ('property_availability');
+
+CREATE INDEX property_availability_property_id_idx ON property_availability (property_id, starts_on);
+
+-- Rent per rent_time of the listing for the nights inside the season
+CREATE TABLE property_seasonal_prices (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    label VARCHAR(255) NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    price BIGINT NOT NULL CHECK (price >= 0),
+    CHECK (starts_on < ends_on)
+);
+
+SELECT
+    diesel_manage_updated_at ('property_seasonal_prices');
+
+CREATE INDEX property_seasonal_prices_property_id_idx ON property_seasonal_prices (property_id, starts_on);
+
+-- Booking requests are leads with the dates asked for
+ALTER TABLE leads
+ADD COLUMN check_in DATE,
+ADD COLUMN check_out DATE,
+ADD CONSTRAINT leads_stay_check CHECK (check_in < check_out);
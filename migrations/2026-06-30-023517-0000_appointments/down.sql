@@ -0,0 +1,12 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE appointments;
+
+DROP TYPE appointment_status;
+
+DROP TABLE agent_availability_slots;
+
+DROP TABLE outbox_messages;
+
+DROP TYPE outbox_status;
+
+DROP TYPE outbox_channel;
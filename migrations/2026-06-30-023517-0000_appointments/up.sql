@@ -0,0 +1,87 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+
+CREATE TYPE outbox_channel AS ENUM ('email', 'whatsapp');
+
+CREATE TYPE outbox_status AS ENUM ('pending', 'sending', 'sent', 'failed', 'cancelled');
+
+-- Messages saved with the change that triggers them and delivered later by the dispatcher
+CREATE TABLE outbox_messages (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    channel outbox_channel NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    attachment_name VARCHAR(255),
+    attachment_type VARCHAR(255),
+    attachment TEXT,
+    -- groups messages so the pending ones can be cancelled together, e.g. appointment-1-reminder
+    key VARCHAR(255),
+    send_after TIMESTAMP NOT NULL DEFAULT NOW (),
+    status outbox_status NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    sent_at TIMESTAMP
+);
+
+SELECT
+    diesel_manage_updated_at ('outbox_messages');
+
+CREATE INDEX outbox_messages_pending_idx ON outbox_messages (send_after)
+WHERE
+    status = 'pending';
+
+CREATE INDEX outbox_messages_key_idx ON outbox_messages (key);
+
+-- Weekly hours an agent takes viewings, weekday 1 is Monday
+CREATE TABLE agent_availability_slots (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    user_id uuid NOT NULL REFERENCES agents (id) ON DELETE CASCADE,
+    weekday SMALLINT NOT NULL CHECK (weekday BETWEEN 1 AND 7),
+    starts_at TIME NOT NULL,
+    ends_at TIME NOT NULL,
+    CHECK (starts_at < ends_at)
+);
+
+SELECT
+    diesel_manage_updated_at ('agent_availability_slots');
+
+CREATE INDEX agent_availability_slots_user_id_idx ON agent_availability_slots (user_id, weekday);
+
+CREATE TYPE appointment_status AS ENUM ('requested', 'confirmed', 'cancelled');
+
+-- Viewings of a listing, arranged from a lead
+CREATE TABLE appointments (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    lead_id INTEGER NOT NULL REFERENCES leads (id) ON DELETE CASCADE,
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    user_id uuid NOT NULL REFERENCES agents (id) ON DELETE CASCADE,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NOT NULL,
+    status appointment_status NOT NULL DEFAULT 'requested',
+    note TEXT,
+    cancel_reason TEXT,
+    -- bumped on every change, so calendars replace the invite they got before
+    sequence INTEGER NOT NULL DEFAULT 0,
+    CHECK (starts_at < ends_at),
+    -- an agent takes one viewing at a time, also when two requests race past the checks
+    CONSTRAINT appointments_no_overlap EXCLUDE USING gist (
+        user_id WITH =,
+        tsrange (starts_at, ends_at) WITH &&
+    )
+    WHERE
+        (status IN ('requested', 'confirmed'))
+);
+
+SELECT
+    diesel_manage_updated_at ('appointments');
+
+CREATE INDEX appointments_user_id_idx ON appointments (user_id, starts_at);
+
+CREATE INDEX appointments_lead_id_idx ON appointments (lead_id);
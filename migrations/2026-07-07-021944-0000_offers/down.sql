@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE offers;
+
+DROP TYPE payment_method;
+
+DROP TYPE offer_party;
+
+DROP TYPE offer_status;
@@ -0,0 +1,55 @@
+-- Your SQL goes here
+CREATE TYPE offer_status AS ENUM (
+    'submitted',
+    'countered',
+    'accepted',
+    'rejected',
+    'withdrawn'
+);
+
+CREATE TYPE offer_party AS ENUM ('buyer', 'seller');
+
+CREATE TYPE payment_method AS ENUM ('cash', 'kpr');
+
+-- Offers on a listing, a counter-offer points at the offer it answers
+CREATE TABLE offers (
+    id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW (),
+    property_id INTEGER NOT NULL REFERENCES properties (id) ON DELETE CASCADE,
+    lead_id INTEGER NOT NULL REFERENCES leads (id) ON DELETE CASCADE,
+    parent_id INTEGER REFERENCES offers (id) ON DELETE CASCADE,
+    party offer_party NOT NULL,
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    currency currency_unit NOT NULL,
+    down_payment BIGINT CHECK (down_payment >= 0),
+    payment_method payment_method NOT NULL,
+    bank_id INTEGER REFERENCES banks (id) ON DELETE SET NULL,
+    terms TEXT,
+    expires_at TIMESTAMP,
+    status offer_status NOT NULL DEFAULT 'submitted',
+    responded_at TIMESTAMP,
+    recorded_by uuid REFERENCES agents (id) ON DELETE SET NULL,
+    CHECK (
+        payment_method = 'kpr'
+        OR bank_id IS NULL
+    )
+);
+
+SELECT
+    diesel_manage_updated_at ('offers');
+
+CREATE INDEX offers_property_id_idx ON offers (property_id);
+
+CREATE INDEX offers_lead_id_idx ON offers (lead_id);
+
+CREATE UNIQUE INDEX offers_parent_id_idx ON offers (parent_id);
+
+CREATE INDEX offers_open_idx ON offers (created_at)
+WHERE
+    status = 'submitted';
+
+-- A lead negotiates one offer at a time on a listing
+CREATE UNIQUE INDEX offers_open_negotiation_idx ON offers (lead_id, property_id)
+WHERE
+    status = 'submitted';
@@ -11,6 +11,9 @@ use std::io::Write;
 pub enum AgentRole {
     Admin,
     Agent,
+    BranchManager,
+    Marketing,
+    Finance,
 }
 
 impl ToSql<sql_types::AgentRole, Pg> for AgentRole {
@@ -18,6 +21,9 @@ impl ToSql<sql_types::AgentRole, Pg> for AgentRole {
         match *self {
             AgentRole::Admin => out.write_all(b"admin")?,
             AgentRole::Agent => out.write_all(b"agent")?,
+            AgentRole::BranchManager => out.write_all(b"branch_manager")?,
+            AgentRole::Marketing => out.write_all(b"marketing")?,
+            AgentRole::Finance => out.write_all(b"finance")?,
         }
         Ok(IsNull::No)
     }
@@ -28,6 +34,9 @@ impl FromSql<sql_types::AgentRole, Pg> for AgentRole {
         match bytes.as_bytes() {
             b"admin" => Ok(AgentRole::Admin),
             b"agent" => Ok(AgentRole::Agent),
+            b"branch_manager" => Ok(AgentRole::BranchManager),
+            b"marketing" => Ok(AgentRole::Marketing),
+            b"finance" => Ok(AgentRole::Finance),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -1,4 +1,11 @@
-use super::model::Agent;
+use super::invite::{AgentInvite, AgentInviteStatus, CreatedAgentInvite};
+use super::model::{Agent, AgentProfile};
+use super::permission::{authorize, can, can_on, scope, Action, Resource};
+use super::stats::{
+    check_period, period, AgentStatsReport, LeaderboardEntry, LeaderboardSort, StatsInterval,
+    MAX_PERIOD_DAYS,
+};
+use super::transfer::AgentTransfer;
 use super::AgentRole;
 use crate::middleware::{JsonFindResponse, Session};
 use crate::{
@@ -6,61 +13,63 @@ use crate::{
     middleware::{AxumResponse, JsonResponse},
     schema,
 };
-use axum::extract::{Json, Path, Query, Request, State};
-use axum::http::{HeaderMap, Method};
-use axum::middleware::{from_fn_with_state, Next};
-use axum::response::Response;
+use axum::extract::{Json, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{delete, get, post, put};
 use axum::Router;
 use diesel::prelude::{AsChangeset, Insertable};
 use serde::Deserialize;
 
-async fn middleware(
-    State(pool): State<DbPool>,
-    req: Request,
-    next: Next,
-) -> Result<Response, AxumResponse<String>> {
-    let headers = req.headers();
-    let user_id = Session::extract_session_user_id(&headers);
-
-    let agent = match Agent::find_by_user_id(&pool, &user_id) {
-        Ok(agent) => agent,
-        Err(err) => {
-            let response = JsonResponse::send(403, None, Some(err.to_string()));
-            return Err(response);
-        }
-    };
-
-    match agent.role {
-        AgentRole::Admin => Ok(next.run(req).await),
-        AgentRole::Agent if req.method() == &Method::PUT => Ok(next.run(req).await),
-        _ => {
-            let response = JsonResponse::send(403, None, None);
-            Err(response)
-        }
-    }
-}
-
 async fn find_agent_by_supertokens_user_id(
     State(pool): State<DbPool>,
     Path(id): Path<String>,
 ) -> AxumResponse<Agent> {
     match Agent::find_by_supertokens_user_id(&pool, &id) {
+        Ok(agent) if !agent.is_active => {
+            JsonResponse::send(403, None, Some("Agent is deactivated".to_string()))
+        }
         Ok(agent) => JsonResponse::send(200, Some(agent), None),
         Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
+async fn find_agent_by_slug(State(pool): State<DbPool>, Path(slug): Path<String>) -> Response {
+    match Agent::find_by_slug(&pool, &slug) {
+        // renamed agents: point the old url at the current one
+        Ok(agent) if agent.slug != slug => {
+            let location = format!("/agents/by-slug/{}", agent.slug);
+            let (_, body) = JsonResponse::send(301, Some(AgentProfile::from(agent)), None);
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                [(header::LOCATION, location)],
+                body,
+            )
+                .into_response()
+        }
+        Ok(agent) => JsonResponse::send(200, Some(AgentProfile::from(agent)), None).into_response(),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::<AgentProfile>::send(404, None, Some("Agent not found".to_string()))
+                .into_response()
+        }
+        Err(err) => {
+            JsonResponse::<AgentProfile>::send(500, None, Some(err.to_string())).into_response()
+        }
+    }
+}
+
 #[derive(Deserialize, Insertable, Clone)]
 #[diesel(table_name = schema::agents)]
 pub struct CreateAgentPayload {
     supertokens_user_id: String,
-    fullname: String,
+    pub(super) fullname: String,
     email: String,
     phone_number: String,
     profile_picture_url: Option<String>,
     instagram: Option<String>,
     description: Option<String>,
+    role: Option<AgentRole>,
+    branch_id: Option<i32>,
 }
 
 async fn create_agent(
@@ -68,7 +77,10 @@ async fn create_agent(
     headers: HeaderMap,
     Json(payload): Json<CreateAgentPayload>,
 ) -> AxumResponse<Agent> {
-    let user_id = Session::extract_session_user_id(&headers);
+    let user_id = match authorize(&pool, &headers, Action::Create, Resource::Agent) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
 
     match Agent::find_by_email(&pool, &payload.email) {
         Ok(_) => JsonResponse::send(400, None, Some("Email already exists".to_string())),
@@ -88,11 +100,17 @@ pub(super) const PAGE_SIZE: i64 = 15;
 pub struct FindAgentQuery {
     pub name_or_email: Option<String>,
     pub page: Option<i64>,
+    pub branch_id: Option<i32>,
 }
 async fn find_agents(
     State(pool): State<DbPool>,
+    headers: HeaderMap,
     Query(query): Query<FindAgentQuery>,
 ) -> AxumResponse<JsonFindResponse<Vec<Agent>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::Agent) {
+        return response;
+    }
+
     let agents = match Agent::find_many(&pool, &None, &None, &query) {
         Ok(agents) => agents,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
@@ -118,58 +136,405 @@ async fn find_agents(
 #[diesel(table_name = schema::agents)]
 pub struct UpdateAgentPayload {
     profile_picture_url: Option<String>,
-    fullname: Option<String>,
+    pub(super) fullname: Option<String>,
     phone_number: Option<String>,
     instagram: Option<String>,
     description: Option<String>,
+    role: Option<AgentRole>,
+    branch_id: Option<i32>,
 }
 
 // for agents to update their information themselves
 async fn update_agent(
     State(pool): State<DbPool>,
     headers: HeaderMap,
-    Path(id): Path<String>,
+    Path(agent_id): Path<uuid::Uuid>,
     Json(payload): Json<UpdateAgentPayload>,
 ) -> AxumResponse<Agent> {
-    let agent_id = uuid::Uuid::parse_str(&id).expect("Invalid agent id");
     let user_id = Session::extract_session_user_id(&headers);
+
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(err) => return JsonResponse::send(403, None, Some(err.to_string())),
+    };
+
+    let can_update_agents = can(&agent, Action::Update, Resource::Agent);
+    let is_allowed = can_update_agents
+        || (agent_id == user_id && can(&agent, Action::Update, Resource::OwnProfile));
+    if !is_allowed {
+        return JsonResponse::send(403, None, None);
+    }
+
     let mut new_payload = payload.clone();
     if let Some(fullname) = new_payload.fullname {
         new_payload.fullname = Some(fullname.to_lowercase());
     }
+    // nobody hands themselves a role
+    if !can_update_agents || agent_id == user_id {
+        new_payload.role = None;
+    }
+    if !can_update_agents {
+        new_payload.branch_id = None;
+    }
+
+    match Agent::update_agent(&pool, &agent_id, &new_payload) {
+        Ok(agent) => JsonResponse::send(200, Some(agent), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Agents are deactivated rather than deleted so their listings and leads survive
+async fn delete_agent(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(agent_id): Path<uuid::Uuid>,
+) -> AxumResponse<Agent> {
+    let user_id = match authorize(&pool, &headers, Action::Delete, Resource::Agent) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
+
     if agent_id == user_id {
-        match Agent::update_agent(&pool, &agent_id, &new_payload) {
-            Ok(agent) => JsonResponse::send(200, Some(agent), None),
-            Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
-        }
-    } else {
-        match Agent::find_by_user_id(&pool, &user_id) {
-            Ok(agent) => match agent.role {
-                AgentRole::Admin => match Agent::update_agent(&pool, &agent_id, &new_payload) {
-                    Ok(agent) => JsonResponse::send(200, Some(agent), None),
-                    Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
-                },
-                _ => JsonResponse::send(403, None, None),
-            },
-            Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+        return JsonResponse::send(400, None, Some("Can't deactivate yourself".to_string()));
+    }
+
+    match Agent::deactivate(&pool, &agent_id, &user_id) {
+        Ok(agent) => JsonResponse::send(200, Some(agent), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Agent not found".to_string()))
         }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
-async fn delete_agent(State(pool): State<DbPool>, Path(id): Path<String>) -> AxumResponse<Agent> {
-    let agent_id = uuid::Uuid::parse_str(&id).expect("Invalid agent id");
-    match Agent::delete_agent(&pool, &agent_id) {
+async fn activate_agent(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(agent_id): Path<uuid::Uuid>,
+) -> AxumResponse<Agent> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::Agent) {
+        return response;
+    }
+
+    match Agent::activate(&pool, &agent_id) {
         Ok(agent) => JsonResponse::send(200, Some(agent), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Agent not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TransferAgentPayload {
+    to_user_id: uuid::Uuid,
+}
+
+async fn transfer_agent(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(agent_id): Path<uuid::Uuid>,
+    Json(payload): Json<TransferAgentPayload>,
+) -> AxumResponse<AgentTransfer> {
+    let user_id = match authorize(&pool, &headers, Action::Update, Resource::Agent) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
+
+    if agent_id == payload.to_user_id {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Can't transfer to the same agent".to_string()),
+        );
+    }
+
+    if let Err(err) = Agent::find_by_user_id(&pool, &agent_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Agent not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    match Agent::find_by_user_id(&pool, &payload.to_user_id) {
+        Ok(agent) if agent.is_active => {}
+        _ => return JsonResponse::send(400, None, Some("Target agent not found".to_string())),
+    }
+
+    match AgentTransfer::transfer(&pool, &agent_id, &payload.to_user_id, &user_id) {
+        Ok(transfer) => JsonResponse::send(200, Some(transfer), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize, Insertable)]
+#[diesel(table_name = schema::agent_invites)]
+pub struct CreateAgentInvitePayload {
+    pub(super) email: String,
+    role: AgentRole,
+    branch_id: Option<i32>,
+}
+
+async fn create_agent_invite(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateAgentInvitePayload>,
+) -> AxumResponse<CreatedAgentInvite> {
+    let user_id = match authorize(&pool, &headers, Action::Create, Resource::Agent) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
+
+    if Agent::find_by_email(&pool, &payload.email).is_ok() {
+        return JsonResponse::send(400, None, Some("Email already exists".to_string()));
+    }
+
+    match AgentInvite::create(&pool, &user_id, &payload) {
+        Ok(invite) => JsonResponse::send(201, Some(invite), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FindAgentInviteQuery {
+    status: Option<AgentInviteStatus>,
+    page: Option<i64>,
+}
+
+async fn find_agent_invites(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<FindAgentInviteQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<AgentInvite>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::Create, Resource::Agent) {
+        return response;
+    }
+
+    let invites = match AgentInvite::find_many(&pool, &query.status, &query.page) {
+        Ok(invites) => invites,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let total_invite_count = match AgentInvite::count_find_many_rows(&pool, &query.status) {
+        Ok(count) => count,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(JsonFindResponse {
+            data: invites,
+            total_pages: (total_invite_count / PAGE_SIZE) + 1,
+            total_data: total_invite_count,
+        }),
+        None,
+    )
+}
+
+async fn revoke_agent_invite(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<AgentInvite> {
+    if let Err(response) = authorize(&pool, &headers, Action::Create, Resource::Agent) {
+        return response;
+    }
+
+    match AgentInvite::revoke(&pool, &id) {
+        Ok(invite) => JsonResponse::send(200, Some(invite), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Pending invite not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AcceptAgentInvitePayload {
+    token: String,
+    fullname: String,
+    phone_number: String,
+    profile_picture_url: Option<String>,
+    instagram: Option<String>,
+    description: Option<String>,
+}
+
+// The invite token stands in for the admin session. The account comes from the
+// SuperTokens session of whoever signed up, and must be the invited email's
+async fn accept_agent_invite(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<AcceptAgentInvitePayload>,
+) -> AxumResponse<Agent> {
+    let user = match Session::find_supertokens_user(&headers).await {
+        Some(user) => user,
+        None => return JsonResponse::send(401, None, None),
+    };
+
+    let invalid_invite = || {
+        JsonResponse::send(
+            400,
+            None,
+            Some("Invite is invalid or has expired".to_string()),
+        )
+    };
+
+    let invite = match AgentInvite::find_by_token(&pool, &payload.token) {
+        Ok(invite) => invite,
+        Err(diesel::result::Error::NotFound) => return invalid_invite(),
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    if !user
+        .emails
+        .iter()
+        .any(|email| email.eq_ignore_ascii_case(&invite.email))
+    {
+        return JsonResponse::send(
+            403,
+            None,
+            Some("Sign up with the email the invite was sent to".to_string()),
+        );
+    }
+    if Agent::find_by_email(&pool, &invite.email).is_ok() {
+        return JsonResponse::send(400, None, Some("Email already exists".to_string()));
     }
+    if Agent::find_by_supertokens_user_id(&pool, &user.id).is_ok() {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("SuperTokens user is already linked to an agent".to_string()),
+        );
+    }
+
+    let agent_payload = CreateAgentPayload {
+        supertokens_user_id: user.id,
+        fullname: payload.fullname.to_lowercase(),
+        email: invite.email,
+        phone_number: payload.phone_number,
+        profile_picture_url: payload.profile_picture_url,
+        instagram: payload.instagram,
+        description: payload.description,
+        role: Some(invite.role),
+        branch_id: invite.branch_id,
+    };
+
+    match AgentInvite::accept(&pool, &invite.id, &agent_payload) {
+        Ok(agent) => JsonResponse::send(201, Some(agent), None),
+        Err(diesel::result::Error::NotFound) => invalid_invite(),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AgentStatsQuery {
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    interval: Option<StatsInterval>,
+}
+
+// Agents see their own numbers, managers and admins those of the agents they report on
+async fn find_agent_stats(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(agent_id): Path<uuid::Uuid>,
+    Query(query): Query<AgentStatsQuery>,
+) -> AxumResponse<AgentStatsReport> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(err) => return JsonResponse::send(403, None, Some(err.to_string())),
+    };
+
+    if agent_id != user_id {
+        let owner = match Agent::find_by_user_id(&pool, &agent_id) {
+            Ok(owner) => owner,
+            Err(diesel::result::Error::NotFound) => {
+                return JsonResponse::send(404, None, Some("Agent not found".to_string()))
+            }
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        };
+        if !can_on(&agent, Action::View, Resource::Report, &owner) {
+            return JsonResponse::send(403, None, None);
+        }
+    }
+
+    let (from, to) = period(query.from, query.to);
+    if let Err(message) = check_period(from, to, MAX_PERIOD_DAYS) {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    let interval = query.interval.unwrap_or_default();
+    match AgentStatsReport::find(&pool, &agent_id, from, to, interval) {
+        Ok(report) => JsonResponse::send(200, Some(report), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    sort: Option<LeaderboardSort>,
+}
+
+async fn find_leaderboard(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<LeaderboardQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<LeaderboardEntry>>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Report) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
+        Err(err) => return JsonResponse::send(403, None, Some(err.to_string())),
+    };
+
+    let (from, to) = period(query.from, query.to);
+    if let Err(message) = check_period(from, to, MAX_PERIOD_DAYS) {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    let entries = match LeaderboardEntry::find_many(
+        &pool,
+        &scope,
+        from,
+        to,
+        query.sort.unwrap_or_default(),
+    ) {
+        Ok(entries) => entries,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(JsonFindResponse {
+            total_data: entries.len() as i64,
+            data: entries,
+            total_pages: 1,
+        }),
+        None,
+    )
 }
 
-pub fn agent_routes(pool: DbPool) -> Router<DbPool> {
+pub fn agent_routes() -> Router<DbPool> {
     Router::new()
         .route("/", post(create_agent))
         .route("/", get(find_agents))
         .route("/{id}", delete(delete_agent))
         .route("/{id}", put(update_agent))
-        .layer(from_fn_with_state(pool.clone(), middleware))
+        .route("/{id}/activate", post(activate_agent))
+        .route("/{id}/transfer", post(transfer_agent))
+        .route("/{id}/stats", get(find_agent_stats))
+        .route("/leaderboard", get(find_leaderboard))
         .route("/supertokens/{id}", get(find_agent_by_supertokens_user_id))
+        .route("/by-slug/{slug}", get(find_agent_by_slug))
+        .route("/invites", post(create_agent_invite))
+        .route("/invites", get(find_agent_invites))
+        .route("/invites/{id}", delete(revoke_agent_invite))
+        .route("/accept-invite", post(accept_agent_invite))
 }
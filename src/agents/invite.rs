@@ -0,0 +1,198 @@
+use diesel::dsl::{now, IntervalDsl};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use super::agent_role::AgentRole;
+use super::controller::{CreateAgentInvitePayload, CreateAgentPayload, PAGE_SIZE};
+use super::model::Agent;
+use crate::{db::DbPool, digest::sha256_hex, schema::agent_invites};
+
+const INVITE_TTL_DAYS: i32 = 7;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentInviteStatus {
+    Pending,
+    Accepted,
+    Revoked,
+    Expired,
+}
+
+// A single-use link for someone to sign up as an agent
+#[derive(Debug, Serialize, Queryable)]
+pub struct AgentInvite {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub email: String,
+    pub role: AgentRole,
+    pub branch_id: Option<i32>,
+    #[allow(dead_code)]
+    #[serde(skip)]
+    token_hash: String,
+    invited_by: Option<uuid::Uuid>,
+    expires_at: chrono::NaiveDateTime,
+    accepted_at: Option<chrono::NaiveDateTime>,
+    agent_id: Option<uuid::Uuid>,
+    revoked_at: Option<chrono::NaiveDateTime>,
+}
+
+// Only returned when the invite is created, afterwards just the hash is known
+#[derive(Debug, Serialize)]
+pub struct CreatedAgentInvite {
+    #[serde(flatten)]
+    invite: AgentInvite,
+    token: String,
+}
+
+fn generate_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+impl AgentInvite {
+    // Creating a new invite for an email revokes the ones still pending
+    pub(super) fn create(
+        pool: &DbPool,
+        invited_by: &uuid::Uuid,
+        payload: &CreateAgentInvitePayload,
+    ) -> QueryResult<CreatedAgentInvite> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        let token = generate_token();
+
+        conn.transaction(|conn| {
+            diesel::update(agent_invites::table)
+                .filter(
+                    agent_invites::email
+                        .eq(&payload.email)
+                        .and(agent_invites::accepted_at.is_null())
+                        .and(agent_invites::revoked_at.is_null()),
+                )
+                .set(agent_invites::revoked_at.eq(now.nullable()))
+                .execute(conn)?;
+
+            let invite = diesel::insert_into(agent_invites::table)
+                .values((
+                    payload,
+                    agent_invites::token_hash.eq(sha256_hex(&token)),
+                    agent_invites::invited_by.eq(invited_by),
+                    agent_invites::expires_at.eq(now + INVITE_TTL_DAYS.days()),
+                ))
+                .get_result(conn)?;
+
+            Ok(CreatedAgentInvite { invite, token })
+        })
+    }
+
+    pub(super) fn find_by_token(pool: &DbPool, token: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        agent_invites::table
+            .filter(agent_invites::token_hash.eq(sha256_hex(token)))
+            .get_result(conn)
+    }
+
+    fn filtered_query<'a>(
+        status: &Option<AgentInviteStatus>,
+    ) -> agent_invites::BoxedQuery<'a, diesel::pg::Pg> {
+        let query = agent_invites::table.into_boxed();
+
+        match status {
+            Some(AgentInviteStatus::Pending) => query.filter(
+                agent_invites::accepted_at
+                    .is_null()
+                    .and(agent_invites::revoked_at.is_null())
+                    .and(agent_invites::expires_at.gt(now)),
+            ),
+            Some(AgentInviteStatus::Accepted) => {
+                query.filter(agent_invites::accepted_at.is_not_null())
+            }
+            Some(AgentInviteStatus::Revoked) => {
+                query.filter(agent_invites::revoked_at.is_not_null())
+            }
+            Some(AgentInviteStatus::Expired) => query.filter(
+                agent_invites::accepted_at
+                    .is_null()
+                    .and(agent_invites::revoked_at.is_null())
+                    .and(agent_invites::expires_at.le(now)),
+            ),
+            None => query,
+        }
+    }
+
+    pub(super) fn find_many(
+        pool: &DbPool,
+        status: &Option<AgentInviteStatus>,
+        page: &Option<i64>,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let offset = (page.unwrap_or(1) - 1) * PAGE_SIZE;
+        Self::filtered_query(status)
+            .order_by(agent_invites::created_at.desc())
+            .offset(offset)
+            .limit(PAGE_SIZE)
+            .get_results(conn)
+    }
+
+    pub(super) fn count_find_many_rows(
+        pool: &DbPool,
+        status: &Option<AgentInviteStatus>,
+    ) -> QueryResult<i64> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        Self::filtered_query(status).count().get_result(conn)
+    }
+
+    // Errors with NotFound when the invite was accepted or revoked in the meantime
+    pub(super) fn revoke(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(agent_invites::table)
+            .filter(
+                agent_invites::id
+                    .eq(id)
+                    .and(agent_invites::accepted_at.is_null())
+                    .and(agent_invites::revoked_at.is_null()),
+            )
+            .set(agent_invites::revoked_at.eq(now.nullable()))
+            .get_result(conn)
+    }
+
+    // Uses up the invite and creates the agent. NotFound when the invite is no longer pending.
+    pub(super) fn accept(
+        pool: &DbPool,
+        id: &i32,
+        payload: &CreateAgentPayload,
+    ) -> QueryResult<Agent> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let invite: AgentInvite = diesel::update(agent_invites::table)
+                .filter(
+                    agent_invites::id
+                        .eq(id)
+                        .and(agent_invites::accepted_at.is_null())
+                        .and(agent_invites::revoked_at.is_null())
+                        .and(agent_invites::expires_at.gt(now)),
+                )
+                .set(agent_invites::accepted_at.eq(now.nullable()))
+                .get_result(conn)?;
+
+            let agent = Agent::create_in(conn, payload)?;
+
+            diesel::update(agent_invites::table)
+                .filter(agent_invites::id.eq(invite.id))
+                .set(agent_invites::agent_id.eq(agent.id))
+                .execute(conn)?;
+
+            Ok(agent)
+        })
+    }
+}
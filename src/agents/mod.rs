@@ -1,7 +1,14 @@
 mod agent_role;
 mod controller;
+mod invite;
 mod model;
+mod permission;
+mod slug;
+mod stats;
+mod transfer;
 
 pub use agent_role::AgentRole;
 pub use controller::agent_routes;
-pub use model::Agent;
+pub use model::{branch_member_ids, Agent};
+pub use permission::{authorize, can, can_on, scope, Action, Resource, Scope};
+pub use stats::{check_period, period, refresh_periodically as refresh_stats_periodically};
@@ -1,14 +1,26 @@
+use diesel::dsl::now;
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods, QueryDsl, QueryResult,
-    Queryable, RunQueryDsl,
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, PgTextExpressionMethods, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
 };
 use serde::Serialize;
 
 use super::agent_role::AgentRole;
 use super::controller::PAGE_SIZE;
 use super::controller::{CreateAgentPayload, FindAgentQuery, UpdateAgentPayload};
+use super::slug::{available_slug, change_slug, slugify};
 use crate::db::DbPool;
-use crate::schema::agents;
+use crate::leads::LeadAssignment;
+use crate::schema::{agent_slug_redirects, agents};
+
+// Members of a branch, for filtering their listings and leads
+#[diesel::dsl::auto_type]
+pub fn branch_member_ids(branch_id: i32) -> _ {
+    agents::table
+        .filter(agents::branch_id.eq(branch_id))
+        .select(agents::id)
+}
 
 #[derive(Debug, Serialize, Queryable)]
 pub struct Agent {
@@ -16,15 +28,45 @@ pub struct Agent {
     supertokens_user_id: Option<String>,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
+    pub fullname: String,
+    pub email: String,
+    pub phone_number: String,
+    pub profile_picture_url: Option<String>,
+    pub role: AgentRole,
+    instagram: Option<String>,
+    description: Option<String>,
+    pub is_active: bool,
+    deactivated_at: Option<chrono::NaiveDateTime>,
+    pub slug: String,
+    pub branch_id: Option<i32>,
+}
+
+// What the public agent page shows, without account and contact details of the team
+#[derive(Debug, Serialize)]
+pub struct AgentProfile {
+    id: uuid::Uuid,
+    slug: String,
     fullname: String,
-    email: String,
     phone_number: String,
     profile_picture_url: Option<String>,
-    pub role: AgentRole,
     instagram: Option<String>,
     description: Option<String>,
 }
 
+impl From<Agent> for AgentProfile {
+    fn from(agent: Agent) -> Self {
+        AgentProfile {
+            id: agent.id,
+            slug: agent.slug,
+            fullname: agent.fullname,
+            phone_number: agent.phone_number,
+            profile_picture_url: agent.profile_picture_url,
+            instagram: agent.instagram,
+            description: agent.description,
+        }
+    }
+}
+
 impl Agent {
     pub(super) fn find_by_supertokens_user_id(
         pool: &DbPool,
@@ -58,25 +100,77 @@ impl Agent {
     ) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        diesel::update(agents::table)
-            .filter(agents::id.eq(user_id))
-            .set(payload)
-            .get_result(conn)
+        conn.transaction(|conn| {
+            let agent: Agent = agents::table.find(user_id).for_update().get_result(conn)?;
+
+            if let Some(fullname) = &payload.fullname {
+                if slugify(fullname) != slugify(&agent.fullname) {
+                    let new_slug = available_slug(conn, fullname, &Some(agent.id))?;
+                    change_slug(conn, &agent.id, &agent.slug, &new_slug)?;
+                }
+            }
+
+            diesel::update(agents::table)
+                .filter(agents::id.eq(user_id))
+                .set(payload)
+                .get_result(conn)
+        })
+    }
+
+    // Nobody follows up open leads of an inactive agent, they're reassigned in the
+    // same transaction so a deactivated agent never keeps any
+    pub(super) fn deactivate(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        deactivated_by: &uuid::Uuid,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let agent = diesel::update(agents::table)
+                .filter(agents::id.eq(user_id))
+                .set((
+                    agents::is_active.eq(false),
+                    agents::deactivated_at.eq(now.nullable()),
+                ))
+                .get_result(conn)?;
+            LeadAssignment::reassign_open_leads_in(conn, user_id, deactivated_by)?;
+            Ok(agent)
+        })
     }
 
-    pub(super) fn delete_agent(pool: &DbPool, user_id: &uuid::Uuid) -> QueryResult<Self> {
+    pub(super) fn activate(pool: &DbPool, user_id: &uuid::Uuid) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        diesel::delete(agents::table)
+        diesel::update(agents::table)
             .filter(agents::id.eq(user_id))
+            .set((
+                agents::is_active.eq(true),
+                agents::deactivated_at.eq(None::<chrono::NaiveDateTime>),
+            ))
             .get_result(conn)
     }
 
-    pub fn find_by_name(pool: &DbPool, name: &str) -> QueryResult<Self> {
+    // Also follows slugs the agent had before a rename, compare `slug` to spot those
+    pub fn find_by_slug(pool: &DbPool, slug: &str) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
+        let agent_id = agents::table
+            .filter(agents::slug.eq(slug))
+            .select(agents::id)
+            .first::<uuid::Uuid>(conn)
+            .optional()?;
+
+        let agent_id = match agent_id {
+            Some(agent_id) => agent_id,
+            None => agent_slug_redirects::table
+                .filter(agent_slug_redirects::slug.eq(slug))
+                .select(agent_slug_redirects::agent_id)
+                .first(conn)?,
+        };
+
         agents::table
-            .filter(agents::fullname.eq(name))
+            .filter(agents::id.eq(agent_id).and(agents::is_active.eq(true)))
             .get_result(conn)
     }
 
@@ -84,7 +178,11 @@ impl Agent {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
         agents::table
-            .filter(agents::email.ne("admin@primeproindonesia.com"))
+            .filter(
+                agents::email
+                    .ne("admin@primeproindonesia.com")
+                    .and(agents::is_active.eq(true)),
+            )
             .get_results(conn)
     }
 
@@ -95,8 +193,17 @@ impl Agent {
     ) -> QueryResult<Agent> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
+        conn.transaction(|conn| Self::create_in(conn, payload))
+    }
+
+    pub(super) fn create_in(
+        conn: &mut PgConnection,
+        payload: &CreateAgentPayload,
+    ) -> QueryResult<Agent> {
+        let slug = available_slug(conn, &payload.fullname, &None)?;
+
         diesel::insert_into(agents::table)
-            .values(payload)
+            .values((payload, agents::slug.eq(slug)))
             .get_result(conn)
     }
 
@@ -113,19 +220,20 @@ impl Agent {
             .order_by(agents::created_at.desc())
             .into_boxed();
 
-        match &find_queries.name_or_email {
-            Some(name_or_email) => {
-                query = query.filter(
-                    agents::fullname
-                        .ilike(format!("%{}", name_or_email))
-                        .or(agents::fullname.ilike(format!("%{}%", name_or_email)))
-                        .or(agents::fullname.ilike(format!("{}%", name_or_email)))
-                        .or(agents::email.ilike(format!("%{}", name_or_email)))
-                        .or(agents::email.ilike(format!("%{}%", name_or_email)))
-                        .or(agents::email.ilike(format!("{}%", name_or_email))),
-                );
-            }
-            None => {}
+        if let Some(name_or_email) = &find_queries.name_or_email {
+            query = query.filter(
+                agents::fullname
+                    .ilike(format!("%{}", name_or_email))
+                    .or(agents::fullname.ilike(format!("%{}%", name_or_email)))
+                    .or(agents::fullname.ilike(format!("{}%", name_or_email)))
+                    .or(agents::email.ilike(format!("%{}", name_or_email)))
+                    .or(agents::email.ilike(format!("%{}%", name_or_email)))
+                    .or(agents::email.ilike(format!("{}%", name_or_email))),
+            );
+        }
+
+        if let Some(branch_id) = find_queries.branch_id {
+            query = query.filter(agents::branch_id.eq(branch_id));
         }
 
         match &find_queries.page {
@@ -154,19 +262,20 @@ impl Agent {
             .filter(agents::role.ne(AgentRole::Admin))
             .into_boxed();
 
-        match &find_queries.name_or_email {
-            Some(name_or_email) => {
-                query = query.filter(
-                    agents::fullname
-                        .ilike(format!("%{}", name_or_email))
-                        .or(agents::fullname.ilike(format!("%{}%", name_or_email)))
-                        .or(agents::fullname.ilike(format!("{}%", name_or_email)))
-                        .or(agents::email.ilike(format!("%{}", name_or_email)))
-                        .or(agents::email.ilike(format!("%{}%", name_or_email)))
-                        .or(agents::email.ilike(format!("{}%", name_or_email))),
-                );
-            }
-            None => {}
+        if let Some(name_or_email) = &find_queries.name_or_email {
+            query = query.filter(
+                agents::fullname
+                    .ilike(format!("%{}", name_or_email))
+                    .or(agents::fullname.ilike(format!("%{}%", name_or_email)))
+                    .or(agents::fullname.ilike(format!("{}%", name_or_email)))
+                    .or(agents::email.ilike(format!("%{}", name_or_email)))
+                    .or(agents::email.ilike(format!("%{}%", name_or_email)))
+                    .or(agents::email.ilike(format!("{}%", name_or_email))),
+            );
+        }
+
+        if let Some(branch_id) = find_queries.branch_id {
+            query = query.filter(agents::branch_id.eq(branch_id));
         }
 
         query.get_result(conn)
@@ -0,0 +1,168 @@
+use axum::http::HeaderMap;
+
+use super::agent_role::AgentRole;
+use super::model::Agent;
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse, Session};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    View,
+    Create,
+    Update,
+    Delete,
+    Export,
+    Assign,
+}
+
+// `Own*` resources are records the agent owns, `Branch*` the records of agents
+// in their branch, the others are everyone's records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Agent,
+    OwnProfile,
+    Property,
+    BranchProperty,
+    OwnProperty,
+    // popular and NJOP flags of any listing, without access to the rest of it
+    PropertyConfigurations,
+    Lead,
+    BranchLead,
+    OwnLead,
+    // routing rules, blocklist and rejected submissions
+    LeadSettings,
+    Bank,
+    Developer,
+    Branch,
+    Report,
+    BranchReport,
+    // logo and placement stamped on listing photos
+    WatermarkSettings,
+    // rupiah rates of the other listing currencies
+    ExchangeRate,
+}
+
+impl Resource {
+    fn in_branch(&self) -> Option<Resource> {
+        match self {
+            Resource::Property => Some(Resource::BranchProperty),
+            Resource::Lead => Some(Resource::BranchLead),
+            Resource::Report => Some(Resource::BranchReport),
+            _ => None,
+        }
+    }
+
+    fn owned(&self) -> Option<Resource> {
+        match self {
+            Resource::Property => Some(Resource::OwnProperty),
+            Resource::Lead => Some(Resource::OwnLead),
+            _ => None,
+        }
+    }
+}
+
+// The records of a resource an agent may act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    Branch(i32),
+    Own(uuid::Uuid),
+}
+
+impl AgentRole {
+    // The permission matrix, every permission check goes through here
+    pub fn can(&self, action: Action, resource: Resource) -> bool {
+        use Action::*;
+        use Resource::*;
+
+        match self {
+            AgentRole::Admin => true,
+            AgentRole::Agent => matches!(
+                (action, resource),
+                (Update, OwnProfile)
+                    | (View | Create | Update | Delete, OwnProperty)
+                    | (View | Export | Update, OwnLead)
+            ),
+            AgentRole::BranchManager => matches!(
+                (action, resource),
+                (Update, OwnProfile)
+                    | (View, Agent)
+                    | (View | Create | Update | Delete, OwnProperty)
+                    | (View | Update, BranchProperty)
+                    | (View | Export | Update | Assign, BranchLead | OwnLead)
+                    | (View, BranchReport)
+            ),
+            AgentRole::Marketing => matches!(
+                (action, resource),
+                (Update, OwnProfile)
+                    | (View, Property)
+                    | (Update, PropertyConfigurations)
+                    | (Create | Update, Bank | Developer)
+            ),
+            AgentRole::Finance => matches!(
+                (action, resource),
+                (Update, OwnProfile)
+                    | (View, Agent | Property | Branch | Report)
+                    | (View | Export, Lead)
+            ),
+        }
+    }
+}
+
+pub fn can(agent: &Agent, action: Action, resource: Resource) -> bool {
+    agent.is_active && agent.role.can(action, resource)
+}
+
+// Everyone's records, the records of the agent's branch or only their own
+pub fn scope(agent: &Agent, action: Action, resource: Resource) -> Option<Scope> {
+    if can(agent, action, resource) {
+        return Some(Scope::All);
+    }
+
+    let branch_scope = match (agent.branch_id, resource.in_branch()) {
+        (Some(branch_id), Some(in_branch)) if can(agent, action, in_branch) => {
+            Some(Scope::Branch(branch_id))
+        }
+        _ => None,
+    };
+
+    branch_scope.or(match resource.owned() {
+        Some(owned) if can(agent, action, owned) => Some(Scope::Own(agent.id)),
+        _ => None,
+    })
+}
+
+// Whether the agent may act on a record owned by `owner`
+pub fn can_on(agent: &Agent, action: Action, resource: Resource, owner: &Agent) -> bool {
+    match scope(agent, action, resource) {
+        Some(Scope::All) => true,
+        Some(Scope::Branch(branch_id)) => {
+            owner.branch_id == Some(branch_id) || can_on_own(agent, action, resource, owner)
+        }
+        Some(Scope::Own(_)) => can_on_own(agent, action, resource, owner),
+        None => false,
+    }
+}
+
+fn can_on_own(agent: &Agent, action: Action, resource: Resource, owner: &Agent) -> bool {
+    match resource.owned() {
+        Some(owned) => agent.id == owner.id && can(agent, action, owned),
+        None => false,
+    }
+}
+
+// Loads the agent behind the session, 403 when the agent is missing or not allowed
+pub fn authorize<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    action: Action,
+    resource: Resource,
+) -> Result<Agent, AxumResponse<T>> {
+    let user_id = Session::extract_session_user_id(headers);
+
+    match Agent::find_by_user_id(pool, &user_id) {
+        Ok(agent) if can(&agent, action, resource) => Ok(agent),
+        Ok(_) => Err(JsonResponse::send(403, None, None)),
+        Err(err) => Err(JsonResponse::send(403, None, Some(err.to_string()))),
+    }
+}
@@ -0,0 +1,82 @@
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    QueryResult, RunQueryDsl,
+};
+
+use crate::schema::{agent_slug_redirects, agents};
+
+pub(super) fn slugify(fullname: &str) -> String {
+    crate::slug::slugify(fullname, "agent")
+}
+
+fn is_taken(
+    conn: &mut PgConnection,
+    slug: &str,
+    agent_id: &Option<uuid::Uuid>,
+) -> QueryResult<bool> {
+    let owner = agents::table
+        .filter(agents::slug.eq(slug))
+        .select(agents::id)
+        .first::<uuid::Uuid>(conn)
+        .optional()?
+        .or(agent_slug_redirects::table
+            .filter(agent_slug_redirects::slug.eq(slug))
+            .select(agent_slug_redirects::agent_id)
+            .first::<uuid::Uuid>(conn)
+            .optional()?);
+
+    Ok(match (owner, agent_id) {
+        (Some(owner), Some(agent_id)) => owner != *agent_id,
+        (Some(_), None) => true,
+        (None, _) => false,
+    })
+}
+
+// First free slug for the name: "budi-santoso", then "budi-santoso-2", "budi-santoso-3", ...
+// Slugs the agent itself used before stay available to them.
+pub(super) fn available_slug(
+    conn: &mut PgConnection,
+    fullname: &str,
+    agent_id: &Option<uuid::Uuid>,
+) -> QueryResult<String> {
+    let base_slug = slugify(fullname);
+    let mut slug = base_slug.clone();
+    let mut suffix = 1;
+
+    while is_taken(conn, &slug, agent_id)? {
+        suffix += 1;
+        slug = format!("{}-{}", base_slug, suffix);
+    }
+
+    Ok(slug)
+}
+
+// Keeps the previous slug pointing at the agent after a rename
+pub(super) fn change_slug(
+    conn: &mut PgConnection,
+    agent_id: &uuid::Uuid,
+    old_slug: &str,
+    new_slug: &str,
+) -> QueryResult<()> {
+    diesel::delete(agent_slug_redirects::table)
+        .filter(
+            agent_slug_redirects::slug
+                .eq(new_slug)
+                .and(agent_slug_redirects::agent_id.eq(agent_id)),
+        )
+        .execute(conn)?;
+
+    diesel::insert_into(agent_slug_redirects::table)
+        .values((
+            agent_slug_redirects::slug.eq(old_slug),
+            agent_slug_redirects::agent_id.eq(agent_id),
+        ))
+        .execute(conn)?;
+
+    diesel::update(agents::table)
+        .filter(agents::id.eq(agent_id))
+        .set(agents::slug.eq(new_slug))
+        .execute(conn)?;
+
+    Ok(())
+}
@@ -0,0 +1,391 @@
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::{Datelike, Duration, NaiveDate};
+use diesel::dsl;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+
+use super::agent_role::AgentRole;
+use super::model::Agent;
+use super::permission::Scope;
+use crate::db::DbPool;
+use crate::properties::SoldStatus;
+use crate::schema::{agents, properties};
+
+diesel::table! {
+    // materialised view, built from properties and leads and refreshed by `refresh_periodically`
+    agent_daily_stats (user_id, day) {
+        user_id -> Uuid,
+        day -> Date,
+        new_listings -> Int4,
+        leads_received -> Int4,
+        responded_leads -> Int4,
+        response_seconds -> Int8,
+        sold_count -> Int4,
+        sold_value -> Int8,
+        rented_count -> Int4,
+        rented_value -> Int8,
+    }
+}
+
+const DEFAULT_REFRESH_MINUTES: u64 = 15;
+const DEFAULT_PERIOD_DAYS: i64 = 30;
+pub(super) const MAX_PERIOD_DAYS: i64 = 366;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsInterval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Queryable)]
+struct AgentDailyStats {
+    #[allow(dead_code)]
+    user_id: uuid::Uuid,
+    day: NaiveDate,
+    new_listings: i32,
+    leads_received: i32,
+    responded_leads: i32,
+    response_seconds: i64,
+    sold_count: i32,
+    sold_value: i64,
+    rented_count: i32,
+    rented_value: i64,
+}
+
+type StatsSums = (
+    uuid::Uuid,
+    Option<i64>,
+    Option<i64>,
+    Option<i64>,
+    Option<BigDecimal>,
+    Option<i64>,
+    Option<BigDecimal>,
+    Option<i64>,
+    Option<BigDecimal>,
+);
+
+#[derive(Debug, Serialize)]
+pub struct AgentStats {
+    from: NaiveDate,
+    to: NaiveDate,
+    new_listings: i64,
+    leads_received: i64,
+    responded_leads: i64,
+    // average over the leads that got a response
+    avg_response_seconds: Option<i64>,
+    sold_count: i64,
+    sold_value: i64,
+    rented_count: i64,
+    rented_value: i64,
+    // closed deals per lead received
+    conversion_rate: Option<f64>,
+    #[serde(skip)]
+    response_seconds: i64,
+}
+
+impl AgentStats {
+    fn new(from: NaiveDate, to: NaiveDate) -> Self {
+        AgentStats {
+            from,
+            to,
+            new_listings: 0,
+            leads_received: 0,
+            responded_leads: 0,
+            avg_response_seconds: None,
+            sold_count: 0,
+            sold_value: 0,
+            rented_count: 0,
+            rented_value: 0,
+            conversion_rate: None,
+            response_seconds: 0,
+        }
+    }
+
+    fn add(&mut self, row: &AgentDailyStats) {
+        self.new_listings += row.new_listings as i64;
+        self.leads_received += row.leads_received as i64;
+        self.responded_leads += row.responded_leads as i64;
+        self.response_seconds += row.response_seconds;
+        self.sold_count += row.sold_count as i64;
+        self.sold_value += row.sold_value;
+        self.rented_count += row.rented_count as i64;
+        self.rented_value += row.rented_value;
+    }
+
+    fn from_sums(from: NaiveDate, to: NaiveDate, sums: StatsSums) -> Self {
+        let to_i64 = |value: Option<BigDecimal>| value.and_then(|v| v.to_i64()).unwrap_or(0);
+
+        let mut stats = AgentStats::new(from, to);
+        stats.new_listings = sums.1.unwrap_or(0);
+        stats.leads_received = sums.2.unwrap_or(0);
+        stats.responded_leads = sums.3.unwrap_or(0);
+        stats.response_seconds = to_i64(sums.4);
+        stats.sold_count = sums.5.unwrap_or(0);
+        stats.sold_value = to_i64(sums.6);
+        stats.rented_count = sums.7.unwrap_or(0);
+        stats.rented_value = to_i64(sums.8);
+        stats.finish()
+    }
+
+    fn finish(mut self) -> Self {
+        if self.responded_leads > 0 {
+            self.avg_response_seconds = Some(self.response_seconds / self.responded_leads);
+        }
+        if self.leads_received > 0 {
+            self.conversion_rate =
+                Some((self.sold_count + self.rented_count) as f64 / self.leads_received as f64);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AgentStatsReport {
+    user_id: uuid::Uuid,
+    // listings currently on the market, not bound to the period
+    active_listings: i64,
+    total: AgentStats,
+    periods: Vec<AgentStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardEntry {
+    agent: Agent,
+    active_listings: i64,
+    stats: AgentStats,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+pub enum LeaderboardSort {
+    #[default]
+    SoldValue,
+    SoldCount,
+    RentedValue,
+    LeadsReceived,
+    NewListings,
+    ConversionRate,
+}
+
+// Inclusive date range, defaults to the last 30 days
+pub fn period(from: Option<NaiveDate>, to: Option<NaiveDate>) -> (NaiveDate, NaiveDate) {
+    let to = to.unwrap_or_else(|| chrono::Local::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(DEFAULT_PERIOD_DAYS - 1));
+    (from, to)
+}
+
+// Rejects ranges ending before they start or spanning more than `max_days` days
+pub fn check_period(from: NaiveDate, to: NaiveDate, max_days: i64) -> Result<(), String> {
+    if to < from {
+        return Err("`to` can't be before `from`".to_string());
+    }
+    if (to - from).num_days() >= max_days {
+        return Err(format!("At most {} days can be asked for", max_days));
+    }
+    Ok(())
+}
+
+fn bucket_start(day: NaiveDate, interval: StatsInterval) -> NaiveDate {
+    match interval {
+        StatsInterval::Day => day,
+        StatsInterval::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+        StatsInterval::Month => day.with_day(1).unwrap_or(day),
+    }
+}
+
+fn next_bucket(start: NaiveDate, interval: StatsInterval) -> NaiveDate {
+    match interval {
+        StatsInterval::Day => start + Duration::days(1),
+        StatsInterval::Week => start + Duration::days(7),
+        StatsInterval::Month => start
+            .checked_add_months(chrono::Months::new(1))
+            .unwrap_or(start + Duration::days(31)),
+    }
+}
+
+impl AgentStatsReport {
+    pub fn find(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+        interval: StatsInterval,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let rows: Vec<AgentDailyStats> = agent_daily_stats::table
+            .filter(
+                agent_daily_stats::user_id
+                    .eq(user_id)
+                    .and(agent_daily_stats::day.between(from, to)),
+            )
+            .order_by(agent_daily_stats::day.asc())
+            .get_results(conn)?;
+
+        let active_listings = properties::table
+            .filter(
+                properties::user_id
+                    .eq(user_id)
+                    .and(properties::is_deleted.eq(false))
+                    .and(properties::sold_status.eq(SoldStatus::Available)),
+            )
+            .count()
+            .get_result(conn)?;
+
+        // every bucket in the range is listed, empty ones included
+        let mut periods = vec![];
+        let mut start = bucket_start(from, interval);
+        while start <= to {
+            let end = next_bucket(start, interval);
+            periods.push(AgentStats::new(
+                start.max(from),
+                (end - Duration::days(1)).min(to),
+            ));
+            start = end;
+        }
+
+        let mut total = AgentStats::new(from, to);
+        for row in &rows {
+            total.add(row);
+            if let Some(period) = periods
+                .iter_mut()
+                .find(|period| period.from <= row.day && row.day <= period.to)
+            {
+                period.add(row);
+            }
+        }
+
+        Ok(AgentStatsReport {
+            user_id: *user_id,
+            active_listings,
+            total: total.finish(),
+            periods: periods.into_iter().map(AgentStats::finish).collect(),
+        })
+    }
+}
+
+impl LeaderboardEntry {
+    pub fn find_many(
+        pool: &DbPool,
+        scope: &Scope,
+        from: NaiveDate,
+        to: NaiveDate,
+        sort: LeaderboardSort,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut agent_query = agents::table
+            .filter(
+                agents::is_active
+                    .eq(true)
+                    .and(agents::role.ne(AgentRole::Admin)),
+            )
+            .into_boxed();
+        agent_query = match scope {
+            Scope::All => agent_query,
+            Scope::Branch(branch_id) => agent_query.filter(agents::branch_id.eq(branch_id)),
+            Scope::Own(user_id) => agent_query.filter(agents::id.eq(user_id)),
+        };
+        let agents: Vec<Agent> = agent_query.get_results(conn)?;
+        let agent_ids: Vec<uuid::Uuid> = agents.iter().map(|agent| agent.id).collect();
+
+        let mut sums: HashMap<uuid::Uuid, StatsSums> = agent_daily_stats::table
+            .filter(
+                agent_daily_stats::user_id
+                    .eq_any(&agent_ids)
+                    .and(agent_daily_stats::day.between(from, to)),
+            )
+            .group_by(agent_daily_stats::user_id)
+            .select((
+                agent_daily_stats::user_id,
+                dsl::sum(agent_daily_stats::new_listings),
+                dsl::sum(agent_daily_stats::leads_received),
+                dsl::sum(agent_daily_stats::responded_leads),
+                dsl::sum(agent_daily_stats::response_seconds),
+                dsl::sum(agent_daily_stats::sold_count),
+                dsl::sum(agent_daily_stats::sold_value),
+                dsl::sum(agent_daily_stats::rented_count),
+                dsl::sum(agent_daily_stats::rented_value),
+            ))
+            .load::<StatsSums>(conn)?
+            .into_iter()
+            .map(|row| (row.0, row))
+            .collect();
+
+        let mut active_listings: HashMap<uuid::Uuid, i64> = properties::table
+            .filter(
+                properties::user_id
+                    .eq_any(&agent_ids)
+                    .and(properties::is_deleted.eq(false))
+                    .and(properties::sold_status.eq(SoldStatus::Available)),
+            )
+            .group_by(properties::user_id)
+            .select((properties::user_id, dsl::count_star()))
+            .load::<(uuid::Uuid, i64)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut entries: Vec<LeaderboardEntry> = agents
+            .into_iter()
+            .map(|agent| {
+                let stats = match sums.remove(&agent.id) {
+                    Some(row) => AgentStats::from_sums(from, to, row),
+                    None => AgentStats::new(from, to),
+                };
+                LeaderboardEntry {
+                    active_listings: active_listings.remove(&agent.id).unwrap_or(0),
+                    agent,
+                    stats,
+                }
+            })
+            .collect();
+
+        entries.sort_by(|a, b| {
+            let (a, b) = (&a.stats, &b.stats);
+            match sort {
+                LeaderboardSort::SoldValue => b.sold_value.cmp(&a.sold_value),
+                LeaderboardSort::SoldCount => b.sold_count.cmp(&a.sold_count),
+                LeaderboardSort::RentedValue => b.rented_value.cmp(&a.rented_value),
+                LeaderboardSort::LeadsReceived => b.leads_received.cmp(&a.leads_received),
+                LeaderboardSort::NewListings => b.new_listings.cmp(&a.new_listings),
+                LeaderboardSort::ConversionRate => b
+                    .conversion_rate
+                    .unwrap_or(0.0)
+                    .total_cmp(&a.conversion_rate.unwrap_or(0.0)),
+            }
+        });
+
+        Ok(entries)
+    }
+}
+
+pub fn refresh(pool: &DbPool) -> QueryResult<usize> {
+    let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+    diesel::sql_query("REFRESH MATERIALIZED VIEW CONCURRENTLY agent_daily_stats").execute(conn)
+}
+
+// Keeps the stats view reasonably fresh, every AGENT_STATS_REFRESH_MINUTES
+pub async fn refresh_periodically(pool: DbPool) {
+    let minutes = env::var("AGENT_STATS_REFRESH_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_REFRESH_MINUTES);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(minutes * 60));
+
+    loop {
+        interval.tick().await;
+        let pool = pool.clone();
+        match tokio::task::spawn_blocking(move || refresh(&pool)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => tracing::error!("Failed to refresh agent stats: {}", err),
+            Err(err) => tracing::error!("Failed to refresh agent stats: {}", err),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use diesel::{Connection, ExpressionMethods, QueryResult, Queryable, RunQueryDsl};
+use serde::Serialize;
+
+use crate::appointments::Appointment;
+use crate::leads::LeadAssignment;
+use crate::properties::Property;
+use crate::{db::DbPool, schema::agent_transfers};
+
+// Audit record of an admin handing all listings and leads of one agent to another
+#[derive(Debug, Serialize, Queryable)]
+pub struct AgentTransfer {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    from_user_id: Option<uuid::Uuid>,
+    to_user_id: Option<uuid::Uuid>,
+    transferred_by: Option<uuid::Uuid>,
+    property_count: i32,
+    lead_count: i32,
+}
+
+impl AgentTransfer {
+    pub(super) fn transfer(
+        pool: &DbPool,
+        from_user_id: &uuid::Uuid,
+        to_user_id: &uuid::Uuid,
+        transferred_by: &uuid::Uuid,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let property_count = Property::transfer_all_in(conn, from_user_id, to_user_id)?;
+            let lead_count =
+                LeadAssignment::transfer_all_in(conn, from_user_id, to_user_id, transferred_by)?;
+            Appointment::transfer_all_in(conn, from_user_id, to_user_id)?;
+
+            diesel::insert_into(agent_transfers::table)
+                .values((
+                    agent_transfers::from_user_id.eq(from_user_id),
+                    agent_transfers::to_user_id.eq(to_user_id),
+                    agent_transfers::transferred_by.eq(transferred_by),
+                    agent_transfers::property_count.eq(property_count as i32),
+                    agent_transfers::lead_count.eq(lead_count as i32),
+                ))
+                .get_result(conn)
+        })
+    }
+}
@@ -0,0 +1,37 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::AppointmentStatus)]
+pub enum AppointmentStatus {
+    Requested,
+    Confirmed,
+    Cancelled,
+}
+
+impl ToSql<sql_types::AppointmentStatus, Pg> for AppointmentStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            AppointmentStatus::Requested => out.write_all(b"requested")?,
+            AppointmentStatus::Confirmed => out.write_all(b"confirmed")?,
+            AppointmentStatus::Cancelled => out.write_all(b"cancelled")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::AppointmentStatus, Pg> for AppointmentStatus {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"requested" => Ok(AppointmentStatus::Requested),
+            b"confirmed" => Ok(AppointmentStatus::Confirmed),
+            b"cancelled" => Ok(AppointmentStatus::Cancelled),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,589 @@
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Extension, Json, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::Router;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime};
+use diesel::prelude::Insertable;
+use diesel::result::DatabaseErrorKind;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use super::appointment_status::AppointmentStatus;
+use super::model::{Appointment, AppointmentError, AvailabilitySlot};
+use super::notification::invite;
+use crate::agents::{can, can_on, scope, Action, Agent, Resource};
+use crate::db::DbPool;
+use crate::leads::{
+    accept_lead, CreateLeadApiPayload, Lead, LeadOutcome, LeadRequest, LeadSettings,
+};
+use crate::middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session};
+use crate::properties::Property;
+use crate::schema;
+
+pub(super) const PAGE_SIZE: i64 = 20;
+// How long a viewing asked for from the site takes
+const VIEWING_MINUTES: i64 = 60;
+const MAX_AVAILABILITY_DAYS: i64 = 31;
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = schema::agent_availability_slots)]
+pub(crate) struct CreateAvailabilitySlotPayload {
+    // 1 is Monday, 7 is Sunday
+    weekday: i16,
+    starts_at: NaiveTime,
+    ends_at: NaiveTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AgentQuery {
+    // Someone else's slots, for those who manage agents
+    user_id: Option<uuid::Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ViewingAvailabilityQuery {
+    property_id: i32,
+    from: Option<NaiveDate>,
+    // Not included
+    to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewingWindow {
+    starts_at: NaiveDateTime,
+    ends_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewingAvailabilityResponse {
+    // Empty when the agent takes viewings at any time
+    windows: Vec<ViewingWindow>,
+    busy: Vec<ViewingWindow>,
+}
+
+#[derive(Deserialize)]
+pub struct ViewingRequestApiPayload {
+    #[serde(flatten)]
+    lead: CreateLeadApiPayload,
+    starts_at: NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindAppointmentQuery {
+    pub page: Option<i64>,
+    pub status: Option<AppointmentStatus>,
+    pub lead_id: Option<i32>,
+    pub property_id: Option<i32>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAppointmentPayload {
+    pub lead_id: i32,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RescheduleAppointmentPayload {
+    starts_at: NaiveDateTime,
+    ends_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelAppointmentPayload {
+    reason: Option<String>,
+}
+
+impl CreateAvailabilitySlotPayload {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=7).contains(&self.weekday) {
+            return Err("Weekday must be between 1 (Monday) and 7 (Sunday)".to_string());
+        }
+        if self.ends_at <= self.starts_at {
+            return Err("ends_at must be after starts_at".to_string());
+        }
+        Ok(())
+    }
+}
+
+// The end of a viewing, an hour unless given
+fn viewing_period(
+    starts_at: NaiveDateTime,
+    ends_at: Option<NaiveDateTime>,
+) -> Result<(NaiveDateTime, NaiveDateTime), String> {
+    let ends_at = ends_at.unwrap_or(starts_at + chrono::Duration::minutes(VIEWING_MINUTES));
+    if ends_at <= starts_at {
+        return Err("ends_at must be after starts_at".to_string());
+    }
+    if starts_at <= chrono::Local::now().naive_local() {
+        return Err("The viewing can't be in the past".to_string());
+    }
+    Ok((starts_at, ends_at))
+}
+
+fn appointment_error_response(err: AppointmentError) -> AxumResponse<Appointment> {
+    match err {
+        AppointmentError::Conflict(appointment) => JsonResponse::send(
+            409,
+            Some(*appointment),
+            Some("The agent has another viewing at that time".to_string()),
+        ),
+        AppointmentError::Query(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Appointment not found".to_string()))
+        }
+        // another viewing of the agent got in between the check and the write
+        AppointmentError::Query(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ExclusionViolation,
+            _,
+        )) => JsonResponse::send(
+            409,
+            None,
+            Some("The agent has another viewing at that time".to_string()),
+        ),
+        AppointmentError::Query(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+fn find_session_agent<T>(pool: &DbPool, headers: &HeaderMap) -> Result<Agent, AxumResponse<T>> {
+    let user_id = Session::extract_session_user_id(headers);
+
+    match Agent::find_by_user_id(pool, &user_id) {
+        Ok(agent) if agent.is_active => Ok(agent),
+        _ => Err(JsonResponse::send(403, None, None)),
+    }
+}
+
+// The session's own slots, or another agent's for those who manage agents
+fn slots_owner<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    query: &AgentQuery,
+) -> Result<uuid::Uuid, AxumResponse<T>> {
+    let agent = find_session_agent(pool, headers)?;
+
+    match query.user_id {
+        Some(user_id) if user_id != agent.id => {
+            if !can(&agent, Action::Update, Resource::Agent) {
+                return Err(JsonResponse::send(403, None, None));
+            }
+            Ok(user_id)
+        }
+        _ => Ok(agent.id),
+    }
+}
+
+// The appointment when the session's agent may do `action` on the lead's records
+fn find_appointment_for<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    id: &i32,
+    action: Action,
+) -> Result<Appointment, AxumResponse<T>> {
+    let agent = find_session_agent(pool, headers)?;
+
+    let appointment = match Appointment::find_by_id(pool, id) {
+        Ok(appointment) => appointment,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Appointment not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    let is_allowed = match Agent::find_by_user_id(pool, &appointment.user_id) {
+        Ok(owner) => can_on(&agent, action, Resource::Lead, &owner),
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    if !is_allowed {
+        return Err(JsonResponse::send(403, None, None));
+    }
+
+    Ok(appointment)
+}
+
+async fn find_slots(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<AgentQuery>,
+) -> AxumResponse<Vec<AvailabilitySlot>> {
+    let user_id = match slots_owner(&pool, &headers, &query) {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    match AvailabilitySlot::find_many(&pool, &user_id) {
+        Ok(slots) => JsonResponse::send(200, Some(slots), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn create_slot(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<AgentQuery>,
+    Json(payload): Json<CreateAvailabilitySlotPayload>,
+) -> AxumResponse<AvailabilitySlot> {
+    let user_id = match slots_owner(&pool, &headers, &query) {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match AvailabilitySlot::create(&pool, &user_id, &payload) {
+        Ok(slot) => JsonResponse::send(201, Some(slot), None),
+        Err(diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        )) => JsonResponse::send(400, None, Some("Agent not found".to_string())),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn delete_slot(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Query(query): Query<AgentQuery>,
+) -> AxumResponse<AvailabilitySlot> {
+    let user_id = match slots_owner(&pool, &headers, &query) {
+        Ok(user_id) => user_id,
+        Err(response) => return response,
+    };
+
+    match AvailabilitySlot::delete(&pool, &user_id, &id) {
+        Ok(slot) => JsonResponse::send(200, Some(slot), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Slot not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Public, when the listing's agent takes viewings and which of those times are taken
+async fn find_viewing_availability(
+    State(pool): State<DbPool>,
+    Query(query): Query<ViewingAvailabilityQuery>,
+) -> AxumResponse<ViewingAvailabilityResponse> {
+    let agent = match Property::find_one_by_id(&pool, &query.property_id) {
+        Ok((property, agent, _)) if !property.is_deleted && agent.is_active => agent,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Property not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let from = query.from.unwrap_or(chrono::Local::now().date_naive());
+    let to = query.to.unwrap_or(from + chrono::Duration::days(7));
+    if to <= from {
+        return JsonResponse::send(400, None, Some("`to` must be after `from`".to_string()));
+    }
+    if (to - from).num_days() > MAX_AVAILABILITY_DAYS {
+        return JsonResponse::send(
+            400,
+            None,
+            Some(format!(
+                "At most {} days can be asked for",
+                MAX_AVAILABILITY_DAYS
+            )),
+        );
+    }
+
+    let slots = match AvailabilitySlot::find_many(&pool, &agent.id) {
+        Ok(slots) => slots,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let windows = from
+        .iter_days()
+        .take_while(|date| *date < to)
+        .flat_map(|date| {
+            slots
+                .iter()
+                .filter(move |slot| slot.weekday as u32 == date.weekday().number_from_monday())
+                .map(move |slot| ViewingWindow {
+                    starts_at: date.and_time(slot.starts_at),
+                    ends_at: date.and_time(slot.ends_at),
+                })
+        })
+        .collect();
+
+    let busy = match Appointment::find_busy(
+        &pool,
+        &agent.id,
+        &from.and_time(NaiveTime::MIN),
+        &to.and_time(NaiveTime::MIN),
+    ) {
+        Ok(busy) => busy
+            .into_iter()
+            .map(|(starts_at, ends_at)| ViewingWindow { starts_at, ends_at })
+            .collect(),
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(ViewingAvailabilityResponse { windows, busy }),
+        None,
+    )
+}
+
+// A lead asking to see the listing, after the same checks as any other lead,
+// the agent confirms or moves the time
+async fn request_viewing(
+    State(pool): State<DbPool>,
+    Extension(settings): Extension<Arc<LeadSettings>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<ViewingRequestApiPayload>,
+) -> AxumResponse<Appointment> {
+    let (starts_at, ends_at) = match viewing_period(payload.starts_at, None) {
+        Ok(period) => period,
+        Err(message) => return JsonResponse::send(400, None, Some(message)),
+    };
+
+    let request = LeadRequest::Viewing { starts_at, ends_at };
+    let lead = match accept_lead(&pool, &settings, &addr, &headers, payload.lead, request).await {
+        Ok(LeadOutcome::Created(lead) | LeadOutcome::Duplicate(lead)) => lead,
+        Ok(LeadOutcome::Dropped) => return JsonResponse::send(201, None, None),
+        Err(response) => return response,
+    };
+    let property_id = match lead.property_id {
+        Some(property_id) => property_id,
+        None => return JsonResponse::send(400, None, None),
+    };
+
+    match Appointment::request(
+        &pool,
+        &lead.id,
+        &property_id,
+        &lead.user_id,
+        &starts_at,
+        &ends_at,
+    ) {
+        Ok(appointment) => JsonResponse::send(201, Some(appointment), None),
+        // taken between the checks and the insert
+        Err(AppointmentError::Conflict(_))
+        | Err(AppointmentError::Query(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ExclusionViolation,
+            _,
+        ))) => JsonResponse::send(409, None, Some("The time is already taken".to_string())),
+        Err(err) => appointment_error_response(err),
+    }
+}
+
+async fn find_many_appointments(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<FindAppointmentQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<Appointment>>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Lead) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
+        _ => return JsonResponse::send(403, None, None),
+    };
+
+    let appointments = match Appointment::find_many(&pool, &scope, &query) {
+        Ok(appointments) => appointments,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let appointments_count = match Appointment::count_find_many_rows(&pool, &scope, &query) {
+        Ok(count) => count,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let body = JsonFindResponse {
+        data: appointments,
+        total_pages: (appointments_count / PAGE_SIZE) + 1,
+        total_data: appointments_count,
+    };
+    JsonResponse::send(200, Some(body), None)
+}
+
+// Arranged by the agent with one of their leads, e.g. over the phone
+async fn create_appointment(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateAppointmentPayload>,
+) -> AxumResponse<Appointment> {
+    let agent = match find_session_agent(&pool, &headers) {
+        Ok(agent) => agent,
+        Err(response) => return response,
+    };
+
+    let lead = match Lead::find_by_id(&pool, &payload.lead_id) {
+        Ok(lead) => lead,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Lead not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let is_allowed = match Agent::find_by_user_id(&pool, &lead.user_id) {
+        Ok(owner) => can_on(&agent, Action::Update, Resource::Lead, &owner),
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    if !is_allowed {
+        return JsonResponse::send(403, None, None);
+    }
+
+    let property_id = match lead.property_id {
+        Some(property_id) => property_id,
+        None => {
+            return JsonResponse::send(400, None, Some("Lead isn't about a property".to_string()))
+        }
+    };
+    let (_, ends_at) = match viewing_period(payload.starts_at, payload.ends_at) {
+        Ok(period) => period,
+        Err(message) => return JsonResponse::send(400, None, Some(message)),
+    };
+
+    match Appointment::create(&pool, &property_id, &lead.user_id, &payload, &ends_at) {
+        Ok(appointment) => JsonResponse::send(201, Some(appointment), None),
+        Err(err) => appointment_error_response(err),
+    }
+}
+
+async fn find_appointment(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Appointment> {
+    match find_appointment_for(&pool, &headers, &id, Action::View) {
+        Ok(appointment) => JsonResponse::send(200, Some(appointment), None),
+        Err(response) => response,
+    }
+}
+
+// The same calendar file the lead and the agent got with their messages
+async fn find_appointment_invite(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<Response, AxumResponse<String>> {
+    let appointment = find_appointment_for(&pool, &headers, &id, Action::View)?;
+    let context = match Appointment::find_context_by_id(&pool, &id) {
+        Ok(context) => context,
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/calendar; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"viewing-{}.ics\"", id),
+            ),
+        ],
+        Body::from(invite(&appointment, &context)),
+    )
+        .into_response())
+}
+
+async fn confirm_appointment(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Appointment> {
+    let appointment = match find_appointment_for(&pool, &headers, &id, Action::Update) {
+        Ok(appointment) => appointment,
+        Err(response) => return response,
+    };
+    if appointment.status != AppointmentStatus::Requested {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Only requested appointments can be confirmed".to_string()),
+        );
+    }
+
+    match Appointment::confirm(&pool, &id) {
+        Ok(appointment) => JsonResponse::send(200, Some(appointment), None),
+        Err(err) => appointment_error_response(err),
+    }
+}
+
+async fn reschedule_appointment(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<RescheduleAppointmentPayload>,
+) -> AxumResponse<Appointment> {
+    let appointment = match find_appointment_for(&pool, &headers, &id, Action::Update) {
+        Ok(appointment) => appointment,
+        Err(response) => return response,
+    };
+    if appointment.status == AppointmentStatus::Cancelled {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Cancelled appointments can't be rescheduled".to_string()),
+        );
+    }
+    let (starts_at, ends_at) = match viewing_period(payload.starts_at, payload.ends_at) {
+        Ok(period) => period,
+        Err(message) => return JsonResponse::send(400, None, Some(message)),
+    };
+
+    match Appointment::reschedule(&pool, &id, &starts_at, &ends_at) {
+        Ok(appointment) => JsonResponse::send(200, Some(appointment), None),
+        Err(err) => appointment_error_response(err),
+    }
+}
+
+async fn cancel_appointment(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<CancelAppointmentPayload>,
+) -> AxumResponse<Appointment> {
+    let appointment = match find_appointment_for(&pool, &headers, &id, Action::Update) {
+        Ok(appointment) => appointment,
+        Err(response) => return response,
+    };
+    if appointment.status == AppointmentStatus::Cancelled {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Appointment is already cancelled".to_string()),
+        );
+    }
+
+    match Appointment::cancel(&pool, &id, &payload.reason) {
+        Ok(appointment) => JsonResponse::send(200, Some(appointment), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Appointment not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub fn appointment_routes() -> Router<DbPool> {
+    Router::new()
+        .route("/", get(find_many_appointments))
+        .route("/", post(create_appointment))
+        .route("/slots", get(find_slots))
+        .route("/slots", post(create_slot))
+        .route("/slots/{id}", delete(delete_slot))
+        .route("/availability", get(find_viewing_availability))
+        .route("/requests", post(request_viewing))
+        .route("/{id}", get(find_appointment))
+        .route("/{id}/invite.ics", get(find_appointment_invite))
+        .route("/{id}/confirm", put(confirm_appointment))
+        .route("/{id}/reschedule", put(reschedule_appointment))
+        .route("/{id}/cancel", put(cancel_appointment))
+}
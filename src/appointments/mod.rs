@@ -0,0 +1,7 @@
+mod appointment_status;
+mod controller;
+mod model;
+mod notification;
+
+pub use controller::appointment_routes;
+pub use model::Appointment;
@@ -0,0 +1,439 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+use diesel::dsl::now;
+use diesel::pg::Pg;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, QueryDsl, QueryResult,
+    Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::appointment_status::AppointmentStatus;
+use super::controller::{
+    CreateAppointmentPayload, CreateAvailabilitySlotPayload, FindAppointmentQuery, PAGE_SIZE,
+};
+use super::notification::{messages, reminder_key, Notice};
+use crate::agents::{branch_member_ids, Scope};
+use crate::db::DbPool;
+use crate::outbox::OutboxMessage;
+use crate::schema::{agent_availability_slots, agents, appointments, leads, properties};
+
+// Weekly hours an agent takes viewings, weekday 1 is Monday
+#[derive(Debug, Serialize, Queryable)]
+pub struct AvailabilitySlot {
+    pub id: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    user_id: uuid::Uuid,
+    pub weekday: i16,
+    pub starts_at: NaiveTime,
+    pub ends_at: NaiveTime,
+}
+
+// A viewing of a listing, arranged from a lead
+#[derive(Debug, Serialize, Queryable)]
+pub struct Appointment {
+    pub id: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    pub lead_id: i32,
+    pub property_id: i32,
+    pub user_id: uuid::Uuid,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub status: AppointmentStatus,
+    note: Option<String>,
+    pub cancel_reason: Option<String>,
+    pub sequence: i32,
+}
+
+// Who and where the messages of an appointment are about
+#[derive(Debug, Queryable)]
+pub struct AppointmentContext {
+    pub lead_name: String,
+    pub lead_phone: String,
+    pub lead_email: Option<String>,
+    pub property_title: String,
+    pub property_street: String,
+    pub property_regency: String,
+    pub agent_name: String,
+    pub agent_email: String,
+    pub agent_phone: String,
+}
+
+pub(super) enum AppointmentError {
+    Query(diesel::result::Error),
+    // The appointment of the agent the new time would overlap
+    Conflict(Box<Appointment>),
+}
+
+impl From<diesel::result::Error> for AppointmentError {
+    fn from(err: diesel::result::Error) -> Self {
+        AppointmentError::Query(err)
+    }
+}
+
+impl AvailabilitySlot {
+    pub(super) fn find_many(pool: &DbPool, user_id: &uuid::Uuid) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        agent_availability_slots::table
+            .filter(agent_availability_slots::user_id.eq(user_id))
+            .order_by((
+                agent_availability_slots::weekday.asc(),
+                agent_availability_slots::starts_at.asc(),
+            ))
+            .get_results(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        payload: &CreateAvailabilitySlotPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(agent_availability_slots::table)
+            .values((payload, agent_availability_slots::user_id.eq(user_id)))
+            .get_result(conn)
+    }
+
+    pub(super) fn delete(pool: &DbPool, user_id: &uuid::Uuid, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(agent_availability_slots::table)
+            .filter(agent_availability_slots::id.eq(id))
+            .filter(agent_availability_slots::user_id.eq(user_id))
+            .get_result(conn)
+    }
+
+    fn covers(&self, starts_at: &NaiveDateTime, ends_at: &NaiveDateTime) -> bool {
+        self.weekday as u32 == starts_at.weekday().number_from_monday()
+            && starts_at.date() == ends_at.date()
+            && self.starts_at <= starts_at.time()
+            && ends_at.time() <= self.ends_at
+    }
+}
+
+impl Appointment {
+    // Requested and confirmed appointments of the agent overlapping the time
+    fn find_overlap(
+        conn: &mut PgConnection,
+        user_id: &uuid::Uuid,
+        starts_at: &NaiveDateTime,
+        ends_at: &NaiveDateTime,
+        except_id: Option<i32>,
+    ) -> QueryResult<Option<Self>> {
+        use diesel::OptionalExtension;
+
+        appointments::table
+            .filter(appointments::user_id.eq(user_id))
+            .filter(appointments::status.ne(AppointmentStatus::Cancelled))
+            .filter(appointments::starts_at.lt(ends_at))
+            .filter(appointments::ends_at.gt(starts_at))
+            .filter(appointments::id.ne(except_id.unwrap_or(0)))
+            .order_by(appointments::starts_at.asc())
+            .first(conn)
+            .optional()
+    }
+
+    // Why the agent can't take a viewing at the time, agents without slots take any time
+    pub fn find_request_conflict(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        starts_at: &NaiveDateTime,
+        ends_at: &NaiveDateTime,
+    ) -> QueryResult<Option<String>> {
+        let slots = AvailabilitySlot::find_many(pool, user_id)?;
+        if !slots.is_empty() && !slots.iter().any(|slot| slot.covers(starts_at, ends_at)) {
+            return Ok(Some("Outside the agent's viewing hours".to_string()));
+        }
+
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        match Self::find_overlap(conn, user_id, starts_at, ends_at, None)? {
+            Some(_) => Ok(Some("The time is already taken".to_string())),
+            None => Ok(None),
+        }
+    }
+
+    // Times the agent is already taken between `from` and `to`
+    pub(super) fn find_busy(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        from: &NaiveDateTime,
+        to: &NaiveDateTime,
+    ) -> QueryResult<Vec<(NaiveDateTime, NaiveDateTime)>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        appointments::table
+            .filter(appointments::user_id.eq(user_id))
+            .filter(appointments::status.ne(AppointmentStatus::Cancelled))
+            .filter(appointments::starts_at.lt(to))
+            .filter(appointments::ends_at.gt(from))
+            .select((appointments::starts_at, appointments::ends_at))
+            .order_by(appointments::starts_at.asc())
+            .get_results(conn)
+    }
+
+    fn find_context(conn: &mut PgConnection, id: &i32) -> QueryResult<AppointmentContext> {
+        appointments::table
+            .inner_join(leads::table)
+            .inner_join(properties::table)
+            .inner_join(agents::table)
+            .filter(appointments::id.eq(id))
+            .select((
+                leads::name,
+                leads::phone,
+                leads::email,
+                properties::title,
+                properties::street,
+                properties::regency,
+                agents::fullname,
+                agents::email,
+                agents::phone_number,
+            ))
+            .get_result(conn)
+    }
+
+    // The messages of the change go to the outbox in the same transaction,
+    // reminders of the old time are dropped
+    fn notify(conn: &mut PgConnection, appointment: &Self, notice: Notice) -> QueryResult<()> {
+        OutboxMessage::cancel_pending(conn, &reminder_key(&appointment.id))?;
+        let context = Self::find_context(conn, &appointment.id)?;
+        OutboxMessage::enqueue(conn, &messages(appointment, &context, notice))?;
+        Ok(())
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        appointments::table.find(id).get_result(conn)
+    }
+
+    pub(super) fn find_context_by_id(pool: &DbPool, id: &i32) -> QueryResult<AppointmentContext> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        Self::find_context(conn, id)
+    }
+
+    fn filtered_query<'a>(
+        scope: &Scope,
+        query: &FindAppointmentQuery,
+    ) -> appointments::BoxedQuery<'a, Pg> {
+        let mut appointment_query = match *scope {
+            Scope::All => appointments::table.into_boxed(),
+            Scope::Branch(branch_id) => appointments::table
+                .filter(appointments::user_id.eq_any(branch_member_ids(branch_id)))
+                .into_boxed(),
+            Scope::Own(user_id) => appointments::table
+                .filter(appointments::user_id.eq(user_id))
+                .into_boxed(),
+        };
+
+        if let Some(status) = query.status {
+            appointment_query = appointment_query.filter(appointments::status.eq(status));
+        }
+        if let Some(lead_id) = query.lead_id {
+            appointment_query = appointment_query.filter(appointments::lead_id.eq(lead_id));
+        }
+        if let Some(property_id) = query.property_id {
+            appointment_query = appointment_query.filter(appointments::property_id.eq(property_id));
+        }
+        if let Some(from) = query.from {
+            appointment_query =
+                appointment_query.filter(appointments::starts_at.ge(from.and_time(NaiveTime::MIN)));
+        }
+        if let Some(to) = query.to {
+            // inclusive of the whole `to` day
+            let until = to.and_time(NaiveTime::MIN) + chrono::Duration::days(1);
+            appointment_query = appointment_query.filter(appointments::starts_at.lt(until));
+        }
+
+        appointment_query
+    }
+
+    pub(super) fn find_many(
+        pool: &DbPool,
+        scope: &Scope,
+        query: &FindAppointmentQuery,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let page = query.page.unwrap_or(1).max(1);
+        Self::filtered_query(scope, query)
+            .order_by((appointments::starts_at.asc(), appointments::id.asc()))
+            .offset((page - 1) * PAGE_SIZE)
+            .limit(PAGE_SIZE)
+            .get_results(conn)
+    }
+
+    pub(super) fn count_find_many_rows(
+        pool: &DbPool,
+        scope: &Scope,
+        query: &FindAppointmentQuery,
+    ) -> QueryResult<i64> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        Self::filtered_query(scope, query).count().get_result(conn)
+    }
+
+    // Asked for from the site, the agent confirms or moves it
+    pub(super) fn request(
+        pool: &DbPool,
+        lead_id: &i32,
+        property_id: &i32,
+        user_id: &uuid::Uuid,
+        starts_at: &NaiveDateTime,
+        ends_at: &NaiveDateTime,
+    ) -> Result<Self, AppointmentError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) = Self::find_overlap(conn, user_id, starts_at, ends_at, None)? {
+                return Err(AppointmentError::Conflict(Box::new(conflict)));
+            }
+            let appointment: Self = diesel::insert_into(appointments::table)
+                .values((
+                    appointments::lead_id.eq(lead_id),
+                    appointments::property_id.eq(property_id),
+                    appointments::user_id.eq(user_id),
+                    appointments::starts_at.eq(starts_at),
+                    appointments::ends_at.eq(ends_at),
+                ))
+                .get_result(conn)?;
+            Self::notify(conn, &appointment, Notice::Requested)?;
+            Ok(appointment)
+        })
+    }
+
+    // Arranged by the agent with the lead, so it's confirmed from the start
+    pub(super) fn create(
+        pool: &DbPool,
+        property_id: &i32,
+        user_id: &uuid::Uuid,
+        payload: &CreateAppointmentPayload,
+        ends_at: &NaiveDateTime,
+    ) -> Result<Self, AppointmentError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) =
+                Self::find_overlap(conn, user_id, &payload.starts_at, ends_at, None)?
+            {
+                return Err(AppointmentError::Conflict(Box::new(conflict)));
+            }
+            let appointment: Self = diesel::insert_into(appointments::table)
+                .values((
+                    appointments::lead_id.eq(payload.lead_id),
+                    appointments::property_id.eq(property_id),
+                    appointments::user_id.eq(user_id),
+                    appointments::starts_at.eq(payload.starts_at),
+                    appointments::ends_at.eq(ends_at),
+                    appointments::status.eq(AppointmentStatus::Confirmed),
+                    appointments::note.eq(&payload.note),
+                ))
+                .get_result(conn)?;
+            Self::notify(conn, &appointment, Notice::Confirmed)?;
+            Ok(appointment)
+        })
+    }
+
+    pub(super) fn confirm(pool: &DbPool, id: &i32) -> Result<Self, AppointmentError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let appointment: Self = appointments::table
+                .find(id)
+                .filter(appointments::status.eq(AppointmentStatus::Requested))
+                .for_update()
+                .get_result(conn)?;
+            if let Some(conflict) = Self::find_overlap(
+                conn,
+                &appointment.user_id,
+                &appointment.starts_at,
+                &appointment.ends_at,
+                Some(appointment.id),
+            )? {
+                return Err(AppointmentError::Conflict(Box::new(conflict)));
+            }
+            let appointment: Self = diesel::update(appointments::table.find(id))
+                .set((
+                    appointments::status.eq(AppointmentStatus::Confirmed),
+                    appointments::sequence.eq(appointments::sequence + 1),
+                ))
+                .get_result(conn)?;
+            Self::notify(conn, &appointment, Notice::Confirmed)?;
+            Ok(appointment)
+        })
+    }
+
+    // A new time set by the agent counts as confirmed
+    pub(super) fn reschedule(
+        pool: &DbPool,
+        id: &i32,
+        starts_at: &NaiveDateTime,
+        ends_at: &NaiveDateTime,
+    ) -> Result<Self, AppointmentError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let appointment: Self = appointments::table
+                .find(id)
+                .filter(appointments::status.ne(AppointmentStatus::Cancelled))
+                .for_update()
+                .get_result(conn)?;
+            if let Some(conflict) = Self::find_overlap(
+                conn,
+                &appointment.user_id,
+                starts_at,
+                ends_at,
+                Some(appointment.id),
+            )? {
+                return Err(AppointmentError::Conflict(Box::new(conflict)));
+            }
+            let appointment: Self = diesel::update(appointments::table.find(id))
+                .set((
+                    appointments::starts_at.eq(starts_at),
+                    appointments::ends_at.eq(ends_at),
+                    appointments::status.eq(AppointmentStatus::Confirmed),
+                    appointments::sequence.eq(appointments::sequence + 1),
+                ))
+                .get_result(conn)?;
+            Self::notify(conn, &appointment, Notice::Rescheduled)?;
+            Ok(appointment)
+        })
+    }
+
+    pub(super) fn cancel(pool: &DbPool, id: &i32, reason: &Option<String>) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let appointment: Self = diesel::update(appointments::table.find(id))
+                .filter(appointments::status.ne(AppointmentStatus::Cancelled))
+                .set((
+                    appointments::status.eq(AppointmentStatus::Cancelled),
+                    appointments::cancel_reason.eq(reason),
+                    appointments::sequence.eq(appointments::sequence + 1),
+                ))
+                .get_result(conn)?;
+            Self::notify(conn, &appointment, Notice::Cancelled)?;
+            Ok(appointment)
+        })
+    }
+
+    // Upcoming viewings move along with the agent's leads
+    pub fn transfer_all_in(
+        conn: &mut PgConnection,
+        from_user_id: &uuid::Uuid,
+        to_user_id: &uuid::Uuid,
+    ) -> QueryResult<usize> {
+        diesel::update(appointments::table)
+            .filter(
+                appointments::user_id
+                    .eq(from_user_id)
+                    .and(appointments::status.ne(AppointmentStatus::Cancelled))
+                    .and(appointments::starts_at.gt(now)),
+            )
+            .set(appointments::user_id.eq(to_user_id))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::{NaiveDateTime, TimeZone};
+
+use super::appointment_status::AppointmentStatus;
+use super::model::{Appointment, AppointmentContext};
+use crate::ical::{self, IcalEvent, IcalTime};
+use crate::outbox::{NewOutboxMessage, OutboxChannel};
+
+const DEFAULT_REMINDER_HOURS: i64 = 24;
+const TIME_FORMAT: &str = "%A %d %B %Y, %H:%M";
+
+// What happened to the appointment, each sends its own messages
+pub(super) enum Notice {
+    Requested,
+    Confirmed,
+    Rescheduled,
+    Cancelled,
+}
+
+// Reminders are queued under this key, so a new time or a cancellation can drop them
+pub(super) fn reminder_key(id: &i32) -> String {
+    format!("appointment-{}-reminder", id)
+}
+
+// Naive times are the server's local time, calendars want them in UTC
+fn to_utc(time: &NaiveDateTime) -> NaiveDateTime {
+    chrono::Local
+        .from_local_datetime(time)
+        .earliest()
+        .map(|time| time.naive_utc())
+        .unwrap_or(*time)
+}
+
+fn location(context: &AppointmentContext) -> String {
+    format!("{}, {}", context.property_street, context.property_regency)
+}
+
+// The viewing as a calendar file, sent along with every message about it
+pub(super) fn invite(appointment: &Appointment, context: &AppointmentContext) -> String {
+    let event = IcalEvent {
+        uid: format!("appointment-{}@primeproindonesia.com", appointment.id),
+        stamp: chrono::Utc::now().naive_utc(),
+        start: IcalTime::DateTime(to_utc(&appointment.starts_at)),
+        end: IcalTime::DateTime(to_utc(&appointment.ends_at)),
+        summary: format!("Viewing: {}", context.property_title),
+        description: Some(format!(
+            "Viewing of {} with {} ({}) and agent {} ({})",
+            context.property_title,
+            context.lead_name,
+            context.lead_phone,
+            context.agent_name,
+            context.agent_phone
+        )),
+        location: Some(location(context)),
+        cancelled: appointment.status == AppointmentStatus::Cancelled,
+        sequence: appointment.sequence,
+    };
+    ical::render(&format!("Viewing: {}", context.property_title), &[event])
+}
+
+fn message(
+    appointment: &Appointment,
+    context: &AppointmentContext,
+    channel: OutboxChannel,
+    recipient: &str,
+    subject: String,
+    body: String,
+) -> NewOutboxMessage {
+    NewOutboxMessage {
+        channel,
+        recipient: recipient.to_string(),
+        subject,
+        body,
+        attachment_name: Some(format!("viewing-{}.ics", appointment.id)),
+        attachment_type: Some("text/calendar".to_string()),
+        attachment: Some(invite(appointment, context)),
+        key: None,
+        send_after: None,
+    }
+}
+
+// Leads without an email are reached on WhatsApp
+fn lead_recipient(context: &AppointmentContext) -> (OutboxChannel, &str) {
+    match &context.lead_email {
+        Some(email) => (OutboxChannel::Email, email.as_str()),
+        None => (OutboxChannel::Whatsapp, context.lead_phone.as_str()),
+    }
+}
+
+fn reminder_hours() -> i64 {
+    std::env::var("APPOINTMENT_REMINDER_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(DEFAULT_REMINDER_HOURS)
+}
+
+pub(super) fn messages(
+    appointment: &Appointment,
+    context: &AppointmentContext,
+    notice: Notice,
+) -> Vec<NewOutboxMessage> {
+    let title = &context.property_title;
+    let time = appointment.starts_at.format(TIME_FORMAT).to_string();
+    let (lead_channel, lead_address) = lead_recipient(context);
+
+    let (lead_subject, agent_subject, body) = match notice {
+        // the lead hears back once the agent confirms
+        Notice::Requested => {
+            return vec![message(
+                appointment,
+                context,
+                OutboxChannel::Email,
+                &context.agent_email,
+                format!("Viewing request for {}", title),
+                format!(
+                    "{} ({}) would like to view {} on {}. Please confirm or reschedule it.",
+                    context.lead_name, context.lead_phone, title, time
+                ),
+            )]
+        }
+        Notice::Confirmed => (
+            format!("Viewing confirmed: {}", title),
+            format!("Viewing confirmed: {}", title),
+            format!(
+                "The viewing of {} at {} is confirmed for {}. Agent: {} ({}).",
+                title,
+                location(context),
+                time,
+                context.agent_name,
+                context.agent_phone
+            ),
+        ),
+        Notice::Rescheduled => (
+            format!("Viewing rescheduled: {}", title),
+            format!("Viewing rescheduled: {}", title),
+            format!(
+                "The viewing of {} at {} has moved to {}. Agent: {} ({}).",
+                title,
+                location(context),
+                time,
+                context.agent_name,
+                context.agent_phone
+            ),
+        ),
+        Notice::Cancelled => (
+            format!("Viewing cancelled: {}", title),
+            format!("Viewing cancelled: {}", title),
+            match &appointment.cancel_reason {
+                Some(reason) => format!(
+                    "The viewing of {} on {} is cancelled: {}",
+                    title, time, reason
+                ),
+                None => format!("The viewing of {} on {} is cancelled.", title, time),
+            },
+        ),
+    };
+
+    let mut messages = vec![
+        message(
+            appointment,
+            context,
+            lead_channel,
+            lead_address,
+            lead_subject,
+            body.clone(),
+        ),
+        message(
+            appointment,
+            context,
+            OutboxChannel::Email,
+            &context.agent_email,
+            agent_subject,
+            format!(
+                "{}\nLead: {} ({})",
+                body, context.lead_name, context.lead_phone
+            ),
+        ),
+    ];
+
+    // no reminder when the viewing is closer than the reminder would be
+    let remind_at = appointment.starts_at - chrono::Duration::hours(reminder_hours());
+    if appointment.status == AppointmentStatus::Confirmed
+        && remind_at > chrono::Local::now().naive_local()
+    {
+        let reminder = |channel, recipient: &str| NewOutboxMessage {
+            key: Some(reminder_key(&appointment.id)),
+            send_after: Some(remind_at),
+            ..message(
+                appointment,
+                context,
+                channel,
+                recipient,
+                format!("Reminder: viewing of {}", title),
+                format!(
+                    "A reminder of the viewing of {} at {} on {}.",
+                    title,
+                    location(context),
+                    time
+                ),
+            )
+        };
+        messages.push(reminder(lead_channel, lead_address));
+        messages.push(reminder(OutboxChannel::Email, &context.agent_email));
+    }
+
+    messages
+}
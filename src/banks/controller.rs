@@ -1,18 +1,22 @@
 use axum::{
-    extract::{Path, Request, State},
+    extract::{Path, Query, Request, State},
+    http::HeaderMap,
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     Json,
 };
 use diesel::prelude::{AsChangeset, Insertable};
+use diesel::result::DatabaseErrorKind;
 use reqwest::Method;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use super::model::Bank;
+use super::model::{Bank, BankDependents, HandleDependents};
+use super::product::BankProduct;
 use crate::{
-    agents::{Agent, AgentRole},
+    agents::{can, Action, Agent, Resource},
     db::DbPool,
     middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session},
+    properties::{find_listing_page, ListingAggregates, ListingPartner, PropertyWithRelation},
     schema,
 };
 
@@ -26,7 +30,7 @@ pub(super) async fn banks_middleware(
         &Method::GET => Ok(next.run(req).await),
         _ => {
             let headers = req.headers();
-            let user_id = Session::extract_session_user_id(&headers);
+            let user_id = Session::extract_session_user_id(headers);
 
             let agent = match Agent::find_by_user_id(&pool, &user_id) {
                 Ok(agent) => agent,
@@ -36,9 +40,15 @@ pub(super) async fn banks_middleware(
                 }
             };
 
-            match agent.role {
-                AgentRole::Admin => Ok(next.run(req).await),
-                _ => {
+            let action = match *method {
+                Method::POST => Action::Create,
+                Method::PUT => Action::Update,
+                _ => Action::Delete,
+            };
+
+            match can(&agent, action, Resource::Bank) {
+                true => Ok(next.run(req).await),
+                false => {
                     let response = JsonResponse::send(403, None, None);
                     Err(response)
                 }
@@ -47,17 +57,64 @@ pub(super) async fn banks_middleware(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct FindBanksQuery {
+    include_products: Option<bool>,
+    include_archived: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct BankWithProducts {
+    #[serde(flatten)]
+    bank: Bank,
+    // Products active and valid today, only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    products: Option<Vec<BankProduct>>,
+}
+
 pub(super) async fn find_many_banks(
     State(pool): State<DbPool>,
-) -> AxumResponse<JsonFindResponse<Vec<Bank>>> {
-    let banks = match Bank::find_many(&pool) {
+    headers: HeaderMap,
+    Query(query): Query<FindBanksQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<BankWithProducts>>> {
+    // archived ones only for the agents managing them, the public site never lists them
+    let include_archived = query.include_archived.unwrap_or(false)
+        && headers.contains_key("x-user-id")
+        && Agent::find_by_user_id(&pool, &Session::extract_session_user_id(&headers))
+            .is_ok_and(|agent| can(&agent, Action::Update, Resource::Bank));
+    let banks = match Bank::find_many(&pool, include_archived) {
         Ok(banks) => banks,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
 
+    let mut products = match query.include_products {
+        Some(true) => {
+            let bank_ids: Vec<i32> = banks.iter().map(|bank| bank.id).collect();
+            match BankProduct::find_many_current(&pool, &bank_ids) {
+                Ok(products) => Some(products),
+                Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+            }
+        }
+        _ => None,
+    };
+
+    let banks: Vec<BankWithProducts> = banks
+        .into_iter()
+        .map(|bank| BankWithProducts {
+            products: products.as_mut().map(|products| {
+                let (own, others) = products
+                    .drain(..)
+                    .partition(|product| product.bank_id == bank.id);
+                *products = others;
+                own
+            }),
+            bank,
+        })
+        .collect();
+
     let res = JsonFindResponse {
-        data: banks.clone(),
         total_data: banks.len() as i64,
+        data: banks,
         total_pages: 1,
     };
 
@@ -68,7 +125,12 @@ pub(super) async fn find_many_banks(
 #[diesel(table_name = schema::banks)]
 pub(super) struct CreateBankPayload {
     logo_path: String,
-    name: String,
+    pub(super) name: String,
+    #[serde(default)]
+    description: String,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
 }
 
 pub(super) async fn create_bank(
@@ -77,7 +139,7 @@ pub(super) async fn create_bank(
 ) -> AxumResponse<Bank> {
     match Bank::create(&pool, &payload) {
         Ok(bank) => JsonResponse::send(201, Some(bank), None),
-        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
@@ -86,6 +148,10 @@ pub(super) async fn create_bank(
 pub(super) struct UpdateBankPayload {
     logo_path: Option<String>,
     name: Option<String>,
+    description: Option<String>,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
 }
 
 pub(super) async fn update_bank(
@@ -95,15 +161,121 @@ pub(super) async fn update_bank(
 ) -> AxumResponse<Bank> {
     match Bank::update(&pool, &id, &payload) {
         Ok(bank) => JsonResponse::send(200, Some(bank), None),
-        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct DeleteBankQuery {
+    // Listings keep going without a bank
+    detach: Option<bool>,
+    // Listings move to this bank
+    reassign_to: Option<i32>,
+}
+
+// A bank with listings is only deleted when told what happens to them
 pub(super) async fn delete_bank(
     State(pool): State<DbPool>,
     Path(id): Path<i32>,
+    Query(query): Query<DeleteBankQuery>,
+) -> Response {
+    let dependents = match (query.detach.unwrap_or(false), query.reassign_to) {
+        (true, Some(_)) => {
+            return JsonResponse::<Bank>::send(
+                400,
+                None,
+                Some("Use either detach or reassign_to".to_string()),
+            )
+            .into_response()
+        }
+        (true, None) => Some(HandleDependents::Detach),
+        (false, Some(to)) if to == id => {
+            return JsonResponse::<Bank>::send(
+                400,
+                None,
+                Some("Can't reassign to the bank being deleted".to_string()),
+            )
+            .into_response()
+        }
+        (false, Some(to)) => match Bank::find_by_id(&pool, &to) {
+            Ok(bank) if bank.archived_at.is_none() => Some(HandleDependents::Reassign(to)),
+            Ok(_) | Err(diesel::result::Error::NotFound) => {
+                return JsonResponse::<Bank>::send(
+                    400,
+                    None,
+                    Some("Bank to reassign to not found".to_string()),
+                )
+                .into_response()
+            }
+            Err(err) => {
+                return JsonResponse::<Bank>::send(500, None, Some(err.to_string())).into_response()
+            }
+        },
+        (false, None) => None,
+    };
+
+    match Bank::find_dependents(&pool, &id) {
+        Ok(found) if !found.products.is_empty() => {
+            return JsonResponse::send(
+                409,
+                Some(found),
+                Some("Bank has products, archive it instead".to_string()),
+            )
+            .into_response()
+        }
+        Ok(found) if dependents.is_none() && !found.is_empty() => {
+            return JsonResponse::send(
+                409,
+                Some(found),
+                Some("Bank still has listings, use detach=true or reassign_to".to_string()),
+            )
+            .into_response()
+        }
+        Ok(_) => {}
+        Err(err) => {
+            return JsonResponse::<BankDependents>::send(500, None, Some(err.to_string()))
+                .into_response()
+        }
+    }
+
+    match Bank::delete(&pool, &id, dependents) {
+        Ok(bank) => JsonResponse::send(200, Some(bank), None).into_response(),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::<Bank>::send(404, None, Some("Bank not found".to_string()))
+                    .into_response()
+            }
+            // A listing or product was added since the check
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                JsonResponse::<Bank>::send(
+                    409,
+                    None,
+                    Some("Bank still has listings or products".to_string()),
+                )
+                .into_response()
+            }
+            _ => JsonResponse::<Bank>::send(500, None, Some(err.to_string())).into_response(),
+        },
+    }
+}
+
+// Archived banks are hidden from public lists and simulations, their listings stay as they are
+pub(super) async fn archive_bank(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> AxumResponse<Bank> {
+    set_archived(&pool, &id, true)
+}
+
+pub(super) async fn unarchive_bank(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
 ) -> AxumResponse<Bank> {
-    match Bank::delete(&pool, &id) {
+    set_archived(&pool, &id, false)
+}
+
+fn set_archived(pool: &DbPool, id: &i32, archived: bool) -> AxumResponse<Bank> {
+    match Bank::set_archived(pool, id, archived) {
         Ok(bank) => JsonResponse::send(200, Some(bank), None),
         Err(err) => match err {
             diesel::result::Error::NotFound => {
@@ -128,3 +300,68 @@ pub(super) async fn find_bank_by_id(
         },
     }
 }
+
+const DEFAULT_PROFILE_PAGE_SIZE: i64 = 12;
+const MAX_PROFILE_PAGE_SIZE: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct BankProfileQuery {
+    page: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct BankProfile {
+    #[serde(flatten)]
+    bank: Bank,
+    // Products active and valid today
+    products: Vec<BankProduct>,
+    aggregates: ListingAggregates,
+    listings: JsonFindResponse<Vec<PropertyWithRelation>>,
+}
+
+// Everything a landing page needs in one request
+pub(super) async fn find_bank_profile(
+    State(pool): State<DbPool>,
+    Path(slug): Path<String>,
+    Query(query): Query<BankProfileQuery>,
+) -> AxumResponse<BankProfile> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PROFILE_PAGE_SIZE)
+        .clamp(1, MAX_PROFILE_PAGE_SIZE);
+
+    let bank = match Bank::find_by_slug(&pool, &slug) {
+        Ok(bank) => bank,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Bank not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let partner = ListingPartner::Bank(bank.id);
+    let aggregates = match ListingAggregates::find(&pool, partner) {
+        Ok(aggregates) => aggregates,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let listings = match find_listing_page(&pool, partner, page, limit) {
+        Ok(listings) => listings,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let products = match BankProduct::find_many_current(&pool, &[bank.id]) {
+        Ok(products) => products,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(BankProfile {
+            bank,
+            products,
+            aggregates,
+            listings,
+        }),
+        None,
+    )
+}
@@ -1,5 +1,8 @@
 mod controller;
 mod model;
+mod product;
+mod product_controller;
 mod routes;
 
+pub use product::BankProduct;
 pub use routes::banks_routes;
@@ -1,9 +1,11 @@
-use diesel::{ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use diesel::dsl;
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
 use serde::Serialize;
 
 use crate::banks::controller::{CreateBankPayload, UpdateBankPayload};
 use crate::db::DbPool;
 use crate::schema;
+use crate::slug::{available_slug, insert_with_slug, slugify};
 
 #[derive(Debug, Serialize, Queryable, Clone)]
 pub(super) struct Bank {
@@ -12,14 +14,58 @@ pub(super) struct Bank {
     updated_at: chrono::NaiveDateTime,
     logo_path: String,
     name: String,
+    description: String,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
+    pub slug: String,
+    pub archived_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub(super) struct DependentListing {
+    id: i32,
+    title: String,
+    is_deleted: bool,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub(super) struct DependentProduct {
+    id: i32,
+    name: String,
+    is_deleted: bool,
+}
+
+// What would stop the bank from being deleted. Listings can be detached or reassigned,
+// products never leave their bank.
+#[derive(Debug, Serialize)]
+pub(super) struct BankDependents {
+    listings: Vec<DependentListing>,
+    pub(super) products: Vec<DependentProduct>,
+}
+
+impl BankDependents {
+    pub(super) fn is_empty(&self) -> bool {
+        self.listings.is_empty() && self.products.is_empty()
+    }
+}
+
+// What happens to the listings of a deleted bank
+pub(super) enum HandleDependents {
+    Detach,
+    Reassign(i32),
 }
 
 impl Bank {
-    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+    pub(super) fn find_many(pool: &DbPool, include_archived: bool) -> QueryResult<Vec<Self>> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        schema::banks::table
+        let mut query = schema::banks::table
             .order_by(schema::banks::name.asc())
-            .get_results(conn)
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(schema::banks::archived_at.is_null());
+        }
+        query.get_results(conn)
     }
 
     pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
@@ -29,13 +75,66 @@ impl Bank {
             .get_result(conn)
     }
 
-    pub(super) fn create(pool: &DbPool, payload: &CreateBankPayload) -> QueryResult<Self> {
+    // Archived banks have no public profile
+    pub(super) fn find_by_slug(pool: &DbPool, slug: &str) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        diesel::insert_into(schema::banks::table)
-            .values(payload)
+        schema::banks::table
+            .filter(schema::banks::slug.eq(slug))
+            .filter(schema::banks::archived_at.is_null())
             .get_result(conn)
     }
 
+    pub(super) fn find_dependents(pool: &DbPool, id: &i32) -> QueryResult<BankDependents> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        let listings = schema::properties::table
+            .filter(schema::properties::bank_id.eq(id))
+            .select((
+                schema::properties::id,
+                schema::properties::title,
+                schema::properties::is_deleted,
+            ))
+            .order_by(schema::properties::id.asc())
+            .get_results(conn)?;
+        let products = schema::bank_products::table
+            .filter(schema::bank_products::bank_id.eq(id))
+            .select((
+                schema::bank_products::id,
+                schema::bank_products::name,
+                schema::bank_products::is_deleted,
+            ))
+            .order_by(schema::bank_products::id.asc())
+            .get_results(conn)?;
+
+        Ok(BankDependents { listings, products })
+    }
+
+    pub(super) fn set_archived(pool: &DbPool, id: &i32, archived: bool) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        let archived_at = match archived {
+            true => Some(chrono::Local::now().naive_local()),
+            false => None,
+        };
+        diesel::update(schema::banks::table)
+            .filter(schema::banks::id.eq(id))
+            .set(schema::banks::archived_at.eq(archived_at))
+            .get_result(conn)
+    }
+
+    pub(super) fn create(pool: &DbPool, payload: &CreateBankPayload) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        insert_with_slug(conn, |conn| {
+            let slug = available_slug(conn, &slugify(&payload.name, "bank"), |conn, slug| {
+                dsl::select(dsl::exists(
+                    schema::banks::table.filter(schema::banks::slug.eq(slug)),
+                ))
+                .get_result(conn)
+            })?;
+            diesel::insert_into(schema::banks::table)
+                .values((schema::banks::slug.eq(slug), payload))
+                .get_result(conn)
+        })
+    }
+
     pub(super) fn update(
         pool: &DbPool,
         id: &i32,
@@ -48,10 +147,29 @@ impl Bank {
             .get_result(conn)
     }
 
-    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+    // Without `dependents` a bank with listings is kept, ForeignKeyViolation
+    pub(super) fn delete(
+        pool: &DbPool,
+        id: &i32,
+        dependents: Option<HandleDependents>,
+    ) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        diesel::delete(schema::banks::table)
-            .filter(schema::banks::id.eq(id))
-            .get_result(conn)
+        conn.transaction(|conn| {
+            let bank_id = match dependents {
+                Some(HandleDependents::Detach) => Some(None),
+                Some(HandleDependents::Reassign(to)) => Some(Some(to)),
+                None => None,
+            };
+            if let Some(bank_id) = bank_id {
+                diesel::update(schema::properties::table)
+                    .filter(schema::properties::bank_id.eq(id))
+                    .set(schema::properties::bank_id.eq(bank_id))
+                    .execute(conn)?;
+            }
+
+            diesel::delete(schema::banks::table)
+                .filter(schema::banks::id.eq(id))
+                .get_result(conn)
+        })
     }
 }
@@ -0,0 +1,328 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, PgConnection, QueryDsl, QueryResult,
+    Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::product_controller::BankProductPayload;
+use crate::db::DbPool;
+use crate::schema::{bank_product_revisions, bank_products, banks};
+
+// Products that are switched on and inside their validity period on the day
+#[diesel::dsl::auto_type]
+fn is_current(today: NaiveDate) -> _ {
+    bank_products::is_active
+        .eq(true)
+        .and(bank_products::is_deleted.eq(false))
+        .and(
+            bank_products::valid_from
+                .is_null()
+                .or(bank_products::valid_from.le(today)),
+        )
+        .and(
+            bank_products::valid_until
+                .is_null()
+                .or(bank_products::valid_until.ge(today)),
+        )
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+// A mortgage (KPR) product, rates are annual percentages
+#[derive(Debug, Serialize, Queryable, Clone)]
+pub struct BankProduct {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub bank_id: i32,
+    pub name: String,
+    pub fixed_rate: BigDecimal,
+    pub fixed_period_months: i32,
+    pub floating_rate: BigDecimal,
+    pub max_tenor_years: i32,
+    pub min_down_payment_percent: BigDecimal,
+    pub provision_fee_percent: BigDecimal,
+    pub admin_fee: i64,
+    pub appraisal_fee: i64,
+    // Bumped on every change, see `BankProductRevision`
+    pub revision: i32,
+    is_active: bool,
+    is_deleted: bool,
+    valid_from: Option<NaiveDate>,
+    valid_until: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct BankProductRevision {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    product_id: i32,
+    revision: i32,
+    changed_by: Option<uuid::Uuid>,
+    name: String,
+    fixed_rate: BigDecimal,
+    fixed_period_months: i32,
+    floating_rate: BigDecimal,
+    max_tenor_years: i32,
+    min_down_payment_percent: BigDecimal,
+    provision_fee_percent: BigDecimal,
+    admin_fee: i64,
+    appraisal_fee: i64,
+    is_active: bool,
+    is_deleted: bool,
+    valid_from: Option<NaiveDate>,
+    valid_until: Option<NaiveDate>,
+}
+
+impl BankProductRevision {
+    // The product as it was at this revision
+    fn into_product(self, bank_id: i32) -> BankProduct {
+        BankProduct {
+            id: self.product_id,
+            created_at: self.created_at,
+            updated_at: self.created_at,
+            bank_id,
+            name: self.name,
+            fixed_rate: self.fixed_rate,
+            fixed_period_months: self.fixed_period_months,
+            floating_rate: self.floating_rate,
+            max_tenor_years: self.max_tenor_years,
+            min_down_payment_percent: self.min_down_payment_percent,
+            provision_fee_percent: self.provision_fee_percent,
+            admin_fee: self.admin_fee,
+            appraisal_fee: self.appraisal_fee,
+            revision: self.revision,
+            is_active: self.is_active,
+            is_deleted: self.is_deleted,
+            valid_from: self.valid_from,
+            valid_until: self.valid_until,
+        }
+    }
+
+    pub(super) fn find_many(
+        pool: &DbPool,
+        bank_id: &i32,
+        product_id: &i32,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        bank_product_revisions::table
+            .inner_join(bank_products::table)
+            .filter(bank_product_revisions::product_id.eq(product_id))
+            .filter(bank_products::bank_id.eq(bank_id))
+            .select(bank_product_revisions::all_columns)
+            .order_by(bank_product_revisions::revision.desc())
+            .get_results(conn)
+    }
+}
+
+#[cfg(test)]
+impl BankProduct {
+    // An active product without fees, for the simulation tests
+    pub(crate) fn sample(fixed_rate: &str, fixed_period_months: i32, floating_rate: &str) -> Self {
+        BankProduct {
+            id: 1,
+            created_at: chrono::NaiveDateTime::default(),
+            updated_at: chrono::NaiveDateTime::default(),
+            bank_id: 1,
+            name: "Sample".to_string(),
+            fixed_rate: fixed_rate.parse().expect("Invalid fixed rate"),
+            fixed_period_months,
+            floating_rate: floating_rate.parse().expect("Invalid floating rate"),
+            max_tenor_years: 30,
+            min_down_payment_percent: BigDecimal::from(10),
+            provision_fee_percent: BigDecimal::from(1),
+            admin_fee: 0,
+            appraisal_fee: 0,
+            revision: 1,
+            is_active: true,
+            is_deleted: false,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+}
+
+impl BankProduct {
+    fn record_revision(
+        conn: &mut PgConnection,
+        product: &Self,
+        changed_by: &uuid::Uuid,
+    ) -> QueryResult<usize> {
+        diesel::insert_into(bank_product_revisions::table)
+            .values((
+                bank_product_revisions::product_id.eq(product.id),
+                bank_product_revisions::revision.eq(product.revision),
+                bank_product_revisions::changed_by.eq(changed_by),
+                bank_product_revisions::name.eq(&product.name),
+                bank_product_revisions::fixed_rate.eq(&product.fixed_rate),
+                bank_product_revisions::fixed_period_months.eq(product.fixed_period_months),
+                bank_product_revisions::floating_rate.eq(&product.floating_rate),
+                bank_product_revisions::max_tenor_years.eq(product.max_tenor_years),
+                bank_product_revisions::min_down_payment_percent
+                    .eq(&product.min_down_payment_percent),
+                bank_product_revisions::provision_fee_percent.eq(&product.provision_fee_percent),
+                bank_product_revisions::admin_fee.eq(product.admin_fee),
+                bank_product_revisions::appraisal_fee.eq(product.appraisal_fee),
+                bank_product_revisions::is_active.eq(product.is_active),
+                bank_product_revisions::is_deleted.eq(product.is_deleted),
+                bank_product_revisions::valid_from.eq(product.valid_from),
+                bank_product_revisions::valid_until.eq(product.valid_until),
+            ))
+            .execute(conn)
+    }
+
+    // Current products with the name of their bank, all unarchived banks when bank_id is None
+    pub fn find_many_current_with_bank(
+        pool: &DbPool,
+        bank_id: Option<i32>,
+        product_id: Option<i32>,
+    ) -> QueryResult<Vec<(Self, String)>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut query = bank_products::table
+            .inner_join(banks::table)
+            .filter(is_current(today()))
+            .filter(banks::archived_at.is_null())
+            .select((bank_products::all_columns, banks::name))
+            .order_by((banks::name.asc(), bank_products::name.asc()))
+            .into_boxed();
+        if let Some(bank_id) = bank_id {
+            query = query.filter(bank_products::bank_id.eq(bank_id));
+        }
+        if let Some(product_id) = product_id {
+            query = query.filter(bank_products::id.eq(product_id));
+        }
+        query.get_results(conn)
+    }
+
+    // A past version with the name of its bank, whether or not it is current
+    pub fn find_revision_with_bank(
+        pool: &DbPool,
+        product_id: &i32,
+        revision: &i32,
+    ) -> QueryResult<(Self, String)> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let (product_revision, bank_id, bank_name): (BankProductRevision, i32, String) =
+            bank_product_revisions::table
+                .inner_join(bank_products::table.inner_join(banks::table))
+                .filter(bank_product_revisions::product_id.eq(product_id))
+                .filter(bank_product_revisions::revision.eq(revision))
+                .select((
+                    bank_product_revisions::all_columns,
+                    bank_products::bank_id,
+                    banks::name,
+                ))
+                .get_result(conn)?;
+
+        Ok((product_revision.into_product(bank_id), bank_name))
+    }
+
+    pub(super) fn find_many_current(pool: &DbPool, bank_ids: &[i32]) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        bank_products::table
+            .filter(bank_products::bank_id.eq_any(bank_ids))
+            .filter(is_current(today()))
+            .order_by(bank_products::name.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn find_many_by_bank(
+        pool: &DbPool,
+        bank_id: &i32,
+        current_only: bool,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut query = bank_products::table
+            .filter(bank_products::bank_id.eq(bank_id))
+            .filter(bank_products::is_deleted.eq(false))
+            .order_by(bank_products::name.asc())
+            .into_boxed();
+        if current_only {
+            query = query.filter(is_current(today()));
+        }
+        query.get_results(conn)
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, bank_id: &i32, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        bank_products::table
+            .filter(bank_products::id.eq(id))
+            .filter(bank_products::bank_id.eq(bank_id))
+            .filter(bank_products::is_deleted.eq(false))
+            .get_result(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        bank_id: &i32,
+        changed_by: &uuid::Uuid,
+        payload: &BankProductPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let product: Self = diesel::insert_into(bank_products::table)
+                .values((bank_products::bank_id.eq(bank_id), payload))
+                .get_result(conn)?;
+            Self::record_revision(conn, &product, changed_by)?;
+            Ok(product)
+        })
+    }
+
+    pub(super) fn update(
+        pool: &DbPool,
+        bank_id: &i32,
+        id: &i32,
+        changed_by: &uuid::Uuid,
+        payload: &BankProductPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let product: Self = diesel::update(bank_products::table)
+                .filter(bank_products::id.eq(id))
+                .filter(bank_products::bank_id.eq(bank_id))
+                .filter(bank_products::is_deleted.eq(false))
+                .set((
+                    payload,
+                    bank_products::revision.eq(bank_products::revision + 1),
+                ))
+                .get_result(conn)?;
+            Self::record_revision(conn, &product, changed_by)?;
+            Ok(product)
+        })
+    }
+
+    // Kept with its revisions, so simulations made with it can still be reproduced
+    pub(super) fn delete(
+        pool: &DbPool,
+        bank_id: &i32,
+        id: &i32,
+        changed_by: &uuid::Uuid,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let product: Self = diesel::update(bank_products::table)
+                .filter(bank_products::id.eq(id))
+                .filter(bank_products::bank_id.eq(bank_id))
+                .filter(bank_products::is_deleted.eq(false))
+                .set((
+                    bank_products::is_deleted.eq(true),
+                    bank_products::revision.eq(bank_products::revision + 1),
+                ))
+                .get_result(conn)?;
+            Self::record_revision(conn, &product, changed_by)?;
+            Ok(product)
+        })
+    }
+}
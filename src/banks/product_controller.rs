@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use diesel::prelude::{AsChangeset, Insertable};
+use serde::Deserialize;
+
+use super::model::Bank;
+use super::product::{BankProduct, BankProductRevision};
+use crate::{
+    agents::{authorize, Action, Resource},
+    db::DbPool,
+    middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session},
+    schema,
+};
+
+fn default_is_active() -> bool {
+    true
+}
+
+// Replaces the whole product, leaving out valid_from or valid_until removes that bound
+#[derive(Debug, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = schema::bank_products)]
+#[diesel(treat_none_as_null = true)]
+pub(super) struct BankProductPayload {
+    name: String,
+    fixed_rate: BigDecimal,
+    #[serde(default)]
+    fixed_period_months: i32,
+    floating_rate: BigDecimal,
+    max_tenor_years: i32,
+    #[serde(default)]
+    min_down_payment_percent: BigDecimal,
+    #[serde(default)]
+    provision_fee_percent: BigDecimal,
+    #[serde(default)]
+    admin_fee: i64,
+    #[serde(default)]
+    appraisal_fee: i64,
+    #[serde(default = "default_is_active")]
+    is_active: bool,
+    valid_from: Option<NaiveDate>,
+    valid_until: Option<NaiveDate>,
+}
+
+impl BankProductPayload {
+    fn validate(&self) -> Result<(), String> {
+        let hundred = BigDecimal::from(100);
+        if self.name.trim().is_empty() {
+            return Err("Name is required".to_string());
+        }
+        if self.fixed_rate < BigDecimal::zero()
+            || self.fixed_rate > hundred
+            || self.floating_rate < BigDecimal::zero()
+            || self.floating_rate > hundred
+        {
+            return Err("Rates must be between 0 and 100".to_string());
+        }
+        if self.fixed_period_months < 0 {
+            return Err("Fixed period can't be negative".to_string());
+        }
+        if self.max_tenor_years <= 0 {
+            return Err("Max tenor must be positive".to_string());
+        }
+        if self.min_down_payment_percent < BigDecimal::zero()
+            || self.min_down_payment_percent >= hundred
+        {
+            return Err("Minimum down payment must be at least 0% and below 100%".to_string());
+        }
+        if self.provision_fee_percent < BigDecimal::zero() || self.provision_fee_percent > hundred {
+            return Err("Provision fee must be between 0% and 100%".to_string());
+        }
+        if self.admin_fee < 0 || self.appraisal_fee < 0 {
+            return Err("Fees can't be negative".to_string());
+        }
+        if let (Some(valid_from), Some(valid_until)) = (self.valid_from, self.valid_until) {
+            if valid_from > valid_until {
+                return Err("valid_from must not be after valid_until".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct FindBankProductsQuery {
+    // Only products that are active and valid today
+    current: Option<bool>,
+}
+
+pub(super) async fn find_bank_products(
+    State(pool): State<DbPool>,
+    Path(bank_id): Path<i32>,
+    Query(query): Query<FindBankProductsQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<BankProduct>>> {
+    if let Err(err) = Bank::find_by_id(&pool, &bank_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Bank not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    let products =
+        match BankProduct::find_many_by_bank(&pool, &bank_id, query.current.unwrap_or(false)) {
+            Ok(products) => products,
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        };
+
+    let res = JsonFindResponse {
+        total_data: products.len() as i64,
+        data: products,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+pub(super) async fn find_bank_product(
+    State(pool): State<DbPool>,
+    Path((bank_id, id)): Path<(i32, i32)>,
+) -> AxumResponse<BankProduct> {
+    match BankProduct::find_by_id(&pool, &bank_id, &id) {
+        Ok(product) => JsonResponse::send(200, Some(product), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Bank product not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+pub(super) async fn create_bank_product(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(bank_id): Path<i32>,
+    Json(payload): Json<BankProductPayload>,
+) -> AxumResponse<BankProduct> {
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    if let Err(err) = Bank::find_by_id(&pool, &bank_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Bank not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    let user_id = Session::extract_session_user_id(&headers);
+    match BankProduct::create(&pool, &bank_id, &user_id, &payload) {
+        Ok(product) => JsonResponse::send(201, Some(product), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub(super) async fn update_bank_product(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((bank_id, id)): Path<(i32, i32)>,
+    Json(payload): Json<BankProductPayload>,
+) -> AxumResponse<BankProduct> {
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    let user_id = Session::extract_session_user_id(&headers);
+    match BankProduct::update(&pool, &bank_id, &id, &user_id, &payload) {
+        Ok(product) => JsonResponse::send(200, Some(product), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Bank product not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+pub(super) async fn delete_bank_product(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((bank_id, id)): Path<(i32, i32)>,
+) -> AxumResponse<BankProduct> {
+    let user_id = Session::extract_session_user_id(&headers);
+    match BankProduct::delete(&pool, &bank_id, &id, &user_id) {
+        Ok(product) => JsonResponse::send(200, Some(product), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Bank product not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+// Newest first, deleted products included. Only for those who manage the products
+pub(super) async fn find_bank_product_history(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((bank_id, id)): Path<(i32, i32)>,
+) -> AxumResponse<JsonFindResponse<Vec<BankProductRevision>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::Bank) {
+        return response;
+    }
+
+    let revisions = match BankProductRevision::find_many(&pool, &bank_id, &id) {
+        Ok(revisions) if revisions.is_empty() => {
+            return JsonResponse::send(404, None, Some("Bank product not found".to_string()))
+        }
+        Ok(revisions) => revisions,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let res = JsonFindResponse {
+        total_data: revisions.len() as i64,
+        data: revisions,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
@@ -3,7 +3,12 @@ use axum::routing::{delete, get, post, put};
 use axum::Router;
 
 use crate::banks::controller::{
-    banks_middleware, create_bank, delete_bank, find_bank_by_id, find_many_banks, update_bank,
+    archive_bank, banks_middleware, create_bank, delete_bank, find_bank_by_id, find_bank_profile,
+    find_many_banks, unarchive_bank, update_bank,
+};
+use crate::banks::product_controller::{
+    create_bank_product, delete_bank_product, find_bank_product, find_bank_product_history,
+    find_bank_products, update_bank_product,
 };
 use crate::db::DbPool;
 
@@ -11,8 +16,20 @@ pub fn banks_routes(pool: DbPool) -> Router<DbPool> {
     axum::Router::new()
         .route("/", get(find_many_banks))
         .route("/{id}", get(find_bank_by_id))
+        .route("/by-slug/{slug}", get(find_bank_profile))
         .route("/", post(create_bank))
         .route("/{id}", put(update_bank))
         .route("/{id}", delete(delete_bank))
+        .route("/{id}/archive", put(archive_bank))
+        .route("/{id}/unarchive", put(unarchive_bank))
+        .route("/{id}/products", get(find_bank_products))
+        .route("/{id}/products", post(create_bank_product))
+        .route("/{id}/products/{product_id}", get(find_bank_product))
+        .route("/{id}/products/{product_id}", put(update_bank_product))
+        .route("/{id}/products/{product_id}", delete(delete_bank_product))
+        .route(
+            "/{id}/products/{product_id}/history",
+            get(find_bank_product_history),
+        )
         .layer(from_fn_with_state(pool.clone(), banks_middleware))
 }
@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use diesel::prelude::{AsChangeset, Insertable};
+use diesel::result::DatabaseErrorKind;
+use reqwest::Method;
+use serde::Deserialize;
+
+use super::model::{Branch, BranchReport};
+use crate::{
+    agents::{can, scope, Action, Agent, Resource},
+    db::DbPool,
+    middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session},
+    schema,
+};
+
+pub(super) async fn branches_middleware(
+    State(pool): State<DbPool>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AxumResponse<String>> {
+    let method = req.method();
+    match method {
+        &Method::GET => Ok(next.run(req).await),
+        _ => {
+            let headers = req.headers();
+            let user_id = Session::extract_session_user_id(headers);
+
+            let agent = match Agent::find_by_user_id(&pool, &user_id) {
+                Ok(agent) => agent,
+                Err(err) => {
+                    let response = JsonResponse::send(403, None, Some(err.to_string()));
+                    return Err(response);
+                }
+            };
+
+            let action = match *method {
+                Method::POST => Action::Create,
+                Method::PUT => Action::Update,
+                _ => Action::Delete,
+            };
+
+            match can(&agent, action, Resource::Branch) {
+                true => Ok(next.run(req).await),
+                false => {
+                    let response = JsonResponse::send(403, None, None);
+                    Err(response)
+                }
+            }
+        }
+    }
+}
+
+pub(super) async fn find_many_branches(
+    State(pool): State<DbPool>,
+) -> AxumResponse<JsonFindResponse<Vec<Branch>>> {
+    let branches = match Branch::find_many(&pool) {
+        Ok(branches) => branches,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let res = JsonFindResponse {
+        total_data: branches.len() as i64,
+        data: branches,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = schema::branches)]
+pub(super) struct CreateBranchPayload {
+    name: String,
+    address: Option<String>,
+    phone_number: Option<String>,
+}
+
+pub(super) async fn create_branch(
+    State(pool): State<DbPool>,
+    Json(payload): Json<CreateBranchPayload>,
+) -> AxumResponse<Branch> {
+    match Branch::create(&pool, &payload) {
+        Ok(branch) => JsonResponse::send(201, Some(branch), None),
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            JsonResponse::send(400, None, Some("Branch already exists".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize, AsChangeset)]
+#[diesel(table_name = schema::branches)]
+pub(super) struct UpdateBranchPayload {
+    name: Option<String>,
+    address: Option<String>,
+    phone_number: Option<String>,
+}
+
+pub(super) async fn update_branch(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateBranchPayload>,
+) -> AxumResponse<Branch> {
+    match Branch::update(&pool, &id, &payload) {
+        Ok(branch) => JsonResponse::send(200, Some(branch), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Branch not found".to_string()))
+        }
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            JsonResponse::send(400, None, Some("Branch already exists".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Members of a deleted branch are left without one
+pub(super) async fn delete_branch(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> AxumResponse<Branch> {
+    match Branch::delete(&pool, &id) {
+        Ok(branch) => JsonResponse::send(200, Some(branch), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Branch not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+pub(super) async fn find_branch_by_id(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> AxumResponse<Branch> {
+    match Branch::find_by_id(&pool, &id) {
+        Ok(branch) => JsonResponse::send(200, Some(branch), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Branch not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct BranchReportQuery {
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+}
+
+pub(super) async fn find_branch_report(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<BranchReportQuery>,
+) -> AxumResponse<Vec<BranchReport>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Report) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
+        Err(err) => return JsonResponse::send(403, None, Some(err.to_string())),
+    };
+
+    match BranchReport::find_many(&pool, &scope, &query) {
+        Ok(report) => JsonResponse::send(200, Some(report), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
@@ -0,0 +1,5 @@
+mod controller;
+mod model;
+mod routes;
+
+pub use routes::branches_routes;
@@ -0,0 +1,137 @@
+use chrono::NaiveTime;
+use diesel::sql_types::{BigInt, Int4, Nullable, Text, Timestamp};
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, Queryable, QueryableByName, RunQueryDsl};
+use serde::Serialize;
+
+use crate::agents::Scope;
+use crate::branches::controller::{BranchReportQuery, CreateBranchPayload, UpdateBranchPayload};
+use crate::db::DbPool;
+use crate::schema::branches;
+
+#[derive(Debug, Serialize, Queryable)]
+pub(super) struct Branch {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub name: String,
+    address: Option<String>,
+    phone_number: Option<String>,
+}
+
+impl Branch {
+    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        branches::table
+            .order_by(branches::name.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        branches::table.filter(branches::id.eq(id)).get_result(conn)
+    }
+
+    pub(super) fn create(pool: &DbPool, payload: &CreateBranchPayload) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        diesel::insert_into(branches::table)
+            .values(payload)
+            .get_result(conn)
+    }
+
+    pub(super) fn update(
+        pool: &DbPool,
+        id: &i32,
+        payload: &UpdateBranchPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        diesel::update(branches::table)
+            .filter(branches::id.eq(id))
+            .set(payload)
+            .get_result(conn)
+    }
+
+    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        diesel::delete(branches::table)
+            .filter(branches::id.eq(id))
+            .get_result(conn)
+    }
+}
+
+// One row per branch, agents without a branch are rolled up under `branch_id: null`
+#[derive(Debug, Serialize, QueryableByName)]
+pub(super) struct BranchReport {
+    #[diesel(sql_type = Nullable<Int4>)]
+    branch_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    name: Option<String>,
+    #[diesel(sql_type = BigInt)]
+    agent_count: i64,
+    #[diesel(sql_type = BigInt)]
+    property_count: i64,
+    #[diesel(sql_type = BigInt)]
+    sold_count: i64,
+    #[diesel(sql_type = BigInt)]
+    lead_count: i64,
+}
+
+impl BranchReport {
+    // Listings count by creation, sales by the day they were sold and leads by arrival
+    pub(super) fn find_many(
+        pool: &DbPool,
+        scope: &Scope,
+        query: &BranchReportQuery,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let branch_id = match scope {
+            Scope::All => None,
+            Scope::Branch(branch_id) => Some(*branch_id),
+            // reports don't go down to a single agent
+            Scope::Own(_) => return Ok(vec![]),
+        };
+
+        let from = query.from.map(|from| from.and_time(NaiveTime::MIN));
+        // inclusive of the whole `to` day
+        let until = query
+            .to
+            .map(|to| to.and_time(NaiveTime::MIN) + chrono::Duration::days(1));
+
+        diesel::sql_query(
+            "SELECT b.id AS branch_id, b.name,
+            COUNT(a.id) FILTER (WHERE a.is_active) AS agent_count,
+            COALESCE(SUM(p.property_count), 0)::BIGINT AS property_count,
+            COALESCE(SUM(p.sold_count), 0)::BIGINT AS sold_count,
+            COALESCE(SUM(l.lead_count), 0)::BIGINT AS lead_count
+            FROM (SELECT id, name FROM branches UNION ALL SELECT NULL, NULL) b
+            LEFT JOIN agents a ON a.branch_id IS NOT DISTINCT FROM b.id
+            LEFT JOIN (
+                SELECT user_id,
+                COUNT(*) FILTER (
+                    WHERE ($1 IS NULL OR created_at >= $1) AND ($2 IS NULL OR created_at < $2)
+                ) AS property_count,
+                COUNT(*) FILTER (
+                    WHERE sold_status = 'sold'
+                    AND ($1 IS NULL OR sold_at >= $1) AND ($2 IS NULL OR sold_at < $2)
+                ) AS sold_count
+                FROM properties
+                WHERE NOT is_deleted
+                GROUP BY user_id
+            ) p ON p.user_id = a.id
+            LEFT JOIN (
+                SELECT user_id, COUNT(*) AS lead_count
+                FROM leads
+                WHERE NOT is_deleted
+                AND ($1 IS NULL OR created_at >= $1) AND ($2 IS NULL OR created_at < $2)
+                GROUP BY user_id
+            ) l ON l.user_id = a.id
+            WHERE $3 IS NULL OR b.id = $3
+            GROUP BY b.id, b.name
+            ORDER BY b.name NULLS LAST",
+        )
+        .bind::<Nullable<Timestamp>, _>(from)
+        .bind::<Nullable<Timestamp>, _>(until)
+        .bind::<Nullable<Int4>, _>(branch_id)
+        .load(conn)
+    }
+}
@@ -0,0 +1,20 @@
+use axum::middleware::from_fn_with_state;
+use axum::routing::{delete, get, post, put};
+use axum::Router;
+
+use crate::branches::controller::{
+    branches_middleware, create_branch, delete_branch, find_branch_by_id, find_branch_report,
+    find_many_branches, update_branch,
+};
+use crate::db::DbPool;
+
+pub fn branches_routes(pool: DbPool) -> Router<DbPool> {
+    axum::Router::new()
+        .route("/", get(find_many_branches))
+        .route("/report", get(find_branch_report))
+        .route("/{id}", get(find_branch_by_id))
+        .route("/", post(create_branch))
+        .route("/{id}", put(update_branch))
+        .route("/{id}", delete(delete_branch))
+        .layer(from_fn_with_state(pool.clone(), branches_middleware))
+}
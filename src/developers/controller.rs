@@ -1,18 +1,21 @@
 use axum::{
-    extract::{Path, Request, State},
+    extract::{Path, Query, Request, State},
+    http::HeaderMap,
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     Json,
 };
 use diesel::prelude::{AsChangeset, Insertable};
+use diesel::result::DatabaseErrorKind;
 use reqwest::Method;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    agents::{Agent, AgentRole},
+    agents::{can, Action, Agent, Resource},
     db::DbPool,
-    developers::model::Developer,
+    developers::model::{Developer, DeveloperDependents, HandleDependents},
     middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session},
+    properties::{find_listing_page, ListingAggregates, ListingPartner, PropertyWithRelation},
     schema,
 };
 
@@ -26,7 +29,7 @@ pub(super) async fn developers_middleware(
         &Method::GET => Ok(next.run(req).await),
         _ => {
             let headers = req.headers();
-            let user_id = Session::extract_session_user_id(&headers);
+            let user_id = Session::extract_session_user_id(headers);
 
             let agent = match Agent::find_by_user_id(&pool, &user_id) {
                 Ok(agent) => agent,
@@ -36,9 +39,15 @@ pub(super) async fn developers_middleware(
                 }
             };
 
-            match agent.role {
-                AgentRole::Admin => Ok(next.run(req).await),
-                _ => {
+            let action = match *method {
+                Method::POST => Action::Create,
+                Method::PUT => Action::Update,
+                _ => Action::Delete,
+            };
+
+            match can(&agent, action, Resource::Developer) {
+                true => Ok(next.run(req).await),
+                false => {
                     let response = JsonResponse::send(403, None, None);
                     Err(response)
                 }
@@ -47,10 +56,22 @@ pub(super) async fn developers_middleware(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct FindDevelopersQuery {
+    include_archived: Option<bool>,
+}
+
 pub(super) async fn find_many_developers(
     State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<FindDevelopersQuery>,
 ) -> AxumResponse<JsonFindResponse<Vec<Developer>>> {
-    let developers = match Developer::find_many(&pool) {
+    // archived ones only for the agents managing them, the public site never lists them
+    let include_archived = query.include_archived.unwrap_or(false)
+        && headers.contains_key("x-user-id")
+        && Agent::find_by_user_id(&pool, &Session::extract_session_user_id(&headers))
+            .is_ok_and(|agent| can(&agent, Action::Update, Resource::Developer));
+    let developers = match Developer::find_many(&pool, include_archived) {
         Ok(devs) => devs,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
@@ -68,7 +89,12 @@ pub(super) async fn find_many_developers(
 #[diesel(table_name = schema::developers)]
 pub(super) struct CreateDeveloperPayload {
     logo_path: String,
-    name: String,
+    pub(super) name: String,
+    #[serde(default)]
+    description: String,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
 }
 
 pub(super) async fn create_developer(
@@ -77,7 +103,7 @@ pub(super) async fn create_developer(
 ) -> AxumResponse<Developer> {
     match Developer::create(&pool, &payload) {
         Ok(dev) => JsonResponse::send(201, Some(dev), None),
-        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
@@ -86,6 +112,10 @@ pub(super) async fn create_developer(
 pub(super) struct UpdateDeveloperPayload {
     logo_path: Option<String>,
     name: Option<String>,
+    description: Option<String>,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
 }
 
 pub(super) async fn update_developer(
@@ -95,15 +125,121 @@ pub(super) async fn update_developer(
 ) -> AxumResponse<Developer> {
     match Developer::update(&pool, &id, &payload) {
         Ok(dev) => JsonResponse::send(200, Some(dev), None),
-        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub(super) struct DeleteDeveloperQuery {
+    // Listings keep going without a developer, the projects are deleted
+    detach: Option<bool>,
+    // Listings and projects move to this developer
+    reassign_to: Option<i32>,
+}
+
+// A developer with listings or projects is only deleted when told what happens to them
 pub(super) async fn delete_developer(
     State(pool): State<DbPool>,
     Path(id): Path<i32>,
+    Query(query): Query<DeleteDeveloperQuery>,
+) -> Response {
+    let dependents = match (query.detach.unwrap_or(false), query.reassign_to) {
+        (true, Some(_)) => {
+            return JsonResponse::<Developer>::send(
+                400,
+                None,
+                Some("Use either detach or reassign_to".to_string()),
+            )
+            .into_response()
+        }
+        (true, None) => Some(HandleDependents::Detach),
+        (false, Some(to)) if to == id => {
+            return JsonResponse::<Developer>::send(
+                400,
+                None,
+                Some("Can't reassign to the developer being deleted".to_string()),
+            )
+            .into_response()
+        }
+        (false, Some(to)) => match Developer::find_by_id(&pool, &to) {
+            Ok(developer) if developer.archived_at.is_none() => {
+                Some(HandleDependents::Reassign(to))
+            }
+            Ok(_) | Err(diesel::result::Error::NotFound) => {
+                return JsonResponse::<Developer>::send(
+                    400,
+                    None,
+                    Some("Developer to reassign to not found".to_string()),
+                )
+                .into_response()
+            }
+            Err(err) => {
+                return JsonResponse::<Developer>::send(500, None, Some(err.to_string()))
+                    .into_response()
+            }
+        },
+        (false, None) => None,
+    };
+
+    if dependents.is_none() {
+        match Developer::find_dependents(&pool, &id) {
+            Ok(found) if !found.is_empty() => {
+                return JsonResponse::send(
+                    409,
+                    Some(found),
+                    Some(
+                        "Developer still has listings or projects, use detach=true or reassign_to"
+                            .to_string(),
+                    ),
+                )
+                .into_response()
+            }
+            Ok(_) => {}
+            Err(err) => {
+                return JsonResponse::<DeveloperDependents>::send(500, None, Some(err.to_string()))
+                    .into_response()
+            }
+        }
+    }
+
+    match Developer::delete(&pool, &id, dependents) {
+        Ok(dev) => JsonResponse::send(200, Some(dev), None).into_response(),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::<Developer>::send(404, None, Some("Developer not found".to_string()))
+                    .into_response()
+            }
+            // A listing or project was added since the check
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => {
+                JsonResponse::<Developer>::send(
+                    409,
+                    None,
+                    Some("Developer still has listings or projects".to_string()),
+                )
+                .into_response()
+            }
+            _ => JsonResponse::<Developer>::send(500, None, Some(err.to_string())).into_response(),
+        },
+    }
+}
+
+// Archived developers are hidden from public lists, their listings stay as they are
+pub(super) async fn archive_developer(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> AxumResponse<Developer> {
+    set_archived(&pool, &id, true)
+}
+
+pub(super) async fn unarchive_developer(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
 ) -> AxumResponse<Developer> {
-    match Developer::delete(&pool, &id) {
+    set_archived(&pool, &id, false)
+}
+
+fn set_archived(pool: &DbPool, id: &i32, archived: bool) -> AxumResponse<Developer> {
+    match Developer::set_archived(pool, id, archived) {
         Ok(dev) => JsonResponse::send(200, Some(dev), None),
         Err(err) => match err {
             diesel::result::Error::NotFound => {
@@ -128,3 +264,61 @@ pub(super) async fn find_developer_by_id(
         },
     }
 }
+
+const DEFAULT_PROFILE_PAGE_SIZE: i64 = 12;
+const MAX_PROFILE_PAGE_SIZE: i64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub(super) struct DeveloperProfileQuery {
+    page: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct DeveloperProfile {
+    #[serde(flatten)]
+    developer: Developer,
+    aggregates: ListingAggregates,
+    listings: JsonFindResponse<Vec<PropertyWithRelation>>,
+}
+
+// Everything a landing page needs in one request
+pub(super) async fn find_developer_profile(
+    State(pool): State<DbPool>,
+    Path(slug): Path<String>,
+    Query(query): Query<DeveloperProfileQuery>,
+) -> AxumResponse<DeveloperProfile> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PROFILE_PAGE_SIZE)
+        .clamp(1, MAX_PROFILE_PAGE_SIZE);
+
+    let developer = match Developer::find_by_slug(&pool, &slug) {
+        Ok(developer) => developer,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Developer not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let partner = ListingPartner::Developer(developer.id);
+    let aggregates = match ListingAggregates::find(&pool, partner) {
+        Ok(aggregates) => aggregates,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let listings = match find_listing_page(&pool, partner, page, limit) {
+        Ok(listings) => listings,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(DeveloperProfile {
+            developer,
+            aggregates,
+            listings,
+        }),
+        None,
+    )
+}
@@ -1,9 +1,14 @@
-use diesel::{ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use diesel::dsl;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
+};
 use serde::Serialize;
 
 use crate::db::DbPool;
 use crate::developers::controller::{CreateDeveloperPayload, UpdateDeveloperPayload};
 use crate::schema;
+use crate::slug::{available_slug, insert_with_slug, slugify};
 
 #[derive(Debug, Serialize, Queryable, Clone)]
 pub struct Developer {
@@ -12,30 +17,128 @@ pub struct Developer {
     updated_at: chrono::NaiveDateTime,
     logo_path: String,
     name: String,
+    description: String,
+    website: Option<String>,
+    contact_email: Option<String>,
+    contact_phone: Option<String>,
+    pub slug: String,
+    pub archived_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub(super) struct DependentListing {
+    id: i32,
+    title: String,
+    is_deleted: bool,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub(super) struct DependentProject {
+    id: i32,
+    slug: String,
+    name: String,
+}
+
+// What would stop the developer from being deleted
+#[derive(Debug, Serialize)]
+pub(super) struct DeveloperDependents {
+    listings: Vec<DependentListing>,
+    projects: Vec<DependentProject>,
+}
+
+impl DeveloperDependents {
+    pub(super) fn is_empty(&self) -> bool {
+        self.listings.is_empty() && self.projects.is_empty()
+    }
+}
+
+// What happens to the listings and projects of a deleted developer
+pub(super) enum HandleDependents {
+    // Listings lose their developer and project, the projects are deleted
+    Detach,
+    // Listings and projects move to another developer
+    Reassign(i32),
 }
 
 impl Developer {
-    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+    pub(super) fn find_many(pool: &DbPool, include_archived: bool) -> QueryResult<Vec<Self>> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        schema::developers::table
+        let mut query = schema::developers::table
             .order_by(schema::developers::name.asc())
-            .get_results(conn)
+            .into_boxed();
+        if !include_archived {
+            query = query.filter(schema::developers::archived_at.is_null());
+        }
+        query.get_results(conn)
     }
 
-    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+    pub fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
         schema::developers::table
             .filter(schema::developers::id.eq(id))
             .get_result(conn)
     }
 
-    pub(super) fn create(pool: &DbPool, payload: &CreateDeveloperPayload) -> QueryResult<Self> {
+    // Archived developers have no public profile
+    pub(super) fn find_by_slug(pool: &DbPool, slug: &str) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        diesel::insert_into(schema::developers::table)
-            .values(payload)
+        schema::developers::table
+            .filter(schema::developers::slug.eq(slug))
+            .filter(schema::developers::archived_at.is_null())
             .get_result(conn)
     }
 
+    pub(super) fn find_dependents(pool: &DbPool, id: &i32) -> QueryResult<DeveloperDependents> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        let listings = schema::properties::table
+            .filter(schema::properties::developer_id.eq(id))
+            .select((
+                schema::properties::id,
+                schema::properties::title,
+                schema::properties::is_deleted,
+            ))
+            .order_by(schema::properties::id.asc())
+            .get_results(conn)?;
+        let projects = schema::projects::table
+            .filter(schema::projects::developer_id.eq(id))
+            .select((
+                schema::projects::id,
+                schema::projects::slug,
+                schema::projects::name,
+            ))
+            .order_by(schema::projects::id.asc())
+            .get_results(conn)?;
+
+        Ok(DeveloperDependents { listings, projects })
+    }
+
+    pub(super) fn set_archived(pool: &DbPool, id: &i32, archived: bool) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        let archived_at = match archived {
+            true => Some(chrono::Local::now().naive_local()),
+            false => None,
+        };
+        diesel::update(schema::developers::table)
+            .filter(schema::developers::id.eq(id))
+            .set(schema::developers::archived_at.eq(archived_at))
+            .get_result(conn)
+    }
+
+    pub(super) fn create(pool: &DbPool, payload: &CreateDeveloperPayload) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+        insert_with_slug(conn, |conn| {
+            let slug = available_slug(conn, &slugify(&payload.name, "developer"), |conn, slug| {
+                dsl::select(dsl::exists(
+                    schema::developers::table.filter(schema::developers::slug.eq(slug)),
+                ))
+                .get_result(conn)
+            })?;
+            diesel::insert_into(schema::developers::table)
+                .values((schema::developers::slug.eq(slug), payload))
+                .get_result(conn)
+        })
+    }
+
     pub(super) fn update(
         pool: &DbPool,
         id: &i32,
@@ -48,10 +151,50 @@ impl Developer {
             .get_result(conn)
     }
 
-    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+    // Without `dependents` a developer with listings or projects is kept, ForeignKeyViolation
+    pub(super) fn delete(
+        pool: &DbPool,
+        id: &i32,
+        dependents: Option<HandleDependents>,
+    ) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
-        diesel::delete(schema::developers::table)
-            .filter(schema::developers::id.eq(id))
-            .get_result(conn)
+        conn.transaction(|conn| {
+            let project_ids = schema::projects::table
+                .filter(schema::projects::developer_id.eq(id))
+                .select(schema::projects::id.nullable());
+            match dependents {
+                Some(HandleDependents::Detach) => {
+                    diesel::update(schema::properties::table)
+                        .filter(
+                            schema::properties::developer_id
+                                .eq(id)
+                                .or(schema::properties::project_id.eq_any(project_ids)),
+                        )
+                        .set((
+                            schema::properties::developer_id.eq(None::<i32>),
+                            schema::properties::project_id.eq(None::<i32>),
+                        ))
+                        .execute(conn)?;
+                    diesel::delete(schema::projects::table)
+                        .filter(schema::projects::developer_id.eq(id))
+                        .execute(conn)?;
+                }
+                Some(HandleDependents::Reassign(to)) => {
+                    diesel::update(schema::properties::table)
+                        .filter(schema::properties::developer_id.eq(id))
+                        .set(schema::properties::developer_id.eq(to))
+                        .execute(conn)?;
+                    diesel::update(schema::projects::table)
+                        .filter(schema::projects::developer_id.eq(id))
+                        .set(schema::projects::developer_id.eq(to))
+                        .execute(conn)?;
+                }
+                None => {}
+            }
+
+            diesel::delete(schema::developers::table)
+                .filter(schema::developers::id.eq(id))
+                .get_result(conn)
+        })
     }
 }
@@ -5,16 +5,21 @@ use axum::Router;
 
 use crate::db::DbPool;
 use crate::developers::controller::{
-    create_developer, delete_developer, developers_middleware, find_developer_by_id,
-    update_developer,
+    archive_developer, create_developer, delete_developer, developers_middleware,
+    find_developer_by_id, find_developer_profile, unarchive_developer, update_developer,
 };
+use crate::projects::find_many_by_developer as find_developer_projects;
 
 pub fn developers_routes(pool: DbPool) -> Router<DbPool> {
     axum::Router::new()
         .route("/", get(find_many_developers))
         .route("/{id}", get(find_developer_by_id))
+        .route("/by-slug/{slug}", get(find_developer_profile))
+        .route("/{id}/projects", get(find_developer_projects))
         .route("/", post(create_developer))
         .route("/{id}", put(update_developer))
         .route("/{id}", delete(delete_developer))
+        .route("/{id}/archive", put(archive_developer))
+        .route("/{id}/unarchive", put(unarchive_developer))
         .layer(from_fn_with_state(pool.clone(), developers_middleware))
 }
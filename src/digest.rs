@@ -0,0 +1,45 @@
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+// Lowercase hex sha256, for values that are only ever looked up by their hash
+pub fn sha256_hex(value: &str) -> String {
+    openssl::sha::sha256(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Lowercase hex HMAC-SHA256 under `key`, for values few enough to be hashed one by one,
+// like ip addresses, which a plain sha256 wouldn't hide
+pub fn hmac_sha256_hex(key: &[u8], value: &str) -> String {
+    let key = PKey::hmac(key).expect("Invalid HMAC key");
+    Signer::new(MessageDigest::sha256(), &key)
+        .and_then(|mut signer| signer.sign_oneshot_to_vec(value.as_bytes()))
+        .expect("Couldn't compute the HMAC")
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_like_sha256() {
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn signs_like_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
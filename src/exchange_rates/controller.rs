@@ -0,0 +1,99 @@
+use axum::extract::{Json, Path, State};
+use axum::http::HeaderMap;
+use axum::routing::{get, post, put};
+use axum::Router;
+use bigdecimal::{BigDecimal, Zero};
+use serde::Deserialize;
+
+use super::import::import;
+use super::model::ExchangeRate;
+use crate::agents::{authorize, Action, Resource};
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse};
+use crate::properties::Currency;
+
+async fn find_exchange_rates(State(pool): State<DbPool>) -> AxumResponse<Vec<ExchangeRate>> {
+    match ExchangeRate::find_many(&pool) {
+        Ok(rates) => JsonResponse::send(200, Some(rates), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateExchangeRatePayload {
+    // Rupiah for one unit of the currency
+    idr_rate: BigDecimal,
+}
+
+// Listings priced in the currency get a new price_idr right away
+async fn update_exchange_rate(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(currency): Path<Currency>,
+    Json(payload): Json<UpdateExchangeRatePayload>,
+) -> AxumResponse<ExchangeRate> {
+    let agent = match authorize(&pool, &headers, Action::Update, Resource::ExchangeRate) {
+        Ok(agent) => agent,
+        Err(response) => return response,
+    };
+
+    if currency == Currency::Idr {
+        return JsonResponse::send(400, None, Some("The IDR rate is always 1".to_string()));
+    }
+    if payload.idr_rate <= BigDecimal::zero() {
+        return JsonResponse::send(400, None, Some("Rate must be positive".to_string()));
+    }
+
+    let rates = [(currency, payload.idr_rate)];
+    match ExchangeRate::upsert_many(&pool, &rates, "manual", Some(agent.id)) {
+        Ok(mut rates) => JsonResponse::send(200, rates.pop(), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn delete_exchange_rate(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(currency): Path<Currency>,
+) -> AxumResponse<ExchangeRate> {
+    if let Err(response) = authorize(&pool, &headers, Action::Delete, Resource::ExchangeRate) {
+        return response;
+    }
+
+    if currency == Currency::Idr {
+        return JsonResponse::send(400, None, Some("The IDR rate is always 1".to_string()));
+    }
+
+    match ExchangeRate::delete(&pool, &currency) {
+        Ok(rate) => JsonResponse::send(200, Some(rate), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Exchange rate not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Runs the import job now instead of waiting for its next run
+async fn import_exchange_rates(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+) -> AxumResponse<Vec<ExchangeRate>> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::ExchangeRate) {
+        return response;
+    }
+
+    match import(&pool).await {
+        Ok(rates) => JsonResponse::send(200, Some(rates), None),
+        Err(err) => JsonResponse::send(500, None, Some(err)),
+    }
+}
+
+pub fn exchange_rate_routes() -> Router<DbPool> {
+    Router::new()
+        .route("/", get(find_exchange_rates))
+        .route("/import", post(import_exchange_rates))
+        .route(
+            "/{currency}",
+            put(update_exchange_rate).delete(delete_exchange_rate),
+        )
+}
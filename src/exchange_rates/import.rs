@@ -0,0 +1,93 @@
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use super::model::ExchangeRate;
+use crate::db::DbPool;
+use crate::properties::Currency;
+
+const DEFAULT_IMPORT_HOURS: u64 = 24;
+const IMPORT_TIMEOUT_SECONDS: u64 = 30;
+
+const IMPORTED_CURRENCIES: [(&str, Currency); 4] = [
+    ("USD", Currency::Usd),
+    ("SGD", Currency::Sgd),
+    ("AUD", Currency::Aud),
+    ("EUR", Currency::Eur),
+];
+
+// How much of each currency one rupiah buys, the shape most rate apis answer
+// with when IDR is the base: {"rates": {"USD": 0.0000615, ...}}
+#[derive(Debug, Deserialize)]
+struct ImportedRates {
+    rates: HashMap<String, BigDecimal>,
+}
+
+fn import_url() -> Option<String> {
+    env::var("EXCHANGE_RATES_URL").ok()
+}
+
+// Replaces the rates of the currencies found at EXCHANGE_RATES_URL, a local stub
+// serving the same shape can stand in for the real api
+pub(super) async fn import(pool: &DbPool) -> Result<Vec<ExchangeRate>, String> {
+    let url = import_url().ok_or("EXCHANGE_RATES_URL is not set".to_string())?;
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(IMPORT_TIMEOUT_SECONDS))
+            .build()
+            .expect("Couldn't build the http client")
+    });
+    let imported = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .json::<ImportedRates>()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let rates: Vec<(Currency, BigDecimal)> = IMPORTED_CURRENCIES
+        .iter()
+        .filter_map(|(code, currency)| {
+            let rate = imported
+                .rates
+                .get(*code)
+                .filter(|rate| **rate > BigDecimal::zero())?;
+            let idr_rate = (BigDecimal::from(1) / rate).with_scale_round(6, RoundingMode::HalfUp);
+            Some((*currency, idr_rate))
+        })
+        .collect();
+    if rates.is_empty() {
+        return Err("No known currency in the imported rates".to_string());
+    }
+
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || ExchangeRate::upsert_many(&pool, &rates, "import", None))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
+
+// Every EXCHANGE_RATES_IMPORT_HOURS, only while EXCHANGE_RATES_URL is set
+pub async fn import_periodically(pool: DbPool) {
+    if import_url().is_none() {
+        return;
+    }
+    let hours = env::var("EXCHANGE_RATES_IMPORT_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_IMPORT_HOURS);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(hours * 60 * 60));
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = import(&pool).await {
+            tracing::error!("Failed to import exchange rates: {}", err);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+mod controller;
+mod import;
+mod model;
+
+pub use controller::exchange_rate_routes;
+pub use import::import_periodically;
+pub use model::ExchangeRates;
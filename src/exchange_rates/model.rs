@@ -0,0 +1,101 @@
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive};
+use diesel::upsert::excluded;
+use diesel::{Connection, ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::db::DbPool;
+use crate::properties::Currency;
+use crate::schema::exchange_rates;
+
+// Rupiah for one unit of the currency, IDR itself is always 1.
+// Listings follow a change through the refresh_price_idr trigger.
+#[derive(Debug, Serialize, Queryable)]
+pub struct ExchangeRate {
+    currency: Currency,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    idr_rate: BigDecimal,
+    // "manual", "import" or "fixed"
+    source: String,
+    updated_by: Option<uuid::Uuid>,
+}
+
+impl ExchangeRate {
+    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        exchange_rates::table
+            .order_by(exchange_rates::currency.asc())
+            .get_results(conn)
+    }
+
+    // Inserts or replaces the rate of every given currency in one go
+    pub(super) fn upsert_many(
+        pool: &DbPool,
+        rates: &[(Currency, BigDecimal)],
+        source: &str,
+        updated_by: Option<uuid::Uuid>,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            rates
+                .iter()
+                .map(|(currency, idr_rate)| {
+                    diesel::insert_into(exchange_rates::table)
+                        .values((
+                            exchange_rates::currency.eq(currency),
+                            exchange_rates::idr_rate.eq(idr_rate),
+                            exchange_rates::source.eq(source),
+                            exchange_rates::updated_by.eq(updated_by),
+                        ))
+                        .on_conflict(exchange_rates::currency)
+                        .do_update()
+                        .set((
+                            exchange_rates::idr_rate.eq(excluded(exchange_rates::idr_rate)),
+                            exchange_rates::source.eq(excluded(exchange_rates::source)),
+                            exchange_rates::updated_by.eq(excluded(exchange_rates::updated_by)),
+                        ))
+                        .get_result(conn)
+                })
+                .collect()
+        })
+    }
+
+    // Listings in the currency lose their price_idr until it gets a rate again
+    pub(super) fn delete(pool: &DbPool, currency: &Currency) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(exchange_rates::table)
+            .filter(exchange_rates::currency.eq(currency))
+            .get_result(conn)
+    }
+}
+
+// Every known rate, for converting prices in responses
+pub struct ExchangeRates(HashMap<Currency, BigDecimal>);
+
+impl ExchangeRates {
+    pub fn find(pool: &DbPool) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let rates: Vec<(Currency, BigDecimal)> = exchange_rates::table
+            .select((exchange_rates::currency, exchange_rates::idr_rate))
+            .get_results(conn)?;
+        Ok(Self(rates.into_iter().collect()))
+    }
+
+    // None while either currency has no rate
+    pub fn convert(&self, amount: i64, from: Currency, to: Currency) -> Option<i64> {
+        if from == to {
+            return Some(amount);
+        }
+        let from_rate = self.0.get(&from)?;
+        let to_rate = self.0.get(&to)?;
+
+        (BigDecimal::from(amount) * from_rate / to_rate)
+            .with_scale_round(0, RoundingMode::HalfUp)
+            .to_i64()
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::{NaiveDate, NaiveDateTime};
+
+const PRODUCT_ID: &str = "-//PrimePro Indonesia//Listings//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+// Whole days, or a moment in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcalTime {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+impl IcalTime {
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            IcalTime::Date(date) => *date,
+            IcalTime::DateTime(time) => time.date(),
+        }
+    }
+
+    fn property(&self, name: &str) -> String {
+        match self {
+            IcalTime::Date(date) => format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d")),
+            IcalTime::DateTime(time) => format!("{}:{}", name, time.format("%Y%m%dT%H%M%SZ")),
+        }
+    }
+
+    // Times with a TZID are read as they are, close enough for whole-day calendars
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_end_matches('Z');
+        match value.len() {
+            8 => NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(IcalTime::Date),
+            _ => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                .ok()
+                .map(IcalTime::DateTime),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IcalEvent {
+    pub uid: String,
+    pub stamp: NaiveDateTime,
+    pub start: IcalTime,
+    // Not included, the check-out day for whole days
+    pub end: IcalTime,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub cancelled: bool,
+    // Bumped on every change, so calendars replace the event they got before
+    pub sequence: i32,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            },
+            _ => unescaped.push(char),
+        }
+    }
+    unescaped
+}
+
+// Long lines continue on the next one after a space, at most 75 octets each
+fn fold(line: &str, calendar: &mut String) {
+    let mut octets = 0;
+    for char in line.chars() {
+        if octets + char.len_utf8() > MAX_LINE_OCTETS {
+            calendar.push_str("\r\n ");
+            octets = 1;
+        }
+        calendar.push(char);
+        octets += char.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
+
+pub fn render(name: &str, events: &[IcalEvent]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(&event.uid)));
+        lines.push(format!("DTSTAMP:{}", event.stamp.format("%Y%m%dT%H%M%SZ")));
+        lines.push(format!("SEQUENCE:{}", event.sequence));
+        lines.push(event.start.property("DTSTART"));
+        lines.push(event.end.property("DTEND"));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(description) = &event.description {
+            lines.push(format!("DESCRIPTION:{}", escape(description)));
+        }
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        if event.cancelled {
+            lines.push("STATUS:CANCELLED".to_string());
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut calendar = String::new();
+    for line in lines {
+        fold(&line, &mut calendar);
+    }
+    calendar
+}
+
+// The events of a calendar, the ones without a start are skipped
+pub fn parse(calendar: &str) -> Vec<IcalEvent> {
+    let mut lines: Vec<String> = vec![];
+    for line in calendar.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = vec![];
+    let mut event: Option<Vec<(String, String)>> = None;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.to_uppercase(), value.to_string()),
+            None => continue,
+        };
+        match (name.as_str(), value.trim().to_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => event = Some(vec![]),
+            ("END", "VEVENT") => {
+                if let Some(event) = event.take().and_then(|properties| into_event(&properties)) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                if let Some(properties) = event.as_mut() {
+                    let name = name.split(';').next().unwrap_or_default().to_string();
+                    properties.push((name, value));
+                }
+            }
+        }
+    }
+    events
+}
+
+fn into_event(properties: &[(String, String)]) -> Option<IcalEvent> {
+    let find = |name: &str| {
+        properties
+            .iter()
+            .find(|(property, _)| property == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let start = IcalTime::parse(find("DTSTART")?)?;
+    // a day long without an end, the spec's default for dates
+    let end = find("DTEND")
+        .and_then(IcalTime::parse)
+        .unwrap_or(match start {
+            IcalTime::Date(date) => IcalTime::Date(date + chrono::Duration::days(1)),
+            IcalTime::DateTime(_) => start,
+        });
+    let stamp = find("DTSTAMP")
+        .and_then(IcalTime::parse)
+        .map(|stamp| match stamp {
+            IcalTime::Date(date) => date.and_time(chrono::NaiveTime::MIN),
+            IcalTime::DateTime(time) => time,
+        })
+        .unwrap_or(chrono::Utc::now().naive_utc());
+    let uid = find("UID")
+        .map(unescape)
+        .unwrap_or(format!("{}-{}", start.date(), end.date()));
+
+    Some(IcalEvent {
+        uid,
+        stamp,
+        start,
+        end,
+        summary: find("SUMMARY").map(unescape).unwrap_or_default(),
+        description: find("DESCRIPTION").map(unescape),
+        location: find("LOCATION").map(unescape),
+        cancelled: find("STATUS").is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED")),
+        sequence: find("SEQUENCE")
+            .and_then(|sequence| sequence.trim().parse().ok())
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).expect("Invalid date")
+    }
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        date(year, month, day)
+            .and_hms_opt(hour, minute, 0)
+            .expect("Invalid time")
+    }
+
+    #[test]
+    fn parses_an_airbnb_export() {
+        let events = parse(include_str!("../fixtures/ical/airbnb.ics"));
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0].start, IcalTime::Date(date(2030, 1, 5)));
+        assert_eq!(events[0].end, IcalTime::Date(date(2030, 1, 10)));
+        assert_eq!(
+            events[0].uid,
+            "1418fb94e984-a4b2b7a0e2b1c9d6b8a1f2e3d4c5b6a7@airbnb.com"
+        );
+        assert_eq!(
+            events[0].description.as_deref(),
+            Some(
+                "Reservation URL: https://www.airbnb.com/hosting/reservations/details/HMABCDE123\n\
+                Phone Number (Last 4 Digits): 1234"
+            )
+        );
+        assert_eq!(events[1].summary, "Airbnb (Not available)");
+        assert!(!events[0].cancelled);
+        assert!(events[3].cancelled);
+    }
+
+    #[test]
+    fn ends_a_date_without_dtend_the_next_day() {
+        let events = parse(include_str!("../fixtures/ical/airbnb.ics"));
+
+        assert_eq!(events[2].uid, "single-day-without-end@airbnb.com");
+        assert_eq!(events[2].start, IcalTime::Date(date(2030, 3, 1)));
+        assert_eq!(events[2].end, IcalTime::Date(date(2030, 3, 2)));
+    }
+
+    #[test]
+    fn parses_a_booking_export() {
+        let events = parse(include_str!("../fixtures/ical/booking.ics"));
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].start, IcalTime::DateTime(time(2030, 6, 1, 14, 0)));
+        assert_eq!(events[0].end, IcalTime::DateTime(time(2030, 6, 4, 11, 0)));
+        assert_eq!(events[0].stamp, time(2026, 1, 1, 12, 0));
+        assert_eq!(events[1].start.date(), date(2030, 7, 10));
+        assert_eq!(events[1].summary, "Escaped, summary; with\\nothing");
+    }
+
+    #[test]
+    fn finds_no_events_in_something_else() {
+        assert!(parse(include_str!("../fixtures/ical/invalid.ics")).is_empty());
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn renders_what_it_parses_back() {
+        let events = vec![
+            IcalEvent {
+                uid: "stay-1@primepro".to_string(),
+                stamp: time(2026, 1, 1, 12, 0),
+                start: IcalTime::Date(date(2030, 1, 5)),
+                end: IcalTime::Date(date(2030, 1, 10)),
+                summary: "Booked; by a guest, with notes".to_string(),
+                description: Some("First line\nSecond \\ line".to_string()),
+                location: None,
+                cancelled: false,
+                sequence: 2,
+            },
+            IcalEvent {
+                uid: "viewing-1@primepro".to_string(),
+                stamp: time(2026, 1, 1, 12, 0),
+                start: IcalTime::DateTime(time(2030, 2, 1, 9, 30)),
+                end: IcalTime::DateTime(time(2030, 2, 1, 10, 30)),
+                summary: "Viewing".to_string(),
+                description: None,
+                location: Some("Jl. Sunset Road 88, Kuta".to_string()),
+                cancelled: true,
+                sequence: 0,
+            },
+        ];
+
+        let calendar = render("Villa Kuta", &events);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.contains("X-WR-CALNAME:Villa Kuta\r\n"));
+        assert!(calendar.contains("DTSTART;VALUE=DATE:20300105\r\n"));
+        assert!(calendar.contains("DTSTART:20300201T093000Z\r\n"));
+        assert!(calendar.contains("SUMMARY:Booked\\; by a guest\\, with notes\r\n"));
+
+        let parsed = parse(&calendar);
+        assert_eq!(parsed.len(), 2);
+        for (parsed, event) in parsed.iter().zip(&events) {
+            assert_eq!(parsed.uid, event.uid);
+            assert_eq!(parsed.stamp, event.stamp);
+            assert_eq!(parsed.start, event.start);
+            assert_eq!(parsed.end, event.end);
+            assert_eq!(parsed.summary, event.summary);
+            assert_eq!(parsed.description, event.description);
+            assert_eq!(parsed.location, event.location);
+            assert_eq!(parsed.cancelled, event.cancelled);
+            assert_eq!(parsed.sequence, event.sequence);
+        }
+    }
+
+    #[test]
+    fn folds_long_lines_at_75_octets() {
+        let event = IcalEvent {
+            uid: "long@primepro".to_string(),
+            stamp: time(2026, 1, 1, 12, 0),
+            start: IcalTime::Date(date(2030, 1, 5)),
+            end: IcalTime::Date(date(2030, 1, 6)),
+            summary: "Rumah ".repeat(20),
+            description: Some("é".repeat(100)),
+            location: None,
+            cancelled: false,
+            sequence: 0,
+        };
+
+        let calendar = render("Folded", std::slice::from_ref(&event));
+        assert!(calendar
+            .split("\r\n")
+            .all(|line| line.len() <= MAX_LINE_OCTETS));
+        let parsed = parse(&calendar);
+        assert_eq!(parsed[0].summary, event.summary);
+        assert_eq!(parsed[0].description, event.description);
+    }
+}
@@ -0,0 +1,54 @@
+use axum::extract::{Json, State};
+use axum::routing::post;
+use axum::Router;
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+
+use super::simulation::{simulate_products, KprSimulationRequest, KprSimulations};
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse};
+
+#[derive(Debug, Deserialize)]
+struct SimulateKprPayload {
+    price: i64,
+    // Percentage of the price
+    dp: Option<BigDecimal>,
+    // Years
+    tenor: Option<i32>,
+    bank_id: Option<i32>,
+    product_id: Option<i32>,
+    product_revision: Option<i32>,
+}
+
+// Public calculator, for any price
+async fn simulate_kpr(
+    State(pool): State<DbPool>,
+    Json(payload): Json<SimulateKprPayload>,
+) -> AxumResponse<KprSimulations> {
+    let request = KprSimulationRequest {
+        price: payload.price,
+        down_payment_percent: payload.dp,
+        suggested_down_payment_percent: None,
+        tenor_years: payload.tenor,
+        bank_id: payload.bank_id,
+        product_id: payload.product_id,
+        product_revision: payload.product_revision,
+    };
+    if let Err(message) = request.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match simulate_products(&pool, &request) {
+        Ok(simulations) => JsonResponse::send(200, Some(simulations), None),
+        Err(diesel::result::Error::NotFound) => JsonResponse::send(
+            404,
+            None,
+            Some("Bank product revision not found".to_string()),
+        ),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub fn kpr_routes() -> Router<DbPool> {
+    Router::new().route("/simulate", post(simulate_kpr))
+}
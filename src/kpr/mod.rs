@@ -0,0 +1,5 @@
+mod controller;
+mod simulation;
+
+pub use controller::kpr_routes;
+pub use simulation::{simulate_products, KprSimulationRequest, KprSimulations};
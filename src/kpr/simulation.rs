@@ -0,0 +1,390 @@
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use serde::Serialize;
+
+use crate::banks::BankProduct;
+use crate::db::DbPool;
+
+pub const DEFAULT_TENOR_YEARS: i32 = 15;
+pub const MAX_TENOR_YEARS: i32 = 40;
+// A quadrillion rupiah, far above any listing but low enough that the schedule's sums fit an i64
+pub const MAX_PRICE: i64 = 1_000_000_000_000_000;
+
+// Digits kept for monthly rates, amounts are rounded to whole rupiah
+const RATE_SCALE: i64 = 20;
+
+#[derive(Debug)]
+pub struct KprSimulationRequest {
+    pub price: i64,
+    // Percentages of the price; a product's own minimum is used when both are missing
+    pub down_payment_percent: Option<BigDecimal>,
+    pub suggested_down_payment_percent: Option<BigDecimal>,
+    pub tenor_years: Option<i32>,
+    pub bank_id: Option<i32>,
+    pub product_id: Option<i32>,
+    // Reproduces a simulation with an earlier version of `product_id`
+    pub product_revision: Option<i32>,
+}
+
+impl KprSimulationRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.price <= 0 {
+            return Err("Price must be positive".to_string());
+        }
+        if self.price > MAX_PRICE {
+            return Err(format!("Price can't be more than {}", MAX_PRICE));
+        }
+        if let Some(tenor_years) = self.tenor_years {
+            if !(1..=MAX_TENOR_YEARS).contains(&tenor_years) {
+                return Err(format!(
+                    "Tenor must be between 1 and {} years",
+                    MAX_TENOR_YEARS
+                ));
+            }
+        }
+        if self.product_revision.is_some() && self.product_id.is_none() {
+            return Err("product_revision needs a product_id".to_string());
+        }
+        if let Some(percent) = &self.down_payment_percent {
+            if *percent < BigDecimal::zero() || *percent >= BigDecimal::from(100) {
+                return Err("Down payment must be at least 0% and below 100%".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KprInstallment {
+    month: i32,
+    annual_rate: BigDecimal,
+    installment: i64,
+    principal: i64,
+    interest: i64,
+    balance: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KprPhase {
+    from_month: i32,
+    to_month: i32,
+    annual_rate: BigDecimal,
+    monthly_installment: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KprSimulation {
+    bank_id: i32,
+    bank_name: String,
+    product_id: i32,
+    product_name: String,
+    product_revision: i32,
+    price: i64,
+    down_payment_percent: BigDecimal,
+    down_payment: i64,
+    loan_amount: i64,
+    tenor_years: i32,
+    provision_fee: i64,
+    admin_fee: i64,
+    appraisal_fee: i64,
+    // Down payment and fees, paid before the first installment
+    upfront_payment: i64,
+    phases: Vec<KprPhase>,
+    total_interest: i64,
+    total_installments: i64,
+    schedule: Vec<KprInstallment>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IneligibleKprProduct {
+    bank_id: i32,
+    bank_name: String,
+    product_id: i32,
+    product_name: String,
+    reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KprSimulations {
+    simulations: Vec<KprSimulation>,
+    ineligible: Vec<IneligibleKprProduct>,
+}
+
+fn to_rupiah(amount: &BigDecimal) -> i64 {
+    amount
+        .with_scale_round(0, RoundingMode::HalfUp)
+        .to_i64()
+        .unwrap_or(i64::MAX)
+}
+
+fn percent_of(amount: i64, percent: &BigDecimal) -> i64 {
+    to_rupiah(&(BigDecimal::from(amount) * percent / BigDecimal::from(100)))
+}
+
+fn monthly_rate(annual_rate: &BigDecimal) -> BigDecimal {
+    (annual_rate / BigDecimal::from(1200)).with_scale_round(RATE_SCALE, RoundingMode::HalfEven)
+}
+
+// Fixed installment paying off the balance over the remaining months
+fn annuity(balance: i64, rate: &BigDecimal, months: i32) -> i64 {
+    let balance = BigDecimal::from(balance);
+    if rate.is_zero() {
+        return to_rupiah(&(balance / BigDecimal::from(months)));
+    }
+    let factor = (BigDecimal::from(1) + rate).powi(months as i64);
+    to_rupiah(&(balance * rate * &factor / (factor - BigDecimal::from(1))))
+}
+
+// Annuity installments, recalculated on the remaining balance when the fixed period ends.
+// The last installment settles whatever rounding left on the balance.
+fn simulate(
+    product: &BankProduct,
+    bank_name: &str,
+    price: i64,
+    down_payment_percent: BigDecimal,
+    tenor_years: i32,
+) -> KprSimulation {
+    let down_payment = percent_of(price, &down_payment_percent);
+    let loan_amount = price - down_payment;
+    let provision_fee = percent_of(loan_amount, &product.provision_fee_percent);
+    let months = tenor_years * 12;
+
+    let mut phases: Vec<KprPhase> = vec![];
+    let mut schedule: Vec<KprInstallment> = Vec::with_capacity(months as usize);
+    let (mut balance, mut total_interest, mut total_installments) = (loan_amount, 0, 0);
+    let (mut annual_rate, mut rate, mut installment) = (BigDecimal::zero(), BigDecimal::zero(), 0);
+    for month in 1..=months {
+        let phase_start = match month {
+            1 if product.fixed_period_months > 0 => Some(&product.fixed_rate),
+            1 => Some(&product.floating_rate),
+            _ if month == product.fixed_period_months + 1 => Some(&product.floating_rate),
+            _ => None,
+        };
+        if let Some(phase_rate) = phase_start {
+            annual_rate = phase_rate.clone();
+            rate = monthly_rate(&annual_rate);
+            installment = annuity(balance, &rate, months - month + 1);
+            if let Some(phase) = phases.last_mut() {
+                phase.to_month = month - 1;
+            }
+            phases.push(KprPhase {
+                from_month: month,
+                to_month: months,
+                annual_rate: annual_rate.clone(),
+                monthly_installment: installment,
+            });
+        }
+
+        let interest = to_rupiah(&(BigDecimal::from(balance) * &rate));
+        let principal = match month == months {
+            true => balance,
+            false => (installment - interest).min(balance),
+        };
+        balance -= principal;
+        total_interest += interest;
+        total_installments += principal + interest;
+        schedule.push(KprInstallment {
+            month,
+            annual_rate: annual_rate.clone(),
+            installment: principal + interest,
+            principal,
+            interest,
+            balance,
+        });
+    }
+
+    KprSimulation {
+        bank_id: product.bank_id,
+        bank_name: bank_name.to_string(),
+        product_id: product.id,
+        product_name: product.name.clone(),
+        product_revision: product.revision,
+        price,
+        down_payment_percent,
+        down_payment,
+        loan_amount,
+        tenor_years,
+        provision_fee,
+        admin_fee: product.admin_fee,
+        appraisal_fee: product.appraisal_fee,
+        upfront_payment: down_payment + provision_fee + product.admin_fee + product.appraisal_fee,
+        phases,
+        total_interest,
+        total_installments,
+        schedule,
+    }
+}
+
+// Every current product of the partner banks, or of a single bank, for the same price
+pub fn simulate_products(
+    pool: &DbPool,
+    request: &KprSimulationRequest,
+) -> diesel::QueryResult<KprSimulations> {
+    let products = match (request.product_id, request.product_revision) {
+        (Some(product_id), Some(revision)) => vec![BankProduct::find_revision_with_bank(
+            pool,
+            &product_id,
+            &revision,
+        )?],
+        _ => BankProduct::find_many_current_with_bank(pool, request.bank_id, request.product_id)?,
+    };
+
+    let mut simulations = KprSimulations {
+        simulations: vec![],
+        ineligible: vec![],
+    };
+    for (product, bank_name) in products {
+        let tenor_years = request
+            .tenor_years
+            .unwrap_or(DEFAULT_TENOR_YEARS.min(product.max_tenor_years));
+        let down_payment_percent = match (
+            &request.down_payment_percent,
+            &request.suggested_down_payment_percent,
+        ) {
+            (Some(percent), _) => percent.clone(),
+            (None, Some(percent)) if *percent > product.min_down_payment_percent => percent.clone(),
+            (None, _) => product.min_down_payment_percent.clone(),
+        };
+
+        let reason = if tenor_years > product.max_tenor_years {
+            Some(format!(
+                "Tenor can be at most {} years",
+                product.max_tenor_years
+            ))
+        } else if down_payment_percent < product.min_down_payment_percent {
+            Some(format!(
+                "Down payment must be at least {}%",
+                product.min_down_payment_percent.normalized()
+            ))
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => simulations.ineligible.push(IneligibleKprProduct {
+                bank_id: product.bank_id,
+                bank_name,
+                product_id: product.id,
+                product_name: product.name,
+                reason,
+            }),
+            None => simulations.simulations.push(simulate(
+                &product,
+                &bank_name,
+                request.price,
+                down_payment_percent,
+                tenor_years,
+            )),
+        }
+    }
+
+    Ok(simulations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn percent(value: &str) -> BigDecimal {
+        value.parse().expect("Invalid percent")
+    }
+
+    #[test]
+    fn rounds_amounts_half_up_to_whole_rupiah() {
+        assert_eq!(percent_of(1_000_005, &percent("10")), 100_001);
+        assert_eq!(percent_of(1_000_004, &percent("10")), 100_000);
+        assert_eq!(percent_of(999, &percent("12.5")), 125);
+        assert_eq!(to_rupiah(&percent("-0.5")), -1);
+    }
+
+    #[test]
+    fn pays_off_an_interest_free_loan_in_equal_installments() {
+        let product = BankProduct::sample("0", 0, "0");
+        let simulation = simulate(&product, "Bank", 120_000_000, percent("0"), 1);
+
+        assert_eq!(simulation.loan_amount, 120_000_000);
+        assert_eq!(simulation.total_interest, 0);
+        assert_eq!(simulation.schedule.len(), 12);
+        assert!(simulation
+            .schedule
+            .iter()
+            .all(|installment| installment.installment == 10_000_000));
+        assert_eq!(simulation.schedule[11].balance, 0);
+    }
+
+    #[test]
+    fn recalculates_the_installment_when_the_fixed_period_ends() {
+        let product = BankProduct::sample("6", 12, "12");
+        let simulation = simulate(&product, "Bank", 100_000_000, percent("20"), 2);
+
+        assert_eq!(simulation.down_payment, 20_000_000);
+        assert_eq!(simulation.loan_amount, 80_000_000);
+        assert_eq!(simulation.provision_fee, 800_000);
+        assert_eq!(simulation.upfront_payment, 20_800_000);
+
+        assert_eq!(simulation.phases.len(), 2);
+        assert_eq!(
+            (
+                simulation.phases[0].from_month,
+                simulation.phases[0].to_month
+            ),
+            (1, 12)
+        );
+        assert_eq!(
+            (
+                simulation.phases[1].from_month,
+                simulation.phases[1].to_month
+            ),
+            (13, 24)
+        );
+        assert_eq!(simulation.phases[0].monthly_installment, 3_545_649);
+        assert!(simulation.phases[1].monthly_installment > 3_545_649);
+
+        let first = &simulation.schedule[0];
+        assert_eq!(first.interest, 400_000);
+        assert_eq!(first.principal, 3_145_649);
+        assert_eq!(first.balance, 80_000_000 - 3_145_649);
+    }
+
+    #[test]
+    fn settles_the_rounding_on_the_last_installment() {
+        let product = BankProduct::sample("7.25", 36, "11.75");
+        let simulation = simulate(&product, "Bank", 987_654_321, percent("15"), 20);
+
+        let principal: i64 = simulation
+            .schedule
+            .iter()
+            .map(|installment| installment.principal)
+            .sum();
+        let interest: i64 = simulation
+            .schedule
+            .iter()
+            .map(|installment| installment.interest)
+            .sum();
+        assert_eq!(simulation.schedule.len(), 240);
+        assert_eq!(principal, simulation.loan_amount);
+        assert_eq!(interest, simulation.total_interest);
+        assert_eq!(
+            simulation.total_installments,
+            simulation.loan_amount + simulation.total_interest
+        );
+        assert_eq!(simulation.schedule.last().map(|last| last.balance), Some(0));
+    }
+
+    #[test]
+    fn rejects_prices_whose_schedule_would_overflow() {
+        let request = KprSimulationRequest {
+            price: MAX_PRICE + 1,
+            down_payment_percent: None,
+            suggested_down_payment_percent: None,
+            tenor_years: None,
+            bank_id: None,
+            product_id: None,
+            product_revision: None,
+        };
+        assert!(request.validate().is_err());
+
+        let product = BankProduct::sample("25", 0, "25");
+        let simulation = simulate(&product, "Bank", MAX_PRICE, percent("0"), MAX_TENOR_YEARS);
+        assert_eq!(simulation.schedule.last().map(|last| last.balance), Some(0));
+    }
+}
@@ -0,0 +1,179 @@
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods, PgConnection,
+    QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::assignment_reason::LeadAssignmentReason;
+use super::contact::Contact;
+use super::model::Lead;
+use super::routing::LeadRoutingRule;
+use crate::{
+    db::DbPool,
+    schema::{lead_assignments, leads, properties},
+};
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct LeadAssignment {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    lead_id: i32,
+    from_user_id: Option<uuid::Uuid>,
+    to_user_id: Option<uuid::Uuid>,
+    assigned_by: Option<uuid::Uuid>,
+    reason: LeadAssignmentReason,
+}
+
+impl LeadAssignment {
+    pub(super) fn find_by_lead_id(pool: &DbPool, lead_id: &i32) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        lead_assignments::table
+            .filter(lead_assignments::lead_id.eq(lead_id))
+            .order_by(lead_assignments::created_at.desc())
+            .get_results(conn)
+    }
+
+    pub(super) fn record(
+        conn: &mut PgConnection,
+        lead_id: &i32,
+        from_user_id: &Option<uuid::Uuid>,
+        to_user_id: &uuid::Uuid,
+        assigned_by: &Option<uuid::Uuid>,
+        reason: LeadAssignmentReason,
+    ) -> QueryResult<Self> {
+        diesel::insert_into(lead_assignments::table)
+            .values((
+                lead_assignments::lead_id.eq(lead_id),
+                lead_assignments::from_user_id.eq(from_user_id),
+                lead_assignments::to_user_id.eq(to_user_id),
+                lead_assignments::assigned_by.eq(assigned_by),
+                lead_assignments::reason.eq(reason),
+            ))
+            .get_result(conn)
+    }
+
+    // Moves the lead to another agent, under that agent's own contact for the buyer
+    fn assign_in(
+        conn: &mut PgConnection,
+        lead: &Lead,
+        to_user_id: &uuid::Uuid,
+        assigned_by: &Option<uuid::Uuid>,
+        reason: LeadAssignmentReason,
+    ) -> QueryResult<Lead> {
+        let contact = Contact::find_or_create_in(
+            conn,
+            to_user_id,
+            &lead.name,
+            &lead.phone_number,
+            &lead.email,
+        )?;
+
+        let updated_lead: Lead = diesel::update(leads::table)
+            .filter(leads::id.eq(lead.id))
+            .set((
+                leads::user_id.eq(to_user_id),
+                leads::contact_id.eq(contact.id),
+            ))
+            .get_result(conn)?;
+
+        Self::record(
+            conn,
+            &lead.id,
+            &Some(lead.user_id),
+            to_user_id,
+            assigned_by,
+            reason,
+        )?;
+
+        Ok(updated_lead)
+    }
+
+    pub(super) fn assign(
+        pool: &DbPool,
+        lead_id: &i32,
+        to_user_id: &uuid::Uuid,
+        assigned_by: &uuid::Uuid,
+    ) -> QueryResult<Lead> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let lead: Lead = leads::table
+                .filter(leads::id.eq(lead_id))
+                .for_update()
+                .get_result(conn)?;
+
+            if lead.user_id == *to_user_id {
+                return Ok(lead);
+            }
+
+            Self::assign_in(
+                conn,
+                &lead,
+                to_user_id,
+                &Some(*assigned_by),
+                LeadAssignmentReason::Manual,
+            )
+        })
+    }
+
+    // Open leads of an agent who leaves go to the agents covering the listing's
+    // regency, or to `fallback_user_id` when nobody covers it
+    pub fn reassign_open_leads_in(
+        conn: &mut PgConnection,
+        from_user_id: &uuid::Uuid,
+        fallback_user_id: &uuid::Uuid,
+    ) -> QueryResult<usize> {
+        let open_leads: Vec<(Lead, Option<String>)> = leads::table
+            .left_join(properties::table)
+            .filter(
+                leads::user_id
+                    .eq(from_user_id)
+                    .and(leads::is_deleted.eq(false)),
+            )
+            .select((leads::all_columns, properties::regency.nullable()))
+            .get_results(conn)?;
+
+        for (lead, regency) in &open_leads {
+            let routed_user_id = match regency {
+                Some(regency) => LeadRoutingRule::next_agent(conn, regency, from_user_id)?,
+                None => None,
+            };
+            let to_user_id = routed_user_id.unwrap_or(*fallback_user_id);
+            Self::assign_in(
+                conn,
+                lead,
+                &to_user_id,
+                &Some(*fallback_user_id),
+                LeadAssignmentReason::AgentRemoved,
+            )?;
+        }
+
+        Ok(open_leads.len())
+    }
+
+    // Every lead of the agent, open or not, follows their listings to the new agent
+    pub fn transfer_all_in(
+        conn: &mut PgConnection,
+        from_user_id: &uuid::Uuid,
+        to_user_id: &uuid::Uuid,
+        transferred_by: &uuid::Uuid,
+    ) -> QueryResult<usize> {
+        let agent_leads: Vec<Lead> = leads::table
+            .filter(leads::user_id.eq(from_user_id))
+            .for_update()
+            .get_results(conn)?;
+
+        for lead in &agent_leads {
+            Self::assign_in(
+                conn,
+                lead,
+                to_user_id,
+                &Some(*transferred_by),
+                LeadAssignmentReason::AgentTransfer,
+            )?;
+        }
+
+        Ok(agent_leads.len())
+    }
+}
@@ -0,0 +1,40 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
+#[diesel(sql_type = sql_types::LeadAssignmentReason)]
+pub enum LeadAssignmentReason {
+    Manual,
+    Routing,
+    AgentRemoved,
+    AgentTransfer,
+}
+
+impl ToSql<sql_types::LeadAssignmentReason, Pg> for LeadAssignmentReason {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            LeadAssignmentReason::Manual => out.write_all(b"manual")?,
+            LeadAssignmentReason::Routing => out.write_all(b"routing")?,
+            LeadAssignmentReason::AgentRemoved => out.write_all(b"agent_removed")?,
+            LeadAssignmentReason::AgentTransfer => out.write_all(b"agent_transfer")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::LeadAssignmentReason, Pg> for LeadAssignmentReason {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"manual" => Ok(LeadAssignmentReason::Manual),
+            b"routing" => Ok(LeadAssignmentReason::Routing),
+            b"agent_removed" => Ok(LeadAssignmentReason::AgentRemoved),
+            b"agent_transfer" => Ok(LeadAssignmentReason::AgentTransfer),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
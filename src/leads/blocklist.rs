@@ -0,0 +1,69 @@
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::controller::CreateBlockedContactPayload;
+use crate::{db::DbPool, schema::blocked_contacts};
+
+#[derive(Debug, Serialize, Queryable, Clone)]
+pub struct BlockedContact {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    phone: Option<String>,
+    email: Option<String>,
+    reason: Option<String>,
+    created_by: Option<uuid::Uuid>,
+}
+
+impl BlockedContact {
+    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        blocked_contacts::table
+            .order_by(blocked_contacts::created_at.desc())
+            .get_results(conn)
+    }
+
+    pub(super) fn find_match(
+        pool: &DbPool,
+        phone: &str,
+        email: &Option<String>,
+    ) -> QueryResult<Option<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut query = blocked_contacts::table.into_boxed();
+        query = match email {
+            Some(email) => query.filter(
+                blocked_contacts::phone
+                    .eq(phone)
+                    .or(blocked_contacts::email.eq(email)),
+            ),
+            None => query.filter(blocked_contacts::phone.eq(phone)),
+        };
+
+        query.first(conn).optional()
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        user_id: &uuid::Uuid,
+        payload: &CreateBlockedContactPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(blocked_contacts::table)
+            .values((payload, blocked_contacts::created_by.eq(user_id)))
+            .get_result(conn)
+    }
+
+    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(blocked_contacts::table)
+            .filter(blocked_contacts::id.eq(id))
+            .get_result(conn)
+    }
+}
@@ -0,0 +1,82 @@
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+
+const VERIFY_TIMEOUT_SECONDS: u64 = 5;
+
+#[derive(Deserialize, Debug)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+// Captcha verification for the public lead form.
+// Selected with CAPTCHA_PROVIDER, disabled when it isn't set.
+pub enum Captcha {
+    Disabled,
+    // Accepts any non-empty token, for local development
+    Mock,
+    // Turnstile, reCAPTCHA and hCaptcha share the same siteverify api
+    SiteVerify {
+        url: String,
+        secret: String,
+        client: reqwest::Client,
+    },
+}
+
+impl Captcha {
+    pub fn from_env() -> Self {
+        let provider = match env::var("CAPTCHA_PROVIDER") {
+            Ok(provider) => provider.to_lowercase(),
+            Err(_) => return Captcha::Disabled,
+        };
+
+        let url = match provider.as_str() {
+            "mock" => return Captcha::Mock,
+            "turnstile" => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+            "recaptcha" => "https://www.google.com/recaptcha/api/siteverify",
+            "hcaptcha" => "https://api.hcaptcha.com/siteverify",
+            _ => panic!("Unknown CAPTCHA_PROVIDER {}", provider),
+        };
+
+        Captcha::SiteVerify {
+            url: env::var("CAPTCHA_VERIFY_URL").unwrap_or(url.to_string()),
+            secret: env::var("CAPTCHA_SECRET").expect("Missing CAPTCHA_SECRET"),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(VERIFY_TIMEOUT_SECONDS))
+                .build()
+                .expect("Couldn't build the http client"),
+        }
+    }
+
+    pub async fn verify(&self, token: &Option<String>, remote_ip: &str) -> bool {
+        let token = match token {
+            Some(token) if !token.is_empty() => token,
+            _ => return matches!(self, Captcha::Disabled),
+        };
+
+        match self {
+            Captcha::Disabled | Captcha::Mock => true,
+            // Fails closed: when the provider errors or doesn't answer in time the lead
+            // is rejected like a failed captcha, the form can be sent again
+            Captcha::SiteVerify {
+                url,
+                secret,
+                client,
+            } => {
+                let params = [
+                    ("secret", secret.as_str()),
+                    ("response", token.as_str()),
+                    ("remoteip", remote_ip),
+                ];
+                let response = client.post(url).form(&params).send().await;
+                match response {
+                    Ok(res) => match res.json::<SiteVerifyResponse>().await {
+                        Ok(body) => body.success,
+                        Err(_) => false,
+                    },
+                    Err(_) => false,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use crate::{
+    db::DbPool,
+    schema::{contacts, leads},
+};
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct Contact {
+    pub id: i32,
+    user_id: uuid::Uuid,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    name: String,
+    phone: String,
+    email: Option<String>,
+    merged_into_id: Option<i32>,
+}
+
+impl Contact {
+    // The agent's contact with the phone number or email, the oldest when both match.
+    // The row stays locked until the transaction ends.
+    fn find_matching_in(
+        conn: &mut PgConnection,
+        user_id: &uuid::Uuid,
+        phone: &str,
+        email: &Option<String>,
+    ) -> QueryResult<Option<Self>> {
+        // without an email `email = NULL` matches nothing, leaving the phone number
+        contacts::table
+            .filter(
+                contacts::user_id
+                    .eq(user_id)
+                    .and(contacts::merged_into_id.is_null())
+                    .and(contacts::phone.eq(phone).or(contacts::email.eq(email))),
+            )
+            .order_by(contacts::created_at.asc())
+            .for_update()
+            .first::<Self>(conn)
+            .optional()
+    }
+
+    // A contact is unique per agent, matched by phone number or email
+    pub(super) fn find_or_create_in(
+        conn: &mut PgConnection,
+        user_id: &uuid::Uuid,
+        name: &str,
+        phone: &str,
+        email: &Option<String>,
+    ) -> QueryResult<Self> {
+        if let Some(contact) = Self::find_matching_in(conn, user_id, phone, email)? {
+            return Ok(contact);
+        }
+
+        // a concurrent submission may have created the contact since, the unique
+        // indexes on phone and email turn that into a no-op insert
+        let contact = diesel::insert_into(contacts::table)
+            .values((
+                contacts::user_id.eq(user_id),
+                contacts::name.eq(name),
+                contacts::phone.eq(phone),
+                contacts::email.eq(email),
+            ))
+            .on_conflict_do_nothing()
+            .get_result(conn)
+            .optional()?;
+
+        match contact {
+            Some(contact) => Ok(contact),
+            None => Self::find_matching_in(conn, user_id, phone, email)?
+                .ok_or(diesel::result::Error::NotFound),
+        }
+    }
+
+    // Moves every lead of the source contacts to the target contact.
+    // Source contacts are kept with merged_into_id pointing to the target.
+    pub(super) fn merge(pool: &DbPool, target_id: &i32, source_ids: &[i32]) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let target: Self = contacts::table
+                .filter(
+                    contacts::id
+                        .eq(target_id)
+                        .and(contacts::merged_into_id.is_null()),
+                )
+                .get_result(conn)?;
+
+            let sources: Vec<i32> = contacts::table
+                .filter(
+                    contacts::id
+                        .eq_any(source_ids)
+                        .and(contacts::id.ne(target_id))
+                        .and(contacts::user_id.eq(target.user_id))
+                        .and(contacts::merged_into_id.is_null()),
+                )
+                .select(contacts::id)
+                .get_results(conn)?;
+
+            if sources.is_empty() || sources.len() != source_ids.len() {
+                return Err(diesel::result::Error::NotFound);
+            }
+
+            diesel::update(leads::table)
+                .filter(leads::contact_id.eq_any(&sources))
+                .set(leads::contact_id.eq(target_id))
+                .execute(conn)?;
+
+            diesel::update(contacts::table)
+                .filter(
+                    contacts::id
+                        .eq_any(&sources)
+                        .or(contacts::merged_into_id.eq_any(&sources)),
+                )
+                .set(contacts::merged_into_id.eq(target_id))
+                .execute(conn)?;
+
+            Ok(target)
+        })
+    }
+}
@@ -1,60 +1,380 @@
-use crate::agents::Agent;
+use crate::agents::{authorize, can_on, scope, Action, Agent, Resource};
+use crate::appointments::Appointment;
 use crate::middleware::{JsonFindResponse, JsonResponse, Session};
-use crate::properties::Property;
+use crate::properties::{AvailabilityRange, Property, ShareLink};
 use crate::{db::DbPool, middleware::AxumResponse, schema};
-use axum::extract::{Json, Query, State};
+use axum::extract::{ConnectInfo, Extension, Json, Path, Query, State};
 use axum::http::HeaderMap;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, post, put};
 use axum::Router;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::Insertable;
+use diesel::result::DatabaseErrorKind;
 use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
+use super::assignment::LeadAssignment;
+use super::blocklist::BlockedContact;
+use super::contact::Contact;
+use super::export::export_leads;
 use super::model::Lead;
+use super::phone::normalize_phone_number;
+use super::rate_limit::{client_ip, IP_SUBMISSIONS, PHONE_RATE_LIMIT};
+use super::rejection::LeadRejection;
+use super::rejection_reason::LeadRejectionReason;
+use super::routing::LeadRoutingRule;
+use super::settings::LeadSettings;
 
-#[derive(Deserialize, Insertable)]
-#[diesel(table_name = schema::leads)]
-pub struct CreateLeadPayload {
+#[derive(Deserialize)]
+pub(crate) struct CreateLeadApiPayload {
     user_id: uuid::Uuid,
     property_id: i32,
     name: String,
     phone: String,
     email: Option<String>,
+    // hidden field on the lead form, only bots fill it in
+    website: Option<String>,
+    captcha_token: Option<String>,
+    // `ref` of the share link the visitor came in through
+    share_code: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::leads)]
+pub struct CreateLeadSqlPayload {
+    pub(super) user_id: uuid::Uuid,
+    pub(super) property_id: i32,
+    pub(super) name: String,
+    pub(super) phone: String,
+    pub(super) email: Option<String>,
+    share_link_id: Option<i32>,
+    pub(super) check_in: Option<NaiveDate>,
+    pub(super) check_out: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub struct BookingRequestApiPayload {
+    #[serde(flatten)]
+    lead: CreateLeadApiPayload,
+    check_in: NaiveDate,
+    // The day the renter leaves
+    check_out: NaiveDate,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::lead_rejections)]
+pub struct CreateLeadRejectionPayload {
+    reason: LeadRejectionReason,
+    ip_address: String,
+    property_id: Option<i32>,
+    name: String,
+    phone: String,
+    email: Option<String>,
+}
+
+fn truncate(value: &str) -> String {
+    value.chars().take(255).collect()
+}
+
+fn reject_lead(
+    pool: &DbPool,
+    reason: LeadRejectionReason,
+    ip_address: &str,
+    payload: &CreateLeadApiPayload,
+) {
+    tracing::warn!(
+        "Rejected lead from {} for property {}: {:?}",
+        ip_address,
+        payload.property_id,
+        reason
+    );
+    let property_id = match Property::find_one_by_id(pool, &payload.property_id) {
+        Ok(_) => Some(payload.property_id),
+        Err(_) => None,
+    };
+    let rejection = CreateLeadRejectionPayload {
+        reason,
+        ip_address: truncate(ip_address),
+        property_id,
+        name: truncate(&payload.name),
+        phone: truncate(&payload.phone),
+        email: payload.email.as_deref().map(truncate),
+    };
+    if let Err(err) = LeadRejection::create(pool, &rejection) {
+        tracing::error!("Failed to log lead rejection: {}", err);
+    }
+}
+
+// What came of a lead that passed the checks
+pub(crate) enum LeadOutcome {
+    Created(Lead),
+    // the same contact asked about the listing within the window
+    Duplicate(Lead),
+    // bots and blocklisted contacts get a success response so they don't adapt
+    Dropped,
+}
+
+// What the lead asks for besides the listing itself
+pub(crate) enum LeadRequest {
+    Inquiry,
+    // check_out is the day the renter leaves
+    Booking {
+        check_in: NaiveDate,
+        check_out: NaiveDate,
+    },
+    Viewing {
+        starts_at: NaiveDateTime,
+        ends_at: NaiveDateTime,
+    },
+}
+
+fn send_outcome(outcome: Result<LeadOutcome, AxumResponse<Lead>>) -> AxumResponse<Lead> {
+    match outcome {
+        Ok(LeadOutcome::Created(lead)) => JsonResponse::send(201, Some(lead), None),
+        Ok(LeadOutcome::Duplicate(lead)) => {
+            JsonResponse::send(200, Some(lead), Some("Duplicate lead".to_string()))
+        }
+        Ok(LeadOutcome::Dropped) => JsonResponse::send(201, None, None),
+        Err(response) => response,
+    }
 }
 
 async fn create_lead(
     State(pool): State<DbPool>,
+    Extension(settings): Extension<Arc<LeadSettings>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-    Json(payload): Json<CreateLeadPayload>,
+    Json(payload): Json<CreateLeadApiPayload>,
 ) -> AxumResponse<Lead> {
+    send_outcome(
+        accept_lead(
+            &pool,
+            &settings,
+            &addr,
+            &headers,
+            payload,
+            LeadRequest::Inquiry,
+        )
+        .await,
+    )
+}
+
+// A lead asking to rent the listing for the stay, after the same checks as any other lead
+async fn create_booking_request(
+    State(pool): State<DbPool>,
+    Extension(settings): Extension<Arc<LeadSettings>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<BookingRequestApiPayload>,
+) -> AxumResponse<Lead> {
+    let request = LeadRequest::Booking {
+        check_in: payload.check_in,
+        check_out: payload.check_out,
+    };
+    send_outcome(accept_lead(&pool, &settings, &addr, &headers, payload.lead, request).await)
+}
+
+// The checks every lead from the site goes through, the lead is saved once they pass
+pub(crate) async fn accept_lead<T>(
+    pool: &DbPool,
+    settings: &LeadSettings,
+    addr: &SocketAddr,
+    headers: &HeaderMap,
+    payload: CreateLeadApiPayload,
+    request: LeadRequest,
+) -> Result<LeadOutcome, AxumResponse<T>> {
     let api_key_option = headers.get("x-api-key");
 
     let api_key = match api_key_option {
         Some(key) => key.to_str().unwrap_or(""),
-        None => return JsonResponse::send(401, None, None),
+        None => return Err(JsonResponse::send(401, None, None)),
     };
 
-    let leads_api_key = std::env::var("API_KEY_LEADS").expect("Missing API_KEY_LEADS");
+    if api_key != settings.api_key {
+        return Err(JsonResponse::send(401, None, None));
+    }
 
-    if api_key != leads_api_key {
-        return JsonResponse::send(401, None, None);
+    let ip_address = client_ip(headers, addr, &settings.trusted_proxies);
+
+    // Bots get a success response so they don't adapt
+    if payload
+        .website
+        .as_ref()
+        .is_some_and(|website| !website.is_empty())
+    {
+        reject_lead(pool, LeadRejectionReason::Honeypot, &ip_address, &payload);
+        return Ok(LeadOutcome::Dropped);
+    }
+
+    if !IP_SUBMISSIONS.check(&ip_address) {
+        reject_lead(
+            pool,
+            LeadRejectionReason::IpRateLimited,
+            &ip_address,
+            &payload,
+        );
+        return Err(JsonResponse::send(429, None, None));
+    }
+
+    if !settings
+        .captcha
+        .verify(&payload.captcha_token, &ip_address)
+        .await
+    {
+        reject_lead(
+            pool,
+            LeadRejectionReason::CaptchaFailed,
+            &ip_address,
+            &payload,
+        );
+        return Err(JsonResponse::send(
+            400,
+            None,
+            Some("Invalid captcha".to_string()),
+        ));
     }
 
-    let property = match Property::find_one_by_id(&pool, &payload.property_id) {
+    let phone = match normalize_phone_number(&payload.phone) {
+        Some(phone) => phone,
+        None => {
+            return Err(JsonResponse::send(
+                400,
+                None,
+                Some("Invalid phone number".to_string()),
+            ))
+        }
+    };
+    let email = payload
+        .email
+        .as_ref()
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+
+    match BlockedContact::find_match(pool, &phone, &email) {
+        Ok(Some(_)) => {
+            reject_lead(
+                pool,
+                LeadRejectionReason::Blocklisted,
+                &ip_address,
+                &payload,
+            );
+            return Ok(LeadOutcome::Dropped);
+        }
+        Ok(None) => {}
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+
+    match Lead::count_recent_by_phone(pool, &phone) {
+        Ok(count) if count >= PHONE_RATE_LIMIT => {
+            reject_lead(
+                pool,
+                LeadRejectionReason::PhoneRateLimited,
+                &ip_address,
+                &payload,
+            );
+            return Err(JsonResponse::send(429, None, None));
+        }
+        Ok(_) => {}
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+
+    let property = match Property::find_one_by_id(pool, &payload.property_id) {
         Ok(property) if property.0.user_id == payload.user_id => property,
-        _ => return JsonResponse::send(400, None, None),
+        _ => return Err(JsonResponse::send(400, None, None)),
     };
-    match Lead::create(&pool, &property.0.user_id, &payload) {
-        Ok(lead) => JsonResponse::send(201, Some(lead), None),
-        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+
+    // Leads on listings of a deactivated agent go to whoever covers the regency
+    let owner_user_id = property.0.user_id;
+    let receiver_user_id = if property.1.is_active {
+        owner_user_id
+    } else {
+        match LeadRoutingRule::route(pool, &property.0.regency, &owner_user_id) {
+            Ok(routed_user_id) => routed_user_id.unwrap_or(owner_user_id),
+            Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+        }
+    };
+
+    match &request {
+        LeadRequest::Inquiry => {}
+        LeadRequest::Booking {
+            check_in,
+            check_out,
+        } => {
+            if let Err(message) = property.0.validate_stay(*check_in, *check_out) {
+                return Err(JsonResponse::send(400, None, Some(message)));
+            }
+            match AvailabilityRange::is_free(pool, &payload.property_id, *check_in, *check_out) {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(JsonResponse::send(
+                        409,
+                        None,
+                        Some("Dates are not available".to_string()),
+                    ))
+                }
+                Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+            }
+        }
+        LeadRequest::Viewing { starts_at, ends_at } => {
+            if property.0.is_deleted {
+                return Err(JsonResponse::send(
+                    400,
+                    None,
+                    Some("Property is no longer available".to_string()),
+                ));
+            }
+            match Appointment::find_request_conflict(pool, &receiver_user_id, starts_at, ends_at) {
+                Ok(None) => {}
+                Ok(Some(message)) => return Err(JsonResponse::send(409, None, Some(message))),
+                Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+            }
+        }
     }
+
+    // codes of another listing's link are ignored rather than failing the lead
+    let share_link_id = match &payload.share_code {
+        Some(code) => match ShareLink::find_by_code(pool, code) {
+            Ok(link) if link.property_id == payload.property_id => Some(link.id),
+            Ok(_) | Err(diesel::result::Error::NotFound) => None,
+            Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+        },
+        None => None,
+    };
+
+    let payload = CreateLeadSqlPayload {
+        user_id: receiver_user_id,
+        property_id: payload.property_id,
+        name: payload.name,
+        phone,
+        email,
+        share_link_id,
+        check_in: match request {
+            LeadRequest::Booking { check_in, .. } => Some(check_in),
+            _ => None,
+        },
+        check_out: match request {
+            LeadRequest::Booking { check_out, .. } => Some(check_out),
+            _ => None,
+        },
+    };
+
+    Lead::create(pool, &owner_user_id, &payload)
+        .map_err(|err| JsonResponse::send(500, None, Some(err.to_string())))
 }
 
 pub(super) const PAGE_SIZE: i64 = 20;
+pub(super) const DUPLICATE_WINDOW_HOURS: i32 = 24;
 
 #[derive(Deserialize)]
 pub struct FindLeadQueryParam {
     pub search: Option<String>,
     pub page: Option<i64>,
+    pub contact_id: Option<i32>,
+    pub property_id: Option<i32>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>,
+    pub branch_id: Option<i32>,
 }
 
 async fn find_many_leads(
@@ -64,20 +384,22 @@ async fn find_many_leads(
 ) -> AxumResponse<JsonFindResponse<Vec<Lead>>> {
     let user_id = Session::extract_session_user_id(&headers);
 
-    let role = match Agent::find_by_user_id(&pool, &user_id) {
-        Ok(agent) => Some(agent.role),
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Lead) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
         _ => {
             return JsonResponse::send(403, None, None);
         }
     };
 
-    let leads = match Lead::find_many(&pool, &Some(user_id), &role, &query_params) {
+    let leads = match Lead::find_many(&pool, &scope, &query_params) {
         Ok(leads_vec) => leads_vec,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
 
-    let leads_count = match Lead::count_find_many_rows(&pool, &Some(user_id), &role, &query_params)
-    {
+    let leads_count = match Lead::count_find_many_rows(&pool, &scope, &query_params) {
         Ok(count) => count,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
@@ -90,8 +412,379 @@ async fn find_many_leads(
     JsonResponse::send(200, Some(body), None)
 }
 
+#[derive(Deserialize)]
+pub struct MergeContactsPayload {
+    target_contact_id: i32,
+    contact_ids: Vec<i32>,
+}
+
+async fn merge_contacts(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<MergeContactsPayload>,
+) -> AxumResponse<Contact> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::Lead) {
+        return response;
+    }
+
+    let mut contact_ids = payload.contact_ids.clone();
+    contact_ids.sort_unstable();
+    contact_ids.dedup();
+    contact_ids.retain(|id| *id != payload.target_contact_id);
+
+    match Contact::merge(&pool, &payload.target_contact_id, &contact_ids) {
+        Ok(contact) => JsonResponse::send(200, Some(contact), None),
+        Err(diesel::result::Error::NotFound) => JsonResponse::send(
+            400,
+            None,
+            Some("Contacts not found or not mergeable".to_string()),
+        ),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn find_blocked_contacts(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+) -> AxumResponse<JsonFindResponse<Vec<BlockedContact>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::LeadSettings) {
+        return response;
+    }
+
+    let blocked_contacts = match BlockedContact::find_many(&pool) {
+        Ok(blocked_contacts) => blocked_contacts,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let res = JsonFindResponse {
+        total_data: blocked_contacts.len() as i64,
+        data: blocked_contacts,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+#[derive(Deserialize)]
+pub struct CreateBlockedContactApiPayload {
+    phone: Option<String>,
+    email: Option<String>,
+    reason: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::blocked_contacts)]
+pub struct CreateBlockedContactPayload {
+    phone: Option<String>,
+    email: Option<String>,
+    reason: Option<String>,
+}
+
+async fn create_blocked_contact(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateBlockedContactApiPayload>,
+) -> AxumResponse<BlockedContact> {
+    if let Err(response) = authorize(&pool, &headers, Action::Create, Resource::LeadSettings) {
+        return response;
+    }
+
+    let phone = match &payload.phone {
+        Some(phone) => match normalize_phone_number(phone) {
+            Some(phone) => Some(phone),
+            None => return JsonResponse::send(400, None, Some("Invalid phone number".to_string())),
+        },
+        None => None,
+    };
+    let email = payload
+        .email
+        .as_ref()
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+
+    if phone.is_none() && email.is_none() {
+        return JsonResponse::send(400, None, Some("Phone or email is required".to_string()));
+    }
+
+    let sql_payload = CreateBlockedContactPayload {
+        phone,
+        email,
+        reason: payload.reason,
+    };
+    let user_id = Session::extract_session_user_id(&headers);
+
+    match BlockedContact::create(&pool, &user_id, &sql_payload) {
+        Ok(blocked_contact) => JsonResponse::send(201, Some(blocked_contact), None),
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            JsonResponse::send(400, None, Some("Already blocked".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn delete_blocked_contact(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<BlockedContact> {
+    if let Err(response) = authorize(&pool, &headers, Action::Delete, Resource::LeadSettings) {
+        return response;
+    }
+
+    match BlockedContact::delete(&pool, &id) {
+        Ok(blocked_contact) => JsonResponse::send(200, Some(blocked_contact), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Blocked contact not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FindLeadRejectionQuery {
+    page: Option<i64>,
+}
+
+async fn find_lead_rejections(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<FindLeadRejectionQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<LeadRejection>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::LeadSettings) {
+        return response;
+    }
+
+    let rejections = match LeadRejection::find_many(&pool, &query.page) {
+        Ok(rejections) => rejections,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let rejections_count = match LeadRejection::count(&pool) {
+        Ok(count) => count,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let body = JsonFindResponse {
+        data: rejections,
+        total_pages: (rejections_count / PAGE_SIZE) + 1,
+        total_data: rejections_count,
+    };
+    JsonResponse::send(200, Some(body), None)
+}
+
+#[derive(Deserialize)]
+pub struct AssignLeadPayload {
+    user_id: uuid::Uuid,
+}
+
+async fn assign_lead(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<AssignLeadPayload>,
+) -> AxumResponse<Lead> {
+    let user_id = Session::extract_session_user_id(&headers);
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let lead = match Lead::find_by_id(&pool, &id) {
+        Ok(lead) => lead,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Lead not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let owner = match Agent::find_by_user_id(&pool, &lead.user_id) {
+        Ok(owner) => owner,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let target = match Agent::find_by_user_id(&pool, &payload.user_id) {
+        Ok(target) if target.is_active => target,
+        _ => return JsonResponse::send(400, None, Some("Agent not found".to_string())),
+    };
+
+    // branch managers only move leads between members of their branch
+    if !can_on(&agent, Action::Assign, Resource::Lead, &owner)
+        || !can_on(&agent, Action::Assign, Resource::Lead, &target)
+    {
+        return JsonResponse::send(403, None, None);
+    }
+
+    match LeadAssignment::assign(&pool, &id, &payload.user_id, &user_id) {
+        Ok(lead) => JsonResponse::send(200, Some(lead), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Lead not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// The agent has reached out to the lead, feeds the response time stats
+async fn respond_to_lead(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Lead> {
+    let user_id = Session::extract_session_user_id(&headers);
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let lead = match Lead::find_by_id(&pool, &id) {
+        Ok(lead) => lead,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Lead not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let is_allowed = match Agent::find_by_user_id(&pool, &lead.user_id) {
+        Ok(owner) => can_on(&agent, Action::Update, Resource::Lead, &owner),
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    if !is_allowed {
+        return JsonResponse::send(403, None, None);
+    }
+
+    match Lead::mark_responded(&pool, &id) {
+        Ok(lead) => JsonResponse::send(200, Some(lead), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn find_lead_assignments(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Vec<LeadAssignment>> {
+    let user_id = Session::extract_session_user_id(&headers);
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let lead = match Lead::find_by_id(&pool, &id) {
+        Ok(lead) => lead,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Lead not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let is_allowed = match Agent::find_by_user_id(&pool, &lead.user_id) {
+        Ok(owner) => can_on(&agent, Action::View, Resource::Lead, &owner),
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    if !is_allowed {
+        return JsonResponse::send(403, None, None);
+    }
+
+    match LeadAssignment::find_by_lead_id(&pool, &id) {
+        Ok(assignments) => JsonResponse::send(200, Some(assignments), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn find_routing_rules(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+) -> AxumResponse<JsonFindResponse<Vec<LeadRoutingRule>>> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::LeadSettings) {
+        return response;
+    }
+
+    let rules = match LeadRoutingRule::find_many(&pool) {
+        Ok(rules) => rules,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let res = JsonFindResponse {
+        total_data: rules.len() as i64,
+        data: rules,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+#[derive(Deserialize, Insertable)]
+#[diesel(table_name = schema::lead_routing_rules)]
+pub struct CreateLeadRoutingRulePayload {
+    regency: String,
+    user_id: uuid::Uuid,
+}
+
+async fn create_routing_rule(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateLeadRoutingRulePayload>,
+) -> AxumResponse<LeadRoutingRule> {
+    if let Err(response) = authorize(&pool, &headers, Action::Create, Resource::LeadSettings) {
+        return response;
+    }
+
+    let regency = payload.regency.trim().to_lowercase();
+    if regency.is_empty() {
+        return JsonResponse::send(400, None, Some("Regency is required".to_string()));
+    }
+
+    if Agent::find_by_user_id(&pool, &payload.user_id).is_err() {
+        return JsonResponse::send(400, None, Some("Agent not found".to_string()));
+    }
+
+    let sql_payload = CreateLeadRoutingRulePayload {
+        regency,
+        user_id: payload.user_id,
+    };
+
+    match LeadRoutingRule::create(&pool, &sql_payload) {
+        Ok(rule) => JsonResponse::send(201, Some(rule), None),
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            JsonResponse::send(400, None, Some("Routing rule already exists".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn delete_routing_rule(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<LeadRoutingRule> {
+    if let Err(response) = authorize(&pool, &headers, Action::Delete, Resource::LeadSettings) {
+        return response;
+    }
+
+    match LeadRoutingRule::delete(&pool, &id) {
+        Ok(rule) => JsonResponse::send(200, Some(rule), None),
+        Err(err) => match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Routing rule not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        },
+    }
+}
+
 pub fn lead_routes() -> Router<DbPool> {
     Router::new()
         .route("/", post(create_lead))
+        .route("/booking-requests", post(create_booking_request))
         .route("/", get(find_many_leads))
+        .route("/export", get(export_leads))
+        .route("/merge", post(merge_contacts))
+        .route("/blocklist", get(find_blocked_contacts))
+        .route("/blocklist", post(create_blocked_contact))
+        .route("/blocklist/{id}", delete(delete_blocked_contact))
+        .route("/rejections", get(find_lead_rejections))
+        .route("/routing-rules", get(find_routing_rules))
+        .route("/routing-rules", post(create_routing_rule))
+        .route("/routing-rules/{id}", delete(delete_routing_rule))
+        .route("/{id}/assign", put(assign_lead))
+        .route("/{id}/assignments", get(find_lead_assignments))
+        .route("/{id}/respond", post(respond_to_lead))
 }
@@ -0,0 +1,295 @@
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use rust_xlsxwriter::Workbook;
+use serde::Deserialize;
+use std::io::{self, Write};
+use tokio::sync::mpsc::{self, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+
+use super::controller::FindLeadQueryParam;
+use super::model::{Lead, LeadExportRow};
+use crate::agents::{scope, Action, Agent, Resource, Scope};
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse, Session};
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const EXPORT_HEADERS: [&str; 9] = [
+    "id",
+    "created_at",
+    "name",
+    "phone",
+    "email",
+    "property_id",
+    "property_title",
+    "property_site_path",
+    "property_price",
+];
+// Spreadsheet apps read a cell starting with one of these as a formula
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LeadExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+#[derive(Deserialize)]
+pub struct LeadExportQuery {
+    format: Option<LeadExportFormat>,
+}
+
+// Forwards everything the csv writer flushes to the response body
+struct ChannelWriter(Sender<io::Result<Vec<u8>>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(buf.to_vec()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Export download cancelled"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn attachment(content_type: &str, extension: &str, body: Body) -> Response {
+    let filename = format!(
+        "attachment; filename=\"leads-{}.{}\"",
+        chrono::Local::now().format("%Y%m%d"),
+        extension
+    );
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// Prefixes text a spreadsheet would run as a formula with a quote, so the name or email
+// a visitor typed into the lead form shows up as text
+fn escape_formula(value: String) -> String {
+    if value.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
+
+fn escape_csv_row(row: LeadExportRow) -> LeadExportRow {
+    LeadExportRow {
+        name: escape_formula(row.name),
+        phone: escape_formula(row.phone),
+        email: row.email.map(escape_formula),
+        property_title: row.property_title.map(escape_formula),
+        property_site_path: row.property_site_path.map(escape_formula),
+        ..row
+    }
+}
+
+fn stream_csv(pool: DbPool, scope: Scope, query_params: FindLeadQueryParam) -> Response {
+    let (tx, rx) = mpsc::channel::<io::Result<Vec<u8>>>(16);
+
+    tokio::task::spawn_blocking(move || {
+        let error_tx = tx.clone();
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(ChannelWriter(tx));
+
+        let mut write_error = None;
+        let result = writer
+            .write_record(EXPORT_HEADERS)
+            .map_err(io::Error::from)
+            .and_then(|_| {
+                Lead::for_each_export_row(&pool, &scope, &query_params, |row| {
+                    match writer.serialize(escape_csv_row(row)) {
+                        Ok(_) => true,
+                        Err(err) => {
+                            write_error = Some(io::Error::from(err));
+                            false
+                        }
+                    }
+                })
+                .map_err(io::Error::other)
+            })
+            .and_then(|_| write_error.map_or(Ok(()), Err))
+            .and_then(|_| writer.flush());
+
+        // an error part way through aborts the download instead of sending a truncated file
+        if let Err(err) = result {
+            let _ = error_tx.blocking_send(Err(err));
+        }
+    });
+
+    attachment(
+        "text/csv; charset=utf-8",
+        "csv",
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+}
+
+fn write_xlsx_row(
+    worksheet: &mut rust_xlsxwriter::Worksheet,
+    row_index: u32,
+    row: &LeadExportRow,
+) -> Result<(), rust_xlsxwriter::XlsxError> {
+    worksheet.write_number(row_index, 0, row.id)?;
+    worksheet.write_string(
+        row_index,
+        1,
+        row.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+    )?;
+    worksheet.write_string(row_index, 2, &row.name)?;
+    worksheet.write_string(row_index, 3, &row.phone)?;
+    worksheet.write_string(row_index, 4, row.email.as_deref().unwrap_or(""))?;
+    if let Some(property_id) = row.property_id {
+        worksheet.write_number(row_index, 5, property_id)?;
+    }
+    worksheet.write_string(row_index, 6, row.property_title.as_deref().unwrap_or(""))?;
+    worksheet.write_string(
+        row_index,
+        7,
+        row.property_site_path.as_deref().unwrap_or(""),
+    )?;
+    if let Some(price) = row.property_price {
+        worksheet.write_number(row_index, 8, price as f64)?;
+    }
+    Ok(())
+}
+
+// xlsx is a zip archive and can only be sent once complete. Rows are still
+// read one by one and the worksheet keeps them on disk instead of in memory.
+fn build_xlsx(
+    pool: &DbPool,
+    scope: Scope,
+    query_params: &FindLeadQueryParam,
+) -> Result<Vec<u8>, String> {
+    let mut workbook = Workbook::new();
+    let mut worksheet = workbook.new_worksheet_with_constant_memory();
+    worksheet.set_name("Leads").map_err(|err| err.to_string())?;
+
+    for (column, title) in EXPORT_HEADERS.iter().enumerate() {
+        worksheet
+            .write_string(0, column as u16, *title)
+            .map_err(|err| err.to_string())?;
+    }
+
+    let mut row_index = 0;
+    let mut write_error = None;
+    Lead::for_each_export_row(pool, &scope, query_params, |row| {
+        row_index += 1;
+        match write_xlsx_row(&mut worksheet, row_index, &row) {
+            Ok(_) => true,
+            Err(err) => {
+                write_error = Some(err.to_string());
+                false
+            }
+        }
+    })
+    .map_err(|err| err.to_string())?;
+
+    if let Some(err) = write_error {
+        return Err(err);
+    }
+
+    workbook.push_worksheet(worksheet);
+    workbook.save_to_buffer().map_err(|err| err.to_string())
+}
+
+pub(super) async fn export_leads(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(export_query): Query<LeadExportQuery>,
+    Query(query_params): Query<FindLeadQueryParam>,
+) -> Result<Response, AxumResponse<String>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::Export, Resource::Lead) {
+            Some(scope) => scope,
+            None => return Err(JsonResponse::send(403, None, None)),
+        },
+        _ => return Err(JsonResponse::send(403, None, None)),
+    };
+
+    match export_query.format.unwrap_or_default() {
+        LeadExportFormat::Csv => Ok(stream_csv(pool, scope, query_params)),
+        LeadExportFormat::Xlsx => {
+            let buffer =
+                tokio::task::spawn_blocking(move || build_xlsx(&pool, scope, &query_params)).await;
+
+            match buffer {
+                Ok(Ok(buffer)) => Ok(attachment(XLSX_CONTENT_TYPE, "xlsx", Body::from(buffer))),
+                Ok(Err(err)) => Err(JsonResponse::send(500, None, Some(err))),
+                Err(err) => Err(JsonResponse::send(500, None, Some(err.to_string()))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, phone: &str, email: Option<&str>) -> LeadExportRow {
+        LeadExportRow {
+            id: 1,
+            created_at: chrono::NaiveDate::from_ymd_opt(2026, 3, 1)
+                .and_then(|day| day.and_hms_opt(9, 30, 0))
+                .expect("Invalid date"),
+            name: name.to_string(),
+            phone: phone.to_string(),
+            email: email.map(str::to_string),
+            property_id: Some(7),
+            property_title: Some("Villa Canggu".to_string()),
+            property_site_path: None,
+            property_price: Some(2_500_000_000),
+        }
+    }
+
+    fn csv_line(row: LeadExportRow) -> String {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        writer
+            .serialize(escape_csv_row(row))
+            .expect("Couldn't write the row");
+        String::from_utf8(writer.into_inner().expect("Couldn't flush the row"))
+            .expect("Invalid utf-8")
+    }
+
+    #[test]
+    fn quotes_cells_a_spreadsheet_would_run() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(escape_formula(value.to_string()), format!("'{}", value));
+        }
+    }
+
+    #[test]
+    fn leaves_plain_text_alone() {
+        for value in ["Budi", "budi@example.com", "a=b", ""] {
+            assert_eq!(escape_formula(value.to_string()), value);
+        }
+    }
+
+    #[test]
+    fn escapes_the_typed_fields_of_a_csv_row() {
+        let line = csv_line(row(
+            "=HYPERLINK(\"http://x\")",
+            "+6281234567890",
+            Some("@evil"),
+        ));
+        assert_eq!(
+            line,
+            "1,2026-03-01T09:30:00,\"'=HYPERLINK(\"\"http://x\"\")\",'+6281234567890,'@evil,7,Villa Canggu,,2500000000\n"
+        );
+    }
+}
@@ -1,5 +1,21 @@
+mod assignment;
+mod assignment_reason;
+mod blocklist;
+mod captcha;
+mod contact;
 mod controller;
+mod export;
 mod model;
+mod phone;
+mod rate_limit;
+mod rejection;
+mod rejection_reason;
+mod routing;
+mod settings;
 
+pub use assignment::LeadAssignment;
 pub use controller::lead_routes;
+pub(crate) use controller::{accept_lead, CreateLeadApiPayload, LeadOutcome, LeadRequest};
 pub use model::Lead;
+pub use rate_limit::client_ip;
+pub use settings::LeadSettings;
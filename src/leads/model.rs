@@ -1,26 +1,69 @@
-use super::controller::{CreateLeadPayload, FindLeadQueryParam, PAGE_SIZE};
+use super::assignment::LeadAssignment;
+use super::assignment_reason::LeadAssignmentReason;
+use super::contact::Contact;
+use super::controller::{
+    CreateLeadSqlPayload, FindLeadQueryParam, LeadOutcome, DUPLICATE_WINDOW_HOURS, PAGE_SIZE,
+};
+use super::phone::phone_search_term;
+use super::rate_limit::PHONE_RATE_WINDOW_HOURS;
+use chrono::NaiveTime;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::pg::{Pg, PgRowByRowLoadingMode};
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, PgTextExpressionMethods, QueryDsl, QueryResult,
-    Queryable, RunQueryDsl,
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, PgExpressionMethods, PgTextExpressionMethods, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
 };
 use serde::Serialize;
 
-use crate::{agents::AgentRole, db::DbPool, schema::leads};
+use crate::{
+    agents::{branch_member_ids, Scope},
+    db::DbPool,
+    schema::{leads, properties},
+};
 
 #[derive(Serialize, Queryable)]
 pub struct Lead {
-    id: i32,
-    user_id: uuid::Uuid,
-    property_id: i32,
+    pub id: i32,
+    pub user_id: uuid::Uuid,
+    pub property_id: Option<i32>,
     created_at: chrono::NaiveDateTime,
     updated_at: chrono::NaiveDateTime,
-    name: String,
-    phone_number: String,
-    email: Option<String>,
+    pub(super) name: String,
+    pub(super) phone_number: String,
+    pub(super) email: Option<String>,
     is_deleted: bool,
+    contact_id: Option<i32>,
+    responded_at: Option<chrono::NaiveDateTime>,
+    share_link_id: Option<i32>,
+    // Set on booking requests, check_out is the day the renter leaves
+    check_in: Option<chrono::NaiveDate>,
+    check_out: Option<chrono::NaiveDate>,
 }
 
 impl Lead {
+    pub fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        leads::table.find(id).get_result(conn)
+    }
+
+    // Only the first response counts towards the response time
+    pub(super) fn mark_responded(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let lead = diesel::update(leads::table)
+            .filter(leads::id.eq(id).and(leads::responded_at.is_null()))
+            .set(leads::responded_at.eq(now.nullable()))
+            .get_result(conn)
+            .optional()?;
+
+        match lead {
+            Some(lead) => Ok(lead),
+            None => leads::table.find(id).get_result(conn),
+        }
+    }
+
     pub fn delete_by_property_id(pool: &DbPool, property_id: &i32) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
@@ -30,48 +73,150 @@ impl Lead {
             .get_result(conn)
     }
 
-    pub fn create(
+    // Saves a lead under the receiving agent's contact for the buyer. The contact stays
+    // locked until the lead is in, so concurrent submissions of the same contact queue
+    // up and a repeat within the window gets the earlier lead back.
+    pub(super) fn create(
         pool: &DbPool,
-        #[allow(unused_variables)] uuid: &uuid::Uuid,
-        payload: &CreateLeadPayload,
-    ) -> QueryResult<Lead> {
+        owner_user_id: &uuid::Uuid,
+        payload: &CreateLeadSqlPayload,
+    ) -> QueryResult<LeadOutcome> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        diesel::insert_into(leads::table)
-            .values(payload)
-            .get_result(conn)
+        conn.transaction(|conn| {
+            let contact = Contact::find_or_create_in(
+                conn,
+                &payload.user_id,
+                &payload.name,
+                &payload.phone,
+                &payload.email,
+            )?;
+
+            if let Some(lead) = Self::find_recent_duplicate_in(conn, &contact.id, payload)? {
+                return Ok(LeadOutcome::Duplicate(lead));
+            }
+
+            let lead: Lead = diesel::insert_into(leads::table)
+                .values((payload, leads::contact_id.eq(contact.id)))
+                .get_result(conn)?;
+
+            if payload.user_id != *owner_user_id {
+                LeadAssignment::record(
+                    conn,
+                    &lead.id,
+                    &Some(*owner_user_id),
+                    &payload.user_id,
+                    &None,
+                    LeadAssignmentReason::Routing,
+                )?;
+            }
+
+            Ok(LeadOutcome::Created(lead))
+        })
     }
 
-    pub fn find_many(
-        pool: &DbPool,
-        user_id_option: &Option<uuid::Uuid>,
-        role_option: &Option<crate::agents::AgentRole>,
-        query_params: &FindLeadQueryParam,
-    ) -> QueryResult<Vec<Lead>> {
+    // Leads from this phone number across all agents, for rate limiting
+    pub(super) fn count_recent_by_phone(pool: &DbPool, phone: &str) -> QueryResult<i64> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        let mut lead_query = match (user_id_option, role_option) {
-            (Some(user_id), Some(role)) => match role {
-                &AgentRole::Admin => leads::table.into_boxed(),
-                &AgentRole::Agent => leads::table
-                    .filter(leads::user_id.eq(user_id).and(leads::is_deleted.eq(false)))
-                    .into_boxed(),
-            },
-            _ => return Err(diesel::result::Error::NotFound),
+        leads::table
+            .filter(
+                leads::phone
+                    .eq(phone)
+                    .and(leads::created_at.gt(now - PHONE_RATE_WINDOW_HOURS.hours())),
+            )
+            .count()
+            .get_result(conn)
+    }
+
+    // Same contact asking about the same property again within the window,
+    // booking requests only for the same dates
+    fn find_recent_duplicate_in(
+        conn: &mut PgConnection,
+        contact_id: &i32,
+        payload: &CreateLeadSqlPayload,
+    ) -> QueryResult<Option<Lead>> {
+        leads::table
+            .filter(
+                leads::contact_id
+                    .eq(contact_id)
+                    .and(leads::property_id.eq(payload.property_id))
+                    .and(leads::is_deleted.eq(false))
+                    .and(leads::created_at.gt(now - DUPLICATE_WINDOW_HOURS.hours())),
+            )
+            .filter(leads::check_in.is_not_distinct_from(payload.check_in))
+            .filter(leads::check_out.is_not_distinct_from(payload.check_out))
+            .order_by(leads::created_at.desc())
+            .first(conn)
+            .optional()
+    }
+
+    // Scope and filters shared by the lead list, its count and the export
+    fn filtered_query<'a>(
+        scope: &Scope,
+        query_params: &FindLeadQueryParam,
+    ) -> leads::BoxedQuery<'a, Pg> {
+        let mut lead_query = match *scope {
+            Scope::All => leads::table.into_boxed(),
+            Scope::Branch(branch_id) => leads::table
+                .filter(
+                    leads::user_id
+                        .eq_any(branch_member_ids(branch_id))
+                        .and(leads::is_deleted.eq(false)),
+                )
+                .into_boxed(),
+            Scope::Own(user_id) => leads::table
+                .filter(leads::user_id.eq(user_id).and(leads::is_deleted.eq(false)))
+                .into_boxed(),
         };
 
+        if let Some(branch_id) = query_params.branch_id {
+            lead_query = lead_query.filter(leads::user_id.eq_any(branch_member_ids(branch_id)));
+        }
+
         if let Some(search) = &query_params.search {
+            let phone_search = phone_search_term(search);
             lead_query = lead_query.filter(
                 leads::name
                     .ilike(format!("%{}", search))
                     .or(leads::name.ilike(format!("%{}%", search)))
                     .or(leads::name.ilike(format!("{}%", search)))
-                    .or(leads::phone.ilike(format!("%{}", search)))
-                    .or(leads::phone.ilike(format!("%{}%", search)))
-                    .or(leads::phone.ilike(format!("{}%", search))),
+                    .or(leads::phone.ilike(format!("%{}", phone_search)))
+                    .or(leads::phone.ilike(format!("%{}%", phone_search)))
+                    .or(leads::phone.ilike(format!("{}%", phone_search))),
             )
         }
 
+        if let Some(contact_id) = query_params.contact_id {
+            lead_query = lead_query.filter(leads::contact_id.eq(contact_id));
+        }
+
+        if let Some(property_id) = query_params.property_id {
+            lead_query = lead_query.filter(leads::property_id.eq(property_id));
+        }
+
+        if let Some(from) = query_params.from {
+            lead_query = lead_query.filter(leads::created_at.ge(from.and_time(NaiveTime::MIN)));
+        }
+
+        if let Some(to) = query_params.to {
+            // inclusive of the whole `to` day
+            let until = to.and_time(NaiveTime::MIN) + chrono::Duration::days(1);
+            lead_query = lead_query.filter(leads::created_at.lt(until));
+        }
+
+        lead_query
+    }
+
+    pub fn find_many(
+        pool: &DbPool,
+        scope: &Scope,
+        query_params: &FindLeadQueryParam,
+    ) -> QueryResult<Vec<Lead>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut lead_query = Self::filtered_query(scope, query_params);
+
         if let Some(page) = query_params.page {
             lead_query = lead_query.offset((page - 1) * PAGE_SIZE).limit(PAGE_SIZE);
         }
@@ -83,34 +228,67 @@ impl Lead {
 
     pub fn count_find_many_rows(
         pool: &DbPool,
-        user_id_option: &Option<uuid::Uuid>,
-        role_option: &Option<crate::agents::AgentRole>,
+        scope: &Scope,
         query_params: &FindLeadQueryParam,
     ) -> QueryResult<i64> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        let mut lead_query = match (user_id_option, role_option) {
-            (Some(user_id), Some(role)) => match role {
-                &AgentRole::Admin => leads::table.into_boxed(),
-                &AgentRole::Agent => leads::table
-                    .filter(leads::user_id.eq(user_id).and(leads::is_deleted.eq(false)))
-                    .into_boxed(),
-            },
-            _ => return Err(diesel::result::Error::NotFound),
-        };
+        Self::filtered_query(scope, query_params)
+            .count()
+            .get_result(conn)
+    }
 
-        if let Some(search) = &query_params.search {
-            lead_query = lead_query.filter(
-                leads::name
-                    .ilike(format!("%{}", search))
-                    .or(leads::name.ilike(format!("%{}%", search)))
-                    .or(leads::name.ilike(format!("{}%", search)))
-                    .or(leads::phone.ilike(format!("%{}", search)))
-                    .or(leads::phone.ilike(format!("%{}%", search)))
-                    .or(leads::phone.ilike(format!("{}%", search))),
-            )
+    // Rows are fetched one by one from postgres and handed to `on_row`,
+    // so large exports never sit in memory as a whole
+    pub(super) fn for_each_export_row<F>(
+        pool: &DbPool,
+        scope: &Scope,
+        query_params: &FindLeadQueryParam,
+        mut on_row: F,
+    ) -> QueryResult<()>
+    where
+        F: FnMut(LeadExportRow) -> bool,
+    {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let lead_ids = Self::filtered_query(scope, query_params).select(leads::id);
+
+        let rows = leads::table
+            .left_join(properties::table)
+            .filter(leads::id.eq_any(lead_ids))
+            .order_by(leads::created_at.desc())
+            .select((
+                leads::id,
+                leads::created_at,
+                leads::name,
+                leads::phone,
+                leads::email,
+                leads::property_id,
+                properties::title.nullable(),
+                properties::site_path.nullable(),
+                properties::price.nullable(),
+            ))
+            .load_iter::<LeadExportRow, PgRowByRowLoadingMode>(conn)?;
+
+        for row in rows {
+            if !on_row(row?) {
+                break;
+            }
         }
 
-        lead_query.count().get_result(conn)
+        Ok(())
     }
 }
+
+#[derive(Serialize, Queryable)]
+pub struct LeadExportRow {
+    pub id: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub name: String,
+    pub phone: String,
+    pub email: Option<String>,
+    pub property_id: Option<i32>,
+    pub property_title: Option<String>,
+    pub property_site_path: Option<String>,
+    pub property_price: Option<i64>,
+}
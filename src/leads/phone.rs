@@ -0,0 +1,53 @@
+const INDONESIA_COUNTRY_CODE: &str = "62";
+
+// Normalises a phone number to E.164 (e.g. "+6281234567890").
+// Numbers without a country code are assumed to be Indonesian, so
+// "0812-3456-7890", "812 3456 7890", "62812..." and "+62 0812..." all
+// end up as the same number. Returns None when the input isn't a phone number.
+pub fn normalize_phone_number(phone: &str) -> Option<String> {
+    let phone = phone.trim();
+    if phone
+        .chars()
+        .any(|c| !c.is_ascii_digit() && !" -.()+".contains(c))
+    {
+        return None;
+    }
+
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    let international = if phone.starts_with('+') {
+        digits
+    } else if let Some(rest) = digits.strip_prefix("00") {
+        rest.to_string()
+    } else if digits.starts_with(INDONESIA_COUNTRY_CODE) {
+        digits
+    } else if let Some(rest) = digits.strip_prefix('0') {
+        format!("{INDONESIA_COUNTRY_CODE}{rest}")
+    } else if digits.starts_with('8') {
+        format!("{INDONESIA_COUNTRY_CODE}{digits}")
+    } else {
+        return None;
+    };
+
+    // "+62 0812..." keeps the trunk prefix, which isn't part of E.164
+    let international = match international.strip_prefix("620") {
+        Some(rest) => format!("{INDONESIA_COUNTRY_CODE}{rest}"),
+        None => international,
+    };
+
+    if international.starts_with('0') || !(8..=15).contains(&international.len()) {
+        return None;
+    }
+
+    Some(format!("+{international}"))
+}
+
+// Leads store E.164 numbers, so a search for "0812..." has to look for "+62812...".
+pub fn phone_search_term(search: &str) -> String {
+    let search = search.trim();
+    match search.strip_prefix('0') {
+        Some(rest) if search.chars().all(|c| c.is_ascii_digit()) => {
+            format!("+{INDONESIA_COUNTRY_CODE}{rest}")
+        }
+        _ => search.to_string(),
+    }
+}
@@ -0,0 +1,40 @@
+use axum::http::HeaderMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::rate_limit::RateLimiter;
+
+const IP_RATE_LIMIT: usize = 5;
+const IP_RATE_WINDOW: Duration = Duration::from_secs(10 * 60);
+pub(super) const PHONE_RATE_LIMIT: i64 = 3;
+pub(super) const PHONE_RATE_WINDOW_HOURS: i32 = 1;
+
+// Lead submissions per client ip
+pub(super) static IP_SUBMISSIONS: LazyLock<RateLimiter> =
+    LazyLock::new(|| RateLimiter::new(IP_RATE_LIMIT, IP_RATE_WINDOW));
+
+// The socket address, unless it's one of our reverse proxies. Each proxy appends the
+// address it got the request from, so the hops are read from the right and the first
+// one that isn't a trusted proxy is the client. Hops further left are client-supplied.
+pub fn client_ip(headers: &HeaderMap, addr: &SocketAddr, trusted_proxies: &[IpAddr]) -> String {
+    let mut ip = addr.ip();
+    if trusted_proxies.contains(&ip) {
+        let hops: Vec<&str> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+        for hop in hops.into_iter().rev() {
+            match hop.trim().parse::<IpAddr>() {
+                Ok(hop) => ip = hop,
+                Err(_) => break,
+            }
+            if !trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+    }
+    ip.to_string()
+}
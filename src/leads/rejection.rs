@@ -0,0 +1,45 @@
+use diesel::{ExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl};
+use serde::Serialize;
+
+use super::controller::{CreateLeadRejectionPayload, PAGE_SIZE};
+use super::rejection_reason::LeadRejectionReason;
+use crate::{db::DbPool, schema::lead_rejections};
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct LeadRejection {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    reason: LeadRejectionReason,
+    ip_address: String,
+    property_id: Option<i32>,
+    name: String,
+    phone: String,
+    email: Option<String>,
+}
+
+impl LeadRejection {
+    pub(super) fn create(pool: &DbPool, payload: &CreateLeadRejectionPayload) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(lead_rejections::table)
+            .values(payload)
+            .get_result(conn)
+    }
+
+    pub(super) fn find_many(pool: &DbPool, page: &Option<i64>) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let offset = (page.unwrap_or(1) - 1) * PAGE_SIZE;
+        lead_rejections::table
+            .order_by(lead_rejections::created_at.desc())
+            .offset(offset)
+            .limit(PAGE_SIZE)
+            .get_results(conn)
+    }
+
+    pub(super) fn count(pool: &DbPool) -> QueryResult<i64> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        lead_rejections::table.count().get_result(conn)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
+#[diesel(sql_type = sql_types::LeadRejectionReason)]
+pub enum LeadRejectionReason {
+    Honeypot,
+    IpRateLimited,
+    PhoneRateLimited,
+    CaptchaFailed,
+    Blocklisted,
+}
+
+impl ToSql<sql_types::LeadRejectionReason, Pg> for LeadRejectionReason {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            LeadRejectionReason::Honeypot => out.write_all(b"honeypot")?,
+            LeadRejectionReason::IpRateLimited => out.write_all(b"ip_rate_limited")?,
+            LeadRejectionReason::PhoneRateLimited => out.write_all(b"phone_rate_limited")?,
+            LeadRejectionReason::CaptchaFailed => out.write_all(b"captcha_failed")?,
+            LeadRejectionReason::Blocklisted => out.write_all(b"blocklisted")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::LeadRejectionReason, Pg> for LeadRejectionReason {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"honeypot" => Ok(LeadRejectionReason::Honeypot),
+            b"ip_rate_limited" => Ok(LeadRejectionReason::IpRateLimited),
+            b"phone_rate_limited" => Ok(LeadRejectionReason::PhoneRateLimited),
+            b"captcha_failed" => Ok(LeadRejectionReason::CaptchaFailed),
+            b"blocklisted" => Ok(LeadRejectionReason::Blocklisted),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use diesel::dsl::now;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, PgSortExpressionMethods, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::controller::CreateLeadRoutingRulePayload;
+use crate::{
+    db::DbPool,
+    schema::{agents, lead_routing_rules},
+};
+
+// An agent covering a regency. Leads whose owner can't take them are
+// spread round-robin over the active agents covering the listing's regency.
+#[derive(Debug, Serialize, Queryable)]
+pub struct LeadRoutingRule {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    regency: String,
+    user_id: uuid::Uuid,
+    last_assigned_at: Option<chrono::NaiveDateTime>,
+}
+
+impl LeadRoutingRule {
+    pub(super) fn find_many(pool: &DbPool) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        lead_routing_rules::table
+            .order_by((
+                lead_routing_rules::regency.asc(),
+                lead_routing_rules::id.asc(),
+            ))
+            .get_results(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        payload: &CreateLeadRoutingRulePayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(lead_routing_rules::table)
+            .values(payload)
+            .get_result(conn)
+    }
+
+    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(lead_routing_rules::table)
+            .filter(lead_routing_rules::id.eq(id))
+            .get_result(conn)
+    }
+
+    pub(super) fn route(
+        pool: &DbPool,
+        regency: &str,
+        excluded_user_id: &uuid::Uuid,
+    ) -> QueryResult<Option<uuid::Uuid>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| Self::next_agent(conn, regency, excluded_user_id))
+    }
+
+    // Picks the active agent covering the regency who received a routed lead the longest ago
+    pub(super) fn next_agent(
+        conn: &mut PgConnection,
+        regency: &str,
+        excluded_user_id: &uuid::Uuid,
+    ) -> QueryResult<Option<uuid::Uuid>> {
+        let rule = lead_routing_rules::table
+            .inner_join(agents::table)
+            .filter(
+                lead_routing_rules::regency
+                    .eq(regency.trim().to_lowercase())
+                    .and(lead_routing_rules::user_id.ne(excluded_user_id))
+                    .and(agents::is_active.eq(true)),
+            )
+            .order_by((
+                lead_routing_rules::last_assigned_at.asc().nulls_first(),
+                lead_routing_rules::id.asc(),
+            ))
+            .select((lead_routing_rules::id, lead_routing_rules::user_id))
+            .for_update()
+            .first::<(i32, uuid::Uuid)>(conn)
+            .optional()?;
+
+        match rule {
+            Some((rule_id, user_id)) => {
+                diesel::update(lead_routing_rules::table)
+                    .filter(lead_routing_rules::id.eq(rule_id))
+                    .set(lead_routing_rules::last_assigned_at.eq(now.nullable()))
+                    .execute(conn)?;
+                Ok(Some(user_id))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::env;
+use std::net::IpAddr;
+
+use super::captcha::Captcha;
+
+// Configuration of the public lead endpoints. Built once in main and shared as an
+// extension, so a missing or invalid value stops the server at startup.
+pub struct LeadSettings {
+    pub(super) api_key: String,
+    pub(super) captcha: Captcha,
+    // Reverse proxies whose X-Forwarded-For hops are trusted, from TRUSTED_PROXIES
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl LeadSettings {
+    pub fn from_env() -> Self {
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|ip| ip.trim())
+            .filter(|ip| !ip.is_empty())
+            .map(|ip| {
+                ip.parse()
+                    .unwrap_or_else(|_| panic!("Invalid TRUSTED_PROXIES address {}", ip))
+            })
+            .collect();
+
+        LeadSettings {
+            api_key: env::var("API_KEY_LEADS").expect("Missing API_KEY_LEADS"),
+            captcha: Captcha::from_env(),
+            trusted_proxies,
+        }
+    }
+}
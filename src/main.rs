@@ -1,17 +1,31 @@
 mod agents;
+mod appointments;
 mod banks;
+mod branches;
 mod db;
 mod developers;
+mod digest;
+mod exchange_rates;
+mod ical;
+mod kpr;
 mod leads;
 mod middleware;
+mod offers;
+mod outbox;
+mod projects;
 mod properties;
+mod rate_limit;
 mod schema;
+mod slug;
+mod uploads;
 
 use crate::db::build_db_pool;
 use axum::http::HeaderValue;
-use axum::{middleware::from_fn, Router};
+use axum::{middleware::from_fn_with_state, Extension, Router};
 use sentry_tower::{NewSentryLayer, SentryHttpLayer};
 use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::Any;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
@@ -21,6 +35,8 @@ async fn main() {
     dotenvy::dotenv().ok();
 
     let pool = build_db_pool();
+    let lead_settings = Arc::new(leads::LeadSettings::from_env());
+    let visitor_hasher = Arc::new(properties::VisitorHasher::from_env());
     let origins = [
         "https://primeproindonesia.com"
             .parse::<HeaderValue>()
@@ -41,7 +57,7 @@ async fn main() {
         _ => CorsLayer::permissive(),
     };
 
-    let tracing_filter = tracing_subscriber::EnvFilter::new("tower_http::trace::make_span=debug,tower_http::trace::on_response=debug,tower_http::trace::on_request=debug");
+    let tracing_filter = tracing_subscriber::EnvFilter::new("tower_http::trace::make_span=debug,tower_http::trace::on_response=debug,tower_http::trace::on_request=debug,inception_axum=info");
     tracing_subscriber::fmt()
         .with_env_filter(tracing_filter)
         .init();
@@ -56,15 +72,34 @@ async fn main() {
         },
     ));
 
+    tokio::spawn(agents::refresh_stats_periodically(pool.clone()));
+    tokio::spawn(uploads::collect_garbage_periodically(pool.clone()));
+    tokio::spawn(exchange_rates::import_periodically(pool.clone()));
+    tokio::spawn(properties::sync_calendars_periodically(pool.clone()));
+    tokio::spawn(outbox::dispatch_periodically(pool.clone()));
+
     // build our application with a route
     let app = Router::new()
-        .nest("/agents", agents::agent_routes(pool.clone()))
+        .nest("/agents", agents::agent_routes())
+        .nest("/appointments", appointments::appointment_routes())
         .nest("/banks", banks::banks_routes(pool.clone()))
+        .nest("/branches", branches::branches_routes(pool.clone()))
         .nest("/developers", developers::developers_routes(pool.clone()))
+        .nest("/exchange-rates", exchange_rates::exchange_rate_routes())
+        .nest("/kpr", kpr::kpr_routes())
         .nest("/leads", leads::lead_routes())
+        .nest("/offers", offers::offer_routes())
+        .nest("/projects", projects::projects_routes())
         .nest("/properties", properties::property_routes())
+        .nest("/s", properties::share_link_routes())
+        .nest("/uploads", uploads::upload_routes())
+        .layer(from_fn_with_state(
+            pool.clone(),
+            middleware::Session::middleware,
+        ))
+        .layer(Extension(lead_settings))
+        .layer(Extension(visitor_hasher))
         .with_state(pool)
-        .layer(from_fn(middleware::Session::middleware))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         .layer(NewSentryLayer::new_from_top())
@@ -80,7 +115,12 @@ async fn main() {
         }
     };
     println!("Server started at {}", host_addr);
-    match axum::serve(listener, app).await {
+    match axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    {
         Ok(_) => {}
         Err(err) => println!("Server failed to start: {}", err),
     }
@@ -1,6 +1,7 @@
 use super::{axum_response::AxumResponse, JsonResponse};
+use crate::{agents::Agent, db::DbPool};
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::{HeaderMap, HeaderValue, Method},
     middleware::Next,
     response::Response,
@@ -16,6 +17,7 @@ struct UserDataInJwt {
 #[allow(non_snake_case)]
 #[derive(Deserialize, Debug)]
 struct VerifySessionData {
+    userId: String,
     userDataInJWT: UserDataInJwt,
 }
 
@@ -25,6 +27,20 @@ struct VerifySessionResponse {
     session: VerifySessionData,
 }
 
+#[derive(Deserialize, Debug)]
+struct FindUserResponse {
+    status: String,
+    user: Option<SupertokensUser>,
+}
+
+// A SuperTokens account, which might not belong to an agent yet
+#[derive(Deserialize, Debug)]
+pub struct SupertokensUser {
+    pub id: String,
+    #[serde(default)]
+    pub emails: Vec<String>,
+}
+
 pub struct Session;
 
 impl Session {
@@ -56,6 +72,38 @@ impl Session {
             .await
     }
 
+    async fn find_user(user_id: &str) -> Result<FindUserResponse, reqwest::Error> {
+        let connection_uri =
+            env::var("SUPERTOKENS_CONNECTION_URI").expect("Missing SUPERTOKENS_CONNECTION_URI");
+        let supertokens_api_key =
+            env::var("SUPERTOKENS_API_KEY").expect("Missing SUPERTOKENS_API_KEY");
+        let url = format!("{}{}", connection_uri, "/user/id");
+
+        reqwest::Client::new()
+            .get(url)
+            .header("Authorization", &supertokens_api_key)
+            .query(&[("userId", user_id)])
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    // The account behind the request's access token, for endpoints that are
+    // used before the account has an agent
+    pub async fn find_supertokens_user(header_map: &HeaderMap) -> Option<SupertokensUser> {
+        let access_token = header_map.get("x-access-token")?.to_str().ok()?;
+        let session = match Self::verify_session(access_token).await {
+            Ok(session) if session.status == "OK" => session.session,
+            _ => return None,
+        };
+
+        match Self::find_user(&session.userId).await {
+            Ok(response) if response.status == "OK" => response.user,
+            _ => None,
+        }
+    }
+
     pub fn extract_session_user_id(header_map: &HeaderMap) -> uuid::Uuid {
         let user_id = header_map
             .get("x-user-id")
@@ -66,6 +114,7 @@ impl Session {
     }
 
     async fn check_session(
+        pool: &DbPool,
         req: Request,
         next: Next,
     ) -> Result<axum::http::Response<axum::body::Body>, AxumResponse<String>> {
@@ -81,6 +130,25 @@ impl Session {
 
         match session_verification {
             Ok(session) if session.status == "OK" => {
+                // a deactivated agent's existing sessions stop working too, and a session
+                // whose agent can't be looked up gets no further
+                let agent = uuid::Uuid::parse_str(&session.session.userDataInJWT.id)
+                    .ok()
+                    .map(|agent_id| Agent::find_by_user_id(pool, &agent_id));
+                match agent {
+                    Some(Ok(agent)) if agent.is_active => {}
+                    Some(Ok(_)) => {
+                        let response =
+                            JsonResponse::send(403, None, Some("Agent is deactivated".to_string()));
+                        return Err(response);
+                    }
+                    Some(Err(diesel::result::Error::NotFound)) | None => {
+                        return Err(JsonResponse::send(403, None, None));
+                    }
+                    Some(Err(err)) => {
+                        return Err(JsonResponse::send(500, None, Some(err.to_string())));
+                    }
+                }
                 let mut new_req = req;
                 let x_user_id = HeaderValue::from_str(&session.session.userDataInJWT.id)
                     .expect("Fail to convert x-user-id");
@@ -94,27 +162,59 @@ impl Session {
         }
     }
 
-    pub async fn middleware(req: Request, next: Next) -> Result<Response, AxumResponse<String>> {
+    pub async fn middleware(
+        State(pool): State<DbPool>,
+        mut req: Request,
+        next: Next,
+    ) -> Result<Response, AxumResponse<String>> {
+        // only a verified session sets the agent, public routes never see a client's own
+        req.headers_mut().remove("x-user-id");
         let method = req.method();
         let path = req.uri().path();
 
-        match method {
-            &Method::GET => {
-                if path == "/agents" || path == "/leads" {
-                    return Self::check_session(req, next).await;
+        match *method {
+            Method::GET => {
+                if path == "/agents"
+                    || path == "/leads"
+                    || path.starts_with("/leads/")
+                    || path == "/appointments"
+                    || path == "/offers"
+                    || path.starts_with("/offers/")
+                    || (path.starts_with("/appointments/") && path != "/appointments/availability")
+                    || path == "/branches/report"
+                    || path == "/agents/leaderboard"
+                    || path == "/agents/invites"
+                    || path.starts_with("/uploads/")
+                    || (path.starts_with("/agents/") && path.ends_with("/stats"))
+                    || (path.starts_with("/properties/") && path.ends_with("/share-links"))
+                    || (path.starts_with("/properties/") && path.ends_with("/events"))
+                    || (path.starts_with("/properties/") && path.ends_with("/availability/ranges"))
+                    || (path.starts_with("/properties/") && path.ends_with("/calendar-feeds"))
+                    || (path.starts_with("/banks/") && path.ends_with("/history"))
+                {
+                    return Self::check_session(&pool, req, next).await;
                 }
-                if path == "/properties" {
+                if path == "/properties" || path == "/banks" || path == "/developers" {
                     let authorization_header = req.headers().get("x-access-token");
                     match authorization_header {
-                        Some(_) => return Self::check_session(req, next).await,
+                        Some(_) => return Self::check_session(&pool, req, next).await,
                         None => return Ok(next.run(req).await),
                     }
                 }
 
                 Ok(next.run(req).await)
             }
-            &Method::POST if path == "/leads" => return Ok(next.run(req).await),
-            _ => Self::check_session(req, next).await,
+            Method::POST
+                if path == "/leads"
+                    || path == "/leads/booking-requests"
+                    || path == "/appointments/requests"
+                    || path == "/agents/accept-invite"
+                    || path == "/kpr/simulate"
+                    || (path.starts_with("/properties/") && path.ends_with("/events")) =>
+            {
+                Ok(next.run(req).await)
+            }
+            _ => Self::check_session(&pool, req, next).await,
         }
     }
 }
@@ -0,0 +1,369 @@
+use axum::extract::{Json, Path, Query, State};
+use axum::http::HeaderMap;
+use axum::routing::{get, post, put};
+use axum::Router;
+use chrono::NaiveDateTime;
+use diesel::prelude::Insertable;
+use diesel::result::DatabaseErrorKind;
+use serde::{Deserialize, Serialize};
+
+use super::model::{Offer, OfferError, OfferWithRelation};
+use super::offer_status::OfferStatus;
+use super::payment_method::PaymentMethod;
+use crate::agents::{can_on, scope, Action, Agent, Resource};
+use crate::db::DbPool;
+use crate::leads::Lead;
+use crate::middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session};
+use crate::properties::{Property, ShareLink, SoldChannel};
+use crate::schema;
+
+pub(super) const PAGE_SIZE: i64 = 20;
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = schema::offers)]
+pub(crate) struct OfferPayload {
+    amount: i64,
+    down_payment: Option<i64>,
+    payment_method: PaymentMethod,
+    // The bank financing a KPR
+    bank_id: Option<i32>,
+    terms: Option<String>,
+    expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOfferPayload {
+    lead_id: i32,
+    #[serde(flatten)]
+    offer: OfferPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptOfferPayload {
+    // Closes the sale on the listing as well
+    mark_sold: Option<bool>,
+    sold_channel: Option<SoldChannel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FindOfferQuery {
+    pub page: Option<i64>,
+    pub property_id: Option<i32>,
+    pub lead_id: Option<i32>,
+    pub status: Option<OfferStatus>,
+    // Negotiations waiting for an answer
+    pub open: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OfferWithChain {
+    #[serde(flatten)]
+    offer: Offer,
+    is_expired: bool,
+    // Every offer of the negotiation, the first one first
+    chain: Vec<Offer>,
+}
+
+impl OfferPayload {
+    fn validate(&self) -> Result<(), String> {
+        if self.amount <= 0 {
+            return Err("Amount must be more than 0".to_string());
+        }
+        if let Some(down_payment) = self.down_payment {
+            if down_payment < 0 || down_payment > self.amount {
+                return Err("Down payment must be between 0 and the amount".to_string());
+            }
+        }
+        match (self.payment_method, self.bank_id) {
+            (PaymentMethod::Kpr, None) => {
+                return Err("A KPR offer needs the bank_id of the lender".to_string())
+            }
+            (PaymentMethod::Cash, Some(_)) => return Err("A cash offer has no bank".to_string()),
+            _ => {}
+        }
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Local::now().naive_local())
+        {
+            return Err("expires_at must be in the future".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn offer_error_response(err: OfferError) -> AxumResponse<Offer> {
+    match err {
+        OfferError::Conflict(offer) => JsonResponse::send(
+            409,
+            Some(*offer),
+            Some("The lead has an open offer on the property, counter it instead".to_string()),
+        ),
+        OfferError::Closed(message) => JsonResponse::send(400, None, Some(message)),
+        OfferError::Query(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Offer not found".to_string()))
+        }
+        OfferError::Query(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        )) => JsonResponse::send(400, None, Some("Bank not found".to_string())),
+        OfferError::Query(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            _,
+        )) => JsonResponse::send(
+            409,
+            None,
+            Some("The lead has an open offer on the property, counter it instead".to_string()),
+        ),
+        OfferError::Query(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// The session's agent, when they may do `action` on the lead's records
+fn authorize_lead<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    lead_id: &i32,
+    action: Action,
+) -> Result<(Agent, Lead), AxumResponse<T>> {
+    let user_id = Session::extract_session_user_id(headers);
+    let agent = match Agent::find_by_user_id(pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return Err(JsonResponse::send(403, None, None)),
+    };
+
+    let lead = match Lead::find_by_id(pool, lead_id) {
+        Ok(lead) => lead,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Lead not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    let is_allowed = match Agent::find_by_user_id(pool, &lead.user_id) {
+        Ok(owner) => can_on(&agent, action, Resource::Lead, &owner),
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    if !is_allowed {
+        return Err(JsonResponse::send(403, None, None));
+    }
+
+    Ok((agent, lead))
+}
+
+// The offer when the session's agent may do `action` on its lead
+fn find_offer_for<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    id: &i32,
+    action: Action,
+) -> Result<(Agent, Offer), AxumResponse<T>> {
+    let offer = match Offer::find_by_id(pool, id) {
+        Ok(offer) => offer,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Offer not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    let (agent, _) = authorize_lead(pool, headers, &offer.lead_id, action)?;
+
+    Ok((agent, offer))
+}
+
+async fn find_many_offers(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<FindOfferQuery>,
+) -> AxumResponse<JsonFindResponse<Vec<OfferWithRelation>>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Lead) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
+        _ => return JsonResponse::send(403, None, None),
+    };
+
+    let offers = match Offer::find_many(&pool, &scope, &query) {
+        Ok(offers) => offers,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let offers_count = match Offer::count_find_many_rows(&pool, &scope, &query) {
+        Ok(count) => count,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let body = JsonFindResponse {
+        data: offers,
+        total_pages: (offers_count / PAGE_SIZE) + 1,
+        total_data: offers_count,
+    };
+    JsonResponse::send(200, Some(body), None)
+}
+
+// A buyer's offer on the listing the lead asked about
+async fn create_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateOfferPayload>,
+) -> AxumResponse<Offer> {
+    let (agent, lead) = match authorize_lead(&pool, &headers, &payload.lead_id, Action::Update) {
+        Ok(authorized) => authorized,
+        Err(response) => return response,
+    };
+    if let Err(message) = payload.offer.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    let property = match lead.property_id {
+        Some(property_id) => match Property::find_one_by_id(&pool, &property_id) {
+            Ok(property) => property.0,
+            Err(diesel::result::Error::NotFound) => {
+                return JsonResponse::send(404, None, Some("Property not found".to_string()))
+            }
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        },
+        None => {
+            return JsonResponse::send(400, None, Some("Lead isn't about a property".to_string()))
+        }
+    };
+    if let Err(message) = property.validate_offer() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match Offer::create(&pool, &lead.id, &property, &agent.id, &payload.offer) {
+        Ok(offer) => JsonResponse::send(201, Some(offer), None),
+        Err(err) => offer_error_response(err),
+    }
+}
+
+async fn find_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<OfferWithChain> {
+    let (_, offer) = match find_offer_for(&pool, &headers, &id, Action::View) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    match Offer::find_chain(&pool, &offer) {
+        Ok(chain) => JsonResponse::send(
+            200,
+            Some(OfferWithChain {
+                is_expired: offer.is_expired(),
+                offer,
+                chain,
+            }),
+            None,
+        ),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+async fn counter_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<OfferPayload>,
+) -> AxumResponse<Offer> {
+    let (agent, _) = match find_offer_for(&pool, &headers, &id, Action::Update) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match Offer::counter(&pool, &id, &agent.id, &payload) {
+        Ok(offer) => JsonResponse::send(201, Some(offer), None),
+        Err(err) => offer_error_response(err),
+    }
+}
+
+async fn accept_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<AcceptOfferPayload>,
+) -> AxumResponse<Offer> {
+    let (agent, offer) = match find_offer_for(&pool, &headers, &id, Action::Update) {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    // marking the listing sold is an update of the listing itself
+    let sold_channel = if payload.mark_sold.unwrap_or(false) {
+        let property = match Property::find_one_by_id(&pool, &offer.property_id) {
+            Ok(property) => property,
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        };
+        if !can_on(&agent, Action::Update, Resource::Property, &property.1) {
+            return JsonResponse::send(403, None, None);
+        }
+        // a sale without a channel goes to the share link behind the latest tracked lead
+        match payload.sold_channel {
+            Some(channel) => Some(Some(channel)),
+            None => match ShareLink::attributed_channel(&pool, &offer.property_id) {
+                Ok(channel) => Some(channel),
+                Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+            },
+        }
+    } else {
+        None
+    };
+
+    match Offer::close(&pool, &id, OfferStatus::Accepted, sold_channel) {
+        Ok(offer) => JsonResponse::send(200, Some(offer), None),
+        Err(err) => offer_error_response(err),
+    }
+}
+
+async fn reject_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Offer> {
+    if let Err(response) = find_offer_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    match Offer::close(&pool, &id, OfferStatus::Rejected, None) {
+        Ok(offer) => JsonResponse::send(200, Some(offer), None),
+        Err(err) => offer_error_response(err),
+    }
+}
+
+// Taken back by the side that made it
+async fn withdraw_offer(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<Offer> {
+    if let Err(response) = find_offer_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    match Offer::close(&pool, &id, OfferStatus::Withdrawn, None) {
+        Ok(offer) => JsonResponse::send(200, Some(offer), None),
+        Err(err) => offer_error_response(err),
+    }
+}
+
+pub fn offer_routes() -> Router<DbPool> {
+    Router::new()
+        .route("/", get(find_many_offers))
+        .route("/", post(create_offer))
+        .route("/{id}", get(find_offer))
+        .route("/{id}/counter", post(counter_offer))
+        .route("/{id}/accept", put(accept_offer))
+        .route("/{id}/reject", put(reject_offer))
+        .route("/{id}/withdraw", put(withdraw_offer))
+}
@@ -0,0 +1,7 @@
+mod controller;
+mod model;
+mod offer_party;
+mod offer_status;
+mod payment_method;
+
+pub use controller::offer_routes;
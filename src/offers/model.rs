@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use diesel::dsl::now;
+use diesel::pg::Pg;
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::controller::{FindOfferQuery, OfferPayload, PAGE_SIZE};
+use super::offer_party::OfferParty;
+use super::offer_status::OfferStatus;
+use super::payment_method::PaymentMethod;
+use crate::agents::{branch_member_ids, Scope};
+use crate::db::DbPool;
+use crate::properties::{Currency, Property, SoldChannel};
+use crate::schema::{leads, offers, properties};
+
+// An offer on a listing, or a counter-offer to the one in `parent_id`
+#[derive(Debug, Serialize, Queryable)]
+pub struct Offer {
+    pub id: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    pub property_id: i32,
+    pub lead_id: i32,
+    pub parent_id: Option<i32>,
+    pub party: OfferParty,
+    amount: i64,
+    currency: Currency,
+    down_payment: Option<i64>,
+    payment_method: PaymentMethod,
+    bank_id: Option<i32>,
+    terms: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub status: OfferStatus,
+    responded_at: Option<NaiveDateTime>,
+    recorded_by: Option<uuid::Uuid>,
+}
+
+// An offer with the listing and lead it's about, for the negotiation lists
+#[derive(Debug, Serialize, Queryable)]
+pub struct OfferWithRelation {
+    #[serde(flatten)]
+    offer: Offer,
+    property_title: String,
+    lead_name: String,
+    // The agent handling the lead
+    user_id: uuid::Uuid,
+}
+
+pub(super) enum OfferError {
+    Query(diesel::result::Error),
+    // The open offer that has to be answered first
+    Conflict(Box<Offer>),
+    // The offer was already answered or expired
+    Closed(String),
+}
+
+impl From<diesel::result::Error> for OfferError {
+    fn from(err: diesel::result::Error) -> Self {
+        OfferError::Query(err)
+    }
+}
+
+impl Offer {
+    pub(super) fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= chrono::Local::now().naive_local())
+    }
+
+    // Only the latest offer of a negotiation is open, the earlier ones were countered
+    fn lock_open(conn: &mut PgConnection, id: &i32) -> Result<Self, OfferError> {
+        let offer: Self = offers::table.find(id).for_update().get_result(conn)?;
+        if offer.status != OfferStatus::Submitted {
+            return Err(OfferError::Closed(
+                "The offer was already answered".to_string(),
+            ));
+        }
+        Ok(offer)
+    }
+
+    fn find_open(
+        conn: &mut PgConnection,
+        lead_id: &i32,
+        property_id: &i32,
+    ) -> QueryResult<Option<Self>> {
+        offers::table
+            .filter(offers::lead_id.eq(lead_id))
+            .filter(offers::property_id.eq(property_id))
+            .filter(offers::status.eq(OfferStatus::Submitted))
+            .first(conn)
+            .optional()
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        offers::table.find(id).get_result(conn)
+    }
+
+    // The whole negotiation the offer is part of, from the first offer to the latest counter
+    pub(super) fn find_chain(pool: &DbPool, offer: &Self) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let offers = offers::table
+            .filter(offers::lead_id.eq(offer.lead_id))
+            .filter(offers::property_id.eq(offer.property_id))
+            .get_results::<Self>(conn)?;
+        Ok(Self::chain_of(offers, offer.id))
+    }
+
+    // Follows the parents of `id` back to the first offer and the counters forward from it.
+    // Earlier negotiations of the lead on the listing aren't linked and stay out.
+    fn chain_of(offers: Vec<Self>, id: i32) -> Vec<Self> {
+        let mut offers: HashMap<i32, Self> =
+            offers.into_iter().map(|offer| (offer.id, offer)).collect();
+        let counters: HashMap<i32, i32> = offers
+            .values()
+            .filter_map(|offer| offer.parent_id.map(|parent_id| (parent_id, offer.id)))
+            .collect();
+
+        let mut first_id = id;
+        while let Some(parent_id) = offers
+            .get(&first_id)
+            .and_then(|offer| offer.parent_id)
+            .filter(|parent_id| offers.contains_key(parent_id))
+        {
+            first_id = parent_id;
+        }
+
+        let mut chain = vec![];
+        let mut next_id = Some(first_id);
+        while let Some(id) = next_id {
+            match offers.remove(&id) {
+                Some(offer) => chain.push(offer),
+                None => break,
+            }
+            next_id = counters.get(&id).copied();
+        }
+        chain
+    }
+
+    fn filtered_query<'a>(scope: &Scope, query: &FindOfferQuery) -> offers::BoxedQuery<'a, Pg> {
+        let mut offer_query = match *scope {
+            Scope::All => offers::table.into_boxed(),
+            Scope::Branch(branch_id) => offers::table
+                .filter(
+                    offers::lead_id.eq_any(
+                        leads::table
+                            .filter(leads::user_id.eq_any(branch_member_ids(branch_id)))
+                            .select(leads::id),
+                    ),
+                )
+                .into_boxed(),
+            Scope::Own(user_id) => offers::table
+                .filter(
+                    offers::lead_id.eq_any(
+                        leads::table
+                            .filter(leads::user_id.eq(user_id))
+                            .select(leads::id),
+                    ),
+                )
+                .into_boxed(),
+        };
+
+        if let Some(property_id) = query.property_id {
+            offer_query = offer_query.filter(offers::property_id.eq(property_id));
+        }
+        if let Some(lead_id) = query.lead_id {
+            offer_query = offer_query.filter(offers::lead_id.eq(lead_id));
+        }
+        if let Some(status) = query.status {
+            offer_query = offer_query.filter(offers::status.eq(status));
+        }
+        // the latest offer of each negotiation still waiting for an answer
+        if query.open.unwrap_or(false) {
+            offer_query = offer_query
+                .filter(offers::status.eq(OfferStatus::Submitted))
+                .filter(
+                    offers::expires_at
+                        .is_null()
+                        .or(offers::expires_at.gt(now.nullable())),
+                );
+        }
+
+        offer_query
+    }
+
+    pub(super) fn find_many(
+        pool: &DbPool,
+        scope: &Scope,
+        query: &FindOfferQuery,
+    ) -> QueryResult<Vec<OfferWithRelation>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let page = query.page.unwrap_or(1).max(1);
+        let offer_ids = Self::filtered_query(scope, query).select(offers::id);
+
+        offers::table
+            .inner_join(leads::table)
+            .inner_join(properties::table)
+            .filter(offers::id.eq_any(offer_ids))
+            .order_by((offers::updated_at.desc(), offers::id.desc()))
+            .offset((page - 1) * PAGE_SIZE)
+            .limit(PAGE_SIZE)
+            .select((
+                offers::all_columns,
+                properties::title,
+                leads::name,
+                leads::user_id,
+            ))
+            .get_results(conn)
+    }
+
+    pub(super) fn count_find_many_rows(
+        pool: &DbPool,
+        scope: &Scope,
+        query: &FindOfferQuery,
+    ) -> QueryResult<i64> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        Self::filtered_query(scope, query).count().get_result(conn)
+    }
+
+    // A buyer's first offer, a lead negotiates one offer at a time on a listing
+    pub(super) fn create(
+        pool: &DbPool,
+        lead_id: &i32,
+        property: &Property,
+        recorded_by: &uuid::Uuid,
+        payload: &OfferPayload,
+    ) -> Result<Self, OfferError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let created = conn.transaction(|conn| {
+            if let Some(open) = Self::find_open(conn, lead_id, &property.id)? {
+                return Err(OfferError::Conflict(Box::new(open)));
+            }
+
+            let offer = diesel::insert_into(offers::table)
+                .values((
+                    payload,
+                    offers::lead_id.eq(lead_id),
+                    offers::property_id.eq(property.id),
+                    offers::party.eq(OfferParty::Buyer),
+                    offers::currency.eq(property.currency),
+                    offers::recorded_by.eq(recorded_by),
+                ))
+                .get_result(conn)?;
+            Ok(offer)
+        });
+
+        match created {
+            // another offer was opened between the check and the insert
+            Err(OfferError::Query(
+                err @ Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _),
+            )) => match Self::find_open(conn, lead_id, &property.id)? {
+                Some(open) => Err(OfferError::Conflict(Box::new(open))),
+                None => Err(OfferError::Query(err)),
+            },
+            created => created,
+        }
+    }
+
+    // The other side answers with its own terms, the countered offer closes
+    pub(super) fn counter(
+        pool: &DbPool,
+        id: &i32,
+        recorded_by: &uuid::Uuid,
+        payload: &OfferPayload,
+    ) -> Result<Self, OfferError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let countered = Self::lock_open(conn, id)?;
+            if countered.is_expired() {
+                return Err(OfferError::Closed("The offer has expired".to_string()));
+            }
+
+            diesel::update(offers::table.find(id))
+                .set((
+                    offers::status.eq(OfferStatus::Countered),
+                    offers::responded_at.eq(now.nullable()),
+                ))
+                .execute(conn)?;
+
+            let offer = diesel::insert_into(offers::table)
+                .values((
+                    payload,
+                    offers::lead_id.eq(countered.lead_id),
+                    offers::property_id.eq(countered.property_id),
+                    offers::parent_id.eq(countered.id),
+                    offers::party.eq(countered.party.other()),
+                    offers::currency.eq(countered.currency),
+                    offers::recorded_by.eq(recorded_by),
+                ))
+                .get_result(conn)?;
+            Ok(offer)
+        })
+    }
+
+    // Accepted, rejected or withdrawn. With `sold_channel` an accepted offer also
+    // marks the listing sold and rejects the other open offers on it
+    pub(super) fn close(
+        pool: &DbPool,
+        id: &i32,
+        status: OfferStatus,
+        sold_channel: Option<Option<SoldChannel>>,
+    ) -> Result<Self, OfferError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let offer = Self::lock_open(conn, id)?;
+            if status == OfferStatus::Accepted && offer.is_expired() {
+                return Err(OfferError::Closed("The offer has expired".to_string()));
+            }
+            // checked again under the lock, another accepted offer may have sold it meanwhile
+            if status == OfferStatus::Accepted && sold_channel.is_some() {
+                Property::lock_in(conn, &offer.property_id)?
+                    .validate_offer()
+                    .map_err(OfferError::Closed)?;
+            }
+
+            let offer: Self = diesel::update(offers::table.find(id))
+                .set((
+                    offers::status.eq(status),
+                    offers::responded_at.eq(now.nullable()),
+                ))
+                .get_result(conn)?;
+
+            if let (OfferStatus::Accepted, Some(sold_channel)) = (status, sold_channel) {
+                Property::mark_sold_in(conn, &offer.property_id, sold_channel)?;
+                diesel::update(offers::table)
+                    .filter(offers::property_id.eq(offer.property_id))
+                    .filter(offers::status.eq(OfferStatus::Submitted))
+                    .set((
+                        offers::status.eq(OfferStatus::Rejected),
+                        offers::responded_at.eq(now.nullable()),
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(offer)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(id: i32, parent_id: Option<i32>, party: OfferParty, status: OfferStatus) -> Offer {
+        Offer {
+            id,
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            property_id: 1,
+            lead_id: 1,
+            parent_id,
+            party,
+            amount: 1_000_000_000,
+            currency: Currency::Idr,
+            down_payment: None,
+            payment_method: PaymentMethod::Cash,
+            bank_id: None,
+            terms: None,
+            expires_at: None,
+            status,
+            responded_at: None,
+            recorded_by: None,
+        }
+    }
+
+    // An earlier rejected negotiation, then an offer countered twice
+    fn negotiations() -> Vec<Offer> {
+        vec![
+            offer(1, None, OfferParty::Buyer, OfferStatus::Rejected),
+            offer(4, Some(3), OfferParty::Buyer, OfferStatus::Countered),
+            offer(2, None, OfferParty::Buyer, OfferStatus::Countered),
+            offer(5, Some(4), OfferParty::Seller, OfferStatus::Submitted),
+            offer(3, Some(2), OfferParty::Seller, OfferStatus::Countered),
+        ]
+    }
+
+    fn ids(chain: &[Offer]) -> Vec<i32> {
+        chain.iter().map(|offer| offer.id).collect()
+    }
+
+    #[test]
+    fn counters_come_from_the_other_party() {
+        assert_eq!(OfferParty::Buyer.other(), OfferParty::Seller);
+        assert_eq!(OfferParty::Seller.other(), OfferParty::Buyer);
+
+        let chain = Offer::chain_of(negotiations(), 5);
+        for (countered, counter) in chain.iter().zip(chain.iter().skip(1)) {
+            assert_eq!(counter.parent_id, Some(countered.id));
+            assert_eq!(counter.party, countered.party.other());
+        }
+    }
+
+    #[test]
+    fn finds_the_whole_chain_from_any_offer_of_it() {
+        for id in [2, 3, 4, 5] {
+            assert_eq!(ids(&Offer::chain_of(negotiations(), id)), vec![2, 3, 4, 5]);
+        }
+    }
+
+    #[test]
+    fn leaves_earlier_negotiations_out() {
+        assert_eq!(ids(&Offer::chain_of(negotiations(), 1)), vec![1]);
+    }
+
+    #[test]
+    fn stops_at_offers_it_did_not_load() {
+        let offers = vec![
+            offer(7, Some(6), OfferParty::Seller, OfferStatus::Countered),
+            offer(8, Some(7), OfferParty::Buyer, OfferStatus::Submitted),
+        ];
+        assert_eq!(ids(&Offer::chain_of(offers, 8)), vec![7, 8]);
+        assert!(Offer::chain_of(vec![], 8).is_empty());
+    }
+}
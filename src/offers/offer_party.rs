@@ -0,0 +1,44 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::OfferParty)]
+pub enum OfferParty {
+    Buyer,
+    Seller,
+}
+
+impl OfferParty {
+    // The side answering an offer of this one
+    pub fn other(self) -> Self {
+        match self {
+            OfferParty::Buyer => OfferParty::Seller,
+            OfferParty::Seller => OfferParty::Buyer,
+        }
+    }
+}
+
+impl ToSql<sql_types::OfferParty, Pg> for OfferParty {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            OfferParty::Buyer => out.write_all(b"buyer")?,
+            OfferParty::Seller => out.write_all(b"seller")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::OfferParty, Pg> for OfferParty {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"buyer" => Ok(OfferParty::Buyer),
+            b"seller" => Ok(OfferParty::Seller),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
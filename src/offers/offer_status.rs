@@ -0,0 +1,43 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::OfferStatus)]
+pub enum OfferStatus {
+    Submitted,
+    Countered,
+    Accepted,
+    Rejected,
+    Withdrawn,
+}
+
+impl ToSql<sql_types::OfferStatus, Pg> for OfferStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            OfferStatus::Submitted => out.write_all(b"submitted")?,
+            OfferStatus::Countered => out.write_all(b"countered")?,
+            OfferStatus::Accepted => out.write_all(b"accepted")?,
+            OfferStatus::Rejected => out.write_all(b"rejected")?,
+            OfferStatus::Withdrawn => out.write_all(b"withdrawn")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::OfferStatus, Pg> for OfferStatus {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"submitted" => Ok(OfferStatus::Submitted),
+            b"countered" => Ok(OfferStatus::Countered),
+            b"accepted" => Ok(OfferStatus::Accepted),
+            b"rejected" => Ok(OfferStatus::Rejected),
+            b"withdrawn" => Ok(OfferStatus::Withdrawn),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
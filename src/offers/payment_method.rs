@@ -0,0 +1,34 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::PaymentMethod)]
+pub enum PaymentMethod {
+    Cash,
+    Kpr,
+}
+
+impl ToSql<sql_types::PaymentMethod, Pg> for PaymentMethod {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            PaymentMethod::Cash => out.write_all(b"cash")?,
+            PaymentMethod::Kpr => out.write_all(b"kpr")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::PaymentMethod, Pg> for PaymentMethod {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"cash" => Ok(PaymentMethod::Cash),
+            b"kpr" => Ok(PaymentMethod::Kpr),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
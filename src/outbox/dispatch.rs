@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::env;
+
+use super::model::OutboxMessage;
+use super::outbox_channel::OutboxChannel;
+use crate::db::DbPool;
+
+const DEFAULT_DISPATCH_SECONDS: u64 = 60;
+const BATCH_SIZE: usize = 50;
+const DELIVERY_TIMEOUT_SECONDS: u64 = 30;
+
+// What the gateway at OUTBOX_WEBHOOK_URL gets for every message, it does the actual
+// sending by email or WhatsApp
+#[derive(Debug, Serialize)]
+struct Delivery<'a> {
+    id: i32,
+    channel: OutboxChannel,
+    recipient: &'a str,
+    subject: &'a str,
+    body: &'a str,
+    attachments: Vec<DeliveryAttachment<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeliveryAttachment<'a> {
+    filename: &'a str,
+    content_type: &'a str,
+    content: &'a str,
+}
+
+fn webhook_url() -> Option<String> {
+    env::var("OUTBOX_WEBHOOK_URL").ok()
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    message: &OutboxMessage,
+) -> Result<(), String> {
+    let attachments = match (&message.attachment_name, &message.attachment) {
+        (Some(filename), Some(content)) => vec![DeliveryAttachment {
+            filename,
+            content_type: message
+                .attachment_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+            content,
+        }],
+        _ => vec![],
+    };
+    let delivery = Delivery {
+        id: message.id,
+        channel: message.channel,
+        recipient: &message.recipient,
+        subject: &message.subject,
+        body: &message.body,
+        attachments,
+    };
+
+    let mut request = client
+        .post(url)
+        .timeout(std::time::Duration::from_secs(DELIVERY_TIMEOUT_SECONDS))
+        .json(&delivery);
+    if let Ok(token) = env::var("OUTBOX_WEBHOOK_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+// Hands up to BATCH_SIZE messages that are due to the gateway, one failing doesn't hold up the others
+async fn dispatch(pool: &DbPool, url: &str) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    for _ in 0..BATCH_SIZE {
+        let find_pool = pool.clone();
+        let message = tokio::task::spawn_blocking(move || OutboxMessage::claim_next(&find_pool))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+        let message = match message {
+            Some(message) => message,
+            None => break,
+        };
+
+        let delivered = deliver(&client, url, &message).await;
+        if let Err(err) = &delivered {
+            tracing::error!("Failed to deliver outbox message {}: {}", message.id, err);
+        }
+
+        let mark_pool = pool.clone();
+        tokio::task::spawn_blocking(move || match delivered {
+            Ok(_) => OutboxMessage::mark_sent(&mark_pool, &message.id),
+            Err(err) => message.mark_failed(&mark_pool, &err),
+        })
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+// Every OUTBOX_DISPATCH_SECONDS, only while OUTBOX_WEBHOOK_URL is set, messages wait until then
+pub async fn dispatch_periodically(pool: DbPool) {
+    let url = match webhook_url() {
+        Some(url) => url,
+        None => return,
+    };
+    let seconds = env::var("OUTBOX_DISPATCH_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_DISPATCH_SECONDS);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(seconds));
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = dispatch(&pool, &url).await {
+            tracing::error!("Failed to dispatch outbox messages: {}", err);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod dispatch;
+mod model;
+mod outbox_channel;
+mod outbox_status;
+
+pub use dispatch::dispatch_periodically;
+pub use model::{NewOutboxMessage, OutboxMessage};
+pub use outbox_channel::OutboxChannel;
@@ -0,0 +1,137 @@
+use chrono::NaiveDateTime;
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::Insertable;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, NullableExpressionMethods,
+    OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::outbox_channel::OutboxChannel;
+use super::outbox_status::OutboxStatus;
+use crate::db::DbPool;
+use crate::schema::outbox_messages;
+
+// Failed deliveries are retried this many times, a little later each time
+const MAX_ATTEMPTS: i32 = 5;
+const RETRY_MINUTES: i32 = 5;
+// A claimed message whose dispatcher didn't report back in this time is claimed again
+const SENDING_LEASE_MINUTES: i32 = 10;
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct OutboxMessage {
+    pub id: i32,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    pub channel: OutboxChannel,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub attachment_name: Option<String>,
+    pub attachment_type: Option<String>,
+    pub attachment: Option<String>,
+    key: Option<String>,
+    send_after: NaiveDateTime,
+    status: OutboxStatus,
+    pub attempts: i32,
+    last_error: Option<String>,
+    sent_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = outbox_messages)]
+pub struct NewOutboxMessage {
+    pub channel: OutboxChannel,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub attachment_name: Option<String>,
+    pub attachment_type: Option<String>,
+    pub attachment: Option<String>,
+    pub key: Option<String>,
+    // Right away when None
+    pub send_after: Option<NaiveDateTime>,
+}
+
+impl OutboxMessage {
+    // Saved in the caller's transaction, so messages only go out for changes that were kept
+    pub fn enqueue(conn: &mut PgConnection, messages: &[NewOutboxMessage]) -> QueryResult<usize> {
+        diesel::insert_into(outbox_messages::table)
+            .values(messages)
+            .execute(conn)
+    }
+
+    // The messages under `key` that haven't gone out yet won't be sent
+    pub fn cancel_pending(conn: &mut PgConnection, key: &str) -> QueryResult<usize> {
+        diesel::update(outbox_messages::table)
+            .filter(outbox_messages::key.eq(key))
+            .filter(outbox_messages::status.eq(OutboxStatus::Pending))
+            .set(outbox_messages::status.eq(OutboxStatus::Cancelled))
+            .execute(conn)
+    }
+
+    // Marks the due messages as sending and returns them. Rows another dispatcher has
+    // locked are skipped, so every message is handed to one dispatcher only.
+    // Claimed one at a time right before delivery, so the lease only ever has to cover one delivery
+    pub(super) fn claim_next(pool: &DbPool) -> QueryResult<Option<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let id: Option<i32> = outbox_messages::table
+                .filter(
+                    outbox_messages::status
+                        .eq(OutboxStatus::Pending)
+                        .and(outbox_messages::send_after.le(now))
+                        .or(outbox_messages::status.eq(OutboxStatus::Sending).and(
+                            outbox_messages::updated_at.lt(now - SENDING_LEASE_MINUTES.minutes()),
+                        )),
+                )
+                .order_by((outbox_messages::send_after.asc(), outbox_messages::id.asc()))
+                .select(outbox_messages::id)
+                .for_update()
+                .skip_locked()
+                .first(conn)
+                .optional()?;
+
+            match id {
+                Some(id) => diesel::update(outbox_messages::table.find(id))
+                    .set(outbox_messages::status.eq(OutboxStatus::Sending))
+                    .get_result(conn)
+                    .map(Some),
+                None => Ok(None),
+            }
+        })
+    }
+
+    pub(super) fn mark_sent(pool: &DbPool, id: &i32) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(outbox_messages::table.find(id))
+            .set((
+                outbox_messages::status.eq(OutboxStatus::Sent),
+                outbox_messages::attempts.eq(outbox_messages::attempts + 1),
+                outbox_messages::last_error.eq(None::<String>),
+                outbox_messages::sent_at.eq(now.nullable()),
+            ))
+            .execute(conn)
+    }
+
+    // Given up on after MAX_ATTEMPTS
+    pub(super) fn mark_failed(&self, pool: &DbPool, error: &str) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let attempts = self.attempts + 1;
+        let status = match attempts >= MAX_ATTEMPTS {
+            true => OutboxStatus::Failed,
+            false => OutboxStatus::Pending,
+        };
+        diesel::update(outbox_messages::table.find(self.id))
+            .set((
+                outbox_messages::status.eq(status),
+                outbox_messages::attempts.eq(attempts),
+                outbox_messages::last_error.eq(error),
+                outbox_messages::send_after.eq(now + (RETRY_MINUTES * attempts).minutes()),
+            ))
+            .execute(conn)
+    }
+}
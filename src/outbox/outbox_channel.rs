@@ -0,0 +1,34 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::OutboxChannel)]
+pub enum OutboxChannel {
+    Email,
+    Whatsapp,
+}
+
+impl ToSql<sql_types::OutboxChannel, Pg> for OutboxChannel {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            OutboxChannel::Email => out.write_all(b"email")?,
+            OutboxChannel::Whatsapp => out.write_all(b"whatsapp")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::OutboxChannel, Pg> for OutboxChannel {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"email" => Ok(OutboxChannel::Email),
+            b"whatsapp" => Ok(OutboxChannel::Whatsapp),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::OutboxStatus)]
+pub enum OutboxStatus {
+    Pending,
+    // Claimed by a dispatcher, see `OutboxMessage::claim_next`
+    Sending,
+    Sent,
+    Failed,
+    Cancelled,
+}
+
+impl ToSql<sql_types::OutboxStatus, Pg> for OutboxStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            OutboxStatus::Pending => out.write_all(b"pending")?,
+            OutboxStatus::Sending => out.write_all(b"sending")?,
+            OutboxStatus::Sent => out.write_all(b"sent")?,
+            OutboxStatus::Failed => out.write_all(b"failed")?,
+            OutboxStatus::Cancelled => out.write_all(b"cancelled")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::OutboxStatus, Pg> for OutboxStatus {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"pending" => Ok(OutboxStatus::Pending),
+            b"sending" => Ok(OutboxStatus::Sending),
+            b"sent" => Ok(OutboxStatus::Sent),
+            b"failed" => Ok(OutboxStatus::Failed),
+            b"cancelled" => Ok(OutboxStatus::Cancelled),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::NaiveDate;
+use diesel::prelude::{AsChangeset, Insertable};
+use serde::{Deserialize, Serialize};
+
+use super::model::{Project, ProjectAvailability};
+use crate::{
+    agents::{authorize, Action, Resource},
+    db::DbPool,
+    developers::Developer,
+    middleware::{AxumResponse, JsonFindResponse, JsonResponse},
+    properties::Facilities,
+    schema,
+};
+
+#[derive(Deserialize, Serialize)]
+pub(super) struct UnitType {
+    name: String,
+    bedrooms: Option<i32>,
+    bathrooms: Option<i32>,
+    land_area: Option<i32>,
+    building_area: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub(super) struct CreateUpdateProjectApiPayload {
+    developer_id: i32,
+    name: String,
+    #[serde(default)]
+    description: String,
+    province: String,
+    regency: String,
+    street: String,
+    gmap_iframe: Option<String>,
+    brochure_path: Option<String>,
+    completion_date: Option<NaiveDate>,
+    #[serde(default)]
+    facilities: Vec<Facilities>,
+    #[serde(default)]
+    unit_types: Vec<UnitType>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = schema::projects)]
+#[diesel(treat_none_as_null = true)]
+pub(super) struct CreateUpdateProjectSqlPayload {
+    developer_id: i32,
+    pub(super) name: String,
+    description: String,
+    province: String,
+    regency: String,
+    street: String,
+    gmap_iframe: Option<String>,
+    brochure_path: Option<String>,
+    completion_date: Option<NaiveDate>,
+    facilities: serde_json::Value,
+    unit_types: serde_json::Value,
+}
+
+impl CreateUpdateProjectApiPayload {
+    fn into_sql_payload(self) -> CreateUpdateProjectSqlPayload {
+        CreateUpdateProjectSqlPayload {
+            developer_id: self.developer_id,
+            name: self.name.trim().to_string(),
+            description: self.description,
+            province: self.province.trim().to_lowercase(),
+            regency: self.regency.trim().to_lowercase(),
+            street: self.street.trim().to_lowercase(),
+            gmap_iframe: self.gmap_iframe,
+            brochure_path: self.brochure_path,
+            completion_date: self.completion_date,
+            facilities: serde_json::json!(&self.facilities),
+            unit_types: serde_json::json!(&self.unit_types),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ProjectWithAvailability {
+    #[serde(flatten)]
+    project: Project,
+    availability: ProjectAvailability,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ProjectDetail {
+    #[serde(flatten)]
+    project: Project,
+    developer: Developer,
+    availability: ProjectAvailability,
+}
+
+fn validate(payload: &CreateUpdateProjectApiPayload) -> Result<(), String> {
+    if payload.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if payload
+        .unit_types
+        .iter()
+        .any(|unit_type| unit_type.name.trim().is_empty())
+    {
+        return Err("Unit types need a name".to_string());
+    }
+    Ok(())
+}
+
+pub(crate) async fn find_many_by_developer(
+    State(pool): State<DbPool>,
+    Path(developer_id): Path<i32>,
+) -> AxumResponse<JsonFindResponse<Vec<ProjectWithAvailability>>> {
+    if let Err(err) = Developer::find_by_id(&pool, &developer_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(404, None, Some("Developer not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    let projects = match Project::find_many_by_developer(&pool, &developer_id) {
+        Ok(projects) => projects,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let ids: Vec<i32> = projects.iter().map(|project| project.id).collect();
+    let mut availability = match Project::find_availability(&pool, &ids) {
+        Ok(availability) => availability,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let projects: Vec<ProjectWithAvailability> = projects
+        .into_iter()
+        .map(|project| ProjectWithAvailability {
+            availability: availability.remove(&project.id).unwrap_or_default(),
+            project,
+        })
+        .collect();
+
+    let res = JsonFindResponse {
+        total_data: projects.len() as i64,
+        data: projects,
+        total_pages: 1,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+pub(super) async fn find_project_by_slug(
+    State(pool): State<DbPool>,
+    Path(slug): Path<String>,
+) -> AxumResponse<ProjectDetail> {
+    let project = match Project::find_by_slug(&pool, &slug) {
+        Ok(project) => project,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Project not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let developer = match Developer::find_by_id(&pool, &project.developer_id) {
+        Ok(developer) => developer,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let availability = match Project::find_availability(&pool, &[project.id]) {
+        Ok(mut availability) => availability.remove(&project.id).unwrap_or_default(),
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(ProjectDetail {
+            project,
+            developer,
+            availability,
+        }),
+        None,
+    )
+}
+
+pub(super) async fn create_project(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateUpdateProjectApiPayload>,
+) -> AxumResponse<Project> {
+    if let Err(response) = authorize(&pool, &headers, Action::Create, Resource::Developer) {
+        return response;
+    }
+    if let Err(message) = validate(&payload) {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    if let Err(err) = Developer::find_by_id(&pool, &payload.developer_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(400, None, Some("Developer not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    match Project::create(&pool, &payload.into_sql_payload()) {
+        Ok(project) => JsonResponse::send(201, Some(project), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub(super) async fn update_project(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+    Json(payload): Json<CreateUpdateProjectApiPayload>,
+) -> AxumResponse<Project> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::Developer) {
+        return response;
+    }
+    if let Err(message) = validate(&payload) {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    if let Err(err) = Developer::find_by_id(&pool, &payload.developer_id) {
+        return match err {
+            diesel::result::Error::NotFound => {
+                JsonResponse::send(400, None, Some("Developer not found".to_string()))
+            }
+            _ => JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+
+    match Project::update(&pool, &slug, &payload.into_sql_payload()) {
+        Ok(project) => JsonResponse::send(200, Some(project), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Project not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub(super) async fn delete_project(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> AxumResponse<Project> {
+    if let Err(response) = authorize(&pool, &headers, Action::Delete, Resource::Developer) {
+        return response;
+    }
+
+    match Project::delete(&pool, &slug) {
+        Ok(project) => JsonResponse::send(200, Some(project), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Project not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
@@ -0,0 +1,7 @@
+mod controller;
+mod model;
+mod routes;
+
+pub(crate) use controller::find_many_by_developer;
+pub use model::Project;
+pub use routes::projects_routes;
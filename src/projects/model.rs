@@ -0,0 +1,157 @@
+use chrono::NaiveDate;
+use diesel::dsl;
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::controller::CreateUpdateProjectSqlPayload;
+use crate::db::DbPool;
+use crate::properties::SoldStatus;
+use crate::schema::{agents, projects, properties};
+use crate::slug::{available_slug, insert_with_slug, slugify};
+
+// Primary-market cluster of a developer, listings are its units
+#[derive(Debug, Serialize, Queryable, Clone)]
+pub struct Project {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub developer_id: i32,
+    pub slug: String,
+    name: String,
+    description: String,
+    province: String,
+    regency: String,
+    street: String,
+    gmap_iframe: Option<String>,
+    brochure_path: Option<String>,
+    completion_date: Option<NaiveDate>,
+    facilities: serde_json::Value,
+    unit_types: serde_json::Value,
+}
+
+// Units listed on the public site, prices are the rupiah prices of the available ones
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct ProjectAvailability {
+    total_units: i64,
+    available_units: i64,
+    sold_units: i64,
+    min_price: Option<i64>,
+    max_price: Option<i64>,
+}
+
+// project_id, sold_status, units, lowest and highest price
+type ProjectStatusRow = (i32, SoldStatus, i64, Option<i64>, Option<i64>);
+
+impl Project {
+    pub(super) fn find_many_by_developer(
+        pool: &DbPool,
+        developer_id: &i32,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        projects::table
+            .filter(projects::developer_id.eq(developer_id))
+            .order_by(projects::name.asc())
+            .get_results(conn)
+    }
+
+    pub fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        projects::table.find(id).get_result(conn)
+    }
+
+    pub(super) fn find_by_slug(pool: &DbPool, slug: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        projects::table
+            .filter(projects::slug.eq(slug))
+            .get_result(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        payload: &CreateUpdateProjectSqlPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        insert_with_slug(conn, |conn| {
+            let slug = available_slug(conn, &slugify(&payload.name, "project"), |conn, slug| {
+                dsl::select(dsl::exists(projects::table.filter(projects::slug.eq(slug))))
+                    .get_result(conn)
+            })?;
+            diesel::insert_into(projects::table)
+                .values((projects::slug.eq(slug), payload))
+                .get_result(conn)
+        })
+    }
+
+    // The slug stays, links to the project keep working after a rename. The units follow
+    // the project to its new developer.
+    pub(super) fn update(
+        pool: &DbPool,
+        slug: &str,
+        payload: &CreateUpdateProjectSqlPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let project: Self = diesel::update(projects::table.filter(projects::slug.eq(slug)))
+                .set(payload)
+                .get_result(conn)?;
+            diesel::update(properties::table)
+                .filter(properties::project_id.eq(project.id))
+                .set(properties::developer_id.eq(project.developer_id))
+                .execute(conn)?;
+            Ok(project)
+        })
+    }
+
+    // Listings stay, without a project
+    pub(super) fn delete(pool: &DbPool, slug: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(projects::table.filter(projects::slug.eq(slug))).get_result(conn)
+    }
+
+    pub(super) fn find_availability(
+        pool: &DbPool,
+        ids: &[i32],
+    ) -> QueryResult<HashMap<i32, ProjectAvailability>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let rows: Vec<ProjectStatusRow> = properties::table
+            .inner_join(agents::table)
+            .filter(properties::project_id.eq_any(ids))
+            .filter(properties::is_deleted.eq(false))
+            .filter(agents::is_active.eq(true))
+            .group_by((properties::project_id, properties::sold_status))
+            .select((
+                properties::project_id.assume_not_null(),
+                properties::sold_status,
+                dsl::count_star(),
+                dsl::min(properties::price_idr),
+                dsl::max(properties::price_idr),
+            ))
+            .get_results(conn)?;
+
+        let mut availability: HashMap<i32, ProjectAvailability> = HashMap::new();
+        for (project_id, sold_status, count, min_price, max_price) in rows {
+            let project = availability.entry(project_id).or_default();
+            project.total_units += count;
+            match sold_status {
+                SoldStatus::Available => {
+                    project.available_units += count;
+                    project.min_price = min_price;
+                    project.max_price = max_price;
+                }
+                SoldStatus::Sold => project.sold_units += count,
+            }
+        }
+        Ok(availability)
+    }
+}
@@ -0,0 +1,15 @@
+use axum::routing::{delete, get, post, put};
+use axum::Router;
+
+use crate::db::DbPool;
+use crate::projects::controller::{
+    create_project, delete_project, find_project_by_slug, update_project,
+};
+
+pub fn projects_routes() -> Router<DbPool> {
+    axum::Router::new()
+        .route("/", post(create_project))
+        .route("/{slug}", get(find_project_by_slug))
+        .route("/{slug}", put(update_project))
+        .route("/{slug}", delete(delete_project))
+}
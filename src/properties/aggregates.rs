@@ -0,0 +1,140 @@
+use diesel::dsl;
+use diesel::pg::Pg;
+use diesel::sql_types::{Bool, Nullable};
+use diesel::{
+    BoolExpressionMethods, BoxableExpression, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::controllers::{FindPropertyQuery, PropertyWithRelation};
+use super::enumerates::SoldStatus;
+use super::model::{active_agent_ids, Property};
+use crate::db::DbPool;
+use crate::middleware::JsonFindResponse;
+use crate::schema::properties;
+
+// Whose listings are summed up
+#[derive(Debug, Clone, Copy)]
+pub enum ListingPartner {
+    Developer(i32),
+    Bank(i32),
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListingGroup {
+    key: String,
+    count: i64,
+    min_price: Option<i64>,
+    max_price: Option<i64>,
+}
+
+// Listings available on the public site, grouped for landing pages, prices in IDR
+#[derive(Debug, Serialize)]
+pub struct ListingAggregates {
+    available: i64,
+    min_price: Option<i64>,
+    max_price: Option<i64>,
+    by_building_type: Vec<ListingGroup>,
+    by_regency: Vec<ListingGroup>,
+}
+
+type ListingFilter = Box<dyn BoxableExpression<properties::table, Pg, SqlType = Nullable<Bool>>>;
+
+fn available_of(partner: ListingPartner) -> ListingFilter {
+    let available = properties::is_deleted
+        .eq(false)
+        .and(properties::sold_status.eq(SoldStatus::Available))
+        .and(properties::user_id.eq_any(active_agent_ids()));
+
+    match partner {
+        ListingPartner::Developer(id) => Box::new(available.and(properties::developer_id.eq(id))),
+        ListingPartner::Bank(id) => Box::new(available.and(properties::bank_id.eq(id))),
+    }
+}
+
+impl ListingAggregates {
+    pub fn find(pool: &DbPool, partner: ListingPartner) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let (available, min_price, max_price) = properties::table
+            .filter(available_of(partner))
+            .select((
+                dsl::count_star(),
+                dsl::min(properties::price_idr),
+                dsl::max(properties::price_idr),
+            ))
+            .get_result::<(i64, Option<i64>, Option<i64>)>(conn)?;
+
+        let by_building_type = properties::table
+            .filter(available_of(partner))
+            .group_by(properties::building_type)
+            .select((
+                properties::building_type,
+                dsl::count_star(),
+                dsl::min(properties::price_idr),
+                dsl::max(properties::price_idr),
+            ))
+            .order_by(dsl::count_star().desc())
+            .then_order_by(properties::building_type.asc())
+            .get_results::<(String, i64, Option<i64>, Option<i64>)>(conn)?;
+
+        let by_regency = properties::table
+            .filter(available_of(partner))
+            .group_by(properties::regency)
+            .select((
+                properties::regency,
+                dsl::count_star(),
+                dsl::min(properties::price_idr),
+                dsl::max(properties::price_idr),
+            ))
+            .order_by(dsl::count_star().desc())
+            .then_order_by(properties::regency.asc())
+            .get_results::<(String, i64, Option<i64>, Option<i64>)>(conn)?;
+
+        let into_groups = |rows: Vec<(String, i64, Option<i64>, Option<i64>)>| {
+            rows.into_iter()
+                .map(|(key, count, min_price, max_price)| ListingGroup {
+                    key,
+                    count,
+                    min_price,
+                    max_price,
+                })
+                .collect()
+        };
+
+        Ok(ListingAggregates {
+            available,
+            min_price,
+            max_price,
+            by_building_type: into_groups(by_building_type),
+            by_regency: into_groups(by_regency),
+        })
+    }
+}
+
+// A page of the partner's listings, as the public site lists them
+pub fn find_listing_page(
+    pool: &DbPool,
+    partner: ListingPartner,
+    page: i64,
+    limit: i64,
+) -> QueryResult<JsonFindResponse<Vec<PropertyWithRelation>>> {
+    let mut query = FindPropertyQuery {
+        page: Some(page),
+        limit: Some(limit),
+        ..Default::default()
+    };
+    match partner {
+        ListingPartner::Developer(id) => query.developer_id = Some(id),
+        ListingPartner::Bank(id) => query.bank_id = Some(id),
+    }
+
+    let data = Property::find_many(pool, &None, &query)?;
+    let total_data = Property::count_find_many_rows(pool, &None, &query)?;
+
+    Ok(JsonFindResponse {
+        data,
+        total_pages: (total_data / limit) + 1,
+        total_data,
+    })
+}
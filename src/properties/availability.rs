@@ -0,0 +1,593 @@
+use chrono::NaiveDate;
+use diesel::dsl::now;
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, OptionalExtension, PgConnection,
+    QueryDsl, QueryResult, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+use super::controllers::{
+    AvailabilityRangePayload, CreateCalendarFeedPayload, SeasonalPricePayload,
+};
+use super::enumerates::AvailabilityKind;
+use crate::db::DbPool;
+use crate::digest::sha256_hex;
+use crate::ical::{self, IcalEvent};
+use crate::schema::{property_availability, property_calendar_feeds, property_seasonal_prices};
+
+const DEFAULT_SYNC_MINUTES: u64 = 60;
+const FEED_TIMEOUT_SECONDS: u64 = 30;
+// Years of bookings are a few hundred kilobytes
+const MAX_FEED_BYTES: usize = 5 * 1024 * 1024;
+
+// Days a listing can't be rented, ends_on is the check-out day and isn't included
+#[derive(Debug, Serialize, Queryable)]
+pub struct AvailabilityRange {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub property_id: i32,
+    pub kind: AvailabilityKind,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    note: Option<String>,
+    lead_id: Option<i32>,
+    // Set on ranges imported from a calendar feed
+    feed_id: Option<i32>,
+    external_uid: Option<String>,
+}
+
+// What the public site sees of a range
+#[derive(Debug, Serialize, Queryable)]
+pub struct BusyRange {
+    kind: AvailabilityKind,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+}
+
+// Rent per rent_time of the listing for the nights inside the season
+#[derive(Debug, Serialize, Queryable)]
+pub struct SeasonalPrice {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    property_id: i32,
+    label: String,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    price: i64,
+}
+
+// An external calendar (Airbnb, Booking.com, ...) synced into the listing's availability
+#[derive(Debug, Serialize, Queryable)]
+pub struct CalendarFeed {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    property_id: i32,
+    name: String,
+    url: String,
+    last_synced_at: Option<chrono::NaiveDateTime>,
+    last_error: Option<String>,
+}
+
+pub(super) enum AvailabilityError {
+    Query(diesel::result::Error),
+    // The range the new one would overlap
+    Conflict(AvailabilityRange),
+}
+
+impl From<diesel::result::Error> for AvailabilityError {
+    fn from(err: diesel::result::Error) -> Self {
+        AvailabilityError::Query(err)
+    }
+}
+
+pub(super) enum SeasonalPriceError {
+    Query(diesel::result::Error),
+    Conflict(SeasonalPrice),
+}
+
+impl From<diesel::result::Error> for SeasonalPriceError {
+    fn from(err: diesel::result::Error) -> Self {
+        SeasonalPriceError::Query(err)
+    }
+}
+
+impl AvailabilityRange {
+    // Bookings can't overlap anything, blocked days can overlap other blocked days
+    fn find_conflict(
+        conn: &mut PgConnection,
+        property_id: &i32,
+        payload: &AvailabilityRangePayload,
+        except_id: Option<i32>,
+    ) -> QueryResult<Option<Self>> {
+        let mut query = property_availability::table
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::starts_on.lt(payload.ends_on))
+            .filter(property_availability::ends_on.gt(payload.starts_on))
+            .filter(property_availability::id.ne(except_id.unwrap_or(0)))
+            .order_by(property_availability::starts_on.asc())
+            .into_boxed();
+        if let AvailabilityKind::Blocked = payload.kind {
+            query = query.filter(property_availability::kind.eq(AvailabilityKind::Booked));
+        }
+        query.first(conn).optional()
+    }
+
+    // Whether any range takes a day between check_in and check_out
+    pub fn is_free(
+        pool: &DbPool,
+        property_id: &i32,
+        check_in: NaiveDate,
+        check_out: NaiveDate,
+    ) -> QueryResult<bool> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_availability::table
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::starts_on.lt(check_out))
+            .filter(property_availability::ends_on.gt(check_in))
+            .count()
+            .get_result(conn)
+            .map(|count: i64| count == 0)
+    }
+
+    pub(super) fn find_busy(
+        pool: &DbPool,
+        property_id: &i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> QueryResult<Vec<BusyRange>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_availability::table
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::starts_on.lt(to))
+            .filter(property_availability::ends_on.gt(from))
+            .select((
+                property_availability::kind,
+                property_availability::starts_on,
+                property_availability::ends_on,
+            ))
+            .order_by(property_availability::starts_on.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn find_many_by_property(
+        pool: &DbPool,
+        property_id: &i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_availability::table
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::starts_on.lt(to))
+            .filter(property_availability::ends_on.gt(from))
+            .order_by(property_availability::starts_on.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        property_id: &i32,
+        payload: &AvailabilityRangePayload,
+    ) -> Result<Self, AvailabilityError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) = Self::find_conflict(conn, property_id, payload, None)? {
+                return Err(AvailabilityError::Conflict(conflict));
+            }
+            Ok(diesel::insert_into(property_availability::table)
+                .values((payload, property_availability::property_id.eq(property_id)))
+                .get_result(conn)?)
+        })
+    }
+
+    // Ranges imported from a feed are replaced on its next sync, so only the agent's own are changed
+    pub(super) fn update(
+        pool: &DbPool,
+        property_id: &i32,
+        id: &i32,
+        payload: &AvailabilityRangePayload,
+    ) -> Result<Self, AvailabilityError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) = Self::find_conflict(conn, property_id, payload, Some(*id))? {
+                return Err(AvailabilityError::Conflict(conflict));
+            }
+            Ok(diesel::update(property_availability::table)
+                .filter(property_availability::id.eq(id))
+                .filter(property_availability::property_id.eq(property_id))
+                .filter(property_availability::feed_id.is_null())
+                .set(payload)
+                .get_result(conn)?)
+        })
+    }
+
+    pub(super) fn delete(pool: &DbPool, property_id: &i32, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(property_availability::table)
+            .filter(property_availability::id.eq(id))
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::feed_id.is_null())
+            .get_result(conn)
+    }
+
+    // Everything from `from` on, for the exported calendar
+    pub(super) fn find_upcoming(
+        pool: &DbPool,
+        property_id: &i32,
+        from: NaiveDate,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_availability::table
+            .filter(property_availability::property_id.eq(property_id))
+            .filter(property_availability::ends_on.gt(from))
+            .order_by(property_availability::starts_on.asc())
+            .get_results(conn)
+    }
+
+    // Only the kind goes out, notes and leads stay with the agent
+    pub(super) fn to_event(&self) -> IcalEvent {
+        IcalEvent {
+            // imported ranges get new ids on every sync, so theirs comes from the source's
+            // uid, hashed to keep the other channel's booking ids out of the public feed
+            uid: match (self.feed_id, &self.external_uid) {
+                (Some(feed_id), Some(external_uid)) => format!(
+                    "availability-{}@primeproindonesia.com",
+                    sha256_hex(&format!("feed-{}-{}", feed_id, external_uid))
+                ),
+                _ => format!("availability-{}@primeproindonesia.com", self.id),
+            },
+            stamp: self.updated_at,
+            start: ical::IcalTime::Date(self.starts_on),
+            end: ical::IcalTime::Date(self.ends_on),
+            summary: match self.kind {
+                AvailabilityKind::Blocked => "Blocked".to_string(),
+                AvailabilityKind::Booked => "Booked".to_string(),
+            },
+            description: None,
+            location: None,
+            cancelled: false,
+            sequence: 0,
+        }
+    }
+}
+
+impl SeasonalPrice {
+    fn find_conflict(
+        conn: &mut PgConnection,
+        property_id: &i32,
+        payload: &SeasonalPricePayload,
+        except_id: Option<i32>,
+    ) -> QueryResult<Option<Self>> {
+        property_seasonal_prices::table
+            .filter(property_seasonal_prices::property_id.eq(property_id))
+            .filter(property_seasonal_prices::starts_on.lt(payload.ends_on))
+            .filter(property_seasonal_prices::ends_on.gt(payload.starts_on))
+            .filter(property_seasonal_prices::id.ne(except_id.unwrap_or(0)))
+            .first(conn)
+            .optional()
+    }
+
+    pub(super) fn find_many_by_property(
+        pool: &DbPool,
+        property_id: &i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_seasonal_prices::table
+            .filter(property_seasonal_prices::property_id.eq(property_id))
+            .filter(property_seasonal_prices::starts_on.lt(to))
+            .filter(property_seasonal_prices::ends_on.gt(from))
+            .order_by(property_seasonal_prices::starts_on.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        property_id: &i32,
+        payload: &SeasonalPricePayload,
+    ) -> Result<Self, SeasonalPriceError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) = Self::find_conflict(conn, property_id, payload, None)? {
+                return Err(SeasonalPriceError::Conflict(conflict));
+            }
+            Ok(diesel::insert_into(property_seasonal_prices::table)
+                .values((
+                    payload,
+                    property_seasonal_prices::property_id.eq(property_id),
+                ))
+                .get_result(conn)?)
+        })
+    }
+
+    pub(super) fn update(
+        pool: &DbPool,
+        property_id: &i32,
+        id: &i32,
+        payload: &SeasonalPricePayload,
+    ) -> Result<Self, SeasonalPriceError> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            if let Some(conflict) = Self::find_conflict(conn, property_id, payload, Some(*id))? {
+                return Err(SeasonalPriceError::Conflict(conflict));
+            }
+            Ok(diesel::update(property_seasonal_prices::table)
+                .filter(property_seasonal_prices::id.eq(id))
+                .filter(property_seasonal_prices::property_id.eq(property_id))
+                .set(payload)
+                .get_result(conn)?)
+        })
+    }
+
+    pub(super) fn delete(pool: &DbPool, property_id: &i32, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(property_seasonal_prices::table)
+            .filter(property_seasonal_prices::id.eq(id))
+            .filter(property_seasonal_prices::property_id.eq(property_id))
+            .get_result(conn)
+    }
+}
+
+impl CalendarFeed {
+    pub(super) fn find_many_by_property(
+        pool: &DbPool,
+        property_id: &i32,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_calendar_feeds::table
+            .filter(property_calendar_feeds::property_id.eq(property_id))
+            .order_by(property_calendar_feeds::created_at.asc())
+            .get_results(conn)
+    }
+
+    fn find_all(pool: &DbPool) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_calendar_feeds::table
+            .order_by(property_calendar_feeds::id.asc())
+            .get_results(conn)
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, property_id: &i32, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        property_calendar_feeds::table
+            .filter(property_calendar_feeds::id.eq(id))
+            .filter(property_calendar_feeds::property_id.eq(property_id))
+            .get_result(conn)
+    }
+
+    pub(super) fn create(
+        pool: &DbPool,
+        property_id: &i32,
+        payload: &CreateCalendarFeedPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(property_calendar_feeds::table)
+            .values((
+                payload,
+                property_calendar_feeds::property_id.eq(property_id),
+            ))
+            .get_result(conn)
+    }
+
+    // Its imported ranges go with it
+    pub(super) fn delete(pool: &DbPool, property_id: &i32, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(property_calendar_feeds::table)
+            .filter(property_calendar_feeds::id.eq(id))
+            .filter(property_calendar_feeds::property_id.eq(property_id))
+            .get_result(conn)
+    }
+
+    // The ranges of the last sync are swapped for the events found now
+    fn replace_ranges(&self, pool: &DbPool, events: &[IcalEvent]) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let today = chrono::Local::now().date_naive();
+        let ranges: Vec<_> = events
+            .iter()
+            .filter(|event| !event.cancelled)
+            .filter_map(|event| {
+                let starts_on = event.start.date();
+                // events inside a single day still take that day
+                let ends_on = event.end.date().max(starts_on + chrono::Duration::days(1));
+                (ends_on > today).then(|| {
+                    (
+                        property_availability::property_id.eq(self.property_id),
+                        property_availability::kind.eq(AvailabilityKind::Booked),
+                        property_availability::starts_on.eq(starts_on),
+                        property_availability::ends_on.eq(ends_on),
+                        property_availability::note
+                            .eq(Some(event.summary.clone()).filter(|summary| !summary.is_empty())),
+                        property_availability::feed_id.eq(self.id),
+                        property_availability::external_uid.eq(event
+                            .uid
+                            .chars()
+                            .take(255)
+                            .collect::<String>()),
+                    )
+                })
+            })
+            .collect();
+
+        conn.transaction(|conn| {
+            diesel::delete(property_availability::table)
+                .filter(property_availability::feed_id.eq(self.id))
+                .execute(conn)?;
+            diesel::insert_into(property_availability::table)
+                .values(&ranges)
+                .execute(conn)?;
+            diesel::update(property_calendar_feeds::table.find(self.id))
+                .set((
+                    property_calendar_feeds::last_synced_at.eq(now.nullable()),
+                    property_calendar_feeds::last_error.eq(None::<String>),
+                ))
+                .get_result(conn)
+        })
+    }
+
+    // The ranges of the last good sync are kept
+    fn record_error(&self, pool: &DbPool, error: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(property_calendar_feeds::table.find(self.id))
+            .set(property_calendar_feeds::last_error.eq(error))
+            .get_result(conn)
+    }
+}
+
+// Addresses on the internet, feeds can't point the api at itself or the private network
+pub(super) fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // carrier-grade NAT, 100.64.0.0/10
+                || (first == 100 && (64..128).contains(&second))
+                || first == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(&IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+// Resolved here and pinned for the request, so the name can't point elsewhere once checked
+async fn resolve_public(url: &reqwest::Url) -> Result<(String, SocketAddr), String> {
+    let host = url
+        .host_str()
+        .ok_or("The feed url has no host")?
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|err| err.to_string())?
+        .collect();
+    match addresses.first() {
+        Some(address) if addresses.iter().all(|address| is_public_ip(&address.ip())) => {
+            Ok((host, *address))
+        }
+        Some(_) => Err("The feed url points to a private address".to_string()),
+        None => Err("The feed url doesn't resolve".to_string()),
+    }
+}
+
+async fn fetch_events(url: &str) -> Result<Vec<IcalEvent>, String> {
+    let url = reqwest::Url::parse(url).map_err(|err| err.to_string())?;
+    let (host, address) = resolve_public(&url).await?;
+    let client = reqwest::Client::builder()
+        .resolve(&host, address)
+        .redirect(reqwest::redirect::Policy::none())
+        .timeout(std::time::Duration::from_secs(FEED_TIMEOUT_SECONDS))
+        .build()
+        .map_err(|err| err.to_string())?;
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?;
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+        if bytes.len() + chunk.len() > MAX_FEED_BYTES {
+            return Err(format!("The feed is larger than {} bytes", MAX_FEED_BYTES));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let calendar = String::from_utf8_lossy(&bytes);
+    if !calendar.contains("BEGIN:VCALENDAR") {
+        return Err("Not an iCal calendar".to_string());
+    }
+
+    Ok(ical::parse(&calendar))
+}
+
+// Imports the feed's events as bookings, a failure is kept on the feed
+pub(super) async fn sync_feed(pool: &DbPool, feed: CalendarFeed) -> Result<CalendarFeed, String> {
+    let events = fetch_events(&feed.url).await;
+
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || match events {
+        Ok(events) => feed
+            .replace_ranges(&pool, &events)
+            .map_err(|err| err.to_string()),
+        Err(err) => {
+            feed.record_error(&pool, &err)
+                .map_err(|err| err.to_string())?;
+            Err(err)
+        }
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+// Every ICAL_SYNC_MINUTES, one feed failing doesn't hold up the others
+pub async fn sync_calendars_periodically(pool: DbPool) {
+    let minutes = env::var("ICAL_SYNC_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SYNC_MINUTES);
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(minutes * 60));
+
+    loop {
+        interval.tick().await;
+
+        let find_pool = pool.clone();
+        let feeds =
+            match tokio::task::spawn_blocking(move || CalendarFeed::find_all(&find_pool)).await {
+                Ok(Ok(feeds)) => feeds,
+                Ok(Err(err)) => {
+                    tracing::error!("Failed to find calendar feeds: {}", err);
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!("Failed to find calendar feeds: {}", err);
+                    continue;
+                }
+            };
+
+        for feed in feeds {
+            let feed_id = feed.id;
+            if let Err(err) = sync_feed(&pool, feed).await {
+                tracing::error!("Failed to sync calendar feed {}: {}", feed_id, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,730 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str};
+use qrcode::{Color, QrCode};
+use serde::Deserialize;
+
+use super::controllers::{Facilities, Images, Measurements, Specifications};
+use super::enumerates::{Currency, PurchaseStatus, RentTime};
+use super::model::{listing_url, Property};
+use crate::agents::Agent;
+use crate::uploads::{decode_image, fetch};
+
+// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+const CONTENT_WIDTH: f32 = PAGE_WIDTH - 2.0 * MARGIN;
+const COVER_HEIGHT: f32 = 220.0;
+const THUMBNAIL_COUNT: usize = 4;
+const THUMBNAIL_GAP: f32 = 8.0;
+const THUMBNAIL_HEIGHT: f32 = 72.0;
+const FOOTER_TOP: f32 = 150.0;
+const QR_SIZE: f32 = 90.0;
+const PHOTO_SIZE: f32 = 70.0;
+// pixels per point the photos are rendered at, enough for print
+const IMAGE_DENSITY: f32 = 2.5;
+const JPEG_QUALITY: u8 = 85;
+// Rendered brochures are kept this long, the agent's photo and details can change meanwhile
+const CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+const CACHE_SIZE: usize = 200;
+
+const ACCENT: (f32, f32, f32) = (0.09, 0.27, 0.55);
+const MUTED: (f32, f32, f32) = (0.4, 0.4, 0.4);
+const TEXT: (f32, f32, f32) = (0.1, 0.1, 0.1);
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BrochureLanguage {
+    #[default]
+    Id,
+    En,
+}
+
+struct Labels {
+    for_sale: &'static str,
+    for_rent: &'static str,
+    for_sale_or_rent: &'static str,
+    per_day: &'static str,
+    per_week: &'static str,
+    per_month: &'static str,
+    per_quarter: &'static str,
+    per_year: &'static str,
+    specifications: &'static str,
+    measurements: &'static str,
+    facilities: &'static str,
+    description: &'static str,
+    bedrooms: &'static str,
+    bathrooms: &'static str,
+    garage: &'static str,
+    carport: &'static str,
+    electrical_power: &'static str,
+    land_area: &'static str,
+    building_area: &'static str,
+    building_level: &'static str,
+    building_type: &'static str,
+    certificate: &'static str,
+    contact: &'static str,
+    scan: &'static str,
+    thousands_separator: char,
+}
+
+impl BrochureLanguage {
+    fn labels(&self) -> &'static Labels {
+        match self {
+            BrochureLanguage::Id => &Labels {
+                for_sale: "Dijual",
+                for_rent: "Disewakan",
+                for_sale_or_rent: "Dijual / Disewakan",
+                per_day: "/ hari",
+                per_week: "/ minggu",
+                per_month: "/ bulan",
+                per_quarter: "/ 3 bulan",
+                per_year: "/ tahun",
+                specifications: "Spesifikasi",
+                measurements: "Ukuran",
+                facilities: "Fasilitas",
+                description: "Deskripsi",
+                bedrooms: "Kamar tidur",
+                bathrooms: "Kamar mandi",
+                garage: "Garasi",
+                carport: "Carport",
+                electrical_power: "Daya listrik",
+                land_area: "Luas tanah",
+                building_area: "Luas bangunan",
+                building_level: "Jumlah lantai",
+                building_type: "Tipe properti",
+                certificate: "Sertifikat",
+                contact: "Hubungi agen",
+                scan: "Pindai untuk melihat listing",
+                thousands_separator: '.',
+            },
+            BrochureLanguage::En => &Labels {
+                for_sale: "For sale",
+                for_rent: "For rent",
+                for_sale_or_rent: "For sale / for rent",
+                per_day: "/ day",
+                per_week: "/ week",
+                per_month: "/ month",
+                per_quarter: "/ quarter",
+                per_year: "/ year",
+                specifications: "Specifications",
+                measurements: "Measurements",
+                facilities: "Facilities",
+                description: "Description",
+                bedrooms: "Bedrooms",
+                bathrooms: "Bathrooms",
+                garage: "Garage",
+                carport: "Carport",
+                electrical_power: "Electrical power",
+                land_area: "Land area",
+                building_area: "Building area",
+                building_level: "Floors",
+                building_type: "Property type",
+                certificate: "Certificate",
+                contact: "Contact the agent",
+                scan: "Scan to view the listing",
+                thousands_separator: ',',
+            },
+        }
+    }
+}
+
+// Photo ready to embed, re-encoded as JPEG so it goes in as is
+pub struct BrochureImage {
+    width: u32,
+    height: u32,
+    jpeg: Vec<u8>,
+}
+
+impl BrochureImage {
+    fn from_bytes(bytes: &[u8], max_width: f32) -> Result<Self, String> {
+        let image = decode_image(bytes)?;
+        let max_width = (max_width * IMAGE_DENSITY) as u32;
+        let image = match image.width() > max_width {
+            true => image.resize(max_width, u32::MAX, FilterType::Lanczos3),
+            false => image,
+        };
+
+        let rgb = image.to_rgb8();
+        let mut jpeg = vec![];
+        rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY))
+            .map_err(|err| err.to_string())?;
+
+        Ok(BrochureImage {
+            width: rgb.width(),
+            height: rgb.height(),
+            jpeg,
+        })
+    }
+}
+
+// Everything the brochure shows that lives outside the database
+pub struct BrochureImages {
+    cover: Option<BrochureImage>,
+    thumbnails: Vec<BrochureImage>,
+    agent_photo: Option<BrochureImage>,
+}
+
+async fn fetch_image(path: &str, max_width: f32) -> Option<BrochureImage> {
+    let bytes = match fetch(path).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::warn!("Skipping brochure image {}: {}", path, err);
+            return None;
+        }
+    };
+
+    let path = path.to_string();
+    match tokio::task::spawn_blocking(move || BrochureImage::from_bytes(&bytes, max_width)).await {
+        Ok(Ok(image)) => Some(image),
+        Ok(Err(err)) => {
+            tracing::warn!("Skipping brochure image {}: {}", path, err);
+            None
+        }
+        Err(err) => {
+            tracing::warn!("Skipping brochure image {}: {}", path, err);
+            None
+        }
+    }
+}
+
+// Images that can't be loaded are left out rather than failing the brochure
+pub async fn fetch_images(property: &Property, agent: &Agent) -> BrochureImages {
+    let images: Vec<Images> = serde_json::from_value(property.images.clone()).unwrap_or_default();
+    let cover_index = images.iter().position(|image| image.is_cover).unwrap_or(0);
+    let thumbnail_width =
+        (CONTENT_WIDTH - THUMBNAIL_GAP * (THUMBNAIL_COUNT - 1) as f32) / THUMBNAIL_COUNT as f32;
+
+    let cover = match images.get(cover_index) {
+        Some(image) => fetch_image(&image.path, CONTENT_WIDTH).await,
+        None => None,
+    };
+
+    let mut thumbnails = vec![];
+    for (index, image) in images.iter().enumerate() {
+        if thumbnails.len() == THUMBNAIL_COUNT {
+            break;
+        }
+        if index == cover_index {
+            continue;
+        }
+        if let Some(thumbnail) = fetch_image(&image.path, thumbnail_width).await {
+            thumbnails.push(thumbnail);
+        }
+    }
+
+    let agent_photo = match &agent.profile_picture_url {
+        Some(path) => fetch_image(path, PHOTO_SIZE).await,
+        None => None,
+    };
+
+    BrochureImages {
+        cover,
+        thumbnails,
+        agent_photo,
+    }
+}
+
+// The base 14 fonts only cover WinAnsi, anything outside it is replaced
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|char| match char {
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => char as u8,
+            '\u{2013}' => 0x96,
+            '\u{2014}' => 0x97,
+            '\u{2018}' => 0x91,
+            '\u{2019}' => 0x92,
+            '\u{201c}' => 0x93,
+            '\u{201d}' => 0x94,
+            '\u{2022}' => 0x95,
+            '\u{20ac}' => 0x80,
+            '\t' | '\n' | '\r' => b' ',
+            _ => b'?',
+        })
+        .collect()
+}
+
+// Rough Helvetica width, good enough to break lines on
+fn text_width(text: &str, size: f32, bold: bool) -> f32 {
+    let factor = match bold {
+        true => 0.56,
+        false => 0.5,
+    };
+    text.chars().count() as f32 * size * factor
+}
+
+fn wrap(text: &str, size: f32, bold: bool, width: f32) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let candidate = match line.is_empty() {
+            true => word.to_string(),
+            false => format!("{} {}", line, word),
+        };
+        if !line.is_empty() && text_width(&candidate, size, bold) > width {
+            lines.push(line);
+            line = word.to_string();
+        } else {
+            line = candidate;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn title_case(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn group_thousands(value: i64, separator: char) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    match value < 0 {
+        true => format!("-{}", grouped),
+        false => grouped,
+    }
+}
+
+fn format_amount(amount: i64, currency: Currency, labels: &Labels) -> String {
+    let amount = group_thousands(amount, labels.thousands_separator);
+    match currency {
+        Currency::Idr => format!("Rp {}", amount),
+        Currency::Usd => format!("US$ {}", amount),
+        Currency::Sgd => format!("S$ {}", amount),
+        Currency::Aud => format!("A$ {}", amount),
+        Currency::Eur => format!("€ {}", amount),
+    }
+}
+
+fn format_rent(amount: i64, property: &Property, labels: &Labels) -> String {
+    let rent = format_amount(amount, property.currency, labels);
+    match property.rent_time {
+        Some(RentTime::Daily) => format!("{} {}", rent, labels.per_day),
+        Some(RentTime::Weekly) => format!("{} {}", rent, labels.per_week),
+        Some(RentTime::Monthly) => format!("{} {}", rent, labels.per_month),
+        Some(RentTime::Quarterly) => format!("{} {}", rent, labels.per_quarter),
+        Some(RentTime::Yearly) => format!("{} {}", rent, labels.per_year),
+        None => rent,
+    }
+}
+
+// listings for sale can still carry a rent time from before
+fn format_price(property: &Property, labels: &Labels) -> String {
+    match (&property.purchase_status, property.rent_price) {
+        (PurchaseStatus::ForSale, _) => format_amount(property.price, property.currency, labels),
+        (PurchaseStatus::ForSaleOrRent, Some(rent_price)) => format!(
+            "{} · {}",
+            format_amount(property.price, property.currency, labels),
+            format_rent(rent_price, property, labels)
+        ),
+        _ => format_rent(property.price, property, labels),
+    }
+}
+
+struct Page {
+    content: Content,
+    images: Vec<(Name<'static>, BrochureImage)>,
+}
+
+static IMAGE_NAMES: [&[u8]; 6] = [b"Im0", b"Im1", b"Im2", b"Im3", b"Im4", b"Im5"];
+
+impl Page {
+    fn fill(&mut self, (red, green, blue): (f32, f32, f32)) {
+        self.content.set_fill_rgb(red, green, blue);
+    }
+
+    fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        let font = match bold {
+            true => Name(b"F2"),
+            false => Name(b"F1"),
+        };
+        self.content.begin_text();
+        self.content.set_font(font, size);
+        self.content.next_line(x, y);
+        self.content.show(Str(&win_ansi(text)));
+        self.content.end_text();
+    }
+
+    // Writes the lines downwards from `y`, returns where the next line would go
+    fn lines(&mut self, x: f32, y: f32, size: f32, leading: f32, lines: &[String]) -> f32 {
+        let mut y = y;
+        for line in lines {
+            self.text(x, y, size, false, line);
+            y -= leading;
+        }
+        y
+    }
+
+    // Scales the image to fit the box, centered horizontally and aligned to the top
+    fn image(&mut self, image: BrochureImage, x: f32, top: f32, width: f32, height: f32) {
+        let scale = (width / image.width as f32).min(height / image.height as f32);
+        let (drawn_width, drawn_height) = (image.width as f32 * scale, image.height as f32 * scale);
+        let name = Name(IMAGE_NAMES[self.images.len()]);
+
+        self.content.save_state();
+        self.content.transform([
+            drawn_width,
+            0.0,
+            0.0,
+            drawn_height,
+            x + (width - drawn_width) / 2.0,
+            top - drawn_height,
+        ]);
+        self.content.x_object(name);
+        self.content.restore_state();
+        self.images.push((name, image));
+    }
+
+    fn qr_code(&mut self, code: &QrCode, x: f32, y: f32, size: f32) {
+        let modules = code.width();
+        // room for the quiet zone around the code
+        let module_size = size / (modules + 2) as f32;
+        self.fill((0.0, 0.0, 0.0));
+        for (index, color) in code.to_colors().iter().enumerate() {
+            if *color == Color::Dark {
+                let (column, row) = (index % modules, index / modules);
+                self.content.rect(
+                    x + (column + 1) as f32 * module_size,
+                    y + size - (row + 2) as f32 * module_size,
+                    module_size,
+                    module_size,
+                );
+            }
+        }
+        self.content.fill_nonzero();
+    }
+}
+
+fn details(property: &Property, labels: &Labels) -> (Vec<String>, Vec<String>) {
+    let specifications: Option<Specifications> =
+        serde_json::from_value(property.specifications.clone()).ok();
+    let measurements: Option<Measurements> =
+        serde_json::from_value(property.measurements.clone()).ok();
+
+    let mut left = vec![format!(
+        "{}: {}",
+        labels.building_type,
+        title_case(&property.building_type)
+    )];
+    if let Some(specifications) = specifications {
+        for (label, value, unit) in [
+            (labels.bedrooms, specifications.bedrooms, ""),
+            (labels.bathrooms, specifications.bathrooms, ""),
+            (labels.garage, specifications.garage, ""),
+            (labels.carport, specifications.carport, ""),
+            (
+                labels.electrical_power,
+                specifications.electrical_power,
+                " VA",
+            ),
+        ] {
+            if let Some(value) = value {
+                left.push(format!("{}: {}{}", label, value, unit));
+            }
+        }
+    }
+
+    let mut right = vec![];
+    if let Some(measurements) = measurements {
+        for (label, value, unit) in [
+            (labels.land_area, measurements.land_area, " m\u{b2}"),
+            (labels.building_area, measurements.building_area, " m\u{b2}"),
+            (labels.building_level, measurements.building_level, ""),
+        ] {
+            if let Some(value) = value {
+                right.push(format!("{}: {}{}", label, value, unit));
+            }
+        }
+    }
+    if !property.building_certificate.is_empty() {
+        right.push(format!(
+            "{}: {}",
+            labels.certificate,
+            property.building_certificate.to_uppercase()
+        ));
+    }
+
+    (left, right)
+}
+
+struct CachedBrochure {
+    // The listing's updated_at it was rendered from, an edit renders it again
+    updated_at: chrono::NaiveDateTime,
+    rendered_at: Instant,
+    pdf: Vec<u8>,
+}
+
+// Rendered brochures per listing and language, kept in memory. Resets when the server restarts.
+static BROCHURES: LazyLock<Mutex<HashMap<(i32, BrochureLanguage), CachedBrochure>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn find_cached(property: &Property, language: BrochureLanguage) -> Option<Vec<u8>> {
+    let brochures = BROCHURES.lock().expect("Brochure cache lock poisoned");
+    brochures
+        .get(&(property.id, language))
+        .filter(|cached| {
+            cached.updated_at == property.updated_at && cached.rendered_at.elapsed() < CACHE_TTL
+        })
+        .map(|cached| cached.pdf.clone())
+}
+
+pub fn cache(property: &Property, language: BrochureLanguage, pdf: &[u8]) {
+    let mut brochures = BROCHURES.lock().expect("Brochure cache lock poisoned");
+
+    brochures.retain(|_, cached| cached.rendered_at.elapsed() < CACHE_TTL);
+    if brochures.len() >= CACHE_SIZE {
+        let oldest = brochures
+            .iter()
+            .min_by_key(|(_, cached)| cached.rendered_at)
+            .map(|(key, _)| *key);
+        if let Some(oldest) = oldest {
+            brochures.remove(&oldest);
+        }
+    }
+    brochures.insert(
+        (property.id, language),
+        CachedBrochure {
+            updated_at: property.updated_at,
+            rendered_at: Instant::now(),
+            pdf: pdf.to_vec(),
+        },
+    );
+}
+
+// One A4 page: header, photos, details, facilities, as much description as fits and the agent
+pub fn render(
+    property: &Property,
+    agent: &Agent,
+    images: BrochureImages,
+    language: BrochureLanguage,
+) -> Result<Vec<u8>, String> {
+    let labels = language.labels();
+    let mut page = Page {
+        content: Content::new(),
+        images: vec![],
+    };
+
+    let mut y = PAGE_HEIGHT - MARGIN - 9.0;
+    page.fill(ACCENT);
+    page.text(MARGIN, y, 9.0, true, "PRIMEPRO INDONESIA");
+
+    y -= 26.0;
+    page.fill(TEXT);
+    for line in wrap(&property.title, 18.0, true, CONTENT_WIDTH)
+        .iter()
+        .take(2)
+    {
+        page.text(MARGIN, y, 18.0, true, line);
+        y -= 22.0;
+    }
+
+    page.fill(MUTED);
+    let address = [&property.street, &property.regency, &property.province]
+        .iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| title_case(part))
+        .collect::<Vec<_>>()
+        .join(", ");
+    page.text(MARGIN, y + 4.0, 10.0, false, &address);
+
+    y -= 20.0;
+    let price = format_price(property, labels);
+    page.fill(ACCENT);
+    page.text(MARGIN, y, 16.0, true, &price);
+    let status = match property.purchase_status {
+        PurchaseStatus::ForSale => labels.for_sale,
+        PurchaseStatus::ForRent => labels.for_rent,
+        PurchaseStatus::ForSaleOrRent => labels.for_sale_or_rent,
+    };
+    page.fill(MUTED);
+    page.text(
+        MARGIN + text_width(&price, 16.0, true) + 12.0,
+        y,
+        10.0,
+        false,
+        status,
+    );
+
+    y -= 12.0;
+    if let Some(cover) = images.cover {
+        page.image(cover, MARGIN, y, CONTENT_WIDTH, COVER_HEIGHT);
+        y -= COVER_HEIGHT + THUMBNAIL_GAP;
+    }
+    if !images.thumbnails.is_empty() {
+        let width =
+            (CONTENT_WIDTH - THUMBNAIL_GAP * (THUMBNAIL_COUNT - 1) as f32) / THUMBNAIL_COUNT as f32;
+        for (index, thumbnail) in images.thumbnails.into_iter().enumerate() {
+            let x = MARGIN + index as f32 * (width + THUMBNAIL_GAP);
+            page.image(thumbnail, x, y, width, THUMBNAIL_HEIGHT);
+        }
+        y -= THUMBNAIL_HEIGHT + THUMBNAIL_GAP;
+    }
+
+    y -= 16.0;
+    let (left, right) = details(property, labels);
+    let column_x = MARGIN + CONTENT_WIDTH / 2.0;
+    page.fill(ACCENT);
+    page.text(MARGIN, y, 11.0, true, labels.specifications);
+    if !right.is_empty() {
+        page.text(column_x, y, 11.0, true, labels.measurements);
+    }
+    page.fill(TEXT);
+    let left_end = page.lines(MARGIN, y - 15.0, 9.5, 13.0, &left);
+    let right_end = page.lines(column_x, y - 15.0, 9.5, 13.0, &right);
+    y = left_end.min(right_end) - 8.0;
+
+    let facilities: Vec<Facilities> =
+        serde_json::from_value(property.facilities.clone()).unwrap_or_default();
+    if !facilities.is_empty() {
+        let names = facilities
+            .iter()
+            .map(|facility| match language {
+                BrochureLanguage::Id => facility.indonesian_label.clone(),
+                BrochureLanguage::En => title_case(&facility.value.replace(['_', '-'], " ")),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        page.fill(ACCENT);
+        page.text(MARGIN, y, 11.0, true, labels.facilities);
+        page.fill(TEXT);
+        let lines = wrap(&names, 9.5, false, CONTENT_WIDTH);
+        y = page.lines(MARGIN, y - 15.0, 9.5, 13.0, &lines[..lines.len().min(3)]) - 8.0;
+    }
+
+    let room = ((y - 15.0 - FOOTER_TOP - 10.0) / 12.0).floor();
+    if !property.description.trim().is_empty() && room >= 1.0 {
+        let mut lines = wrap(&property.description, 9.0, false, CONTENT_WIDTH);
+        if lines.len() > room as usize {
+            lines.truncate(room as usize);
+            if let Some(last) = lines.last_mut() {
+                last.push_str(" ...");
+            }
+        }
+        page.fill(ACCENT);
+        page.text(MARGIN, y, 11.0, true, labels.description);
+        page.fill(TEXT);
+        page.lines(MARGIN, y - 15.0, 9.0, 12.0, &lines);
+    }
+
+    page.fill((0.85, 0.85, 0.85));
+    page.content.rect(MARGIN, FOOTER_TOP, CONTENT_WIDTH, 0.75);
+    page.content.fill_nonzero();
+
+    let photo_top = FOOTER_TOP - 20.0;
+    let mut contact_x = MARGIN;
+    if let Some(photo) = images.agent_photo {
+        page.image(photo, MARGIN, photo_top, PHOTO_SIZE, PHOTO_SIZE);
+        contact_x += PHOTO_SIZE + 12.0;
+    }
+    page.fill(MUTED);
+    page.text(contact_x, photo_top - 9.0, 9.0, false, labels.contact);
+    page.fill(TEXT);
+    page.text(
+        contact_x,
+        photo_top - 26.0,
+        13.0,
+        true,
+        &title_case(&agent.fullname),
+    );
+    page.text(
+        contact_x,
+        photo_top - 42.0,
+        10.0,
+        false,
+        &agent.phone_number,
+    );
+    page.text(contact_x, photo_top - 56.0, 10.0, false, &agent.email);
+
+    let code = QrCode::new(listing_url(&property.id).as_bytes()).map_err(|err| err.to_string())?;
+    let qr_x = MARGIN + CONTENT_WIDTH - QR_SIZE;
+    let qr_y = photo_top - QR_SIZE + 6.0;
+    page.qr_code(&code, qr_x, qr_y, QR_SIZE);
+    page.fill(MUTED);
+    let scan_width = text_width(labels.scan, 7.5, false);
+    page.text(
+        qr_x + (QR_SIZE - scan_width) / 2.0,
+        qr_y - 6.0,
+        7.5,
+        false,
+        labels.scan,
+    );
+
+    Ok(write_pdf(page))
+}
+
+fn write_pdf(page: Page) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let content_id = Ref::new(4);
+    let regular_font_id = Ref::new(5);
+    let bold_font_id = Ref::new(6);
+    let image_ids: Vec<Ref> = (0..page.images.len())
+        .map(|index| Ref::new(7 + index as i32))
+        .collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+
+    let mut pdf_page = pdf.page(page_id);
+    pdf_page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+    pdf_page.parent(page_tree_id);
+    pdf_page.contents(content_id);
+    let mut resources = pdf_page.resources();
+    resources
+        .fonts()
+        .pair(Name(b"F1"), regular_font_id)
+        .pair(Name(b"F2"), bold_font_id);
+    let mut x_objects = resources.x_objects();
+    for ((name, _), id) in page.images.iter().zip(&image_ids) {
+        x_objects.pair(*name, *id);
+    }
+    x_objects.finish();
+    resources.finish();
+    pdf_page.finish();
+
+    for (id, font) in [
+        (regular_font_id, Name(b"Helvetica")),
+        (bold_font_id, Name(b"Helvetica-Bold")),
+    ] {
+        pdf.type1_font(id)
+            .base_font(font)
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+    }
+
+    for ((_, image), id) in page.images.iter().zip(&image_ids) {
+        let mut xobject = pdf.image_xobject(*id, &image.jpeg);
+        xobject.filter(Filter::DctDecode);
+        xobject.width(image.width as i32);
+        xobject.height(image.height as i32);
+        xobject.color_space().device_rgb();
+        xobject.bits_per_component(8);
+        xobject.finish();
+    }
+
+    pdf.stream(content_id, &page.content.finish());
+    pdf.finish()
+}
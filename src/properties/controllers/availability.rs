@@ -0,0 +1,522 @@
+use axum::body::Body;
+use axum::extract::{Json, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use chrono::NaiveDate;
+use diesel::prelude::{AsChangeset, Insertable};
+use diesel::result::DatabaseErrorKind;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::agents::{can_on, Action, Agent, Resource};
+use crate::db::DbPool;
+use crate::ical;
+use crate::middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session};
+use crate::properties::availability::{
+    is_public_ip, sync_feed, AvailabilityError, AvailabilityRange, BusyRange, CalendarFeed,
+    SeasonalPrice, SeasonalPriceError,
+};
+use crate::properties::enumerates::{AvailabilityKind, Currency, RentTime};
+use crate::properties::model::Property;
+use crate::schema;
+
+const DEFAULT_RANGE_DAYS: i64 = 365;
+const MAX_RANGE_DAYS: i64 = 731;
+// How far back the exported calendar goes
+const EXPORT_PAST_DAYS: i64 = 30;
+
+#[derive(Debug, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = schema::property_availability)]
+#[diesel(treat_none_as_null = true)]
+pub(crate) struct AvailabilityRangePayload {
+    pub(crate) kind: AvailabilityKind,
+    pub(crate) starts_on: NaiveDate,
+    // The check-out day, not included
+    pub(crate) ends_on: NaiveDate,
+    note: Option<String>,
+    lead_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Insertable, AsChangeset)]
+#[diesel(table_name = schema::property_seasonal_prices)]
+pub(crate) struct SeasonalPricePayload {
+    label: String,
+    pub(crate) starts_on: NaiveDate,
+    pub(crate) ends_on: NaiveDate,
+    price: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = schema::property_calendar_feeds)]
+pub(crate) struct CreateCalendarFeedPayload {
+    name: String,
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AvailabilityQuery {
+    from: Option<NaiveDate>,
+    // Not included
+    to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityResponse {
+    currency: Currency,
+    rent_time: Option<RentTime>,
+    busy: Vec<BusyRange>,
+    seasonal_prices: Vec<SeasonalPrice>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AvailabilityRangesResponse {
+    ranges: Vec<AvailabilityRange>,
+    seasonal_prices: Vec<SeasonalPrice>,
+}
+
+impl AvailabilityQuery {
+    // A year from today unless asked otherwise
+    fn period(&self) -> Result<(NaiveDate, NaiveDate), String> {
+        let from = self.from.unwrap_or(chrono::Local::now().date_naive());
+        let to = self
+            .to
+            .unwrap_or(from + chrono::Duration::days(DEFAULT_RANGE_DAYS));
+        if to <= from {
+            return Err("`to` must be after `from`".to_string());
+        }
+        if (to - from).num_days() > MAX_RANGE_DAYS {
+            return Err(format!("At most {} days can be asked for", MAX_RANGE_DAYS));
+        }
+        Ok((from, to))
+    }
+}
+
+impl AvailabilityRangePayload {
+    fn validate(&self) -> Result<(), String> {
+        if self.ends_on <= self.starts_on {
+            return Err("ends_on must be after starts_on".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl SeasonalPricePayload {
+    fn validate(&self) -> Result<(), String> {
+        if self.label.trim().is_empty() || self.label.chars().count() > 255 {
+            return Err("Label must be between 1 and 255 characters".to_string());
+        }
+        if self.ends_on <= self.starts_on {
+            return Err("ends_on must be after starts_on".to_string());
+        }
+        if self.price < 0 {
+            return Err("Price can't be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl CreateCalendarFeedPayload {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.chars().count() > 255 {
+            return Err("Name must be between 1 and 255 characters".to_string());
+        }
+        let url = match reqwest::Url::parse(&self.url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+            _ => return Err("Url must be an http(s) link to an iCal calendar".to_string()),
+        };
+        // names are checked again when the feed is fetched, once they're resolved
+        let is_public = match url.host_str().map(|host| host.trim_matches(['[', ']'])) {
+            Some(host) => match host.parse::<IpAddr>() {
+                Ok(ip) => is_public_ip(&ip),
+                Err(_) => {
+                    let domain = host.trim_end_matches('.').to_lowercase();
+                    domain != "localhost" && !domain.ends_with(".localhost")
+                }
+            },
+            None => false,
+        };
+        match is_public {
+            true => Ok(()),
+            false => Err("Url can't point to a private address".to_string()),
+        }
+    }
+}
+
+// Listings shown on the public site, for the public calendar endpoints
+fn find_public_property<T>(pool: &DbPool, id: &i32) -> Result<Property, AxumResponse<T>> {
+    match Property::find_one_by_id(pool, id) {
+        Ok(property) if !property.0.is_deleted && property.1.is_active => Ok(property.0),
+        Ok(_) | Err(diesel::result::Error::NotFound) => Err(JsonResponse::send(
+            404,
+            None,
+            Some("Property not found".to_string()),
+        )),
+        Err(err) => Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+}
+
+// The listing when the session's agent may do `action` on it
+fn find_property_for<T>(
+    pool: &DbPool,
+    headers: &HeaderMap,
+    id: &i32,
+    action: Action,
+) -> Result<Property, AxumResponse<T>> {
+    let user_id = Session::extract_session_user_id(headers);
+
+    let agent = match Agent::find_by_user_id(pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return Err(JsonResponse::send(403, None, None)),
+    };
+
+    let property = match Property::find_one_by_id(pool, id) {
+        Ok(property) if !property.0.is_deleted => property,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Property not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+
+    if !can_on(&agent, action, Resource::Property, &property.1) {
+        return Err(JsonResponse::send(403, None, None));
+    }
+
+    Ok(property.0)
+}
+
+// Public, which days are taken and the seasonal rents, without notes or leads
+pub async fn find_availability(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+    Query(query): Query<AvailabilityQuery>,
+) -> AxumResponse<AvailabilityResponse> {
+    let property = match find_public_property(&pool, &id) {
+        Ok(property) => property,
+        Err(response) => return response,
+    };
+    let (from, to) = match query.period() {
+        Ok(period) => period,
+        Err(message) => return JsonResponse::send(400, None, Some(message)),
+    };
+
+    let busy = match AvailabilityRange::find_busy(&pool, &id, from, to) {
+        Ok(busy) => busy,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let seasonal_prices = match SeasonalPrice::find_many_by_property(&pool, &id, from, to) {
+        Ok(seasonal_prices) => seasonal_prices,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(AvailabilityResponse {
+            currency: property.currency,
+            rent_time: property.rent_time,
+            busy,
+            seasonal_prices,
+        }),
+        None,
+    )
+}
+
+// The ranges as the agent manages them, with notes, leads and the feed they came from
+pub async fn find_availability_ranges(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Query(query): Query<AvailabilityQuery>,
+) -> AxumResponse<AvailabilityRangesResponse> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::View) {
+        return response;
+    }
+    let (from, to) = match query.period() {
+        Ok(period) => period,
+        Err(message) => return JsonResponse::send(400, None, Some(message)),
+    };
+
+    let ranges = match AvailabilityRange::find_many_by_property(&pool, &id, from, to) {
+        Ok(ranges) => ranges,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let seasonal_prices = match SeasonalPrice::find_many_by_property(&pool, &id, from, to) {
+        Ok(seasonal_prices) => seasonal_prices,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    JsonResponse::send(
+        200,
+        Some(AvailabilityRangesResponse {
+            ranges,
+            seasonal_prices,
+        }),
+        None,
+    )
+}
+
+fn availability_error_response(err: AvailabilityError) -> AxumResponse<AvailabilityRange> {
+    match err {
+        AvailabilityError::Conflict(range) => JsonResponse::send(
+            409,
+            Some(range),
+            Some("Dates overlap another range".to_string()),
+        ),
+        AvailabilityError::Query(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Range not found".to_string()))
+        }
+        AvailabilityError::Query(diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ForeignKeyViolation,
+            _,
+        )) => JsonResponse::send(400, None, Some("Lead not found".to_string())),
+        AvailabilityError::Query(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub async fn create_availability_range(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<AvailabilityRangePayload>,
+) -> AxumResponse<AvailabilityRange> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match AvailabilityRange::create(&pool, &id, &payload) {
+        Ok(range) => JsonResponse::send(201, Some(range), None),
+        Err(err) => availability_error_response(err),
+    }
+}
+
+// Ranges imported from a calendar feed can't be changed here, they come back on its next sync
+pub async fn update_availability_range(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, range_id)): Path<(i32, i32)>,
+    Json(payload): Json<AvailabilityRangePayload>,
+) -> AxumResponse<AvailabilityRange> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match AvailabilityRange::update(&pool, &id, &range_id, &payload) {
+        Ok(range) => JsonResponse::send(200, Some(range), None),
+        Err(err) => availability_error_response(err),
+    }
+}
+
+pub async fn delete_availability_range(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, range_id)): Path<(i32, i32)>,
+) -> AxumResponse<AvailabilityRange> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    match AvailabilityRange::delete(&pool, &id, &range_id) {
+        Ok(range) => JsonResponse::send(200, Some(range), None),
+        Err(err) => availability_error_response(AvailabilityError::Query(err)),
+    }
+}
+
+fn seasonal_price_error_response(err: SeasonalPriceError) -> AxumResponse<SeasonalPrice> {
+    match err {
+        SeasonalPriceError::Conflict(price) => JsonResponse::send(
+            409,
+            Some(price),
+            Some("Dates overlap another season".to_string()),
+        ),
+        SeasonalPriceError::Query(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Seasonal price not found".to_string()))
+        }
+        SeasonalPriceError::Query(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub async fn create_seasonal_price(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<SeasonalPricePayload>,
+) -> AxumResponse<SeasonalPrice> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match SeasonalPrice::create(&pool, &id, &payload) {
+        Ok(price) => JsonResponse::send(201, Some(price), None),
+        Err(err) => seasonal_price_error_response(err),
+    }
+}
+
+pub async fn update_seasonal_price(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, price_id)): Path<(i32, i32)>,
+    Json(payload): Json<SeasonalPricePayload>,
+) -> AxumResponse<SeasonalPrice> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match SeasonalPrice::update(&pool, &id, &price_id, &payload) {
+        Ok(price) => JsonResponse::send(200, Some(price), None),
+        Err(err) => seasonal_price_error_response(err),
+    }
+}
+
+pub async fn delete_seasonal_price(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, price_id)): Path<(i32, i32)>,
+) -> AxumResponse<SeasonalPrice> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    match SeasonalPrice::delete(&pool, &id, &price_id) {
+        Ok(price) => JsonResponse::send(200, Some(price), None),
+        Err(err) => seasonal_price_error_response(SeasonalPriceError::Query(err)),
+    }
+}
+
+pub async fn find_calendar_feeds(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<JsonFindResponse<Vec<CalendarFeed>>> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::View) {
+        return response;
+    }
+
+    match CalendarFeed::find_many_by_property(&pool, &id) {
+        Ok(feeds) => JsonResponse::send(
+            200,
+            Some(JsonFindResponse {
+                total_data: feeds.len() as i64,
+                total_pages: 1,
+                data: feeds,
+            }),
+            None,
+        ),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Synced right away, a feed that can't be read is still saved with the error
+pub async fn create_calendar_feed(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<CreateCalendarFeedPayload>,
+) -> AxumResponse<CalendarFeed> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    let feed = match CalendarFeed::create(&pool, &id, &payload) {
+        Ok(feed) => feed,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let feed_id = feed.id;
+    if let Err(err) = sync_feed(&pool, feed).await {
+        tracing::warn!("First sync of calendar feed {} failed: {}", feed_id, err);
+    }
+
+    match CalendarFeed::find_by_id(&pool, &id, &feed_id) {
+        Ok(feed) => JsonResponse::send(201, Some(feed), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Runs the sync now instead of waiting for its next run
+pub async fn sync_calendar_feed(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, feed_id)): Path<(i32, i32)>,
+) -> AxumResponse<CalendarFeed> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    let feed = match CalendarFeed::find_by_id(&pool, &id, &feed_id) {
+        Ok(feed) => feed,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Calendar feed not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    match sync_feed(&pool, feed).await {
+        Ok(feed) => JsonResponse::send(200, Some(feed), None),
+        Err(err) => JsonResponse::send(500, None, Some(err)),
+    }
+}
+
+pub async fn delete_calendar_feed(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path((id, feed_id)): Path<(i32, i32)>,
+) -> AxumResponse<CalendarFeed> {
+    if let Err(response) = find_property_for(&pool, &headers, &id, Action::Update) {
+        return response;
+    }
+
+    match CalendarFeed::delete(&pool, &id, &feed_id) {
+        Ok(feed) => JsonResponse::send(200, Some(feed), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Calendar feed not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Public iCal feed of the taken days, for Airbnb, Booking.com or a phone calendar to subscribe to
+pub async fn find_calendar(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+) -> Result<Response, AxumResponse<String>> {
+    let property = find_public_property(&pool, &id)?;
+
+    let from = chrono::Local::now().date_naive() - chrono::Duration::days(EXPORT_PAST_DAYS);
+    let ranges = match AvailabilityRange::find_upcoming(&pool, &id, from) {
+        Ok(ranges) => ranges,
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+    let events: Vec<_> = ranges.iter().map(AvailabilityRange::to_event).collect();
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/calendar; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"property-{}.ics\"", id),
+            ),
+        ],
+        Body::from(ical::render(&property.title, &events)),
+    )
+        .into_response())
+}
@@ -0,0 +1,64 @@
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse};
+use crate::properties::brochure::{cache, fetch_images, find_cached, render, BrochureLanguage};
+use crate::properties::model::Property;
+
+#[derive(Debug, Deserialize)]
+pub struct BrochureQuery {
+    #[serde(default)]
+    lang: BrochureLanguage,
+}
+
+// Printable A4 flyer of a listing, `lang` picks the Indonesian (default) or English template
+pub async fn find_brochure(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+    Query(query): Query<BrochureQuery>,
+) -> Result<Response, AxumResponse<String>> {
+    let (property, agent, _) = match Property::find_one_by_id(&pool, &id) {
+        Ok(property) if !property.0.is_deleted && property.1.is_active => property,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Property not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+
+    let pdf = match find_cached(&property, query.lang) {
+        Some(pdf) => Ok(Ok(pdf)),
+        None => {
+            let images = fetch_images(&property, &agent).await;
+            tokio::task::spawn_blocking(move || {
+                let pdf = render(&property, &agent, images, query.lang)?;
+                cache(&property, query.lang, &pdf);
+                Ok(pdf)
+            })
+            .await
+        }
+    };
+
+    match pdf {
+        Ok(Ok(pdf)) => Ok((
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"brochure-{}.pdf\"", id),
+                ),
+            ],
+            Body::from(pdf),
+        )
+            .into_response()),
+        Ok(Err(err)) => Err(JsonResponse::send(500, None, Some(err))),
+        Err(err) => Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
-    agents::{Agent, AgentRole},
+    agents::{authorize, Action, Resource},
     db::DbPool,
-    middleware::{AxumResponse, JsonResponse, Session},
+    middleware::{AxumResponse, JsonResponse},
     properties::Property,
     schema,
 };
@@ -43,25 +43,19 @@ pub async fn update_configurations(
     Path(id): Path<i32>,
     Json(payload): Json<UpdateConfigurationsPayload>,
 ) -> AxumResponse<Property> {
-    let user_id = Session::extract_session_user_id(&headers);
-
-    let is_admin = match Agent::find_by_user_id(&pool, &user_id) {
-        Ok(agent) => match agent.role {
-            AgentRole::Admin => true,
-            _ => false,
-        },
-        Err(_) => false,
-    };
+    if let Err(response) = authorize(
+        &pool,
+        &headers,
+        Action::Update,
+        Resource::PropertyConfigurations,
+    ) {
+        return response;
+    }
 
-    match is_admin {
-        true => {
-            let sql_payload = &payload.to_sql_payload();
+    let sql_payload = &payload.to_sql_payload();
 
-            match Property::update_configurations(&pool, &id, sql_payload) {
-                Ok(property) => JsonResponse::send(200, Some(property), None),
-                Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
-            }
-        }
-        false => JsonResponse::send(403, None, None),
+    match Property::update_configurations(&pool, &id, sql_payload) {
+        Ok(property) => JsonResponse::send(200, Some(property), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
@@ -1,8 +1,11 @@
-use crate::agents::{Agent, AgentRole};
+use crate::agents::{authorize, can_on, Action, Agent, Resource};
 use crate::middleware::Session;
+use crate::projects::Project;
 use crate::properties::enumerates::{Currency, RentTime, SoldChannel, SoldStatus};
 use crate::properties::model::Property;
+use crate::properties::share_link::ShareLink;
 use crate::schema;
+use crate::uploads::Upload;
 use crate::{
     db::DbPool,
     middleware::{AxumResponse, JsonResponse},
@@ -18,32 +21,32 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Images {
-    is_cover: bool,
-    path: String,
-    english_label: String,
-    indonesian_label: String,
+    pub(crate) is_cover: bool,
+    pub(crate) path: String,
+    pub(crate) english_label: String,
+    pub(crate) indonesian_label: String,
 }
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Measurements {
-    land_area: Option<i32>,
-    building_area: Option<i32>,
-    building_level: Option<i32>,
+    pub(crate) land_area: Option<i32>,
+    pub(crate) building_area: Option<i32>,
+    pub(crate) building_level: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Specifications {
-    bedrooms: Option<i32>,
-    bathrooms: Option<i32>,
-    garage: Option<i32>,
-    carport: Option<i32>,
-    electrical_power: Option<i32>,
+    pub(crate) bedrooms: Option<i32>,
+    pub(crate) bathrooms: Option<i32>,
+    pub(crate) garage: Option<i32>,
+    pub(crate) carport: Option<i32>,
+    pub(crate) electrical_power: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub(crate) struct Facilities {
-    value: String,
-    indonesian_label: String,
+    pub(crate) value: String,
+    pub(crate) indonesian_label: String,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -72,6 +75,9 @@ pub(crate) struct CreateUpdatePropertyApiPayload {
     price_down_payment: Option<i64>,
     developer_id: Option<i32>,
     bank_id: Option<i32>,
+    project_id: Option<i32>,
+    rent_price: Option<i64>,
+    min_rent_duration: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize, Insertable, AsChangeset)]
@@ -102,10 +108,42 @@ pub struct CreateUpdatePropertySqlPayload {
     price_down_payment: Option<i64>,
     developer_id: Option<i32>,
     bank_id: Option<i32>,
+    project_id: Option<i32>,
+    rent_price: Option<i64>,
+    min_rent_duration: Option<i32>,
 }
 
 impl CreateUpdatePropertyApiPayload {
-    fn to_sql_payload(self) -> CreateUpdatePropertySqlPayload {
+    // A listing for rent keeps its rent in `price`, only sale or rent needs both
+    fn validate(&self) -> Result<(), String> {
+        if self.rent_price.is_some()
+            && !matches!(self.purchase_status, PurchaseStatus::ForSaleOrRent)
+        {
+            return Err("rent_price is only for listings for sale or rent".to_string());
+        }
+        if self.rent_price.is_some_and(|rent_price| rent_price < 0) {
+            return Err("rent_price can't be negative".to_string());
+        }
+        if let Some(min_rent_duration) = self.min_rent_duration {
+            if matches!(self.purchase_status, PurchaseStatus::ForSale) {
+                return Err("min_rent_duration is only for listings for rent".to_string());
+            }
+            if min_rent_duration <= 0 {
+                return Err("min_rent_duration must be positive".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // A unit of a project always belongs to the project's developer, NotFound for an unknown project
+    fn apply_project(&mut self, pool: &DbPool) -> diesel::QueryResult<()> {
+        if let Some(project_id) = self.project_id {
+            self.developer_id = Some(Project::find_by_id(pool, &project_id)?.developer_id);
+        }
+        Ok(())
+    }
+
+    fn into_sql_payload(self) -> CreateUpdatePropertySqlPayload {
         let purchase_status_slug = &self.purchase_status.to_slug();
         let building_type_slug = &self.building_type.trim().replace(" ", "-").to_lowercase();
         let province_slug = &self.province.trim().replace(" ", "-").to_lowercase();
@@ -129,10 +167,7 @@ impl CreateUpdatePropertyApiPayload {
             building_type: self.building_type.to_lowercase(),
             building_condition: self.building_condition,
             building_furniture_capacity: self.building_furniture_capacity,
-            building_certificate: match self.building_certificate {
-                Some(cert) => Some(cert.to_lowercase()),
-                None => None,
-            },
+            building_certificate: self.building_certificate.map(|cert| cert.to_lowercase()),
             specifications: serde_json::json!(&self.specifications),
             facilities: serde_json::json!(&self.facilities),
             sold_channel: self.sold_channel,
@@ -142,17 +177,64 @@ impl CreateUpdatePropertyApiPayload {
             price_down_payment: self.price_down_payment,
             developer_id: self.developer_id,
             bank_id: self.bank_id,
+            project_id: self.project_id,
+            rent_price: self.rent_price,
+            min_rent_duration: self.min_rent_duration,
         }
     }
 }
 
+// Listing photos have to be uploaded with `POST /uploads?kind=property`, so only
+// watermarked sizes are ever public. On an update the photos it already has pass.
+fn check_images<T>(
+    pool: &DbPool,
+    payload: &CreateUpdatePropertyApiPayload,
+    property_id: Option<i32>,
+) -> Result<(), AxumResponse<T>> {
+    let paths: Vec<String> = payload
+        .images
+        .iter()
+        .map(|image| image.path.clone())
+        .collect();
+
+    match Upload::find_unwatermarked_paths(pool, &paths, property_id) {
+        Ok(unwatermarked) if unwatermarked.is_empty() => Ok(()),
+        Ok(unwatermarked) => Err(JsonResponse::send(
+            400,
+            None,
+            Some(format!(
+                "Images must be uploaded as listing photos: {}",
+                unwatermarked.join(", ")
+            )),
+        )),
+        Err(err) => Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+}
+
 pub async fn create_property(
     State(pool): State<DbPool>,
     headers: HeaderMap,
     Json(payload): Json<CreateUpdatePropertyApiPayload>,
 ) -> AxumResponse<Property> {
-    let user_id = Session::extract_session_user_id(&headers);
-    let sql_payload = payload.to_sql_payload();
+    let user_id = match authorize(&pool, &headers, Action::Create, Resource::OwnProperty) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    if let Err(response) = check_images(&pool, &payload, None) {
+        return response;
+    }
+    let mut payload = payload;
+    match payload.apply_project(&pool) {
+        Ok(_) => {}
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(400, None, Some("Project not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    }
+    let sql_payload = payload.into_sql_payload();
 
     match Property::create(&pool, &user_id, &sql_payload) {
         Ok(property) => JsonResponse::send(201, Some(property), None),
@@ -168,11 +250,8 @@ pub async fn update_property(
 ) -> AxumResponse<Property> {
     let user_id = Session::extract_session_user_id(&headers);
 
-    let is_admin = match Agent::find_by_user_id(&pool, &user_id) {
-        Ok(agent) => match agent.role {
-            AgentRole::Admin => true,
-            _ => false,
-        },
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
         Err(err) => return JsonResponse::send(400, None, Some(err.to_string())),
     };
 
@@ -181,11 +260,34 @@ pub async fn update_property(
         Err(err) => return JsonResponse::send(400, None, Some(err.to_string())),
     };
 
-    if property.0.user_id != user_id && !is_admin {
+    if !can_on(&agent, Action::Update, Resource::Property, &property.1) {
         return JsonResponse::send(403, None, Some("Forbidden".to_string()));
     }
 
-    let sql_payload = payload.to_sql_payload();
+    if let Err(message) = payload.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+    if let Err(response) = check_images(&pool, &payload, Some(id)) {
+        return response;
+    }
+
+    // a sale without a channel goes to the share link behind the latest tracked lead
+    let mut payload = payload;
+    if matches!(payload.sold_status, Some(SoldStatus::Sold)) && payload.sold_channel.is_none() {
+        match ShareLink::attributed_channel(&pool, &id) {
+            Ok(channel) => payload.sold_channel = channel,
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        }
+    }
+    match payload.apply_project(&pool) {
+        Ok(_) => {}
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(400, None, Some("Project not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    }
+
+    let sql_payload = payload.into_sql_payload();
 
     match Property::update(&pool, &id, &sql_payload) {
         Ok(property) => JsonResponse::send(200, Some(property), None),
@@ -1,4 +1,4 @@
-use crate::agents::{Agent, AgentRole};
+use crate::agents::{can_on, Action, Agent, Resource};
 use crate::leads::Lead;
 use crate::middleware::Session;
 use crate::properties::model::Property;
@@ -16,31 +16,26 @@ pub async fn delete_property(
 ) -> AxumResponse<Property> {
     let user_id = Session::extract_session_user_id(&headers);
 
-    let role = match Agent::find_by_user_id(&pool, &user_id) {
-        Ok(agent) => agent.role,
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
         Err(err) => return JsonResponse::send(401, None, Some(err.to_string())),
     };
 
-    let is_admin = match role {
-        AgentRole::Admin => true,
-        AgentRole::Agent => false,
-    };
-
     let property = match Property::find_one_by_id(&pool, &id) {
         Ok(property) => property,
         Err(err) => return JsonResponse::send(400, None, Some(err.to_string())),
     };
 
-    if property.0.user_id != user_id && !is_admin {
+    if !can_on(&agent, Action::Delete, Resource::Property, &property.1) {
         return JsonResponse::send(403, None, Some("Forbidden".to_string()));
     }
 
     // Update leads is_deleted to true
-    if let AgentRole::Agent = role {
+    if !agent.role.can(Action::Delete, Resource::Property) {
         let _ = Lead::delete_by_property_id(&pool, &property.0.id);
     }
 
-    match Property::delete(&pool, &id, &role) {
+    match Property::delete(&pool, &id, &agent.role) {
         Ok(property) => JsonResponse::send(200, Some(property), None),
         Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
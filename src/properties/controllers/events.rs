@@ -0,0 +1,124 @@
+use axum::extract::{ConnectInfo, Extension, Json, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use crate::agents::{can_on, check_period, period, Action, Agent, Resource};
+use crate::db::DbPool;
+use crate::leads::{client_ip, LeadSettings};
+use crate::middleware::{AxumResponse, JsonResponse, Session};
+use crate::properties::engagement::{PropertyEngagement, PropertyEngagementReport, VisitorHasher};
+use crate::properties::enumerates::PropertyEventType;
+use crate::properties::model::Property;
+use crate::rate_limit::RateLimiter;
+
+const IP_EVENT_LIMIT: usize = 120;
+const IP_EVENT_WINDOW: Duration = Duration::from_secs(10 * 60);
+// The daily report lists every day of the range
+const MAX_REPORT_DAYS: i64 = 92;
+
+// Events per client ip
+static IP_EVENTS: LazyLock<RateLimiter> =
+    LazyLock::new(|| RateLimiter::new(IP_EVENT_LIMIT, IP_EVENT_WINDOW));
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePropertyEventPayload {
+    event_type: PropertyEventType,
+    visitor_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PropertyEventsQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+// Without a visitor id from the site the ip and user agent stand in for one
+fn visitor_hash(
+    hasher: &VisitorHasher,
+    headers: &HeaderMap,
+    ip: &str,
+    visitor_id: &Option<String>,
+) -> String {
+    match visitor_id.as_deref().map(str::trim) {
+        Some(visitor_id) if !visitor_id.is_empty() => hasher.hash(&format!("id:{}", visitor_id)),
+        _ => {
+            let user_agent = headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            hasher.hash(&format!("ip:{}|{}", ip, user_agent))
+        }
+    }
+}
+
+// Public, repeated events of a visitor on the same day are accepted but not counted
+pub async fn create_property_event(
+    State(pool): State<DbPool>,
+    Extension(settings): Extension<Arc<LeadSettings>>,
+    Extension(hasher): Extension<Arc<VisitorHasher>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<CreatePropertyEventPayload>,
+) -> AxumResponse<String> {
+    let ip = client_ip(&headers, &addr, &settings.trusted_proxies);
+    if !IP_EVENTS.check(&ip) {
+        return JsonResponse::send(429, None, Some("Too many events".to_string()));
+    }
+
+    match Property::find_one_by_id(&pool, &id) {
+        Ok(property) if !property.0.is_deleted => {}
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Property not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let visitor_hash = visitor_hash(&hasher, &headers, &ip, &payload.visitor_id);
+    let ip_hash = hasher.hash(&format!("ip:{}", ip));
+    match PropertyEngagement::record(&pool, &id, payload.event_type, &visitor_hash, &ip_hash) {
+        Ok(_) => JsonResponse::send(202, None, None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Daily counts of a listing, for agents who can see it
+pub async fn find_property_events(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Query(query): Query<PropertyEventsQuery>,
+) -> AxumResponse<PropertyEngagementReport> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let property = match Property::find_one_by_id(&pool, &id) {
+        Ok(property) => property,
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Property not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    if !can_on(&agent, Action::View, Resource::Property, &property.1) {
+        return JsonResponse::send(403, None, None);
+    }
+
+    let (from, to) = period(query.from, query.to);
+    if let Err(message) = check_period(from, to, MAX_REPORT_DAYS) {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match PropertyEngagement::find_report(&pool, &id, from, to) {
+        Ok(report) => JsonResponse::send(200, Some(report), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
@@ -2,23 +2,28 @@ use crate::{
     agents::Agent,
     db::DbPool,
     developers::Developer,
+    exchange_rates::ExchangeRates,
     middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session},
+    properties::engagement::PropertyEngagement,
     properties::model::Property,
 };
 use crate::{
-    agents::AgentRole,
-    properties::enumerates::{PurchaseStatus, SoldStatus},
+    agents::{scope, Action, Resource, Scope},
+    properties::enumerates::{Currency, PurchaseStatus, SoldStatus},
 };
 use axum::{
     extract::{Path, Query, State},
-    http::HeaderMap,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Deserialize, Debug)]
 pub enum FindPropertySort {
     LowestPrice,
     HighestPrice,
+    MostViewed,
 }
 #[derive(Deserialize, Default, Debug)]
 pub struct FindPropertyQuery {
@@ -35,38 +40,128 @@ pub struct FindPropertyQuery {
     pub building_type: Option<String>,
     pub sort: Option<FindPropertySort>,
     pub developer_id: Option<i32>,
+    pub project_id: Option<i32>,
     pub bank_id: Option<i32>,
+    pub branch_id: Option<i32>,
     pub ids: Option<String>,
+    // In display_currency, IDR when it isn't given, a monthly rent with purchase_status=ForRent
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+    // Listings for rent whose minimum rental duration fits this many days
+    pub rent_days: Option<i32>,
+    // Converts the prices in the response
+    pub display_currency: Option<Currency>,
+}
+
+impl FindPropertyQuery {
+    // Listings are filtered on their rupiah price
+    fn price_range_in_idr(&mut self, rates: &ExchangeRates) -> Result<(), String> {
+        let currency = match self.display_currency {
+            Some(currency) => currency,
+            None => return Ok(()),
+        };
+        for price in [&mut self.min_price, &mut self.max_price]
+            .into_iter()
+            .flatten()
+        {
+            *price = rates
+                .convert(*price, currency, Currency::Idr)
+                .ok_or(format!("No exchange rate for {:?}", currency))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DisplayCurrencyQuery {
+    display_currency: Option<Currency>,
+}
+
+// Rates are only loaded when prices are shown in another currency
+fn find_display_rates(
+    pool: &DbPool,
+    display_currency: Option<Currency>,
+) -> diesel::QueryResult<Option<ExchangeRates>> {
+    match display_currency {
+        Some(_) => ExchangeRates::find(pool).map(Some),
+        None => Ok(None),
+    }
 }
 
 pub(crate) type PropertyWithRelation = (Property, Agent, Option<Developer>);
+// Engagement counts are only included for agents
+pub(crate) type PropertyWithEngagement = (
+    Property,
+    Agent,
+    Option<Developer>,
+    Option<PropertyEngagement>,
+);
 
 pub async fn find_many_properties(
     State(pool): State<DbPool>,
     headers: HeaderMap,
     Query(query): Query<FindPropertyQuery>,
-) -> AxumResponse<JsonFindResponse<Vec<PropertyWithRelation>>> {
+) -> AxumResponse<JsonFindResponse<Vec<PropertyWithEngagement>>> {
+    let rates = match find_display_rates(&pool, query.display_currency) {
+        Ok(rates) => rates,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+    let mut query = query;
+    if let Some(rates) = &rates {
+        if let Err(message) = query.price_range_in_idr(rates) {
+            return JsonResponse::send(400, None, Some(message));
+        }
+    }
+
     let header_user_id = headers.get("x-user-id");
-    let (user_id, role) = match header_user_id {
+    let scope = match header_user_id {
         Some(_) => {
             let user_id = Session::extract_session_user_id(&headers);
-            match Agent::find_by_user_id(&pool, &user_id) {
-                Ok(agent) => (Some(user_id), Some(agent.role)),
+            let agent = match Agent::find_by_user_id(&pool, &user_id) {
+                Ok(agent) => agent,
                 _ => {
                     return JsonResponse::send(403, None, None);
                 }
+            };
+            match scope(&agent, Action::View, Resource::Property) {
+                Some(scope) => Some(scope),
+                None => return JsonResponse::send(403, None, None),
             }
         }
-        None => (None, None),
+        None => None,
     };
 
-    let property_with_agent = match Property::find_many(&pool, &user_id, &role, &query) {
+    let property_with_agent = match Property::find_many(&pool, &scope, &query) {
         Ok(property_with_agent) => property_with_agent,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
 
-    let total_property_count = match Property::count_find_many_rows(&pool, &user_id, &role, &query)
-    {
+    let mut engagements = HashMap::new();
+    if scope.is_some() {
+        let ids: Vec<i32> = property_with_agent
+            .iter()
+            .map(|(property, _, _)| property.id)
+            .collect();
+        engagements = match PropertyEngagement::find_totals(&pool, &ids) {
+            Ok(engagements) => engagements,
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        };
+    }
+    let property_with_agent: Vec<PropertyWithEngagement> = property_with_agent
+        .into_iter()
+        .map(|(mut property, agent, developer)| {
+            if let (Some(rates), Some(currency)) = (&rates, query.display_currency) {
+                property.display_in(rates, currency);
+            }
+            let engagement = match scope {
+                Some(_) => Some(engagements.remove(&property.id).unwrap_or_default()),
+                None => None,
+            };
+            (property, agent, developer, engagement)
+        })
+        .collect();
+
+    let total_property_count = match Property::count_find_many_rows(&pool, &scope, &query) {
         Ok(property_with_agent_count) => property_with_agent_count,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
@@ -90,9 +185,20 @@ pub async fn find_many_properties(
 pub async fn find_one_by_id(
     State(pool): State<DbPool>,
     Path(id): Path<i32>,
+    Query(query): Query<DisplayCurrencyQuery>,
 ) -> AxumResponse<PropertyWithRelation> {
+    let rates = match find_display_rates(&pool, query.display_currency) {
+        Ok(rates) => rates,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
     match Property::find_one_by_id(&pool, &id) {
-        Ok(property) => JsonResponse::send(200, Some(property), None),
+        Ok(mut property) => {
+            if let (Some(rates), Some(currency)) = (&rates, query.display_currency) {
+                property.0.display_in(rates, currency);
+            }
+            JsonResponse::send(200, Some(property), None)
+        }
         Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
     }
 }
@@ -150,33 +256,61 @@ pub struct AgentWithProperties {
     properties: Vec<PropertyWithRelation>,
 }
 
-pub async fn find_many_by_agent_name(
+pub async fn find_many_by_agent_slug(
     State(pool): State<DbPool>,
-    Path(name): Path<String>,
-) -> AxumResponse<AgentWithProperties> {
-    let agent_name = name.replace("-", " ");
-    let agent = match Agent::find_by_name(&pool, &agent_name) {
+    Path(slug): Path<String>,
+) -> Response {
+    let agent = match Agent::find_by_slug(&pool, &slug) {
         Ok(data) => data,
-        Err(err) => return JsonResponse::send(400, None, Some(err.to_string())),
+        Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::<AgentWithProperties>::send(
+                404,
+                None,
+                Some("Agent not found".to_string()),
+            )
+            .into_response()
+        }
+        Err(err) => {
+            return JsonResponse::<AgentWithProperties>::send(500, None, Some(err.to_string()))
+                .into_response()
+        }
     };
+
+    if agent.slug != slug {
+        let location = format!("/properties/agents/{}", agent.slug);
+        return (
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, location)],
+        )
+            .into_response();
+    }
+
     let properties = match Property::find_many(
         &pool,
-        &Some(agent.id),
-        &Some(AgentRole::Agent),
+        &Some(Scope::Own(agent.id)),
         &FindPropertyQuery::default(),
     ) {
         Ok(property) => property,
-        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        Err(err) => {
+            return JsonResponse::<AgentWithProperties>::send(500, None, Some(err.to_string()))
+                .into_response()
+        }
     };
 
     let agent_with_properties = AgentWithProperties { agent, properties };
-    JsonResponse::send(200, Some(agent_with_properties), None)
+    JsonResponse::send(200, Some(agent_with_properties), None).into_response()
 }
 
 pub async fn find_many_related(
     State(pool): State<DbPool>,
     Path(id): Path<i32>,
+    Query(display): Query<DisplayCurrencyQuery>,
 ) -> AxumResponse<Vec<PropertyWithRelation>> {
+    let rates = match find_display_rates(&pool, display.display_currency) {
+        Ok(rates) => rates,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
     let property = match Property::find_one_by_id(&pool, &id) {
         Ok(property) => property,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
@@ -189,10 +323,15 @@ pub async fn find_many_related(
         }
     };
 
-    let property_with_agent = match Property::find_many_related(&pool, &id, &query) {
+    let mut property_with_agent = match Property::find_many_related(&pool, &id, &query) {
         Ok(property_with_agent) => property_with_agent,
         Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
     };
+    if let (Some(rates), Some(currency)) = (&rates, display.display_currency) {
+        for (property, _, _) in property_with_agent.iter_mut() {
+            property.display_in(rates, currency);
+        }
+    }
 
     JsonResponse::send(200, Some(property_with_agent), None)
 }
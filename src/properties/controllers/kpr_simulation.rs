@@ -0,0 +1,74 @@
+use axum::extract::{Path, Query, State};
+use bigdecimal::{BigDecimal, RoundingMode};
+use serde::Deserialize;
+
+use crate::db::DbPool;
+use crate::kpr::{simulate_products, KprSimulationRequest, KprSimulations};
+use crate::middleware::{AxumResponse, JsonResponse};
+use crate::properties::enumerates::{Currency, PurchaseStatus};
+use crate::properties::model::Property;
+
+#[derive(Debug, Deserialize)]
+pub struct KprSimulationQuery {
+    // Years
+    tenor: Option<i32>,
+    // Percentage of the price
+    dp: Option<BigDecimal>,
+    bank_id: Option<i32>,
+}
+
+// Without a dp the listing's own down payment is used, when the product allows it
+pub async fn find_kpr_simulation(
+    State(pool): State<DbPool>,
+    Path(id): Path<i32>,
+    Query(query): Query<KprSimulationQuery>,
+) -> AxumResponse<KprSimulations> {
+    let property = match Property::find_one_by_id(&pool, &id) {
+        Ok((property, agent, _)) if !property.is_deleted && agent.is_active => property,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Property not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    if let PurchaseStatus::ForRent = property.purchase_status {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("KPR simulation is only available for listings for sale".to_string()),
+        );
+    }
+    if property.currency != Currency::Idr {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("KPR simulation is only available for listings priced in IDR".to_string()),
+        );
+    }
+
+    let suggested_down_payment_percent = property
+        .price_down_payment
+        .filter(|_| property.price > 0)
+        .map(|down_payment| {
+            (BigDecimal::from(down_payment) * BigDecimal::from(100)
+                / BigDecimal::from(property.price))
+            .with_scale_round(2, RoundingMode::HalfUp)
+        });
+    let request = KprSimulationRequest {
+        price: property.price,
+        down_payment_percent: query.dp,
+        suggested_down_payment_percent,
+        tenor_years: query.tenor,
+        bank_id: query.bank_id,
+        product_id: None,
+        product_revision: None,
+    };
+    if let Err(message) = request.validate() {
+        return JsonResponse::send(400, None, Some(message));
+    }
+
+    match simulate_products(&pool, &request) {
+        Ok(simulations) => JsonResponse::send(200, Some(simulations), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
@@ -2,14 +2,25 @@ use crate::db::DbPool;
 use axum::routing::{delete, get, post, put};
 use axum::Router;
 
+mod availability;
+mod brochure;
 mod configurations;
 mod create_update;
 mod delete;
+mod events;
 mod find;
+mod kpr_simulation;
+mod share_links;
 
+pub(crate) use availability::{
+    AvailabilityRangePayload, CreateCalendarFeedPayload, SeasonalPricePayload,
+};
 pub(crate) use configurations::UpdateConfigurationsSqlPayload;
-pub(crate) use create_update::CreateUpdatePropertySqlPayload;
+pub(crate) use create_update::{
+    CreateUpdatePropertySqlPayload, Facilities, Images, Measurements, Specifications,
+};
 pub(crate) use find::{FindPropertyQuery, FindPropertySort, PropertyWithRelation};
+pub(crate) use share_links::CreateShareLinkPayload;
 
 pub fn property_routes() -> Router<DbPool> {
     Router::new()
@@ -18,13 +29,67 @@ pub fn property_routes() -> Router<DbPool> {
         .route("/site-paths", get(find::find_site_paths))
         .route("/navigation", get(find::find_navigation))
         .route("/agents", get(find::find_all_property_agents))
-        .route("/agents/{name}", get(find::find_many_by_agent_name))
+        .route("/agents/{slug}", get(find::find_many_by_agent_slug))
         .route("/related/{id}", get(find::find_many_related))
         .route("/{id}", get(find::find_one_by_id))
         .route("/{id}", put(create_update::update_property))
         .route("/{id}", delete(delete::delete_property))
+        .route("/{id}/brochure.pdf", get(brochure::find_brochure))
+        .route(
+            "/{id}/kpr-simulation",
+            get(kpr_simulation::find_kpr_simulation),
+        )
+        .route(
+            "/{id}/share-links",
+            post(share_links::create_share_link).get(share_links::find_share_links),
+        )
+        .route(
+            "/{id}/events",
+            post(events::create_property_event).get(events::find_property_events),
+        )
+        .route(
+            "/{id}/availability",
+            get(availability::find_availability).post(availability::create_availability_range),
+        )
+        .route(
+            "/{id}/availability/ranges",
+            get(availability::find_availability_ranges),
+        )
+        .route(
+            "/{id}/availability/{range_id}",
+            put(availability::update_availability_range)
+                .delete(availability::delete_availability_range),
+        )
+        .route(
+            "/{id}/seasonal-prices",
+            post(availability::create_seasonal_price),
+        )
+        .route(
+            "/{id}/seasonal-prices/{price_id}",
+            put(availability::update_seasonal_price).delete(availability::delete_seasonal_price),
+        )
+        .route(
+            "/{id}/calendar-feeds",
+            get(availability::find_calendar_feeds).post(availability::create_calendar_feed),
+        )
+        .route(
+            "/{id}/calendar-feeds/{feed_id}",
+            delete(availability::delete_calendar_feed),
+        )
+        .route(
+            "/{id}/calendar-feeds/{feed_id}/sync",
+            post(availability::sync_calendar_feed),
+        )
+        .route("/{id}/calendar.ics", get(availability::find_calendar))
         .route(
             "/configurations/{id}",
             put(configurations::update_configurations),
         )
 }
+
+// Short links, served outside /properties to keep them short
+pub fn share_link_routes() -> Router<DbPool> {
+    Router::new()
+        .route("/{code}", get(share_links::redirect_share_link))
+        .route("/{code}/qr.png", get(share_links::find_share_link_qr))
+}
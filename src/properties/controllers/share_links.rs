@@ -0,0 +1,213 @@
+use axum::extract::{Json, Path, Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Redirect, Response};
+use diesel::prelude::Insertable;
+use image::codecs::png::PngEncoder;
+use image::{GrayImage, Luma};
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+
+use crate::agents::{can_on, scope, Action, Agent, Resource};
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonFindResponse, JsonResponse, Session};
+use crate::properties::enumerates::SoldChannel;
+use crate::properties::model::{listing_url, Property};
+use crate::properties::share_link::{share_url, ShareLink};
+use crate::schema;
+
+const DEFAULT_QR_SIZE: u32 = 512;
+const MAX_QR_SIZE: u32 = 2048;
+
+#[derive(Debug, Deserialize, Insertable)]
+#[diesel(table_name = schema::share_links)]
+pub(crate) struct CreateShareLinkPayload {
+    channel: SoldChannel,
+    campaign: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLinkResponse {
+    #[serde(flatten)]
+    link: ShareLink,
+    url: String,
+    lead_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QrQuery {
+    size: Option<u32>,
+}
+
+// The link is tagged with the agent creating it
+pub async fn create_share_link(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Json(payload): Json<CreateShareLinkPayload>,
+) -> AxumResponse<ShareLinkResponse> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let agent = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => agent,
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let property = match Property::find_one_by_id(&pool, &id) {
+        Ok(property) if !property.0.is_deleted => property,
+        Ok(_) | Err(diesel::result::Error::NotFound) => {
+            return JsonResponse::send(404, None, Some("Property not found".to_string()))
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    if !can_on(&agent, Action::View, Resource::Property, &property.1) {
+        return JsonResponse::send(403, None, None);
+    }
+    if payload
+        .campaign
+        .as_ref()
+        .is_some_and(|campaign| campaign.chars().count() > 255)
+    {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Campaign can be at most 255 characters".to_string()),
+        );
+    }
+
+    match ShareLink::create(&pool, &id, &agent.id, &payload) {
+        Ok(link) => JsonResponse::send(
+            201,
+            Some(ShareLinkResponse {
+                url: share_url(&link.code),
+                link,
+                lead_count: 0,
+            }),
+            None,
+        ),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Everyone's links for those who see every listing, otherwise the links of their branch or their own
+pub async fn find_share_links(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> AxumResponse<JsonFindResponse<Vec<ShareLinkResponse>>> {
+    let user_id = Session::extract_session_user_id(&headers);
+
+    let scope = match Agent::find_by_user_id(&pool, &user_id) {
+        Ok(agent) => match scope(&agent, Action::View, Resource::Property) {
+            Some(scope) => scope,
+            None => return JsonResponse::send(403, None, None),
+        },
+        Err(_) => return JsonResponse::send(403, None, None),
+    };
+
+    let links = match ShareLink::find_many_by_property(&pool, &id, &scope) {
+        Ok(links) => links,
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    let data: Vec<ShareLinkResponse> = links
+        .into_iter()
+        .map(|(link, lead_count)| ShareLinkResponse {
+            url: share_url(&link.code),
+            link,
+            lead_count,
+        })
+        .collect();
+    let res = JsonFindResponse {
+        total_data: data.len() as i64,
+        total_pages: 1,
+        data,
+    };
+
+    JsonResponse::send(200, Some(res), None)
+}
+
+// Counts the click and sends the visitor on to the listing, with the code in `ref`
+// for the site to send along with a lead
+pub async fn redirect_share_link(
+    State(pool): State<DbPool>,
+    Path(code): Path<String>,
+) -> Result<Redirect, AxumResponse<String>> {
+    let link = match ShareLink::record_click(&pool, &code) {
+        Ok(link) => link,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Share link not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+
+    let url = listing_url(&link.property_id);
+    let separator = match url.contains('?') {
+        true => '&',
+        false => '?',
+    };
+
+    Ok(Redirect::to(&format!(
+        "{}{}ref={}",
+        url, separator, link.code
+    )))
+}
+
+fn render_qr_png(url: &str, size: u32) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(url.as_bytes()).map_err(|err| err.to_string())?;
+    let modules = code.width() as u32;
+    // a quiet zone of 2 modules on each side
+    let module_size = (size / (modules + 4)).max(1);
+    let image_size = module_size * (modules + 4);
+    let colors = code.to_colors();
+
+    let image = GrayImage::from_fn(image_size, image_size, |x, y| {
+        let (column, row) = (x / module_size, y / module_size);
+        let is_dark = column >= 2
+            && row >= 2
+            && column < modules + 2
+            && row < modules + 2
+            && colors[((row - 2) * modules + column - 2) as usize] == Color::Dark;
+        match is_dark {
+            true => Luma([0]),
+            false => Luma([255]),
+        }
+    });
+
+    let mut bytes = vec![];
+    image
+        .write_with_encoder(PngEncoder::new(&mut bytes))
+        .map_err(|err| err.to_string())?;
+    Ok(bytes)
+}
+
+// PNG of the short link for print, `size` in pixels
+pub async fn find_share_link_qr(
+    State(pool): State<DbPool>,
+    Path(code): Path<String>,
+    Query(query): Query<QrQuery>,
+) -> Result<Response, AxumResponse<String>> {
+    let link = match ShareLink::find_by_code(&pool, &code) {
+        Ok(link) => link,
+        Err(diesel::result::Error::NotFound) => {
+            return Err(JsonResponse::send(
+                404,
+                None,
+                Some("Share link not found".to_string()),
+            ))
+        }
+        Err(err) => return Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    };
+
+    let size = query.size.unwrap_or(DEFAULT_QR_SIZE).clamp(64, MAX_QR_SIZE);
+    let url = share_url(&link.code);
+    match tokio::task::spawn_blocking(move || render_qr_png(&url, size)).await {
+        Ok(Ok(png)) => Ok(([(header::CONTENT_TYPE, "image/png")], png).into_response()),
+        Ok(Err(err)) => Err(JsonResponse::send(500, None, Some(err))),
+        Err(err) => Err(JsonResponse::send(500, None, Some(err.to_string()))),
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::NaiveDate;
+use diesel::dsl::{self, sql};
+use diesel::expression::SqlLiteral;
+use diesel::sql_types::BigInt;
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+
+use super::enumerates::PropertyEventType;
+use crate::db::DbPool;
+use crate::digest::hmac_sha256_hex;
+use crate::schema::{property_event_daily, property_events};
+
+// Visitors behind one ip that are counted per listing, event type and day. Offices and
+// mobile networks share ips, but more ids than this from one ip are made up.
+const MAX_VISITORS_PER_IP: i64 = 5;
+
+// Key of the visitor and ip hashes saved with events, from VISITOR_HASH_SECRET. Built
+// once in main and shared as an extension, so a missing value stops the server at startup.
+pub struct VisitorHasher {
+    secret: Vec<u8>,
+}
+
+impl VisitorHasher {
+    pub fn from_env() -> Self {
+        let secret = env::var("VISITOR_HASH_SECRET").expect("Missing VISITOR_HASH_SECRET");
+        if secret.is_empty() {
+            panic!("Empty VISITOR_HASH_SECRET");
+        }
+        VisitorHasher {
+            secret: secret.into_bytes(),
+        }
+    }
+
+    pub(super) fn hash(&self, value: &str) -> String {
+        hmac_sha256_hex(&self.secret, value)
+    }
+}
+
+// Deduplicated event counts of a listing
+#[derive(Debug, Serialize, Default, Clone)]
+pub struct PropertyEngagement {
+    views: i64,
+    gallery_opens: i64,
+    phone_reveals: i64,
+    whatsapp_clicks: i64,
+    shares: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropertyEngagementDay {
+    day: NaiveDate,
+    #[serde(flatten)]
+    counts: PropertyEngagement,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropertyEngagementReport {
+    property_id: i32,
+    from: NaiveDate,
+    to: NaiveDate,
+    total: PropertyEngagement,
+    days: Vec<PropertyEngagementDay>,
+}
+
+// Listings with the most views first, for `sort=MostViewed`
+pub fn most_viewed() -> SqlLiteral<BigInt> {
+    sql::<BigInt>(
+        "(SELECT COALESCE(SUM(d.count), 0) FROM property_event_daily d
+        WHERE d.property_id = properties.id AND d.event_type = 'view')",
+    )
+}
+
+impl PropertyEngagement {
+    fn add(&mut self, event_type: PropertyEventType, count: i64) {
+        match event_type {
+            PropertyEventType::View => self.views += count,
+            PropertyEventType::GalleryOpen => self.gallery_opens += count,
+            PropertyEventType::PhoneReveal => self.phone_reveals += count,
+            PropertyEventType::WhatsappClick => self.whatsapp_clicks += count,
+            PropertyEventType::Share => self.shares += count,
+        }
+    }
+
+    // Only the first event of a kind per visitor and day counts, and only for the first few
+    // visitors of an ip. Returns whether this one did.
+    pub(super) fn record(
+        pool: &DbPool,
+        property_id: &i32,
+        event_type: PropertyEventType,
+        visitor_hash: &str,
+        ip_hash: &str,
+    ) -> QueryResult<bool> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            let day: Option<NaiveDate> = diesel::insert_into(property_events::table)
+                .values((
+                    property_events::property_id.eq(property_id),
+                    property_events::event_type.eq(event_type),
+                    property_events::visitor_hash.eq(visitor_hash),
+                    property_events::ip_hash.eq(ip_hash),
+                ))
+                .on_conflict_do_nothing()
+                .returning(property_events::day)
+                .get_result(conn)
+                .optional()?;
+
+            let day = match day {
+                Some(day) => day,
+                None => return Ok(false),
+            };
+
+            let ip_visitors: i64 = property_events::table
+                .filter(property_events::property_id.eq(property_id))
+                .filter(property_events::event_type.eq(event_type))
+                .filter(property_events::ip_hash.eq(ip_hash))
+                .filter(property_events::day.eq(day))
+                .count()
+                .get_result(conn)?;
+            if ip_visitors > MAX_VISITORS_PER_IP {
+                return Ok(false);
+            }
+
+            diesel::insert_into(property_event_daily::table)
+                .values((
+                    property_event_daily::property_id.eq(property_id),
+                    property_event_daily::day.eq(day),
+                    property_event_daily::event_type.eq(event_type),
+                    property_event_daily::count.eq(1),
+                ))
+                .on_conflict((
+                    property_event_daily::property_id,
+                    property_event_daily::day,
+                    property_event_daily::event_type,
+                ))
+                .do_update()
+                .set(property_event_daily::count.eq(property_event_daily::count + 1))
+                .execute(conn)?;
+
+            Ok(true)
+        })
+    }
+
+    // All-time counts per listing
+    pub(super) fn find_totals(
+        pool: &DbPool,
+        property_ids: &[i32],
+    ) -> QueryResult<HashMap<i32, Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let rows: Vec<(i32, PropertyEventType, Option<i64>)> = property_event_daily::table
+            .filter(property_event_daily::property_id.eq_any(property_ids))
+            .group_by((
+                property_event_daily::property_id,
+                property_event_daily::event_type,
+            ))
+            .select((
+                property_event_daily::property_id,
+                property_event_daily::event_type,
+                dsl::sum(property_event_daily::count),
+            ))
+            .get_results(conn)?;
+
+        let mut totals: HashMap<i32, Self> = HashMap::new();
+        for (property_id, event_type, count) in rows {
+            totals
+                .entry(property_id)
+                .or_default()
+                .add(event_type, count.unwrap_or(0));
+        }
+        Ok(totals)
+    }
+
+    // Counts per day of an inclusive range, days without events included
+    pub(super) fn find_report(
+        pool: &DbPool,
+        property_id: &i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> QueryResult<PropertyEngagementReport> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let rows: Vec<(NaiveDate, PropertyEventType, i32)> = property_event_daily::table
+            .filter(property_event_daily::property_id.eq(property_id))
+            .filter(property_event_daily::day.between(from, to))
+            .select((
+                property_event_daily::day,
+                property_event_daily::event_type,
+                property_event_daily::count,
+            ))
+            .get_results(conn)?;
+
+        let mut total = PropertyEngagement::default();
+        let mut days: Vec<PropertyEngagementDay> = from
+            .iter_days()
+            .take_while(|day| *day <= to)
+            .map(|day| PropertyEngagementDay {
+                day,
+                counts: PropertyEngagement::default(),
+            })
+            .collect();
+        for (day, event_type, count) in rows {
+            total.add(event_type, count as i64);
+            let index = (day - from).num_days() as usize;
+            if let Some(engagement_day) = days.get_mut(index) {
+                engagement_day.counts.add(event_type, count as i64);
+            }
+        }
+
+        Ok(PropertyEngagementReport {
+            property_id: *property_id,
+            from,
+            to,
+            total,
+            days,
+        })
+    }
+}
@@ -0,0 +1,35 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, FromSqlRow)]
+#[diesel(sql_type = sql_types::AvailabilityKind)]
+pub enum AvailabilityKind {
+    // Kept free by the agent, e.g. for maintenance or the owner's own stay
+    Blocked,
+    Booked,
+}
+
+impl ToSql<sql_types::AvailabilityKind, Pg> for AvailabilityKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            AvailabilityKind::Blocked => out.write_all(b"blocked")?,
+            AvailabilityKind::Booked => out.write_all(b"booked")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::AvailabilityKind, Pg> for AvailabilityKind {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"blocked" => Ok(AvailabilityKind::Blocked),
+            b"booked" => Ok(AvailabilityKind::Booked),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
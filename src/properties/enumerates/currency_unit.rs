@@ -6,11 +6,16 @@ use diesel::serialize::{self, IsNull, Output, ToSql};
 use serde::{Deserialize, Serialize};
 use std::io::Write;
 
-#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
+#[derive(
+    Debug, AsExpression, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, FromSqlRow,
+)]
 #[diesel(sql_type = sql_types::CurrencyUnit)]
 pub enum Currency {
     Idr,
     Usd,
+    Sgd,
+    Aud,
+    Eur,
 }
 
 impl ToSql<sql_types::CurrencyUnit, Pg> for Currency {
@@ -18,6 +23,9 @@ impl ToSql<sql_types::CurrencyUnit, Pg> for Currency {
         match *self {
             Currency::Idr => out.write_all(b"IDR")?,
             Currency::Usd => out.write_all(b"USD")?,
+            Currency::Sgd => out.write_all(b"SGD")?,
+            Currency::Aud => out.write_all(b"AUD")?,
+            Currency::Eur => out.write_all(b"EUR")?,
         }
         Ok(IsNull::No)
     }
@@ -28,6 +36,9 @@ impl FromSql<sql_types::CurrencyUnit, Pg> for Currency {
         match bytes.as_bytes() {
             b"IDR" => Ok(Currency::Idr),
             b"USD" => Ok(Currency::Usd),
+            b"SGD" => Ok(Currency::Sgd),
+            b"AUD" => Ok(Currency::Aud),
+            b"EUR" => Ok(Currency::Eur),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -1,14 +1,18 @@
+mod availability_kind;
 mod building_condition;
 mod currency_unit;
 mod furniture_capacity;
+mod property_event_type;
 mod purchase_status;
 mod rent_time;
 mod sold_channel;
 mod sold_status;
 
+pub use availability_kind::AvailabilityKind;
 pub use building_condition::BuildingCondition;
 pub use currency_unit::Currency;
 pub use furniture_capacity::FurnitureCapacity;
+pub use property_event_type::PropertyEventType;
 pub use purchase_status::PurchaseStatus;
 pub use rent_time::RentTime;
 pub use sold_channel::SoldChannel;
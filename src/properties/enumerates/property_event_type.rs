@@ -0,0 +1,43 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, FromSqlRow)]
+#[diesel(sql_type = sql_types::PropertyEventType)]
+pub enum PropertyEventType {
+    View,
+    GalleryOpen,
+    PhoneReveal,
+    WhatsappClick,
+    Share,
+}
+
+impl ToSql<sql_types::PropertyEventType, Pg> for PropertyEventType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            PropertyEventType::View => out.write_all(b"view")?,
+            PropertyEventType::GalleryOpen => out.write_all(b"gallery_open")?,
+            PropertyEventType::PhoneReveal => out.write_all(b"phone_reveal")?,
+            PropertyEventType::WhatsappClick => out.write_all(b"whatsapp_click")?,
+            PropertyEventType::Share => out.write_all(b"share")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::PropertyEventType, Pg> for PropertyEventType {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"view" => Ok(PropertyEventType::View),
+            b"gallery_open" => Ok(PropertyEventType::GalleryOpen),
+            b"phone_reveal" => Ok(PropertyEventType::PhoneReveal),
+            b"whatsapp_click" => Ok(PropertyEventType::WhatsappClick),
+            b"share" => Ok(PropertyEventType::Share),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -8,6 +8,7 @@ use std::io::Write;
 
 #[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
 #[diesel(sql_type = sql_types::PurchaseStatus)]
+#[allow(clippy::enum_variant_names)]
 pub enum PurchaseStatus {
     ForSale,
     ForRent,
@@ -9,14 +9,20 @@ use std::io::Write;
 #[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
 #[diesel(sql_type = sql_types::RentTimeUnit)]
 pub enum RentTime {
+    Daily,
+    Weekly,
     Monthly,
+    Quarterly,
     Yearly,
 }
 
 impl ToSql<sql_types::RentTimeUnit, Pg> for RentTime {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
         match *self {
+            RentTime::Daily => out.write_all(b"daily")?,
+            RentTime::Weekly => out.write_all(b"weekly")?,
             RentTime::Monthly => out.write_all(b"monthly")?,
+            RentTime::Quarterly => out.write_all(b"quarterly")?,
             RentTime::Yearly => out.write_all(b"yearly")?,
         }
         Ok(IsNull::No)
@@ -26,7 +32,10 @@ impl ToSql<sql_types::RentTimeUnit, Pg> for RentTime {
 impl FromSql<sql_types::RentTimeUnit, Pg> for RentTime {
     fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
         match bytes.as_bytes() {
+            b"daily" => Ok(RentTime::Daily),
+            b"weekly" => Ok(RentTime::Weekly),
             b"monthly" => Ok(RentTime::Monthly),
+            b"quarterly" => Ok(RentTime::Quarterly),
             b"yearly" => Ok(RentTime::Yearly),
             _ => Err("Unrecognized enum variant".into()),
         }
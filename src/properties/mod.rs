@@ -1,7 +1,18 @@
+mod aggregates;
+mod availability;
+mod brochure;
 mod controllers;
+mod engagement;
 mod enumerates;
 mod model;
+mod share_link;
 
-pub use controllers::property_routes;
+pub use controllers::{property_routes, share_link_routes};
+pub(crate) use controllers::{Facilities, PropertyWithRelation};
 
+pub use aggregates::{find_listing_page, ListingAggregates, ListingPartner};
+pub use availability::{sync_calendars_periodically, AvailabilityRange};
+pub use engagement::VisitorHasher;
+pub use enumerates::{Currency, SoldChannel, SoldStatus};
 pub use model::Property;
+pub use share_link::ShareLink;
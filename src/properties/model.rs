@@ -3,58 +3,156 @@ use super::{
         CreateUpdatePropertySqlPayload, FindPropertyQuery, FindPropertySort, PropertyWithRelation,
         UpdateConfigurationsSqlPayload,
     },
+    engagement::most_viewed,
     enumerates::{
         BuildingCondition, Currency, FurnitureCapacity, PurchaseStatus, RentTime, SoldChannel,
         SoldStatus,
     },
 };
 use crate::{
-    agents::AgentRole,
+    agents::{branch_member_ids, Action, AgentRole, Resource, Scope},
     db::DbPool,
+    exchange_rates::ExchangeRates,
     schema::{agents, developers, properties},
 };
 use diesel::{
-    BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, PgJsonbExpressionMethods,
-    PgTextExpressionMethods, QueryDsl, QueryResult, Queryable, RunQueryDsl,
+    BoolExpressionMethods, ExpressionMethods, NullableExpressionMethods, PgConnection,
+    PgJsonbExpressionMethods, PgSortExpressionMethods, PgTextExpressionMethods, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
 };
 use serde::Serialize;
 
+// Listings of deactivated agents are hidden from the public site
+#[diesel::dsl::auto_type]
+pub(super) fn active_agent_ids() -> _ {
+    agents::table
+        .filter(agents::is_active.eq(true))
+        .select(agents::id)
+}
+
+// Where the public site shows a listing, LISTING_URL with `{id}` filled in
+pub fn listing_url(id: &i32) -> String {
+    std::env::var("LISTING_URL")
+        .unwrap_or("https://primeproindonesia.com/properti/{id}".to_string())
+        .replace("{id}", &id.to_string())
+}
+
+type NavigationRow = (String, PurchaseStatus, String, String, String, String);
+
 #[derive(Debug, Serialize, Queryable)]
 pub struct Property {
     pub id: i32,
     pub user_id: uuid::Uuid,
     created_at: chrono::NaiveDateTime,
-    updated_at: chrono::NaiveDateTime,
+    pub(super) updated_at: chrono::NaiveDateTime,
     site_path: String,
-    title: String,
-    description: String,
-    province: String,
+    pub(super) title: String,
+    pub(super) description: String,
+    pub(super) province: String,
     pub regency: String,
     pub street: String,
     gmap_iframe: Option<String>,
-    price: i64,
-    images: serde_json::Value,
-    purchase_status: PurchaseStatus,
+    pub(super) price: i64,
+    pub(super) images: serde_json::Value,
+    pub(super) purchase_status: PurchaseStatus,
     sold_status: SoldStatus,
-    measurements: serde_json::Value,
-    building_type: String,
+    pub(super) measurements: serde_json::Value,
+    pub(super) building_type: String,
     building_condition: BuildingCondition,
     building_furniture_capacity: Option<FurnitureCapacity>,
-    building_certificate: String,
-    specifications: serde_json::Value,
-    facilities: serde_json::Value,
-    is_deleted: bool,
+    pub(super) building_certificate: String,
+    pub(super) specifications: serde_json::Value,
+    pub(super) facilities: serde_json::Value,
+    pub is_deleted: bool,
     sold_channel: Option<SoldChannel>,
     configurations: serde_json::Value,
-    currency: Currency,
-    rent_time: Option<RentTime>,
+    pub currency: Currency,
+    pub(super) rent_time: Option<RentTime>,
     description_seo: Option<String>,
-    price_down_payment: Option<i64>,
+    pub(super) price_down_payment: Option<i64>,
     developer_id: Option<i32>,
     bank_id: Option<i32>,
+    sold_at: Option<chrono::NaiveDateTime>,
+    project_id: Option<i32>,
+    // Kept by the set_normalised_prices trigger, None while the currency has no rate
+    price_idr: Option<i64>,
+    // The rent when a listing for sale or rent has its own, `price` is then the sale price
+    pub(super) rent_price: Option<i64>,
+    // In rent_time periods
+    min_rent_duration: Option<i32>,
+    // Kept by the set_normalised_prices trigger, None for listings only for sale
+    monthly_rent_idr: Option<i64>,
+    min_rent_days: Option<i32>,
 }
 
 impl Property {
+    // Prices shown in another currency, left as they are when a rate is missing
+    pub(super) fn display_in(&mut self, rates: &ExchangeRates, currency: Currency) {
+        let price = match rates.convert(self.price, self.currency, currency) {
+            Some(price) => price,
+            None => return,
+        };
+        let price_down_payment = match self.price_down_payment {
+            Some(down_payment) => match rates.convert(down_payment, self.currency, currency) {
+                Some(down_payment) => Some(down_payment),
+                None => return,
+            },
+            None => None,
+        };
+        let rent_price = match self.rent_price {
+            Some(rent_price) => match rates.convert(rent_price, self.currency, currency) {
+                Some(rent_price) => Some(rent_price),
+                None => return,
+            },
+            None => None,
+        };
+
+        self.price = price;
+        self.price_down_payment = price_down_payment;
+        self.rent_price = rent_price;
+        self.currency = currency;
+    }
+
+    // Whether the listing can be rented for the stay, check_out is the day the renter leaves
+    pub fn validate_stay(
+        &self,
+        check_in: chrono::NaiveDate,
+        check_out: chrono::NaiveDate,
+    ) -> Result<(), String> {
+        if self.is_deleted || matches!(self.sold_status, SoldStatus::Sold) {
+            return Err("Property is no longer available".to_string());
+        }
+        if let PurchaseStatus::ForSale = self.purchase_status {
+            return Err("Property is not for rent".to_string());
+        }
+        if check_out <= check_in {
+            return Err("Check-out must be after check-in".to_string());
+        }
+        if check_in < chrono::Local::now().date_naive() {
+            return Err("Check-in can't be in the past".to_string());
+        }
+        if let Some(min_rent_days) = self.min_rent_days {
+            if (check_out - check_in).num_days() < min_rent_days as i64 {
+                return Err(format!(
+                    "Property is rented for at least {} days",
+                    min_rent_days
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Whether buyers can still make offers on the listing
+    pub fn validate_offer(&self) -> Result<(), String> {
+        if self.is_deleted || matches!(self.sold_status, SoldStatus::Sold) {
+            return Err("Property is no longer available".to_string());
+        }
+        if let PurchaseStatus::ForRent = self.purchase_status {
+            return Err("Property is not for sale".to_string());
+        }
+        Ok(())
+    }
+
     pub fn find_one_by_id(pool: &DbPool, id: &i32) -> QueryResult<PropertyWithRelation> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
@@ -85,11 +183,12 @@ impl Property {
     pub(super) fn delete(pool: &DbPool, id: &i32, role: &AgentRole) -> QueryResult<Self> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        match role {
-            AgentRole::Admin => diesel::delete(properties::table)
+        // the others only hide it, leads and history stay
+        match role.can(Action::Delete, Resource::Property) {
+            true => diesel::delete(properties::table)
                 .filter(properties::id.eq(id))
                 .get_result(conn),
-            AgentRole::Agent => diesel::update(properties::table)
+            false => diesel::update(properties::table)
                 .filter(properties::id.eq(id))
                 .set(properties::is_deleted.eq(true))
                 .get_result(conn),
@@ -108,6 +207,37 @@ impl Property {
             .get_result(conn)
     }
 
+    pub fn transfer_all_in(
+        conn: &mut PgConnection,
+        from_user_id: &uuid::Uuid,
+        to_user_id: &uuid::Uuid,
+    ) -> QueryResult<usize> {
+        diesel::update(properties::table)
+            .filter(properties::user_id.eq(from_user_id))
+            .set(properties::user_id.eq(to_user_id))
+            .execute(conn)
+    }
+
+    // The sale closed through an accepted offer, sold_at is set by its trigger
+    // Held until the caller's transaction ends, so the listing can't be sold twice meanwhile
+    pub fn lock_in(conn: &mut PgConnection, id: &i32) -> QueryResult<Self> {
+        properties::table.find(id).for_update().get_result(conn)
+    }
+
+    pub fn mark_sold_in(
+        conn: &mut PgConnection,
+        id: &i32,
+        sold_channel: Option<SoldChannel>,
+    ) -> QueryResult<usize> {
+        diesel::update(properties::table)
+            .filter(properties::id.eq(id))
+            .set((
+                properties::sold_status.eq(SoldStatus::Sold),
+                properties::sold_channel.eq(sold_channel),
+            ))
+            .execute(conn)
+    }
+
     pub fn find_distinct_site_paths(pool: &DbPool) -> QueryResult<Vec<String>> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
@@ -196,39 +326,31 @@ impl Property {
                 properties::id
                     .ne(property_id)
                     .and(properties::is_deleted.eq(false))
-                    .and(properties::sold_status.eq(SoldStatus::Available)),
+                    .and(properties::sold_status.eq(SoldStatus::Available))
+                    .and(properties::user_id.eq_any(active_agent_ids())),
             )
             .into_boxed();
 
-        match &query.regency {
-            Some(regency_query) => {
-                property_query =
-                    property_query.filter(properties::regency.eq(regency_query.to_lowercase()));
-            }
-            None => {}
+        if let Some(regency_query) = &query.regency {
+            property_query =
+                property_query.filter(properties::regency.eq(regency_query.to_lowercase()));
         }
 
-        match &query.street {
-            Some(street_query) => {
-                property_query =
-                    property_query.filter(properties::street.eq(street_query.to_lowercase()));
-            }
-            None => {}
+        if let Some(street_query) = &query.street {
+            property_query =
+                property_query.filter(properties::street.eq(street_query.to_lowercase()));
         }
 
-        match &query.limit {
-            Some(limit) => {
-                match &query.page {
-                    Some(page) => {
-                        let offset = (page - 1) * limit;
-                        property_query = property_query.offset(offset).limit(limit.clone());
-                    }
-                    None => {
-                        property_query = property_query.limit(limit.clone());
-                    }
-                };
-            }
-            None => {}
+        if let Some(limit) = &query.limit {
+            match &query.page {
+                Some(page) => {
+                    let offset = (page - 1) * limit;
+                    property_query = property_query.offset(offset).limit(*limit);
+                }
+                None => {
+                    property_query = property_query.limit(*limit);
+                }
+            };
         }
 
         property_query
@@ -243,9 +365,7 @@ impl Property {
             .get_results::<PropertyWithRelation>(conn)
     }
 
-    pub fn find_navigation(
-        pool: &DbPool,
-    ) -> QueryResult<Vec<(String, PurchaseStatus, String, String, String, String)>> {
+    pub fn find_navigation(pool: &DbPool) -> QueryResult<Vec<NavigationRow>> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
         properties::table
             .distinct_on(properties::site_path)
@@ -275,37 +395,43 @@ impl Property {
 
     pub fn find_many(
         pool: &DbPool,
-        user_id: &Option<uuid::Uuid>,
-        role: &Option<AgentRole>,
+        scope: &Option<Scope>,
         query: &FindPropertyQuery,
     ) -> QueryResult<Vec<PropertyWithRelation>> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        let mut property_query = match role {
-            Some(role) => match role {
-                AgentRole::Admin => properties::table.into_boxed(),
-                AgentRole::Agent => properties::table
-                    .filter(
-                        properties::user_id
-                            .eq(user_id.unwrap())
-                            .and(properties::is_deleted.eq(false)),
-                    )
-                    .into_boxed(),
-            },
+        let mut property_query = match scope {
+            Some(Scope::All) => properties::table.into_boxed(),
+            Some(Scope::Branch(branch_id)) => properties::table
+                .filter(
+                    properties::user_id
+                        .eq_any(branch_member_ids(*branch_id))
+                        .and(properties::is_deleted.eq(false)),
+                )
+                .into_boxed(),
+            Some(Scope::Own(user_id)) => properties::table
+                .filter(
+                    properties::user_id
+                        .eq(user_id)
+                        .and(properties::is_deleted.eq(false)),
+                )
+                .into_boxed(),
             None => match &query.s {
                 Some(_) => properties::table
                     .distinct_on(properties::site_path)
                     .filter(
                         properties::is_deleted
                             .eq(false)
-                            .and(properties::sold_status.eq(SoldStatus::Available)),
+                            .and(properties::sold_status.eq(SoldStatus::Available))
+                            .and(properties::user_id.eq_any(active_agent_ids())),
                     )
                     .into_boxed(),
                 None => properties::table
                     .filter(
                         properties::is_deleted
                             .eq(false)
-                            .and(properties::sold_status.eq(SoldStatus::Available)),
+                            .and(properties::sold_status.eq(SoldStatus::Available))
+                            .and(properties::user_id.eq_any(active_agent_ids())),
                     )
                     .into_boxed(),
             },
@@ -376,16 +502,57 @@ impl Property {
             property_query = property_query.filter(properties::developer_id.eq(dev_id));
         }
 
+        if let Some(project_id) = &query.project_id {
+            property_query = property_query.filter(properties::project_id.eq(project_id));
+        }
+
         if let Some(bank_id) = &query.bank_id {
             property_query = property_query.filter(properties::bank_id.eq(bank_id));
         }
 
+        // listings for rent are compared on their monthly rent
+        match query.purchase_status {
+            Some(PurchaseStatus::ForRent) => {
+                if let Some(min_price) = query.min_price {
+                    property_query =
+                        property_query.filter(properties::monthly_rent_idr.ge(min_price));
+                }
+                if let Some(max_price) = query.max_price {
+                    property_query =
+                        property_query.filter(properties::monthly_rent_idr.le(max_price));
+                }
+            }
+            _ => {
+                if let Some(min_price) = query.min_price {
+                    property_query = property_query.filter(properties::price_idr.ge(min_price));
+                }
+                if let Some(max_price) = query.max_price {
+                    property_query = property_query.filter(properties::price_idr.le(max_price));
+                }
+            }
+        }
+
+        if let Some(rent_days) = query.rent_days {
+            property_query = property_query.filter(
+                properties::monthly_rent_idr.is_not_null().and(
+                    properties::min_rent_days
+                        .is_null()
+                        .or(properties::min_rent_days.le(rent_days)),
+                ),
+            );
+        }
+
+        if let Some(branch_id) = query.branch_id {
+            property_query =
+                property_query.filter(properties::user_id.eq_any(branch_member_ids(branch_id)));
+        }
+
         if let Some(ids) = &query.ids {
             let id_list: Vec<i32> = ids
                 .split(",")
                 .filter_map(|id| id.trim().parse::<i32>().ok())
                 .collect();
-            if id_list.len() > 0 {
+            if !id_list.is_empty() {
                 property_query = property_query.filter(properties::id.eq_any(id_list));
             }
         }
@@ -394,21 +561,44 @@ impl Property {
             match &query.page {
                 Some(page) => {
                     let offset = (page - 1) * limit;
-                    property_query = property_query.offset(offset).limit(limit.clone());
+                    property_query = property_query.offset(offset).limit(*limit);
                 }
                 None => {
-                    property_query = property_query.limit(limit.clone());
+                    property_query = property_query.limit(*limit);
                 }
             };
         }
 
         match &query.sort {
             Some(sort) => match sort {
+                // listings without a rupiah price go last, listings for rent by their monthly rent
                 FindPropertySort::LowestPrice => {
-                    property_query = property_query.order_by(properties::price.asc())
+                    property_query = match query.purchase_status {
+                        Some(PurchaseStatus::ForRent) => property_query.order_by((
+                            properties::monthly_rent_idr.asc().nulls_last(),
+                            properties::id.desc(),
+                        )),
+                        _ => property_query.order_by((
+                            properties::price_idr.asc().nulls_last(),
+                            properties::id.desc(),
+                        )),
+                    }
                 }
                 FindPropertySort::HighestPrice => {
-                    property_query = property_query.order_by(properties::price.desc())
+                    property_query = match query.purchase_status {
+                        Some(PurchaseStatus::ForRent) => property_query.order_by((
+                            properties::monthly_rent_idr.desc().nulls_last(),
+                            properties::id.desc(),
+                        )),
+                        _ => property_query.order_by((
+                            properties::price_idr.desc().nulls_last(),
+                            properties::id.desc(),
+                        )),
+                    }
+                }
+                FindPropertySort::MostViewed => {
+                    property_query =
+                        property_query.order_by((most_viewed().desc(), properties::id.desc()))
                 }
             },
             None => match &query.s {
@@ -433,28 +623,33 @@ impl Property {
 
     pub fn count_find_many_rows(
         pool: &DbPool,
-        user_id: &Option<uuid::Uuid>,
-        role: &Option<AgentRole>,
+        scope: &Option<Scope>,
         query: &FindPropertyQuery,
     ) -> QueryResult<i64> {
         let conn = &mut pool.get().expect("Couldn't get db connection from pool");
 
-        let mut property_query = match role {
-            Some(role) => match role {
-                AgentRole::Admin => properties::table.into_boxed(),
-                AgentRole::Agent => properties::table
-                    .filter(
-                        properties::user_id
-                            .eq(user_id.unwrap())
-                            .and(properties::is_deleted.eq(false)),
-                    )
-                    .into_boxed(),
-            },
+        let mut property_query = match scope {
+            Some(Scope::All) => properties::table.into_boxed(),
+            Some(Scope::Branch(branch_id)) => properties::table
+                .filter(
+                    properties::user_id
+                        .eq_any(branch_member_ids(*branch_id))
+                        .and(properties::is_deleted.eq(false)),
+                )
+                .into_boxed(),
+            Some(Scope::Own(user_id)) => properties::table
+                .filter(
+                    properties::user_id
+                        .eq(user_id)
+                        .and(properties::is_deleted.eq(false)),
+                )
+                .into_boxed(),
             None => properties::table
                 .filter(
                     properties::is_deleted
                         .eq(false)
-                        .and(properties::sold_status.eq(SoldStatus::Available)),
+                        .and(properties::sold_status.eq(SoldStatus::Available))
+                        .and(properties::user_id.eq_any(active_agent_ids())),
                 )
                 .into_boxed(),
         };
@@ -523,10 +718,51 @@ impl Property {
             property_query = property_query.filter(properties::developer_id.eq(dev_id));
         }
 
+        if let Some(project_id) = &query.project_id {
+            property_query = property_query.filter(properties::project_id.eq(project_id));
+        }
+
         if let Some(bank_id) = &query.bank_id {
             property_query = property_query.filter(properties::bank_id.eq(bank_id));
         }
 
+        // listings for rent are compared on their monthly rent
+        match query.purchase_status {
+            Some(PurchaseStatus::ForRent) => {
+                if let Some(min_price) = query.min_price {
+                    property_query =
+                        property_query.filter(properties::monthly_rent_idr.ge(min_price));
+                }
+                if let Some(max_price) = query.max_price {
+                    property_query =
+                        property_query.filter(properties::monthly_rent_idr.le(max_price));
+                }
+            }
+            _ => {
+                if let Some(min_price) = query.min_price {
+                    property_query = property_query.filter(properties::price_idr.ge(min_price));
+                }
+                if let Some(max_price) = query.max_price {
+                    property_query = property_query.filter(properties::price_idr.le(max_price));
+                }
+            }
+        }
+
+        if let Some(rent_days) = query.rent_days {
+            property_query = property_query.filter(
+                properties::monthly_rent_idr.is_not_null().and(
+                    properties::min_rent_days
+                        .is_null()
+                        .or(properties::min_rent_days.le(rent_days)),
+                ),
+            );
+        }
+
+        if let Some(branch_id) = query.branch_id {
+            property_query =
+                property_query.filter(properties::user_id.eq_any(branch_member_ids(branch_id)));
+        }
+
         property_query.count().get_result(conn)
     }
 }
@@ -0,0 +1,146 @@
+use diesel::dsl::{self, now};
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{
+    ExpressionMethods, NullableExpressionMethods, OptionalExtension, QueryDsl, QueryResult,
+    Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+use super::controllers::CreateShareLinkPayload;
+use super::enumerates::SoldChannel;
+use crate::agents::{branch_member_ids, Scope};
+use crate::db::DbPool;
+use crate::schema::{leads, share_links};
+
+// No 0/o or 1/l, codes end up typed from banners too
+const CODE_ALPHABET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+const CODE_LENGTH: usize = 8;
+const CODE_ATTEMPTS: usize = 3;
+
+// A tracked link to a listing, tagged with who shared it, where and for which campaign
+#[derive(Debug, Serialize, Queryable)]
+pub struct ShareLink {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub code: String,
+    pub property_id: i32,
+    user_id: Option<uuid::Uuid>,
+    pub channel: SoldChannel,
+    campaign: Option<String>,
+    click_count: i32,
+    last_clicked_at: Option<chrono::NaiveDateTime>,
+}
+
+fn generate_code() -> String {
+    uuid::Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(CODE_LENGTH)
+        .map(|byte| CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+// The short link handed out, SHARE_LINK_URL with `{code}` filled in
+pub fn share_url(code: &str) -> String {
+    std::env::var("SHARE_LINK_URL")
+        .unwrap_or("https://primeproindonesia.com/s/{code}".to_string())
+        .replace("{code}", code)
+}
+
+impl ShareLink {
+    pub(super) fn create(
+        pool: &DbPool,
+        property_id: &i32,
+        user_id: &uuid::Uuid,
+        payload: &CreateShareLinkPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let mut attempts = 0;
+        loop {
+            let result = diesel::insert_into(share_links::table)
+                .values((
+                    payload,
+                    share_links::code.eq(generate_code()),
+                    share_links::property_id.eq(property_id),
+                    share_links::user_id.eq(user_id),
+                ))
+                .get_result(conn);
+
+            attempts += 1;
+            match result {
+                // a code that's already taken, try another one
+                Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+                    if attempts < CODE_ATTEMPTS => {}
+                result => return result,
+            }
+        }
+    }
+
+    pub fn find_by_code(pool: &DbPool, code: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        share_links::table
+            .filter(share_links::code.eq(code))
+            .get_result(conn)
+    }
+
+    // Links of a listing with the number of leads each brought in, limited to the links
+    // the scope covers
+    pub(super) fn find_many_by_property(
+        pool: &DbPool,
+        property_id: &i32,
+        scope: &Scope,
+    ) -> QueryResult<Vec<(Self, i64)>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let query = share_links::table
+            .left_join(leads::table)
+            .filter(share_links::property_id.eq(property_id))
+            .group_by(share_links::id)
+            .select((share_links::all_columns, dsl::count(leads::id.nullable())))
+            .order_by(share_links::created_at.desc())
+            .into_boxed();
+
+        let query = match scope {
+            Scope::All => query,
+            Scope::Branch(branch_id) => query.filter(
+                share_links::user_id
+                    .assume_not_null()
+                    .eq_any(branch_member_ids(*branch_id)),
+            ),
+            Scope::Own(user_id) => query.filter(share_links::user_id.eq(user_id)),
+        };
+
+        query.get_results(conn)
+    }
+
+    pub(super) fn record_click(pool: &DbPool, code: &str) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(share_links::table)
+            .filter(share_links::code.eq(code))
+            .set((
+                share_links::click_count.eq(share_links::click_count + 1),
+                share_links::last_clicked_at.eq(now.nullable()),
+            ))
+            .get_result(conn)
+    }
+
+    // Channel of the link behind the latest tracked lead on the listing
+    pub fn attributed_channel(
+        pool: &DbPool,
+        property_id: &i32,
+    ) -> QueryResult<Option<SoldChannel>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        leads::table
+            .inner_join(share_links::table)
+            .filter(leads::property_id.eq(property_id))
+            .order_by(leads::created_at.desc())
+            .select(share_links::channel)
+            .first(conn)
+            .optional()
+    }
+}
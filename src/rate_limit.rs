@@ -0,0 +1,67 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// At most `limit` hits per key within a sliding `window`, kept in memory.
+// Resets when the server restarts.
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records a hit and returns false once the key went over the limit
+    pub fn check(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().expect("Rate limit lock poisoned");
+
+        hits.retain(|_, timestamps| {
+            while let Some(timestamp) = timestamps.front() {
+                if now.duration_since(*timestamp) < self.window {
+                    break;
+                }
+                timestamps.pop_front();
+            }
+            !timestamps.is_empty()
+        });
+
+        let timestamps = hits.entry(key.to_string()).or_default();
+        if timestamps.len() >= self.limit {
+            return false;
+        }
+        timestamps.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_hits_over_the_limit_per_key() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("10.0.0.1"));
+        assert!(limiter.check("10.0.0.1"));
+        assert!(!limiter.check("10.0.0.1"));
+        assert!(limiter.check("10.0.0.2"));
+    }
+
+    #[test]
+    fn forgets_hits_once_the_window_passed() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(20));
+        assert!(limiter.check("10.0.0.1"));
+        assert!(!limiter.check("10.0.0.1"));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.check("10.0.0.1"));
+    }
+}
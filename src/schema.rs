@@ -5,6 +5,14 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "agent_role"))]
     pub struct AgentRole;
 
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "appointment_status"))]
+    pub struct AppointmentStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "availability_kind"))]
+    pub struct AvailabilityKind;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "building_condition"))]
     pub struct BuildingCondition;
@@ -17,6 +25,42 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "furniture_capacity"))]
     pub struct FurnitureCapacity;
 
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "job_status"))]
+    pub struct JobStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "lead_assignment_reason"))]
+    pub struct LeadAssignmentReason;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "lead_rejection_reason"))]
+    pub struct LeadRejectionReason;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "offer_party"))]
+    pub struct OfferParty;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "offer_status"))]
+    pub struct OfferStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "outbox_channel"))]
+    pub struct OutboxChannel;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "outbox_status"))]
+    pub struct OutboxStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "payment_method"))]
+    pub struct PaymentMethod;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "property_event_type"))]
+    pub struct PropertyEventType;
+
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "purchase_status"))]
     pub struct PurchaseStatus;
@@ -32,6 +76,66 @@ pub mod sql_types {
     #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "sold_status"))]
     pub struct SoldStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "watermark_position"))]
+    pub struct WatermarkPosition;
+}
+
+diesel::table! {
+    agent_availability_slots (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Uuid,
+        weekday -> Int2,
+        starts_at -> Time,
+        ends_at -> Time,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AgentRole;
+
+    agent_invites (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        email -> Varchar,
+        role -> AgentRole,
+        branch_id -> Nullable<Int4>,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        invited_by -> Nullable<Uuid>,
+        expires_at -> Timestamp,
+        accepted_at -> Nullable<Timestamp>,
+        agent_id -> Nullable<Uuid>,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    agent_slug_redirects (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        #[max_length = 255]
+        slug -> Varchar,
+        agent_id -> Uuid,
+    }
+}
+
+diesel::table! {
+    agent_transfers (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        from_user_id -> Nullable<Uuid>,
+        to_user_id -> Nullable<Uuid>,
+        transferred_by -> Nullable<Uuid>,
+        property_count -> Int4,
+        lead_count -> Int4,
+    }
 }
 
 diesel::table! {
@@ -51,6 +155,77 @@ diesel::table! {
         #[max_length = 255]
         instagram -> Nullable<Varchar>,
         description -> Nullable<Varchar>,
+        is_active -> Bool,
+        deactivated_at -> Nullable<Timestamp>,
+        #[max_length = 255]
+        slug -> Varchar,
+        branch_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AppointmentStatus;
+
+    appointments (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        lead_id -> Int4,
+        property_id -> Int4,
+        user_id -> Uuid,
+        starts_at -> Timestamp,
+        ends_at -> Timestamp,
+        status -> AppointmentStatus,
+        note -> Nullable<Text>,
+        cancel_reason -> Nullable<Text>,
+        sequence -> Int4,
+    }
+}
+
+diesel::table! {
+    bank_product_revisions (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        product_id -> Int4,
+        revision -> Int4,
+        changed_by -> Nullable<Uuid>,
+        name -> Varchar,
+        fixed_rate -> Numeric,
+        fixed_period_months -> Int4,
+        floating_rate -> Numeric,
+        max_tenor_years -> Int4,
+        min_down_payment_percent -> Numeric,
+        provision_fee_percent -> Numeric,
+        admin_fee -> Int8,
+        appraisal_fee -> Int8,
+        is_active -> Bool,
+        is_deleted -> Bool,
+        valid_from -> Nullable<Date>,
+        valid_until -> Nullable<Date>,
+    }
+}
+
+diesel::table! {
+    bank_products (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        bank_id -> Int4,
+        name -> Varchar,
+        fixed_rate -> Numeric,
+        fixed_period_months -> Int4,
+        floating_rate -> Numeric,
+        max_tenor_years -> Int4,
+        min_down_payment_percent -> Numeric,
+        provision_fee_percent -> Numeric,
+        admin_fee -> Int8,
+        appraisal_fee -> Int8,
+        revision -> Int4,
+        is_active -> Bool,
+        is_deleted -> Bool,
+        valid_from -> Nullable<Date>,
+        valid_until -> Nullable<Date>,
     }
 }
 
@@ -61,6 +236,55 @@ diesel::table! {
         updated_at -> Timestamp,
         logo_path -> Varchar,
         name -> Varchar,
+        description -> Text,
+        website -> Nullable<Varchar>,
+        contact_email -> Nullable<Varchar>,
+        contact_phone -> Nullable<Varchar>,
+        slug -> Varchar,
+        archived_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    blocked_contacts (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        phone -> Nullable<Varchar>,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        reason -> Nullable<Varchar>,
+        created_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    branches (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        name -> Varchar,
+        address -> Nullable<Varchar>,
+        #[max_length = 255]
+        phone_number -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    contacts (id) {
+        id -> Int4,
+        user_id -> Uuid,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 255]
+        phone -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        merged_into_id -> Nullable<Int4>,
     }
 }
 
@@ -71,6 +295,74 @@ diesel::table! {
         updated_at -> Timestamp,
         logo_path -> Varchar,
         name -> Varchar,
+        description -> Text,
+        website -> Nullable<Varchar>,
+        contact_email -> Nullable<Varchar>,
+        contact_phone -> Nullable<Varchar>,
+        slug -> Varchar,
+        archived_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CurrencyUnit;
+
+    exchange_rates (currency) {
+        currency -> CurrencyUnit,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        idr_rate -> Numeric,
+        #[max_length = 255]
+        source -> Varchar,
+        updated_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::LeadAssignmentReason;
+
+    lead_assignments (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        lead_id -> Int4,
+        from_user_id -> Nullable<Uuid>,
+        to_user_id -> Nullable<Uuid>,
+        assigned_by -> Nullable<Uuid>,
+        reason -> LeadAssignmentReason,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::LeadRejectionReason;
+
+    lead_rejections (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        reason -> LeadRejectionReason,
+        #[max_length = 255]
+        ip_address -> Varchar,
+        property_id -> Nullable<Int4>,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 255]
+        phone -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    lead_routing_rules (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        regency -> Varchar,
+        user_id -> Uuid,
+        last_assigned_at -> Nullable<Timestamp>,
     }
 }
 
@@ -78,7 +370,7 @@ diesel::table! {
     leads (id) {
         id -> Int4,
         user_id -> Uuid,
-        property_id -> Int4,
+        property_id -> Nullable<Int4>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
         #[max_length = 255]
@@ -88,6 +380,89 @@ diesel::table! {
         #[max_length = 255]
         email -> Nullable<Varchar>,
         is_deleted -> Bool,
+        contact_id -> Nullable<Int4>,
+        responded_at -> Nullable<Timestamp>,
+        share_link_id -> Nullable<Int4>,
+        check_in -> Nullable<Date>,
+        check_out -> Nullable<Date>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CurrencyUnit;
+    use super::sql_types::OfferParty;
+    use super::sql_types::PaymentMethod;
+    use super::sql_types::OfferStatus;
+
+    offers (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        property_id -> Int4,
+        lead_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        party -> OfferParty,
+        amount -> Int8,
+        currency -> CurrencyUnit,
+        down_payment -> Nullable<Int8>,
+        payment_method -> PaymentMethod,
+        bank_id -> Nullable<Int4>,
+        terms -> Nullable<Text>,
+        expires_at -> Nullable<Timestamp>,
+        status -> OfferStatus,
+        responded_at -> Nullable<Timestamp>,
+        recorded_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::OutboxChannel;
+    use super::sql_types::OutboxStatus;
+
+    outbox_messages (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        channel -> OutboxChannel,
+        #[max_length = 255]
+        recipient -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        body -> Text,
+        #[max_length = 255]
+        attachment_name -> Nullable<Varchar>,
+        #[max_length = 255]
+        attachment_type -> Nullable<Varchar>,
+        attachment -> Nullable<Text>,
+        #[max_length = 255]
+        key -> Nullable<Varchar>,
+        send_after -> Timestamp,
+        status -> OutboxStatus,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        sent_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    projects (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        developer_id -> Int4,
+        slug -> Varchar,
+        name -> Varchar,
+        description -> Text,
+        province -> Varchar,
+        regency -> Varchar,
+        street -> Varchar,
+        gmap_iframe -> Nullable<Text>,
+        brochure_path -> Nullable<Varchar>,
+        completion_date -> Nullable<Date>,
+        facilities -> Jsonb,
+        unit_types -> Jsonb,
     }
 }
 
@@ -140,13 +515,234 @@ diesel::table! {
         price_down_payment -> Nullable<Int8>,
         developer_id -> Nullable<Int4>,
         bank_id -> Nullable<Int4>,
+        sold_at -> Nullable<Timestamp>,
+        project_id -> Nullable<Int4>,
+        price_idr -> Nullable<Int8>,
+        rent_price -> Nullable<Int8>,
+        min_rent_duration -> Nullable<Int4>,
+        monthly_rent_idr -> Nullable<Int8>,
+        min_rent_days -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AvailabilityKind;
+
+    property_availability (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        property_id -> Int4,
+        kind -> AvailabilityKind,
+        starts_on -> Date,
+        ends_on -> Date,
+        note -> Nullable<Text>,
+        lead_id -> Nullable<Int4>,
+        feed_id -> Nullable<Int4>,
+        #[max_length = 255]
+        external_uid -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    property_calendar_feeds (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        property_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        url -> Text,
+        last_synced_at -> Nullable<Timestamp>,
+        last_error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PropertyEventType;
+
+    property_event_daily (property_id, day, event_type) {
+        property_id -> Int4,
+        day -> Date,
+        event_type -> PropertyEventType,
+        count -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::PropertyEventType;
+
+    property_events (id) {
+        id -> Int8,
+        created_at -> Timestamp,
+        property_id -> Int4,
+        event_type -> PropertyEventType,
+        #[max_length = 64]
+        visitor_hash -> Varchar,
+        #[max_length = 64]
+        ip_hash -> Varchar,
+        day -> Date,
+    }
+}
+
+diesel::table! {
+    property_seasonal_prices (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        property_id -> Int4,
+        #[max_length = 255]
+        label -> Varchar,
+        starts_on -> Date,
+        ends_on -> Date,
+        price -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SoldChannel;
+
+    share_links (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        #[max_length = 16]
+        code -> Varchar,
+        property_id -> Int4,
+        user_id -> Nullable<Uuid>,
+        channel -> SoldChannel,
+        #[max_length = 255]
+        campaign -> Nullable<Varchar>,
+        click_count -> Int4,
+        last_clicked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    uploads (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        user_id -> Nullable<Uuid>,
+        #[max_length = 255]
+        original_filename -> Nullable<Varchar>,
+        width -> Int4,
+        height -> Int4,
+        variants -> Jsonb,
+        original_key -> Nullable<Varchar>,
+        is_watermarked -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::JobStatus;
+
+    watermark_jobs (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        started_by -> Nullable<Uuid>,
+        status -> JobStatus,
+        total -> Int4,
+        processed -> Int4,
+        failed -> Int4,
+        error -> Nullable<Text>,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::WatermarkPosition;
+
+    watermark_settings (id) {
+        id -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        logo_upload_id -> Nullable<Int4>,
+        position -> WatermarkPosition,
+        opacity -> Float4,
+        scale -> Float4,
     }
 }
 
+diesel::joinable!(agent_availability_slots -> agents (user_id));
+diesel::joinable!(agent_invites -> branches (branch_id));
+diesel::joinable!(agent_slug_redirects -> agents (agent_id));
+diesel::joinable!(agents -> branches (branch_id));
+diesel::joinable!(appointments -> agents (user_id));
+diesel::joinable!(appointments -> leads (lead_id));
+diesel::joinable!(appointments -> properties (property_id));
+diesel::joinable!(bank_product_revisions -> agents (changed_by));
+diesel::joinable!(bank_product_revisions -> bank_products (product_id));
+diesel::joinable!(bank_products -> banks (bank_id));
+diesel::joinable!(blocked_contacts -> agents (created_by));
+diesel::joinable!(contacts -> agents (user_id));
+diesel::joinable!(exchange_rates -> agents (updated_by));
+diesel::joinable!(lead_assignments -> leads (lead_id));
+diesel::joinable!(lead_rejections -> properties (property_id));
+diesel::joinable!(lead_routing_rules -> agents (user_id));
 diesel::joinable!(leads -> agents (user_id));
+diesel::joinable!(leads -> contacts (contact_id));
 diesel::joinable!(leads -> properties (property_id));
+diesel::joinable!(leads -> share_links (share_link_id));
+diesel::joinable!(offers -> agents (recorded_by));
+diesel::joinable!(offers -> banks (bank_id));
+diesel::joinable!(offers -> leads (lead_id));
+diesel::joinable!(offers -> properties (property_id));
+diesel::joinable!(projects -> developers (developer_id));
 diesel::joinable!(properties -> agents (user_id));
 diesel::joinable!(properties -> banks (bank_id));
 diesel::joinable!(properties -> developers (developer_id));
+diesel::joinable!(properties -> projects (project_id));
+diesel::joinable!(property_availability -> leads (lead_id));
+diesel::joinable!(property_availability -> properties (property_id));
+diesel::joinable!(property_availability -> property_calendar_feeds (feed_id));
+diesel::joinable!(property_calendar_feeds -> properties (property_id));
+diesel::joinable!(property_event_daily -> properties (property_id));
+diesel::joinable!(property_events -> properties (property_id));
+diesel::joinable!(property_seasonal_prices -> properties (property_id));
+diesel::joinable!(share_links -> agents (user_id));
+diesel::joinable!(share_links -> properties (property_id));
+diesel::joinable!(uploads -> agents (user_id));
+diesel::joinable!(watermark_jobs -> agents (started_by));
+diesel::joinable!(watermark_settings -> uploads (logo_upload_id));
 
-diesel::allow_tables_to_appear_in_same_query!(agents, banks, developers, leads, properties,);
+diesel::allow_tables_to_appear_in_same_query!(
+    agent_availability_slots,
+    agent_invites,
+    agent_slug_redirects,
+    agent_transfers,
+    agents,
+    appointments,
+    bank_product_revisions,
+    bank_products,
+    banks,
+    blocked_contacts,
+    branches,
+    contacts,
+    developers,
+    exchange_rates,
+    lead_assignments,
+    lead_rejections,
+    lead_routing_rules,
+    leads,
+    offers,
+    outbox_messages,
+    projects,
+    properties,
+    property_availability,
+    property_calendar_feeds,
+    property_event_daily,
+    property_events,
+    property_seasonal_prices,
+    share_links,
+    uploads,
+    watermark_jobs,
+    watermark_settings,
+);
@@ -0,0 +1,57 @@
+use diesel::result::{DatabaseErrorKind, Error};
+use diesel::{Connection, PgConnection, QueryResult};
+
+const SLUG_ATTEMPTS: usize = 3;
+
+// "Budi  Santoso!" -> "budi-santoso", the fallback when no letters or digits are left
+pub fn slugify(text: &str, fallback: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+
+    match slug.is_empty() {
+        true => fallback.to_string(),
+        false => slug,
+    }
+}
+
+// First slug from `base` that `is_taken` turns down: "summarecon", then "summarecon-2", ...
+pub fn available_slug<F>(
+    conn: &mut PgConnection,
+    base: &str,
+    mut is_taken: F,
+) -> QueryResult<String>
+where
+    F: FnMut(&mut PgConnection, &str) -> QueryResult<bool>,
+{
+    let mut slug = base.to_string();
+    let mut suffix = 1;
+
+    while is_taken(conn, &slug)? {
+        suffix += 1;
+        slug = format!("{}-{}", base, suffix);
+    }
+
+    Ok(slug)
+}
+
+// Runs `insert` in a transaction, again when another row took the slug it picked meanwhile
+pub fn insert_with_slug<T, F>(conn: &mut PgConnection, mut insert: F) -> QueryResult<T>
+where
+    F: FnMut(&mut PgConnection) -> QueryResult<T>,
+{
+    let mut attempts = 0;
+    loop {
+        let result = conn.transaction(&mut insert);
+
+        attempts += 1;
+        match result {
+            Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _))
+                if attempts < SLUG_ATTEMPTS => {}
+            result => return result,
+        }
+    }
+}
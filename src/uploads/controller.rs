@@ -0,0 +1,336 @@
+use axum::extract::multipart::Field;
+use axum::extract::{DefaultBodyLimit, Json, Multipart, Path, Query, State};
+use axum::http::HeaderMap;
+use axum::routing::{get, post};
+use axum::Router;
+use diesel::result::DatabaseErrorKind;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::Arc;
+
+use super::model::{NewUpload, Upload, UploadVariant};
+use super::processing::{process, ProcessedImage, VariantFormat};
+use super::storage::{private_storage, storage, Storage};
+use super::watermark::{
+    load_watermark, run_rerender_job, UpdateWatermarkSettingsPayload, WatermarkJob,
+    WatermarkSettings,
+};
+use crate::agents::{authorize, Action, Resource};
+use crate::db::DbPool;
+use crate::middleware::{AxumResponse, JsonResponse, Session};
+
+const DEFAULT_MAX_FILE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_FILES: usize = 20;
+
+fn max_file_bytes() -> usize {
+    env::var("UPLOAD_MAX_BYTES")
+        .ok()
+        .and_then(|bytes| bytes.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_FILE_BYTES)
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadResponse {
+    #[serde(flatten)]
+    upload: Upload,
+    // largest size in the fallback format, the one to save when only one path fits
+    path: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadKind {
+    // listing photos, the public sizes get the watermark
+    Property,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    kind: Option<UploadKind>,
+}
+
+// Reads a `file` field in chunks, stopping as soon as it goes over `max_bytes`
+async fn read_field(
+    field: &mut Field<'_>,
+    max_bytes: usize,
+) -> Result<Vec<u8>, AxumResponse<Vec<UploadResponse>>> {
+    let mut bytes = vec![];
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) => {
+                if bytes.len() + chunk.len() > max_bytes {
+                    return Err(JsonResponse::send(
+                        413,
+                        None,
+                        Some(format!("Files can be at most {} bytes", max_bytes)),
+                    ));
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            Ok(None) => return Ok(bytes),
+            Err(err) => return Err(JsonResponse::send(400, None, Some(err.to_string()))),
+        }
+    }
+}
+
+// Takes one or more `file` fields, returns every stored size per file.
+// Each file is processed and stored as it arrives, only one is in memory at a time.
+async fn create_uploads(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Query(query): Query<UploadQuery>,
+    mut multipart: Multipart,
+) -> AxumResponse<Vec<UploadResponse>> {
+    let user_id = Session::extract_session_user_id(&headers);
+    let max_bytes = max_file_bytes();
+
+    let is_watermarked = query.kind == Some(UploadKind::Property);
+    let watermark = match is_watermarked {
+        true => match load_watermark(&pool).await {
+            Ok(watermark) => Arc::new(watermark),
+            Err(err) => return JsonResponse::send(500, None, Some(err)),
+        },
+        false => Arc::new(None),
+    };
+
+    let mut responses = vec![];
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return JsonResponse::send(400, None, Some(err.to_string())),
+        };
+        if field.name() != Some("file") {
+            continue;
+        }
+        if responses.len() == MAX_FILES {
+            return JsonResponse::send(
+                400,
+                None,
+                Some(format!("At most {} files per upload", MAX_FILES)),
+            );
+        }
+
+        let filename: Option<String> = field
+            .file_name()
+            .map(|name| name.chars().take(255).collect());
+        let bytes = match read_field(&mut field, max_bytes).await {
+            Ok(bytes) => bytes,
+            Err(response) => return response,
+        };
+
+        // the original comes back out of the blocking task to be stored as well
+        let watermark = watermark.clone();
+        let processing = tokio::task::spawn_blocking(move || {
+            process(&bytes, (*watermark).as_ref()).map(|processed| (processed, bytes))
+        });
+        let (processed, original) = match processing.await {
+            Ok(Ok(processed)) => processed,
+            Ok(Err(err)) => return JsonResponse::send(400, None, Some(err)),
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        };
+
+        match store(
+            &pool,
+            &user_id,
+            &filename,
+            original,
+            processed,
+            is_watermarked,
+        )
+        .await
+        {
+            Ok(response) => responses.push(response),
+            Err(err) => return JsonResponse::send(500, None, Some(err)),
+        }
+    }
+
+    if responses.is_empty() {
+        return JsonResponse::send(400, None, Some("No file uploaded".to_string()));
+    }
+
+    JsonResponse::send(201, Some(responses), None)
+}
+
+// The row goes in first, so files of a failed upload still get garbage collected.
+// The original is kept in private storage to render the public sizes from again.
+async fn store(
+    pool: &DbPool,
+    user_id: &uuid::Uuid,
+    filename: &Option<String>,
+    original: Vec<u8>,
+    processed: ProcessedImage,
+    is_watermarked: bool,
+) -> Result<UploadResponse, String> {
+    let prefix = format!(
+        "{}/{}",
+        chrono::Local::now().format("%Y/%m"),
+        uuid::Uuid::new_v4()
+    );
+    let original_key = format!("{}/original.{}", prefix, processed.format.extension());
+
+    let variants: Vec<UploadVariant> = processed
+        .variants
+        .iter()
+        .map(|variant| {
+            let key = format!(
+                "{}/{}.{}",
+                prefix,
+                variant.width,
+                variant.format.extension()
+            );
+            UploadVariant {
+                path: storage().url(&key),
+                key,
+                width: variant.width,
+                height: variant.height,
+                format: variant.format,
+            }
+        })
+        .collect();
+
+    let path = variants
+        .iter()
+        .rev()
+        .find(|variant| variant.format != VariantFormat::Webp)
+        .map(|variant| variant.path.clone())
+        .unwrap_or_default();
+
+    let upload = Upload::create(
+        pool,
+        &NewUpload {
+            user_id,
+            original_filename: filename.as_deref(),
+            width: processed.width as i32,
+            height: processed.height as i32,
+            variants: serde_json::json!(variants),
+            original_key: &original_key,
+            is_watermarked,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+
+    private_storage()
+        .put(&original_key, original, processed.format.content_type())
+        .await?;
+
+    for (variant, encoded) in variants.iter().zip(processed.variants) {
+        storage()
+            .put(&variant.key, encoded.bytes, variant.format.content_type())
+            .await?;
+    }
+
+    Ok(UploadResponse { upload, path })
+}
+
+async fn find_watermark_settings(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+) -> AxumResponse<WatermarkSettings> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::WatermarkSettings) {
+        return response;
+    }
+
+    match WatermarkSettings::find(&pool) {
+        Ok(settings) => JsonResponse::send(200, Some(settings), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Only applies to new uploads, existing ones change with a re-render
+async fn update_watermark_settings(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateWatermarkSettingsPayload>,
+) -> AxumResponse<WatermarkSettings> {
+    if let Err(response) = authorize(&pool, &headers, Action::Update, Resource::WatermarkSettings) {
+        return response;
+    }
+
+    if !(0.0..=1.0).contains(&payload.opacity) {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Opacity must be between 0 and 1".to_string()),
+        );
+    }
+    if payload.scale <= 0.0 || payload.scale > 1.0 {
+        return JsonResponse::send(
+            400,
+            None,
+            Some("Scale must be above 0 and at most 1".to_string()),
+        );
+    }
+    if let Some(logo_upload_id) = payload.logo_upload_id {
+        match Upload::find_by_id(&pool, &logo_upload_id) {
+            Ok(upload) if upload.original_key.is_some() => {}
+            Ok(_) | Err(diesel::result::Error::NotFound) => {
+                return JsonResponse::send(400, None, Some("Logo upload not found".to_string()))
+            }
+            Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+        }
+    }
+
+    match WatermarkSettings::update(&pool, &payload) {
+        Ok(settings) => JsonResponse::send(200, Some(settings), None),
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+// Starts re-rendering every watermarked upload in the background, poll the job for progress
+async fn create_watermark_job(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+) -> AxumResponse<WatermarkJob> {
+    let user_id = match authorize(&pool, &headers, Action::Update, Resource::WatermarkSettings) {
+        Ok(agent) => agent.id,
+        Err(response) => return response,
+    };
+
+    let job = match WatermarkJob::create(&pool, &user_id) {
+        Ok(job) => job,
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            return JsonResponse::send(
+                409,
+                None,
+                Some("A watermark job is already running".to_string()),
+            )
+        }
+        Err(err) => return JsonResponse::send(500, None, Some(err.to_string())),
+    };
+
+    tokio::spawn(run_rerender_job(pool.clone(), job.id));
+
+    JsonResponse::send(202, Some(job), None)
+}
+
+async fn find_watermark_job(
+    State(pool): State<DbPool>,
+    headers: HeaderMap,
+    Path(job_id): Path<i32>,
+) -> AxumResponse<WatermarkJob> {
+    if let Err(response) = authorize(&pool, &headers, Action::View, Resource::WatermarkSettings) {
+        return response;
+    }
+
+    match WatermarkJob::find_by_id(&pool, &job_id) {
+        Ok(job) => JsonResponse::send(200, Some(job), None),
+        Err(diesel::result::Error::NotFound) => {
+            JsonResponse::send(404, None, Some("Watermark job not found".to_string()))
+        }
+        Err(err) => JsonResponse::send(500, None, Some(err.to_string())),
+    }
+}
+
+pub fn upload_routes() -> Router<DbPool> {
+    Router::new()
+        .route("/", post(create_uploads))
+        .route(
+            "/watermark",
+            get(find_watermark_settings).put(update_watermark_settings),
+        )
+        .route("/watermark/jobs", post(create_watermark_job))
+        .route("/watermark/jobs/{id}", get(find_watermark_job))
+        // room for a full set of files, each one is checked against UPLOAD_MAX_BYTES
+        .layer(DefaultBodyLimit::max(max_file_bytes() * MAX_FILES))
+}
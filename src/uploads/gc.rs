@@ -0,0 +1,62 @@
+use std::env;
+
+use super::model::Upload;
+use super::storage::{private_storage, storage, Storage};
+use crate::db::DbPool;
+
+const DEFAULT_GRACE_HOURS: i32 = 24;
+const GC_INTERVAL_MINUTES: u64 = 60;
+const GC_BATCH_SIZE: i64 = 100;
+
+async fn collect_garbage(pool: &DbPool, grace_hours: i32) -> Result<usize, String> {
+    let find_pool = pool.clone();
+    let unused = tokio::task::spawn_blocking(move || {
+        Upload::find_unused(&find_pool, grace_hours, GC_BATCH_SIZE)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())?;
+
+    let mut removed = 0;
+    for upload in unused {
+        let id = upload.id;
+        let mut result = Ok(());
+        for key in &upload.keys {
+            result = result.and(storage().delete(key).await);
+        }
+        if let Some(original_key) = &upload.original_key {
+            result = result.and(private_storage().delete(original_key).await);
+        }
+        // the row stays when a file couldn't be removed, so the next run retries it
+        if let Err(err) = result {
+            tracing::error!("Failed to remove upload {}: {}", id, err);
+            continue;
+        }
+
+        let delete_pool = pool.clone();
+        match tokio::task::spawn_blocking(move || Upload::delete(&delete_pool, &id)).await {
+            Ok(Ok(_)) => removed += 1,
+            Ok(Err(err)) => tracing::error!("Failed to remove upload {}: {}", id, err),
+            Err(err) => tracing::error!("Failed to remove upload {}: {}", id, err),
+        }
+    }
+
+    Ok(removed)
+}
+
+// Removes uploads nobody saved a path of within UPLOAD_GC_GRACE_HOURS, checked hourly
+pub async fn collect_garbage_periodically(pool: DbPool) {
+    let grace_hours = env::var("UPLOAD_GC_GRACE_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse::<i32>().ok())
+        .unwrap_or(DEFAULT_GRACE_HOURS);
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(GC_INTERVAL_MINUTES * 60));
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = collect_garbage(&pool, grace_hours).await {
+            tracing::error!("Failed to collect unused uploads: {}", err);
+        }
+    }
+}
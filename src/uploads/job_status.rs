@@ -0,0 +1,37 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, FromSqlRow)]
+#[diesel(sql_type = sql_types::JobStatus)]
+pub enum JobStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+impl ToSql<sql_types::JobStatus, Pg> for JobStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            JobStatus::Running => out.write_all(b"running")?,
+            JobStatus::Done => out.write_all(b"done")?,
+            JobStatus::Failed => out.write_all(b"failed")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::JobStatus, Pg> for JobStatus {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"running" => Ok(JobStatus::Running),
+            b"done" => Ok(JobStatus::Done),
+            b"failed" => Ok(JobStatus::Failed),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+mod controller;
+mod gc;
+mod job_status;
+mod model;
+mod processing;
+mod storage;
+mod watermark;
+mod watermark_position;
+
+pub use controller::upload_routes;
+pub use gc::collect_garbage_periodically;
+pub use model::Upload;
+pub use processing::decode as decode_image;
+pub use storage::fetch;
@@ -0,0 +1,214 @@
+use diesel::prelude::Insertable;
+use diesel::sql_types::{Array, Int4, Nullable, Text};
+use diesel::{
+    Connection, ExpressionMethods, QueryDsl, QueryResult, Queryable, QueryableByName, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+use super::processing::VariantFormat;
+use crate::{db::DbPool, schema::uploads};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadVariant {
+    pub key: String,
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: VariantFormat,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct Upload {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    user_id: Option<uuid::Uuid>,
+    original_filename: Option<String>,
+    width: i32,
+    height: i32,
+    pub variants: serde_json::Value,
+    #[serde(skip)]
+    pub original_key: Option<String>,
+    // the public sizes carry the watermark, re-rendered when the logo changes
+    pub is_watermarked: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = uploads)]
+pub(super) struct NewUpload<'a> {
+    pub user_id: &'a uuid::Uuid,
+    pub original_filename: Option<&'a str>,
+    pub width: i32,
+    pub height: i32,
+    pub variants: serde_json::Value,
+    pub original_key: &'a str,
+    pub is_watermarked: bool,
+}
+
+// Public keys of the sizes plus the key of the private original
+#[derive(QueryableByName)]
+pub(super) struct UnusedUpload {
+    #[diesel(sql_type = Int4)]
+    pub id: i32,
+    #[diesel(sql_type = Array<Text>)]
+    pub keys: Vec<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub original_key: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct ImagePath {
+    #[diesel(sql_type = Text)]
+    path: String,
+}
+
+impl Upload {
+    pub(super) fn create(pool: &DbPool, upload: &NewUpload) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::insert_into(uploads::table)
+            .values(upload)
+            .get_result(conn)
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        uploads::table.find(id).get_result(conn)
+    }
+
+    // Watermarked uploads in id order, `after_id` being the last one of the previous batch
+    pub(super) fn find_watermarked(
+        pool: &DbPool,
+        after_id: i32,
+        limit: i64,
+    ) -> QueryResult<Vec<Self>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        uploads::table
+            .filter(uploads::is_watermarked.eq(true))
+            .filter(uploads::id.gt(after_id))
+            .order_by(uploads::id)
+            .limit(limit)
+            .get_results(conn)
+    }
+
+    pub(super) fn count_watermarked(pool: &DbPool) -> QueryResult<i64> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        uploads::table
+            .filter(uploads::is_watermarked.eq(true))
+            .count()
+            .get_result(conn)
+    }
+
+    // Swaps the public sizes of a re-rendered upload for their new keys, listings
+    // showing the old paths get the new ones in the same transaction
+    pub(super) fn replace_variants(
+        pool: &DbPool,
+        id: &i32,
+        old_variants: &[UploadVariant],
+        new_variants: &[UploadVariant],
+    ) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            diesel::update(uploads::table.find(id))
+                .set(uploads::variants.eq(serde_json::json!(new_variants)))
+                .execute(conn)?;
+
+            let mut updated = 0;
+            for (old_variant, new_variant) in old_variants.iter().zip(new_variants) {
+                updated += diesel::sql_query(
+                    "UPDATE properties SET images = (
+                        SELECT jsonb_agg(
+                            CASE WHEN img->>'path' = $1 THEN jsonb_set(img, '{path}', to_jsonb($2::text))
+                            ELSE img END
+                            ORDER BY position
+                        )
+                        FROM jsonb_array_elements(images) WITH ORDINALITY AS t(img, position)
+                    )
+                    WHERE images @> jsonb_build_array(jsonb_build_object('path', $1::text))",
+                )
+                .bind::<Text, _>(&old_variant.path)
+                .bind::<Text, _>(&new_variant.path)
+                .execute(conn)?;
+            }
+            Ok(updated)
+        })
+    }
+
+    // Of the image paths, those that aren't a size of a watermarked upload. Paths
+    // already on the listing `property_id` pass, they were saved before uploads existed
+    pub fn find_unwatermarked_paths(
+        pool: &DbPool,
+        paths: &[String],
+        property_id: Option<i32>,
+    ) -> QueryResult<Vec<String>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::sql_query(
+            "SELECT path FROM unnest($1::text[]) AS path
+            WHERE NOT EXISTS (
+                SELECT 1 FROM uploads u
+                WHERE u.is_watermarked
+                AND u.variants @> jsonb_build_array(jsonb_build_object('path', path))
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM properties p
+                WHERE p.id = $2
+                AND p.images @> jsonb_build_array(jsonb_build_object('path', path))
+            )",
+        )
+        .bind::<Array<Text>, _>(paths)
+        .bind::<Nullable<Int4>, _>(property_id)
+        .load::<ImagePath>(conn)
+        .map(|rows| rows.into_iter().map(|row| row.path).collect())
+    }
+
+    pub(super) fn parsed_variants(&self) -> Result<Vec<UploadVariant>, String> {
+        serde_json::from_value(self.variants.clone()).map_err(|err| err.to_string())
+    }
+
+    // Uploads past the grace period whose paths aren't saved on any listing, agent, bank or developer,
+    // leaving out the watermark logo
+    pub(super) fn find_unused(
+        pool: &DbPool,
+        grace_hours: i32,
+        limit: i64,
+    ) -> QueryResult<Vec<UnusedUpload>> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::sql_query(
+            "SELECT u.id, ARRAY(SELECT v->>'key' FROM jsonb_array_elements(u.variants) v) AS keys,
+            u.original_key
+            FROM uploads u
+            WHERE u.created_at < NOW() - ($1 * INTERVAL '1 hour')
+            AND NOT EXISTS (SELECT 1 FROM watermark_settings w WHERE w.logo_upload_id = u.id)
+            AND NOT EXISTS (
+                SELECT 1 FROM jsonb_array_elements(u.variants) v
+                WHERE EXISTS (
+                    SELECT 1 FROM properties p, jsonb_array_elements(p.images) img
+                    WHERE img->>'path' = v->>'path'
+                )
+                OR EXISTS (SELECT 1 FROM agents a WHERE a.profile_picture_url = v->>'path')
+                OR EXISTS (SELECT 1 FROM banks b WHERE b.logo_path = v->>'path')
+                OR EXISTS (SELECT 1 FROM developers d WHERE d.logo_path = v->>'path')
+                OR EXISTS (SELECT 1 FROM projects pr WHERE pr.brochure_path = v->>'path')
+            )
+            ORDER BY u.created_at
+            LIMIT $2",
+        )
+        .bind::<Int4, _>(grace_hours)
+        .bind::<diesel::sql_types::BigInt, _>(limit)
+        .load::<UnusedUpload>(conn)
+    }
+
+    pub(super) fn delete(pool: &DbPool, id: &i32) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::delete(uploads::table)
+            .filter(uploads::id.eq(id))
+            .execute(conn)
+    }
+}
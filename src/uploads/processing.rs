@@ -0,0 +1,200 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::{overlay, FilterType};
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use super::watermark_position::WatermarkPosition;
+
+// Widths of the responsive sizes, never larger than the original
+const VARIANT_WIDTHS: [u32; 4] = [320, 640, 1024, 1600];
+// Listings show at most 1600px wide, this still takes a 24MP camera photo while a
+// decoded image stays under 100MB
+const MAX_DIMENSION: u32 = 8_000;
+const MAX_PIXELS: u64 = 24_000_000;
+const JPEG_QUALITY: u8 = 82;
+const WEBP_QUALITY: f32 = 80.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl VariantFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VariantFormat::Jpeg => "jpg",
+            VariantFormat::Png => "png",
+            VariantFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            VariantFormat::Jpeg => "image/jpeg",
+            VariantFormat::Png => "image/png",
+            VariantFormat::Webp => "image/webp",
+        }
+    }
+}
+
+pub struct EncodedVariant {
+    pub width: u32,
+    pub height: u32,
+    pub format: VariantFormat,
+    pub bytes: Vec<u8>,
+}
+
+pub struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    // format of the uploaded file itself
+    pub format: VariantFormat,
+    pub variants: Vec<EncodedVariant>,
+}
+
+// Logo stamped on the public sizes, `scale` is its width relative to the image width
+pub struct Watermark {
+    pub logo: DynamicImage,
+    pub position: WatermarkPosition,
+    pub opacity: f32,
+    pub scale: f32,
+}
+
+// Only formats browsers show everywhere, checked on the content rather than the filename
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage, String> {
+    decode_with_format(bytes).map(|(image, _)| image)
+}
+
+fn decode_with_format(bytes: &[u8]) -> Result<(DynamicImage, VariantFormat), String> {
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|err| err.to_string())?;
+
+    let format = match reader.format() {
+        Some(ImageFormat::Jpeg) => VariantFormat::Jpeg,
+        Some(ImageFormat::Png) => VariantFormat::Png,
+        Some(ImageFormat::WebP) => VariantFormat::Webp,
+        _ => return Err("Only JPEG, PNG and WebP images are allowed".to_string()),
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|err| err.to_string())?;
+    let (width, height) = decoder.dimensions();
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(format!(
+            "Images can be at most {} megapixels",
+            MAX_PIXELS / 1_000_000
+        ));
+    }
+    let orientation = decoder.orientation().map_err(|err| err.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|err| err.to_string())?;
+    // metadata is dropped when re-encoding, so bake the EXIF rotation into the pixels first
+    image.apply_orientation(orientation);
+
+    Ok((image, format))
+}
+
+pub fn encode(image: &DynamicImage, format: VariantFormat) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    match format {
+        VariantFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY))
+            .map_err(|err| err.to_string())?,
+        VariantFormat::Png => image
+            .write_with_encoder(PngEncoder::new(&mut bytes))
+            .map_err(|err| err.to_string())?,
+        // lossy, the encoder in `image` only does lossless which ends up bigger than the JPEG
+        VariantFormat::Webp => {
+            let rgba = image.to_rgba8();
+            bytes = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode(WEBP_QUALITY)
+                .to_vec();
+        }
+    };
+
+    Ok(bytes)
+}
+
+fn apply_watermark(image: DynamicImage, watermark: &Watermark) -> DynamicImage {
+    let logo_width = ((image.width() as f32 * watermark.scale).round() as u32).max(1);
+    let mut logo = watermark
+        .logo
+        .resize(logo_width, u32::MAX, FilterType::Lanczos3)
+        .to_rgba8();
+    for pixel in logo.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * watermark.opacity).round() as u8;
+    }
+
+    let margin = image.width().min(image.height()) as i64 / 40;
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let (logo_width, logo_height) = (logo.width() as i64, logo.height() as i64);
+    let (x, y) = match watermark.position {
+        WatermarkPosition::TopLeft => (margin, margin),
+        WatermarkPosition::TopRight => (width - logo_width - margin, margin),
+        WatermarkPosition::BottomLeft => (margin, height - logo_height - margin),
+        WatermarkPosition::BottomRight => {
+            (width - logo_width - margin, height - logo_height - margin)
+        }
+        WatermarkPosition::Center => ((width - logo_width) / 2, (height - logo_height) / 2),
+    };
+
+    let mut stamped = image.to_rgba8();
+    overlay(&mut stamped, &logo, x, y);
+    DynamicImage::ImageRgba8(stamped)
+}
+
+// Every responsive size in the fallback format plus WebP, watermarked when one is given.
+// Images with transparency fall back to PNG, everything else to JPEG.
+pub fn process(bytes: &[u8], watermark: Option<&Watermark>) -> Result<ProcessedImage, String> {
+    let (image, format) = decode_with_format(bytes)?;
+    let (width, height) = (image.width(), image.height());
+    let fallback = match image.color().has_alpha() {
+        true => VariantFormat::Png,
+        false => VariantFormat::Jpeg,
+    };
+
+    let mut widths: Vec<u32> = VARIANT_WIDTHS
+        .into_iter()
+        .filter(|variant_width| *variant_width < width)
+        .collect();
+    widths.push(width.min(*VARIANT_WIDTHS.last().unwrap_or(&width)));
+    widths.dedup();
+
+    let mut variants = vec![];
+    for variant_width in widths {
+        let resized = match variant_width == width {
+            true => image.clone(),
+            false => image.resize(variant_width, u32::MAX, FilterType::Lanczos3),
+        };
+        // stamped after resizing so the logo stays sharp on the small sizes
+        let resized = match watermark {
+            Some(watermark) => apply_watermark(resized, watermark),
+            None => resized,
+        };
+        for format in [fallback, VariantFormat::Webp] {
+            variants.push(EncodedVariant {
+                width: resized.width(),
+                height: resized.height(),
+                format,
+                bytes: encode(&resized, format)?,
+            });
+        }
+    }
+
+    Ok(ProcessedImage {
+        width,
+        height,
+        format,
+        variants,
+    })
+}
@@ -0,0 +1,286 @@
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use std::env;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const FETCH_TIMEOUT_SECONDS: u64 = 10;
+// Larger than any original the upload endpoint accepts
+const MAX_FETCH_BYTES: usize = 20 * 1024 * 1024;
+
+// Where uploaded files end up. `key` is the object name, `url` is the path clients save.
+pub trait Storage {
+    fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        content_type: &str,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+    fn get(&self, key: &str) -> impl Future<Output = Result<Vec<u8>, String>> + Send;
+    fn delete(&self, key: &str) -> impl Future<Output = Result<(), String>> + Send;
+    fn url(&self, key: &str) -> String;
+}
+
+// Files on disk, served by whatever sits in front of the api
+pub struct LocalStorage {
+    dir: PathBuf,
+    public_url: String,
+}
+
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, bytes: Vec<u8>, _content_type: &str) -> Result<(), String> {
+        let path = self.dir.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+        tokio::fs::write(path, bytes)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String> {
+        tokio::fs::read(self.dir.join(key))
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match tokio::fs::remove_file(self.dir.join(key)).await {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+// Any S3 compatible bucket, MinIO included
+pub struct S3Storage {
+    bucket: Box<Bucket>,
+    public_url: String,
+}
+
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), String> {
+        self.bucket
+            .put_object_with_content_type(key, &bytes, content_type)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String> {
+        self.bucket
+            .get_object(key)
+            .await
+            .map(|response| response.bytes().to_vec())
+            .map_err(|err| err.to_string())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.bucket
+            .delete_object(key)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+}
+
+pub enum StorageBackend {
+    Local(LocalStorage),
+    S3(S3Storage),
+}
+
+impl Storage for StorageBackend {
+    async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), String> {
+        match self {
+            StorageBackend::Local(storage) => storage.put(key, bytes, content_type).await,
+            StorageBackend::S3(storage) => storage.put(key, bytes, content_type).await,
+        }
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String> {
+        match self {
+            StorageBackend::Local(storage) => storage.get(key).await,
+            StorageBackend::S3(storage) => storage.get(key).await,
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match self {
+            StorageBackend::Local(storage) => storage.delete(key).await,
+            StorageBackend::S3(storage) => storage.delete(key).await,
+        }
+    }
+
+    fn url(&self, key: &str) -> String {
+        match self {
+            StorageBackend::Local(storage) => storage.url(key),
+            StorageBackend::S3(storage) => storage.url(key),
+        }
+    }
+}
+
+impl StorageBackend {
+    fn public_url(&self) -> &str {
+        match self {
+            StorageBackend::Local(storage) => &storage.public_url,
+            StorageBackend::S3(storage) => &storage.public_url,
+        }
+    }
+
+    // The key behind a path handed out by `url`. Keys are always relative names below
+    // the upload dir or bucket, so `..`, absolute and empty components don't resolve.
+    fn key_of<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let key = path.strip_prefix(self.public_url())?.strip_prefix('/')?;
+        let is_relative = !key.is_empty()
+            && !key.split('/').any(|segment| segment.is_empty())
+            && Path::new(key)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        is_relative.then_some(key)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visibility {
+    Public,
+    // originals, never handed out to clients
+    Private,
+}
+
+impl StorageBackend {
+    fn from_env(visibility: Visibility) -> Self {
+        match env::var("STORAGE_DRIVER").as_deref() {
+            Ok("s3") => {
+                let public_bucket_name = env::var("S3_BUCKET").expect("Missing S3_BUCKET");
+                let bucket_name = match visibility {
+                    Visibility::Public => public_bucket_name,
+                    Visibility::Private => env::var("S3_PRIVATE_BUCKET")
+                        .unwrap_or(format!("{}-private", public_bucket_name)),
+                };
+                let endpoint = env::var("S3_ENDPOINT").expect("Missing S3_ENDPOINT");
+                let region = Region::Custom {
+                    region: env::var("S3_REGION").unwrap_or("us-east-1".to_string()),
+                    endpoint: endpoint.clone(),
+                };
+                let credentials = Credentials::new(
+                    Some(&env::var("S3_ACCESS_KEY").expect("Missing S3_ACCESS_KEY")),
+                    Some(&env::var("S3_SECRET_KEY").expect("Missing S3_SECRET_KEY")),
+                    None,
+                    None,
+                    None,
+                )
+                .expect("Invalid S3 credentials");
+                let bucket = Bucket::new(&bucket_name, region, credentials)
+                    .expect("Invalid S3 bucket")
+                    .with_path_style();
+                let public_url = env::var("S3_PUBLIC_URL").unwrap_or(format!(
+                    "{}/{}",
+                    endpoint.trim_end_matches('/'),
+                    bucket_name
+                ));
+
+                StorageBackend::S3(S3Storage {
+                    bucket,
+                    public_url: public_url.trim_end_matches('/').to_string(),
+                })
+            }
+            _ => StorageBackend::Local(LocalStorage {
+                dir: PathBuf::from(match visibility {
+                    Visibility::Public => env::var("UPLOADS_DIR").unwrap_or("uploads".to_string()),
+                    Visibility::Private => {
+                        env::var("UPLOADS_PRIVATE_DIR").unwrap_or("uploads-private".to_string())
+                    }
+                }),
+                public_url: env::var("UPLOADS_PUBLIC_URL")
+                    .unwrap_or("/uploads".to_string())
+                    .trim_end_matches('/')
+                    .to_string(),
+            }),
+        }
+    }
+}
+
+// Picked once from STORAGE_DRIVER, `local` unless set to `s3`
+pub fn storage() -> &'static StorageBackend {
+    static STORAGE: OnceLock<StorageBackend> = OnceLock::new();
+    STORAGE.get_or_init(|| StorageBackend::from_env(Visibility::Public))
+}
+
+// The host of an absolute url, lowercased and with the port
+fn host_of(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    if host.is_empty() || host.contains('@') {
+        return None;
+    }
+    Some(host.to_lowercase())
+}
+
+// Remote images are only downloaded from our own public url, or the CDN in front of it
+// from UPLOADS_CDN_URL, so a saved path can't make the api request arbitrary hosts
+fn is_allowed_host(url: &str) -> bool {
+    let Some(host) = host_of(url) else {
+        return false;
+    };
+    let cdn_url = env::var("UPLOADS_CDN_URL").unwrap_or_default();
+    let is_allowed = [storage().public_url(), cdn_url.as_str()]
+        .into_iter()
+        .filter_map(host_of)
+        .any(|allowed| allowed == host);
+    is_allowed
+}
+
+// Bytes behind a saved path, read from storage when it's one of ours and downloaded otherwise
+pub async fn fetch(path: &str) -> Result<Vec<u8>, String> {
+    if let Some(key) = storage().key_of(path) {
+        return storage().get(key).await;
+    }
+    if !is_allowed_host(path) {
+        return Err(format!("Can't fetch {}", path));
+    }
+
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    let client = CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(FETCH_TIMEOUT_SECONDS))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Couldn't build the http client")
+    });
+    let mut response = client
+        .get(path)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?;
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|err| err.to_string())? {
+        if bytes.len() + chunk.len() > MAX_FETCH_BYTES {
+            return Err(format!("{} is larger than {} bytes", path, MAX_FETCH_BYTES));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+// Same driver, but UPLOADS_PRIVATE_DIR or S3_PRIVATE_BUCKET (`<S3_BUCKET>-private` by default)
+pub fn private_storage() -> &'static StorageBackend {
+    static STORAGE: OnceLock<StorageBackend> = OnceLock::new();
+    STORAGE.get_or_init(|| StorageBackend::from_env(Visibility::Private))
+}
@@ -0,0 +1,332 @@
+use diesel::dsl::{now, IntervalDsl};
+use diesel::prelude::AsChangeset;
+use diesel::{
+    Connection, ExpressionMethods, NullableExpressionMethods, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::job_status::JobStatus;
+use super::model::{Upload, UploadVariant};
+use super::processing::{decode, process, Watermark};
+use super::storage::{private_storage, storage, Storage};
+use super::watermark_position::WatermarkPosition;
+use crate::db::DbPool;
+use crate::schema::{watermark_jobs, watermark_settings};
+
+const RERENDER_BATCH_SIZE: i64 = 50;
+// A running job saves its progress after every upload, one that hasn't for this long is dead
+const JOB_LEASE_MINUTES: i32 = 10;
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct WatermarkSettings {
+    id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    pub logo_upload_id: Option<i32>,
+    pub position: WatermarkPosition,
+    pub opacity: f32,
+    pub scale: f32,
+}
+
+#[derive(Debug, Deserialize, AsChangeset)]
+#[diesel(table_name = watermark_settings)]
+#[diesel(treat_none_as_null = true)]
+pub(super) struct UpdateWatermarkSettingsPayload {
+    pub logo_upload_id: Option<i32>,
+    pub position: WatermarkPosition,
+    pub opacity: f32,
+    pub scale: f32,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct WatermarkJob {
+    pub id: i32,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+    started_by: Option<uuid::Uuid>,
+    status: JobStatus,
+    total: i32,
+    processed: i32,
+    failed: i32,
+    error: Option<String>,
+    finished_at: Option<chrono::NaiveDateTime>,
+}
+
+// There's a single settings row, created by the migration
+impl WatermarkSettings {
+    pub(super) fn find(pool: &DbPool) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        watermark_settings::table.first(conn)
+    }
+
+    pub(super) fn update(
+        pool: &DbPool,
+        payload: &UpdateWatermarkSettingsPayload,
+    ) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(watermark_settings::table)
+            .set(payload)
+            .get_result(conn)
+    }
+}
+
+impl WatermarkJob {
+    // UniqueViolation while another job is still running. A running job whose instance
+    // stopped reporting progress is failed first, so it doesn't block new ones forever.
+    pub(super) fn create(pool: &DbPool, started_by: &uuid::Uuid) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        conn.transaction(|conn| {
+            diesel::update(watermark_jobs::table)
+                .filter(watermark_jobs::status.eq(JobStatus::Running))
+                .filter(watermark_jobs::updated_at.lt(now - JOB_LEASE_MINUTES.minutes()))
+                .set((
+                    watermark_jobs::status.eq(JobStatus::Failed),
+                    watermark_jobs::error.eq("Interrupted"),
+                    watermark_jobs::finished_at.eq(now.nullable()),
+                ))
+                .execute(conn)?;
+
+            diesel::insert_into(watermark_jobs::table)
+                .values(watermark_jobs::started_by.eq(started_by))
+                .get_result(conn)
+        })
+    }
+
+    pub(super) fn find_by_id(pool: &DbPool, id: &i32) -> QueryResult<Self> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        watermark_jobs::table.find(id).get_result(conn)
+    }
+
+    fn update_progress(
+        pool: &DbPool,
+        id: &i32,
+        total: i32,
+        processed: i32,
+        failed: i32,
+    ) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        diesel::update(watermark_jobs::table.find(id))
+            .set((
+                watermark_jobs::total.eq(total),
+                watermark_jobs::processed.eq(processed),
+                watermark_jobs::failed.eq(failed),
+            ))
+            .execute(conn)
+    }
+
+    fn finish(pool: &DbPool, id: &i32, error: Option<String>) -> QueryResult<usize> {
+        let conn = &mut pool.get().expect("Couldn't get db connection from pool");
+
+        let status = match error {
+            Some(_) => JobStatus::Failed,
+            None => JobStatus::Done,
+        };
+        diesel::update(watermark_jobs::table.find(id))
+            .set((
+                watermark_jobs::status.eq(status),
+                watermark_jobs::error.eq(error),
+                watermark_jobs::finished_at.eq(now.nullable()),
+            ))
+            .execute(conn)
+    }
+}
+
+// The configured watermark, None while no logo is set
+pub(super) async fn load_watermark(pool: &DbPool) -> Result<Option<Watermark>, String> {
+    let find_pool = pool.clone();
+    let settings = tokio::task::spawn_blocking(move || WatermarkSettings::find(&find_pool))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())?;
+
+    let logo_upload_id = match settings.logo_upload_id {
+        Some(logo_upload_id) => logo_upload_id,
+        None => return Ok(None),
+    };
+    let find_pool = pool.clone();
+    let logo_upload =
+        tokio::task::spawn_blocking(move || Upload::find_by_id(&find_pool, &logo_upload_id))
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+    let original_key = logo_upload
+        .original_key
+        .ok_or("Watermark logo has no original".to_string())?;
+
+    let bytes = private_storage().get(&original_key).await?;
+    let logo = tokio::task::spawn_blocking(move || decode(&bytes))
+        .await
+        .map_err(|err| err.to_string())??;
+
+    Ok(Some(Watermark {
+        logo,
+        position: settings.position,
+        opacity: settings.opacity,
+        scale: settings.scale,
+    }))
+}
+
+// `2026/04/<uuid>/640.jpg` becomes `2026/04/<uuid>/640.v12.jpg` for job 12
+fn versioned_key(key: &str, width: u32, extension: &str, job_id: i32) -> String {
+    let dir = key.rsplit_once('/').map(|(dir, _)| dir).unwrap_or_default();
+    format!("{}/{}.v{}.{}", dir, width, job_id, extension)
+}
+
+// Renders the public sizes again from the private original. They're stored under new
+// keys, so CDN and browser caches of the old watermark don't outlive the swap.
+async fn rerender_upload(
+    pool: &DbPool,
+    upload: &Upload,
+    watermark: Arc<Option<Watermark>>,
+    job_id: i32,
+) -> Result<(), String> {
+    let original_key = upload
+        .original_key
+        .clone()
+        .ok_or("Upload has no original".to_string())?;
+    let old_variants = upload.parsed_variants()?;
+
+    let bytes = private_storage().get(&original_key).await?;
+    let processed = tokio::task::spawn_blocking(move || process(&bytes, (*watermark).as_ref()))
+        .await
+        .map_err(|err| err.to_string())??;
+
+    let mut new_variants = vec![];
+    for old_variant in &old_variants {
+        let encoded = processed
+            .variants
+            .iter()
+            .find(|encoded| {
+                encoded.width == old_variant.width && encoded.format == old_variant.format
+            })
+            .ok_or(format!(
+                "No {}px {} size rendered",
+                old_variant.width,
+                old_variant.format.extension()
+            ))?;
+        let key = versioned_key(
+            &old_variant.key,
+            old_variant.width,
+            old_variant.format.extension(),
+            job_id,
+        );
+        storage()
+            .put(
+                &key,
+                encoded.bytes.clone(),
+                old_variant.format.content_type(),
+            )
+            .await?;
+        new_variants.push(UploadVariant {
+            path: storage().url(&key),
+            key,
+            ..old_variant.clone()
+        });
+    }
+
+    let (replace_pool, id) = (pool.clone(), upload.id);
+    let replaced_variants = new_variants.clone();
+    tokio::task::spawn_blocking(move || {
+        Upload::replace_variants(&replace_pool, &id, &old_variants, &replaced_variants)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())?;
+
+    // the old files are garbage now, a failure only leaves them behind
+    for old_variant in upload.parsed_variants()? {
+        if new_variants
+            .iter()
+            .any(|variant| variant.key == old_variant.key)
+        {
+            continue;
+        }
+        if let Err(err) = storage().delete(&old_variant.key).await {
+            tracing::error!("Failed to remove {}: {}", old_variant.key, err);
+        }
+    }
+
+    Ok(())
+}
+
+async fn rerender(pool: &DbPool, job_id: i32) -> Result<(), String> {
+    let watermark = Arc::new(load_watermark(pool).await?);
+
+    let count_pool = pool.clone();
+    let total = tokio::task::spawn_blocking(move || Upload::count_watermarked(&count_pool))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())? as i32;
+
+    let progress_pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        WatermarkJob::update_progress(&progress_pool, &job_id, total, 0, 0)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())?;
+
+    let (mut processed, mut failed, mut after_id) = (0, 0, 0);
+    loop {
+        let find_pool = pool.clone();
+        let uploads = tokio::task::spawn_blocking(move || {
+            Upload::find_watermarked(&find_pool, after_id, RERENDER_BATCH_SIZE)
+        })
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())?;
+        if uploads.is_empty() {
+            break;
+        }
+
+        for upload in &uploads {
+            after_id = upload.id;
+            match rerender_upload(pool, upload, watermark.clone(), job_id).await {
+                Ok(_) => processed += 1,
+                Err(err) => {
+                    tracing::error!(
+                        "Failed to re-render watermark of upload {}: {}",
+                        upload.id,
+                        err
+                    );
+                    failed += 1;
+                }
+            }
+
+            // also tells other instances the job is still alive
+            let progress_pool = pool.clone();
+            tokio::task::spawn_blocking(move || {
+                WatermarkJob::update_progress(&progress_pool, &job_id, total, processed, failed)
+            })
+            .await
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Runs a created job to the end, progress is saved after every upload
+pub(super) async fn run_rerender_job(pool: DbPool, job_id: i32) {
+    let error = rerender(&pool, job_id).await.err();
+    if let Some(err) = &error {
+        tracing::error!("Watermark job {} failed: {}", job_id, err);
+    }
+
+    let finish_pool = pool.clone();
+    match tokio::task::spawn_blocking(move || WatermarkJob::finish(&finish_pool, &job_id, error))
+        .await
+    {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => tracing::error!("Failed to finish watermark job {}: {}", job_id, err),
+        Err(err) => tracing::error!("Failed to finish watermark job {}: {}", job_id, err),
+    }
+}
@@ -0,0 +1,43 @@
+use crate::schema::sql_types;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, AsExpression, Deserialize, Serialize, Clone, Copy, FromSqlRow)]
+#[diesel(sql_type = sql_types::WatermarkPosition)]
+pub enum WatermarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl ToSql<sql_types::WatermarkPosition, Pg> for WatermarkPosition {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        match *self {
+            WatermarkPosition::TopLeft => out.write_all(b"top_left")?,
+            WatermarkPosition::TopRight => out.write_all(b"top_right")?,
+            WatermarkPosition::BottomLeft => out.write_all(b"bottom_left")?,
+            WatermarkPosition::BottomRight => out.write_all(b"bottom_right")?,
+            WatermarkPosition::Center => out.write_all(b"center")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<sql_types::WatermarkPosition, Pg> for WatermarkPosition {
+    fn from_sql(bytes: PgValue) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"top_left" => Ok(WatermarkPosition::TopLeft),
+            b"top_right" => Ok(WatermarkPosition::TopRight),
+            b"bottom_left" => Ok(WatermarkPosition::BottomLeft),
+            b"bottom_right" => Ok(WatermarkPosition::BottomRight),
+            b"center" => Ok(WatermarkPosition::Center),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}